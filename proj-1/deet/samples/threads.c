@@ -0,0 +1,41 @@
+#define _GNU_SOURCE
+#include <pthread.h>
+#include <stdio.h>
+
+#define NUM_WORKERS 2
+
+static pthread_barrier_t barrier;
+
+int work(long id) {
+    return id * 10;
+}
+
+// Called once every worker has named itself, while they all wait for main to carry on
+void all_named(void) {
+}
+
+void *worker(void *arg) {
+    long id = (long)arg;
+    char name[16];
+    snprintf(name, sizeof(name), "worker-%ld", id);
+    pthread_setname_np(pthread_self(), name);
+    pthread_barrier_wait(&barrier);
+    pthread_barrier_wait(&barrier);
+    printf("worker %ld: %d\n", id, work(id));
+    return NULL;
+}
+
+int main() {
+    pthread_t workers[NUM_WORKERS];
+    pthread_barrier_init(&barrier, NULL, NUM_WORKERS + 1);
+    for (long i = 0; i < NUM_WORKERS; i++) {
+        pthread_create(&workers[i], NULL, worker, (void *)(i + 1));
+    }
+    pthread_barrier_wait(&barrier);
+    all_named();
+    pthread_barrier_wait(&barrier);
+    for (int i = 0; i < NUM_WORKERS; i++) {
+        pthread_join(workers[i], NULL);
+    }
+    return 0;
+}
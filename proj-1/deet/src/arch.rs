@@ -0,0 +1,239 @@
+//! Everything deet needs to know about the processor the inferior runs on: which registers hold
+//! the program counter, stack pointer and frame pointer, what a breakpoint instruction looks like,
+//! where a function finds its return address, and how functions and system calls are called.
+//!
+//! The architecture is picked at runtime from the ELF header of the program being debugged, so
+//! that a 64-bit deet on x86 can debug both x86-64 and i386 programs. The register layout
+//! (`Registers`) is whatever ptrace hands over on the machine deet itself runs on.
+
+use crate::error::DeetError;
+use nix::unistd::Pid;
+
+#[cfg(target_arch = "aarch64")]
+mod aarch64;
+#[cfg(target_arch = "x86_64")]
+mod x86;
+
+/// The registers of a stopped inferior, as ptrace reads and writes them.
+pub type Registers = libc::user_regs_struct;
+
+/// The contents of a floating point or vector register.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FpValue {
+    /// An x87 register, in 80-bit extended precision.
+    Extended([u8; 10]),
+    /// A vector register, lowest byte first.
+    Vector(Vec<u8>),
+    /// A control or status register.
+    Control(u64),
+}
+
+/// Where a value lives: in a register (whose contents are given), or in memory at an address.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Slot {
+    Register(usize),
+    Stack(usize),
+}
+
+pub trait Arch {
+    fn name(&self) -> &'static str;
+
+    /// The size of a pointer, in bytes.
+    fn word_size(&self) -> usize;
+
+    /// The instruction that traps into the debugger, as it is laid out in memory.
+    fn breakpoint(&self) -> &'static [u8];
+
+    /// How far past a breakpoint's address the program counter is left once it has trapped.
+    fn breakpoint_pc_offset(&self) -> usize;
+
+    fn pc_name(&self) -> &'static str;
+    fn sp_name(&self) -> &'static str;
+    fn frame_pointer_name(&self) -> &'static str;
+
+    fn pc(&self, regs: &Registers) -> usize;
+    fn set_pc(&self, regs: &mut Registers, pc: usize);
+    fn sp(&self, regs: &Registers) -> usize;
+    fn set_sp(&self, regs: &mut Registers, sp: usize);
+    fn frame_pointer(&self, regs: &Registers) -> usize;
+    fn set_frame_pointer(&self, regs: &mut Registers, fp: usize);
+
+    /// The integer value a function returned.
+    fn return_value(&self, regs: &Registers) -> u64;
+    fn set_return_value(&self, regs: &mut Registers, value: u64);
+
+    /// The registers worth showing the user, in the order they are usually listed.
+    fn register_summary(&self, regs: &Registers) -> Vec<(&'static str, u64)>;
+
+    /// The register `register_summary` calls `name`, for changing it.
+    fn register_mut<'a>(&self, regs: &'a mut Registers, name: &str) -> Option<&'a mut u64>;
+
+    /// The value of the register that DWARF numbers `number`, if it is one ptrace hands over
+    /// with the general purpose registers.
+    fn dwarf_register(&self, regs: &Registers, number: u16) -> Option<u64>;
+
+    /// The floating point and vector registers, given the state `get_fp_registers` read.
+    fn fp_registers(&self, state: &[u8]) -> Vec<(String, FpValue)>;
+
+    /// The name of the vector register that DWARF numbers `number`, if it is one.
+    fn dwarf_vector_register(&self, number: u16) -> Option<String>;
+
+    /// Where the `index`th integer argument is, on entry to a function.
+    fn argument(&self, regs: &Registers, index: usize) -> Slot;
+
+    /// Where the return address is, on entry to a function.
+    fn return_address_at_entry(&self, regs: &Registers) -> Slot;
+
+    /// How far above the stack pointer the canonical frame address is on entry to a function.
+    fn entry_cfa_offset(&self) -> usize;
+
+    /// Instructions that may come before the prologue proper, such as endbr64.
+    fn prologue_prefixes(&self) -> &'static [&'static [u8]];
+
+    /// The length of the prologue instruction that saves the frame pointer and (except on x86,
+    /// where `call` already did) the return address.
+    fn frame_save_len(&self) -> usize;
+
+    /// How far the canonical frame address is above the saved frame pointer, given the first
+    /// instruction of the prologue (after any prefixes).
+    fn frame_size(&self, prologue: &[u8]) -> usize;
+
+    /// Sets up `regs` for calling `func` with `args` and returning to `return_addr`, using the
+    /// stack below the current stack pointer. Returns the words that have to be written to the
+    /// stack, as (address, value) pairs.
+    fn prepare_call(
+        &self,
+        regs: &mut Registers,
+        func: usize,
+        args: &[u64],
+        return_addr: usize,
+    ) -> Result<Vec<(usize, usize)>, DeetError>;
+
+    /// The instruction that enters the kernel.
+    fn syscall_instruction(&self) -> &'static [u8];
+
+    /// The number of the mprotect system call.
+    fn mprotect_number(&self) -> i64;
+
+    /// Sets up `regs` for making system call `number` with `args`.
+    fn prepare_syscall(&self, regs: &mut Registers, number: i64, args: &[u64]);
+
+    /// The raw result of a system call (a negated errno on failure).
+    fn syscall_result(&self, regs: &Registers) -> i64;
+}
+
+/// Picks the architecture for programs of ELF machine type `machine`.
+pub fn for_machine(machine: u16) -> Result<Box<dyn Arch>, DeetError> {
+    native(machine).ok_or_else(|| {
+        DeetError::Parse(format!("Programs for machine type {} are not supported.", machine))
+    })
+}
+
+#[cfg(target_arch = "x86_64")]
+fn native(machine: u16) -> Option<Box<dyn Arch>> {
+    const EM_386: u16 = 3;
+    const EM_X86_64: u16 = 62;
+    match machine {
+        EM_386 => Some(Box::new(x86::I386)),
+        EM_X86_64 => Some(Box::new(x86::X86_64)),
+        _ => None,
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+fn native(machine: u16) -> Option<Box<dyn Arch>> {
+    const EM_AARCH64: u16 = 183;
+    match machine {
+        EM_AARCH64 => Some(Box::new(aarch64::Aarch64)),
+        _ => None,
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+pub fn get_registers(pid: Pid) -> Result<Registers, DeetError> {
+    Ok(nix::sys::ptrace::getregs(pid)?)
+}
+
+#[cfg(target_arch = "x86_64")]
+pub fn set_registers(pid: Pid, regs: Registers) -> Result<(), DeetError> {
+    Ok(nix::sys::ptrace::setregs(pid, regs)?)
+}
+
+/// Reads the floating point and vector registers: the xsave area, which has the ymm registers on
+/// top of what PTRACE_GETFPREGS returns (the fxsave layout it starts with), if the kernel and
+/// processor support it.
+#[cfg(target_arch = "x86_64")]
+pub fn get_fp_registers(pid: Pid) -> Result<Vec<u8>, DeetError> {
+    const NT_X86_XSTATE: libc::c_int = 0x202;
+    const XSAVE_MAX_SIZE: usize = 4096;
+    let mut state = vec![0u8; XSAVE_MAX_SIZE];
+    if let Ok(len) = read_regset(pid, NT_X86_XSTATE, &mut state) {
+        state.truncate(len);
+        return Ok(state);
+    }
+    let mut fxsave: libc::user_fpregs_struct = unsafe { std::mem::zeroed() };
+    let null = std::ptr::null_mut::<libc::c_void>();
+    let result = unsafe {
+        libc::ptrace(libc::PTRACE_GETFPREGS, pid.as_raw(), null, &mut fxsave as *mut _)
+    };
+    if result < 0 {
+        return Err(DeetError::Ptrace(nix::Error::last()));
+    }
+    let size = std::mem::size_of::<libc::user_fpregs_struct>();
+    let bytes = unsafe { std::slice::from_raw_parts(&fxsave as *const _ as *const u8, size) };
+    Ok(bytes.to_vec())
+}
+
+/// Reads the floating point and SIMD registers (the NT_PRFPREG register set).
+#[cfg(target_arch = "aarch64")]
+pub fn get_fp_registers(pid: Pid) -> Result<Vec<u8>, DeetError> {
+    let mut state = vec![0u8; std::mem::size_of::<libc::user_fpsimd_struct>()];
+    let len = read_regset(pid, libc::NT_PRFPREG, &mut state)?;
+    state.truncate(len);
+    Ok(state)
+}
+
+/// Reads register set `kind` into `buf`, returning how much of it the kernel filled in.
+fn read_regset(pid: Pid, kind: libc::c_int, buf: &mut [u8]) -> Result<usize, DeetError> {
+    let mut iov = libc::iovec {
+        iov_base: buf.as_mut_ptr() as *mut libc::c_void,
+        iov_len: buf.len(),
+    };
+    let result = unsafe {
+        libc::ptrace(libc::PTRACE_GETREGSET, pid.as_raw(), kind, &mut iov as *mut libc::iovec)
+    };
+    if result < 0 {
+        return Err(DeetError::Ptrace(nix::Error::last()));
+    }
+    Ok(iov.iov_len)
+}
+
+// PTRACE_GETREGS only exists on x86; elsewhere the general purpose registers are read as the
+// NT_PRSTATUS register set
+
+#[cfg(target_arch = "aarch64")]
+pub fn get_registers(pid: Pid) -> Result<Registers, DeetError> {
+    let mut regs: Registers = unsafe { std::mem::zeroed() };
+    regset(pid, libc::PTRACE_GETREGSET, &mut regs)?;
+    Ok(regs)
+}
+
+#[cfg(target_arch = "aarch64")]
+pub fn set_registers(pid: Pid, mut regs: Registers) -> Result<(), DeetError> {
+    regset(pid, libc::PTRACE_SETREGSET, &mut regs)
+}
+
+#[cfg(target_arch = "aarch64")]
+fn regset(pid: Pid, request: libc::c_uint, regs: &mut Registers) -> Result<(), DeetError> {
+    let mut iov = libc::iovec {
+        iov_base: regs as *mut Registers as *mut libc::c_void,
+        iov_len: std::mem::size_of::<Registers>(),
+    };
+    let result = unsafe {
+        libc::ptrace(request, pid.as_raw(), libc::NT_PRSTATUS, &mut iov as *mut libc::iovec)
+    };
+    if result < 0 {
+        return Err(DeetError::Ptrace(nix::Error::last()));
+    }
+    Ok(())
+}
@@ -0,0 +1,249 @@
+//! 64-bit ARM. Functions compiled with a frame pointer open with
+//! `stp x29, x30, [sp, #-N]!; mov x29, sp`, saving the caller's frame pointer and the return
+//! address (which `bl` leaves in x30) at the bottom of an N-byte frame.
+
+use super::{Arch, FpValue, Registers, Slot};
+use crate::error::DeetError;
+
+/// `brk #0`
+const BRK: [u8; 4] = [0x00, 0x00, 0x20, 0xd4];
+/// `bti c`
+const BTI_C: [u8; 4] = [0x5f, 0x24, 0x03, 0xd5];
+/// `paciasp`
+const PACIASP: [u8; 4] = [0x3f, 0x23, 0x03, 0xd5];
+
+/// The link register, which holds the return address on entry to a function.
+const LR: usize = 30;
+/// The frame pointer.
+const FP: usize = 29;
+
+const REGISTER_NAMES: [&str; 31] = [
+    "x0", "x1", "x2", "x3", "x4", "x5", "x6", "x7", "x8", "x9", "x10", "x11", "x12", "x13", "x14",
+    "x15", "x16", "x17", "x18", "x19", "x20", "x21", "x22", "x23", "x24", "x25", "x26", "x27",
+    "x28", "x29", "x30",
+];
+
+pub struct Aarch64;
+
+impl Arch for Aarch64 {
+    fn name(&self) -> &'static str {
+        "aarch64"
+    }
+
+    fn word_size(&self) -> usize {
+        8
+    }
+
+    fn breakpoint(&self) -> &'static [u8] {
+        &BRK
+    }
+
+    /// The program counter is left on the `brk` itself.
+    fn breakpoint_pc_offset(&self) -> usize {
+        0
+    }
+
+    fn pc_name(&self) -> &'static str {
+        "pc"
+    }
+
+    fn sp_name(&self) -> &'static str {
+        "sp"
+    }
+
+    fn frame_pointer_name(&self) -> &'static str {
+        "x29"
+    }
+
+    fn pc(&self, regs: &Registers) -> usize {
+        regs.pc as usize
+    }
+
+    fn set_pc(&self, regs: &mut Registers, pc: usize) {
+        regs.pc = pc as u64;
+    }
+
+    fn sp(&self, regs: &Registers) -> usize {
+        regs.sp as usize
+    }
+
+    fn set_sp(&self, regs: &mut Registers, sp: usize) {
+        regs.sp = sp as u64;
+    }
+
+    fn frame_pointer(&self, regs: &Registers) -> usize {
+        regs.regs[FP] as usize
+    }
+
+    fn set_frame_pointer(&self, regs: &mut Registers, fp: usize) {
+        regs.regs[FP] = fp as u64;
+    }
+
+    fn return_value(&self, regs: &Registers) -> u64 {
+        regs.regs[0]
+    }
+
+    fn set_return_value(&self, regs: &mut Registers, value: u64) {
+        regs.regs[0] = value;
+    }
+
+    fn register_summary(&self, regs: &Registers) -> Vec<(&'static str, u64)> {
+        let mut summary: Vec<(&'static str, u64)> =
+            REGISTER_NAMES.iter().cloned().zip(regs.regs.iter().cloned()).collect();
+        summary.push(("sp", regs.sp));
+        summary.push(("pc", regs.pc));
+        summary.push(("pstate", regs.pstate));
+        summary
+    }
+
+    fn register_mut<'a>(&self, regs: &'a mut Registers, name: &str) -> Option<&'a mut u64> {
+        match name {
+            "sp" => Some(&mut regs.sp),
+            "pc" => Some(&mut regs.pc),
+            "pstate" => Some(&mut regs.pstate),
+            _ => {
+                let number = REGISTER_NAMES.iter().position(|register| *register == name)?;
+                Some(&mut regs.regs[number])
+            }
+        }
+    }
+
+    fn dwarf_register(&self, regs: &Registers, number: u16) -> Option<u64> {
+        match number {
+            0..=30 => Some(regs.regs[number as usize]),
+            31 => Some(regs.sp),
+            _ => None,
+        }
+    }
+
+    /// `state` is a user_fpsimd_struct: the 32 128-bit vector registers, then fpsr and fpcr.
+    fn fp_registers(&self, state: &[u8]) -> Vec<(String, FpValue)> {
+        let mut registers = Vec::new();
+        if state.len() < 32 * 16 + 8 {
+            return registers;
+        }
+        for i in 0..32 {
+            let value = state[i * 16..(i + 1) * 16].to_vec();
+            registers.push((format!("v{}", i), FpValue::Vector(value)));
+        }
+        let u32_at = |offset: usize| {
+            let bytes = [state[offset], state[offset + 1], state[offset + 2], state[offset + 3]];
+            u32::from_le_bytes(bytes) as u64
+        };
+        registers.push((String::from("fpsr"), FpValue::Control(u32_at(32 * 16))));
+        registers.push((String::from("fpcr"), FpValue::Control(u32_at(32 * 16 + 4))));
+        registers
+    }
+
+    fn dwarf_vector_register(&self, number: u16) -> Option<String> {
+        match number {
+            64..=95 => Some(format!("v{}", number - 64)),
+            _ => None,
+        }
+    }
+
+    fn argument(&self, regs: &Registers, index: usize) -> Slot {
+        if index < 8 {
+            Slot::Register(regs.regs[index] as usize)
+        } else {
+            Slot::Stack(regs.sp as usize + (index - 8) * 8)
+        }
+    }
+
+    fn return_address_at_entry(&self, regs: &Registers) -> Slot {
+        Slot::Register(regs.regs[LR] as usize)
+    }
+
+    /// Nothing has been pushed by `bl`, so the canonical frame address is the stack pointer.
+    fn entry_cfa_offset(&self) -> usize {
+        0
+    }
+
+    fn prologue_prefixes(&self) -> &'static [&'static [u8]] {
+        &[&BTI_C, &PACIASP]
+    }
+
+    /// `stp x29, x30, [sp, #-N]!`
+    fn frame_save_len(&self) -> usize {
+        4
+    }
+
+    fn frame_size(&self, prologue: &[u8]) -> usize {
+        frame_size(prologue).unwrap_or(16)
+    }
+
+    fn prepare_call(
+        &self,
+        regs: &mut Registers,
+        func: usize,
+        args: &[u64],
+        return_addr: usize,
+    ) -> Result<Vec<(usize, usize)>, DeetError> {
+        if args.len() > 8 {
+            return Err(DeetError::Parse(String::from("At most 8 arguments are supported")));
+        }
+        for (i, arg) in args.iter().enumerate() {
+            regs.regs[i] = *arg;
+        }
+        // There is no red zone on aarch64, but leave some room anyway; sp must stay 16-byte
+        // aligned
+        regs.sp = (regs.sp - 128) & !0xf;
+        regs.regs[LR] = return_addr as u64;
+        regs.pc = func as u64;
+        Ok(Vec::new())
+    }
+
+    /// `svc #0`
+    fn syscall_instruction(&self) -> &'static [u8] {
+        &[0x01, 0x00, 0x00, 0xd4]
+    }
+
+    fn mprotect_number(&self) -> i64 {
+        226
+    }
+
+    /// The system call number goes in x8, the arguments in x0 to x5.
+    fn prepare_syscall(&self, regs: &mut Registers, number: i64, args: &[u64]) {
+        regs.regs[8] = number as u64;
+        for (i, arg) in args.iter().take(6).enumerate() {
+            regs.regs[i] = *arg;
+        }
+    }
+
+    fn syscall_result(&self, regs: &Registers) -> i64 {
+        regs.regs[0] as i64
+    }
+}
+
+/// Decodes the frame size N out of `stp x29, x30, [sp, #-N]!`.
+fn frame_size(prologue: &[u8]) -> Option<usize> {
+    if prologue.len() < 4 {
+        return None;
+    }
+    let insn = u32::from_le_bytes([prologue[0], prologue[1], prologue[2], prologue[3]]);
+    // STP (64-bit, pre-index) with Rt = x29, Rt2 = x30, Rn = sp; imm7 is a signed multiple of 8
+    if insn & 0xffc0_7fff != 0xa980_7bfd {
+        return None;
+    }
+    let imm7 = ((insn >> 15) & 0x7f) as i32;
+    let offset = if imm7 & 0x40 != 0 { imm7 - 0x80 } else { imm7 } * 8;
+    if offset >= 0 {
+        return None;
+    }
+    Some(-offset as usize)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_frame_size() {
+        // stp x29, x30, [sp, #-32]!
+        assert_eq!(frame_size(&0xa9be7bfdu32.to_le_bytes()), Some(32));
+        // stp x29, x30, [sp, #-16]!
+        assert_eq!(frame_size(&0xa9bf7bfdu32.to_le_bytes()), Some(16));
+        // mov x29, sp
+        assert_eq!(frame_size(&0x910003fdu32.to_le_bytes()), None);
+    }
+}
@@ -0,0 +1,459 @@
+//! x86-64, and i386 programs running on an x86-64 kernel. The registers of a 32-bit program are
+//! read through the 64-bit layout, with each e-register in the low half of its r-register.
+
+use super::{Arch, FpValue, Registers, Slot};
+use crate::error::DeetError;
+
+/// `int3`
+const INT3: [u8; 1] = [0xcc];
+const ENDBR64: [u8; 4] = [0xf3, 0x0f, 0x1e, 0xfa];
+const ENDBR32: [u8; 4] = [0xf3, 0x0f, 0x1e, 0xfb];
+
+/// How many bytes below the stack pointer a function may use without moving it.
+const RED_ZONE: usize = 128;
+
+// Where things are in the xsave area (which starts with the fxsave layout)
+const FXSAVE_ST: usize = 32;
+const FXSAVE_XMM: usize = 160;
+const XSAVE_HEADER: usize = 512;
+const XSAVE_YMM_HIGH: usize = 576;
+/// The bit in the xsave header's state vector saying the ymm registers' upper halves were saved.
+const XSTATE_AVX: u64 = 1 << 2;
+
+pub struct X86_64;
+
+pub struct I386;
+
+impl Arch for X86_64 {
+    fn name(&self) -> &'static str {
+        "x86-64"
+    }
+
+    fn word_size(&self) -> usize {
+        8
+    }
+
+    fn breakpoint(&self) -> &'static [u8] {
+        &INT3
+    }
+
+    fn breakpoint_pc_offset(&self) -> usize {
+        INT3.len()
+    }
+
+    fn pc_name(&self) -> &'static str {
+        "rip"
+    }
+
+    fn sp_name(&self) -> &'static str {
+        "rsp"
+    }
+
+    fn frame_pointer_name(&self) -> &'static str {
+        "rbp"
+    }
+
+    fn pc(&self, regs: &Registers) -> usize {
+        regs.rip as usize
+    }
+
+    fn set_pc(&self, regs: &mut Registers, pc: usize) {
+        regs.rip = pc as u64;
+    }
+
+    fn sp(&self, regs: &Registers) -> usize {
+        regs.rsp as usize
+    }
+
+    fn set_sp(&self, regs: &mut Registers, sp: usize) {
+        regs.rsp = sp as u64;
+    }
+
+    fn frame_pointer(&self, regs: &Registers) -> usize {
+        regs.rbp as usize
+    }
+
+    fn set_frame_pointer(&self, regs: &mut Registers, fp: usize) {
+        regs.rbp = fp as u64;
+    }
+
+    fn return_value(&self, regs: &Registers) -> u64 {
+        regs.rax
+    }
+
+    fn set_return_value(&self, regs: &mut Registers, value: u64) {
+        regs.rax = value;
+    }
+
+    fn register_summary(&self, regs: &Registers) -> Vec<(&'static str, u64)> {
+        vec![
+            ("rax", regs.rax), ("rbx", regs.rbx), ("rcx", regs.rcx), ("rdx", regs.rdx),
+            ("rsi", regs.rsi), ("rdi", regs.rdi), ("rbp", regs.rbp), ("rsp", regs.rsp),
+            ("r8", regs.r8), ("r9", regs.r9), ("r10", regs.r10), ("r11", regs.r11),
+            ("r12", regs.r12), ("r13", regs.r13), ("r14", regs.r14), ("r15", regs.r15),
+            ("rip", regs.rip), ("eflags", regs.eflags), ("fs_base", regs.fs_base),
+            ("gs_base", regs.gs_base),
+        ]
+    }
+
+    fn register_mut<'a>(&self, regs: &'a mut Registers, name: &str) -> Option<&'a mut u64> {
+        Some(match name {
+            "rax" => &mut regs.rax, "rbx" => &mut regs.rbx, "rcx" => &mut regs.rcx,
+            "rdx" => &mut regs.rdx, "rsi" => &mut regs.rsi, "rdi" => &mut regs.rdi,
+            "rbp" => &mut regs.rbp, "rsp" => &mut regs.rsp, "r8" => &mut regs.r8,
+            "r9" => &mut regs.r9, "r10" => &mut regs.r10, "r11" => &mut regs.r11,
+            "r12" => &mut regs.r12, "r13" => &mut regs.r13, "r14" => &mut regs.r14,
+            "r15" => &mut regs.r15, "rip" => &mut regs.rip, "eflags" => &mut regs.eflags,
+            "fs_base" => &mut regs.fs_base, "gs_base" => &mut regs.gs_base,
+            _ => return None,
+        })
+    }
+
+    fn dwarf_register(&self, regs: &Registers, number: u16) -> Option<u64> {
+        let numbered = [
+            regs.rax, regs.rdx, regs.rcx, regs.rbx, regs.rsi, regs.rdi, regs.rbp, regs.rsp,
+            regs.r8, regs.r9, regs.r10, regs.r11, regs.r12, regs.r13, regs.r14, regs.r15, regs.rip,
+        ];
+        numbered.get(number as usize).copied()
+    }
+
+    fn fp_registers(&self, state: &[u8]) -> Vec<(String, FpValue)> {
+        xsave_registers(state, 16)
+    }
+
+    fn dwarf_vector_register(&self, number: u16) -> Option<String> {
+        match number {
+            17..=32 => Some(format!("xmm{}", number - 17)),
+            _ => None,
+        }
+    }
+
+    fn argument(&self, regs: &Registers, index: usize) -> Slot {
+        let args = [regs.rdi, regs.rsi, regs.rdx, regs.rcx, regs.r8, regs.r9];
+        match args.get(index) {
+            Some(value) => Slot::Register(*value as usize),
+            None => Slot::Stack(regs.rsp as usize + (index - args.len() + 1) * 8),
+        }
+    }
+
+    fn return_address_at_entry(&self, regs: &Registers) -> Slot {
+        Slot::Stack(regs.rsp as usize)
+    }
+
+    fn entry_cfa_offset(&self) -> usize {
+        8
+    }
+
+    fn prologue_prefixes(&self) -> &'static [&'static [u8]] {
+        &[&ENDBR64]
+    }
+
+    /// `push rbp`
+    fn frame_save_len(&self) -> usize {
+        1
+    }
+
+    fn frame_size(&self, _prologue: &[u8]) -> usize {
+        16
+    }
+
+    fn prepare_call(
+        &self,
+        regs: &mut Registers,
+        func: usize,
+        args: &[u64],
+        return_addr: usize,
+    ) -> Result<Vec<(usize, usize)>, DeetError> {
+        let mut arg_regs = [
+            &mut regs.rdi, &mut regs.rsi, &mut regs.rdx, &mut regs.rcx, &mut regs.r8, &mut regs.r9,
+        ];
+        if args.len() > arg_regs.len() {
+            return Err(DeetError::Parse(format!(
+                "At most {} arguments are supported",
+                arg_regs.len()
+            )));
+        }
+        for (reg, arg) in arg_regs.iter_mut().zip(args) {
+            **reg = *arg;
+        }
+        // Skip the red zone, then push the return address so that rsp + 8 is 16-byte aligned on
+        // entry, as the ABI requires
+        let stack_top = ((regs.rsp as usize - RED_ZONE) & !0xf) - 8;
+        regs.rsp = stack_top as u64;
+        regs.rip = func as u64;
+        // No vector registers are used for arguments (this matters to variadic functions)
+        regs.rax = 0;
+        Ok(vec![(stack_top, return_addr)])
+    }
+
+    /// `syscall`
+    fn syscall_instruction(&self) -> &'static [u8] {
+        &[0x0f, 0x05]
+    }
+
+    fn mprotect_number(&self) -> i64 {
+        libc::SYS_mprotect
+    }
+
+    fn prepare_syscall(&self, regs: &mut Registers, number: i64, args: &[u64]) {
+        regs.rax = number as u64;
+        let mut arg_regs = [
+            &mut regs.rdi, &mut regs.rsi, &mut regs.rdx, &mut regs.r10, &mut regs.r8, &mut regs.r9,
+        ];
+        for (reg, arg) in arg_regs.iter_mut().zip(args) {
+            **reg = *arg;
+        }
+    }
+
+    fn syscall_result(&self, regs: &Registers) -> i64 {
+        regs.rax as i64
+    }
+}
+
+impl Arch for I386 {
+    fn name(&self) -> &'static str {
+        "i386"
+    }
+
+    fn word_size(&self) -> usize {
+        4
+    }
+
+    fn breakpoint(&self) -> &'static [u8] {
+        &INT3
+    }
+
+    fn breakpoint_pc_offset(&self) -> usize {
+        INT3.len()
+    }
+
+    fn pc_name(&self) -> &'static str {
+        "eip"
+    }
+
+    fn sp_name(&self) -> &'static str {
+        "esp"
+    }
+
+    fn frame_pointer_name(&self) -> &'static str {
+        "ebp"
+    }
+
+    fn pc(&self, regs: &Registers) -> usize {
+        regs.rip as usize
+    }
+
+    fn set_pc(&self, regs: &mut Registers, pc: usize) {
+        regs.rip = pc as u64;
+    }
+
+    fn sp(&self, regs: &Registers) -> usize {
+        regs.rsp as usize
+    }
+
+    fn set_sp(&self, regs: &mut Registers, sp: usize) {
+        regs.rsp = sp as u64;
+    }
+
+    fn frame_pointer(&self, regs: &Registers) -> usize {
+        regs.rbp as usize
+    }
+
+    fn set_frame_pointer(&self, regs: &mut Registers, fp: usize) {
+        regs.rbp = fp as u64;
+    }
+
+    fn return_value(&self, regs: &Registers) -> u64 {
+        regs.rax as u32 as i32 as i64 as u64
+    }
+
+    fn set_return_value(&self, regs: &mut Registers, value: u64) {
+        regs.rax = value as u32 as u64;
+    }
+
+    fn register_summary(&self, regs: &Registers) -> Vec<(&'static str, u64)> {
+        vec![
+            ("eax", regs.rax), ("ebx", regs.rbx), ("ecx", regs.rcx), ("edx", regs.rdx),
+            ("esi", regs.rsi), ("edi", regs.rdi), ("ebp", regs.rbp), ("esp", regs.rsp),
+            ("eip", regs.rip), ("eflags", regs.eflags),
+        ]
+    }
+
+    fn register_mut<'a>(&self, regs: &'a mut Registers, name: &str) -> Option<&'a mut u64> {
+        Some(match name {
+            "eax" => &mut regs.rax, "ebx" => &mut regs.rbx, "ecx" => &mut regs.rcx,
+            "edx" => &mut regs.rdx, "esi" => &mut regs.rsi, "edi" => &mut regs.rdi,
+            "ebp" => &mut regs.rbp, "esp" => &mut regs.rsp, "eip" => &mut regs.rip,
+            "eflags" => &mut regs.eflags,
+            _ => return None,
+        })
+    }
+
+    /// The numbering follows the order of the instruction encoding, unlike on x86-64.
+    fn dwarf_register(&self, regs: &Registers, number: u16) -> Option<u64> {
+        let numbered = [
+            regs.rax, regs.rcx, regs.rdx, regs.rbx, regs.rsp, regs.rbp, regs.rsi, regs.rdi,
+            regs.rip,
+        ];
+        numbered.get(number as usize).map(|value| *value as u32 as u64)
+    }
+
+    /// Only xmm0 to xmm7 exist in 32-bit mode.
+    fn fp_registers(&self, state: &[u8]) -> Vec<(String, FpValue)> {
+        xsave_registers(state, 8)
+    }
+
+    fn dwarf_vector_register(&self, number: u16) -> Option<String> {
+        match number {
+            21..=28 => Some(format!("xmm{}", number - 21)),
+            _ => None,
+        }
+    }
+
+    /// Arguments are all passed on the stack, right above the return address.
+    fn argument(&self, regs: &Registers, index: usize) -> Slot {
+        Slot::Stack(regs.rsp as usize + (index + 1) * 4)
+    }
+
+    fn return_address_at_entry(&self, regs: &Registers) -> Slot {
+        Slot::Stack(regs.rsp as usize)
+    }
+
+    fn entry_cfa_offset(&self) -> usize {
+        4
+    }
+
+    fn prologue_prefixes(&self) -> &'static [&'static [u8]] {
+        &[&ENDBR32]
+    }
+
+    /// `push ebp`
+    fn frame_save_len(&self) -> usize {
+        1
+    }
+
+    fn frame_size(&self, _prologue: &[u8]) -> usize {
+        8
+    }
+
+    fn prepare_call(
+        &self,
+        regs: &mut Registers,
+        func: usize,
+        args: &[u64],
+        return_addr: usize,
+    ) -> Result<Vec<(usize, usize)>, DeetError> {
+        // Push the arguments, then the return address, so that the stack is 16-byte aligned
+        // past the return address on entry. i386 has no red zone, but skipping one does no harm.
+        let stack_top = ((regs.rsp as usize - RED_ZONE - args.len() * 4) & !0xf) - 4;
+        let mut words = vec![(stack_top, return_addr)];
+        for (i, arg) in args.iter().enumerate() {
+            words.push((stack_top + (i + 1) * 4, *arg as usize));
+        }
+        regs.rsp = stack_top as u64;
+        regs.rip = func as u64;
+        Ok(words)
+    }
+
+    /// `int 0x80`
+    fn syscall_instruction(&self) -> &'static [u8] {
+        &[0xcd, 0x80]
+    }
+
+    /// i386 system calls are numbered differently from x86-64 ones.
+    fn mprotect_number(&self) -> i64 {
+        125
+    }
+
+    fn prepare_syscall(&self, regs: &mut Registers, number: i64, args: &[u64]) {
+        regs.rax = number as u64;
+        let mut arg_regs = [
+            &mut regs.rbx, &mut regs.rcx, &mut regs.rdx, &mut regs.rsi, &mut regs.rdi,
+            &mut regs.rbp,
+        ];
+        for (reg, arg) in arg_regs.iter_mut().zip(args) {
+            **reg = *arg;
+        }
+    }
+
+    fn syscall_result(&self, regs: &Registers) -> i64 {
+        regs.rax as u32 as i32 as i64
+    }
+}
+
+/// Picks the x87 and SSE registers (and the AVX ones, if saved) out of an xsave area.
+fn xsave_registers(state: &[u8], xmm_count: usize) -> Vec<(String, FpValue)> {
+    let u16_at = |offset: usize| u16::from_le_bytes([state[offset], state[offset + 1]]) as u64;
+    let mut registers = Vec::new();
+    if state.len() < XSAVE_HEADER {
+        return registers;
+    }
+    for i in 0..8 {
+        let mut value = [0u8; 10];
+        value.copy_from_slice(&state[FXSAVE_ST + i * 16..FXSAVE_ST + i * 16 + 10]);
+        registers.push((format!("st{}", i), FpValue::Extended(value)));
+    }
+    registers.push((String::from("fctrl"), FpValue::Control(u16_at(0))));
+    registers.push((String::from("fstat"), FpValue::Control(u16_at(2))));
+    let mxcsr = u32::from_le_bytes([state[24], state[25], state[26], state[27]]);
+    registers.push((String::from("mxcsr"), FpValue::Control(mxcsr as u64)));
+    for i in 0..xmm_count {
+        let xmm = &state[FXSAVE_XMM + i * 16..FXSAVE_XMM + (i + 1) * 16];
+        registers.push((format!("xmm{}", i), FpValue::Vector(xmm.to_vec())));
+    }
+    // An area big enough to hold the ymm registers means the processor has them. The state
+    // vector bit is clear while they are all zero.
+    if state.len() >= XSAVE_YMM_HIGH + 16 * 16 {
+        let mut header = [0u8; 8];
+        header.copy_from_slice(&state[XSAVE_HEADER..XSAVE_HEADER + 8]);
+        let saved = u64::from_le_bytes(header) & XSTATE_AVX != 0;
+        for i in 0..xmm_count {
+            let mut ymm = state[FXSAVE_XMM + i * 16..FXSAVE_XMM + (i + 1) * 16].to_vec();
+            if saved {
+                ymm.extend(&state[XSAVE_YMM_HIGH + i * 16..XSAVE_YMM_HIGH + (i + 1) * 16]);
+            } else {
+                ymm.extend(&[0u8; 16]);
+            }
+            registers.push((format!("ymm{}", i), FpValue::Vector(ymm)));
+        }
+    }
+    registers
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_prepare_call() {
+        let mut regs: Registers = unsafe { std::mem::zeroed() };
+        regs.rsp = 0x7ffc_0108;
+        let words = X86_64.prepare_call(&mut regs, 0x401000, &[1, 2], 0x400000).unwrap();
+        assert_eq!((regs.rdi, regs.rsi, regs.rip), (1, 2, 0x401000));
+        assert_eq!(words, vec![(0x7ffc_0078, 0x400000)]);
+        assert_eq!(regs.rsp, 0x7ffc_0078);
+        assert_eq!((regs.rsp + 8) % 16, 0);
+
+        regs.rsp = 0xffff_d108;
+        let words = I386.prepare_call(&mut regs, 0x8049000, &[7, 9], 0x8048000).unwrap();
+        assert_eq!(
+            words,
+            vec![(0xffff_d07c, 0x8048000), (0xffff_d080, 7), (0xffff_d084, 9)]
+        );
+        assert_eq!((regs.rsp + 4) % 16, 0);
+    }
+
+    #[test]
+    fn test_register_mut_covers_the_summary() {
+        for arch in [&X86_64 as &dyn Arch, &I386].iter() {
+            let mut regs: Registers = unsafe { std::mem::zeroed() };
+            let names: Vec<&str> = arch.register_summary(&regs).iter().map(|r| r.0).collect();
+            for (i, name) in names.iter().enumerate() {
+                *arch.register_mut(&mut regs, name).unwrap() = i as u64 + 1;
+            }
+            let values: Vec<u64> = arch.register_summary(&regs).iter().map(|r| r.1).collect();
+            assert_eq!(values, (1..=names.len() as u64).collect::<Vec<u64>>());
+            assert!(arch.register_mut(&mut regs, "xmm0").is_none());
+        }
+    }
+}
@@ -0,0 +1,157 @@
+//! The operating system interface an `Inferior` drives its process through. Memory belongs to the
+//! process as a whole, while waiting, resuming and registers are per thread, so those take the
+//! tid of the thread they apply to (which is the pid for the main thread). `Ptrace` is the real
+//! thing; `mock::MockBackend` (in tests) pretends to be a process, so that the debugger's
+//! bookkeeping can be exercised without spawning one.
+
+use crate::arch::{self, Registers};
+use crate::error::DeetError;
+use nix::sys::ptrace;
+use nix::sys::signal;
+use nix::sys::wait::{waitpid, WaitPidFlag, WaitStatus};
+use nix::unistd::Pid;
+
+#[cfg(test)]
+pub mod mock;
+
+pub trait InferiorBackend {
+    /// Waits for thread `tid` to change state, as waitpid does.
+    fn wait(&mut self, tid: Pid, options: Option<WaitPidFlag>) -> Result<WaitStatus, DeetError>;
+
+    /// The message attached to the ptrace event thread `tid` is stopped at, such as a new child's
+    /// pid or a new thread's tid.
+    fn event_message(&self, tid: Pid) -> Result<usize, DeetError>;
+
+    /// Starts tracing the process with PTRACE_SEIZE, which (unlike PTRACE_ATTACH or
+    /// PTRACE_TRACEME) leaves it running and reports group-stops as such.
+    fn seize(&mut self, options: ptrace::Options) -> Result<(), DeetError>;
+
+    /// Stops running thread `tid` with PTRACE_INTERRUPT. The stop is reported as a
+    /// PTRACE_EVENT_STOP with SIGTRAP.
+    fn interrupt(&mut self, tid: Pid) -> Result<(), DeetError>;
+
+    /// Has thread `tid` execute a single instruction.
+    fn step(&mut self, tid: Pid) -> Result<(), DeetError>;
+
+    fn cont(&mut self, tid: Pid) -> Result<(), DeetError>;
+
+    /// Kills the whole process.
+    fn kill(&mut self) -> Result<(), DeetError>;
+
+    /// Stops tracing thread `tid` and lets it run.
+    fn detach(&mut self, tid: Pid) -> Result<(), DeetError>;
+
+    /// Reads the ptrace word (8 bytes) at `addr`.
+    fn peek(&self, addr: usize) -> Result<usize, DeetError>;
+
+    /// Writes the ptrace word (8 bytes) at `addr`.
+    fn poke(&mut self, addr: usize, word: usize) -> Result<(), DeetError>;
+
+    fn registers(&self, tid: Pid) -> Result<Registers, DeetError>;
+
+    fn set_registers(&mut self, tid: Pid, regs: Registers) -> Result<(), DeetError>;
+
+    /// Thread `tid`'s floating point and vector registers, laid out as `arch::get_fp_registers`
+    /// reads them.
+    fn fp_registers(&self, tid: Pid) -> Result<Vec<u8>, DeetError>;
+
+    /// The address whose access caused the signal thread `tid` is stopped with.
+    fn fault_address(&self, tid: Pid) -> Result<usize, DeetError>;
+
+    /// The ELF machine type of the program the process is running.
+    fn machine(&self) -> Result<u16, DeetError>;
+
+    /// The process's memory mappings, in the format of /proc/<pid>/maps.
+    fn maps(&self) -> Result<String, DeetError>;
+}
+
+/// A real process, traced with ptrace.
+pub struct Ptrace {
+    pid: Pid,
+}
+
+impl Ptrace {
+    pub fn new(pid: Pid) -> Ptrace {
+        Ptrace { pid }
+    }
+}
+
+impl InferiorBackend for Ptrace {
+    fn wait(&mut self, tid: Pid, options: Option<WaitPidFlag>) -> Result<WaitStatus, DeetError> {
+        // Threads other than the main one are clones rather than children, which waitpid only
+        // looks at with __WALL
+        let options = options.unwrap_or_else(WaitPidFlag::empty) | WaitPidFlag::__WALL;
+        Ok(waitpid(tid, Some(options))?)
+    }
+
+    fn event_message(&self, tid: Pid) -> Result<usize, DeetError> {
+        Ok(ptrace::getevent(tid)? as usize)
+    }
+
+    fn seize(&mut self, options: ptrace::Options) -> Result<(), DeetError> {
+        Ok(ptrace::seize(self.pid, options)?)
+    }
+
+    fn interrupt(&mut self, tid: Pid) -> Result<(), DeetError> {
+        // nix has no wrapper for PTRACE_INTERRUPT
+        let null = std::ptr::null_mut::<libc::c_void>();
+        let result = unsafe { libc::ptrace(libc::PTRACE_INTERRUPT, tid.as_raw(), null, null) };
+        if result < 0 {
+            return Err(DeetError::Ptrace(nix::Error::last()));
+        }
+        Ok(())
+    }
+
+    fn step(&mut self, tid: Pid) -> Result<(), DeetError> {
+        Ok(ptrace::step(tid, None)?)
+    }
+
+    fn cont(&mut self, tid: Pid) -> Result<(), DeetError> {
+        Ok(ptrace::cont(tid, None)?)
+    }
+
+    fn kill(&mut self) -> Result<(), DeetError> {
+        Ok(signal::kill(self.pid, signal::Signal::SIGKILL)?)
+    }
+
+    fn detach(&mut self, tid: Pid) -> Result<(), DeetError> {
+        Ok(ptrace::detach(tid, None)?)
+    }
+
+    fn peek(&self, addr: usize) -> Result<usize, DeetError> {
+        Ok(ptrace::read(self.pid, addr as ptrace::AddressType)? as usize)
+    }
+
+    fn poke(&mut self, addr: usize, word: usize) -> Result<(), DeetError> {
+        let word = word as *mut std::ffi::c_void;
+        Ok(ptrace::write(self.pid, addr as ptrace::AddressType, word)?)
+    }
+
+    fn registers(&self, tid: Pid) -> Result<Registers, DeetError> {
+        arch::get_registers(tid)
+    }
+
+    fn set_registers(&mut self, tid: Pid, regs: Registers) -> Result<(), DeetError> {
+        arch::set_registers(tid, regs)
+    }
+
+    fn fp_registers(&self, tid: Pid) -> Result<Vec<u8>, DeetError> {
+        arch::get_fp_registers(tid)
+    }
+
+    fn fault_address(&self, tid: Pid) -> Result<usize, DeetError> {
+        let siginfo = ptrace::getsiginfo(tid)?;
+        Ok(unsafe { siginfo.si_addr() } as usize)
+    }
+
+    fn machine(&self) -> Result<u16, DeetError> {
+        use std::io::Read;
+        let mut header = [0u8; 20];
+        std::fs::File::open(format!("/proc/{}/exe", self.pid))?.read_exact(&mut header)?;
+        Ok(u16::from_le_bytes([header[18], header[19]]))
+    }
+
+    fn maps(&self) -> Result<String, DeetError> {
+        Ok(std::fs::read_to_string(format!("/proc/{}/maps", self.pid))?)
+    }
+}
@@ -0,0 +1,217 @@
+//! A pretend process for tests. Rather than running real code, it walks through a list of
+//! instruction addresses, trapping whenever one of them holds a breakpoint instruction, so that
+//! what the debugger does with breakpoints can be checked without any process at all. It has a
+//! single thread, so the tids passed in are ignored.
+
+use super::InferiorBackend;
+use crate::arch::{self, Arch, Registers};
+use crate::error::DeetError;
+use nix::sys::ptrace;
+use nix::sys::signal::Signal;
+use nix::sys::wait::{WaitPidFlag, WaitStatus};
+use nix::unistd::Pid;
+use std::cell::{RefCell, RefMut};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// The pid the mock process claims to have.
+pub const MOCK_PID: i32 = 4242;
+
+#[cfg(target_arch = "x86_64")]
+const NATIVE_MACHINE: u16 = 62;
+#[cfg(target_arch = "aarch64")]
+const NATIVE_MACHINE: u16 = 183;
+
+pub struct MockProcess {
+    /// Bytes of memory that have been written. Everything else reads as zero.
+    pub memory: HashMap<usize, u8>,
+    pub regs: Registers,
+    /// The addresses of the instructions the program executes, in order, whatever the debugger
+    /// does to the program counter. Once they run out, the program exits with `exit_code`.
+    pub trace: Vec<usize>,
+    pub exit_code: i32,
+    /// Index into `trace` of the next instruction to execute.
+    position: usize,
+    /// What the next wait reports, if the process has changed state since the last one.
+    status: Option<WaitStatus>,
+    exited: bool,
+    arch: Box<dyn Arch>,
+}
+
+impl MockProcess {
+    fn byte(&self, addr: usize) -> u8 {
+        self.memory.get(&addr).copied().unwrap_or(0)
+    }
+
+    /// Executes the next instruction, returning whether the process is still running
+    /// afterwards (as opposed to having trapped or exited).
+    fn execute(&mut self) -> bool {
+        let pid = Pid::from_raw(MOCK_PID);
+        let addr = self.trace[self.position];
+        let breakpoint = self.arch.breakpoint();
+        if (0..breakpoint.len()).all(|i| self.byte(addr + i) == breakpoint[i]) {
+            let pc = addr + self.arch.breakpoint_pc_offset();
+            self.arch.set_pc(&mut self.regs, pc);
+            self.status = Some(WaitStatus::Stopped(pid, Signal::SIGTRAP));
+            return false;
+        }
+        self.position += 1;
+        match self.trace.get(self.position) {
+            Some(next) => {
+                self.arch.set_pc(&mut self.regs, *next);
+                true
+            }
+            None => {
+                self.exited = true;
+                self.status = Some(WaitStatus::Exited(pid, self.exit_code));
+                false
+            }
+        }
+    }
+
+    fn check_alive(&self) -> Result<(), DeetError> {
+        if self.exited {
+            return Err(DeetError::Ptrace(nix::Error::Sys(nix::errno::Errno::ESRCH)));
+        }
+        Ok(())
+    }
+}
+
+/// A handle on a `MockProcess`. Clones share the process, so a test can keep one to look at
+/// what the debugger has done to it.
+#[derive(Clone)]
+pub struct MockBackend {
+    process: Rc<RefCell<MockProcess>>,
+}
+
+impl MockBackend {
+    /// A process that is stopped at the first address in `trace`, which must not be empty.
+    pub fn new(trace: Vec<usize>) -> MockBackend {
+        let arch = arch::for_machine(NATIVE_MACHINE).unwrap();
+        let mut regs: Registers = unsafe { std::mem::zeroed() };
+        arch.set_pc(&mut regs, trace[0]);
+        MockBackend {
+            process: Rc::new(RefCell::new(MockProcess {
+                memory: HashMap::new(),
+                regs,
+                trace,
+                exit_code: 0,
+                position: 0,
+                status: None,
+                exited: false,
+                arch,
+            })),
+        }
+    }
+
+    pub fn pid(&self) -> Pid {
+        Pid::from_raw(MOCK_PID)
+    }
+
+    pub fn process(&self) -> RefMut<'_, MockProcess> {
+        self.process.borrow_mut()
+    }
+}
+
+impl InferiorBackend for MockBackend {
+    fn wait(&mut self, _tid: Pid, options: Option<WaitPidFlag>) -> Result<WaitStatus, DeetError> {
+        let mut process = self.process();
+        match process.status.take() {
+            Some(status) => Ok(status),
+            None if options.is_some_and(|options| options.contains(WaitPidFlag::WNOHANG)) => {
+                Ok(WaitStatus::StillAlive)
+            }
+            None => Err(DeetError::Ptrace(nix::Error::Sys(nix::errno::Errno::ECHILD))),
+        }
+    }
+
+    fn event_message(&self, _tid: Pid) -> Result<usize, DeetError> {
+        Err(DeetError::Ptrace(nix::Error::UnsupportedOperation))
+    }
+
+    fn seize(&mut self, _options: ptrace::Options) -> Result<(), DeetError> {
+        Ok(())
+    }
+
+    /// The mock process only ever runs while the debugger waits for it, so there is nothing to
+    /// interrupt; this just reports the stop.
+    fn interrupt(&mut self, _tid: Pid) -> Result<(), DeetError> {
+        let mut process = self.process();
+        process.check_alive()?;
+        process.status = Some(WaitStatus::PtraceEvent(self.pid(), Signal::SIGTRAP, 128));
+        Ok(())
+    }
+
+    fn step(&mut self, _tid: Pid) -> Result<(), DeetError> {
+        let mut process = self.process();
+        process.check_alive()?;
+        if process.execute() {
+            process.status = Some(WaitStatus::Stopped(self.pid(), Signal::SIGTRAP));
+        }
+        Ok(())
+    }
+
+    fn cont(&mut self, _tid: Pid) -> Result<(), DeetError> {
+        let mut process = self.process();
+        process.check_alive()?;
+        while process.execute() {}
+        Ok(())
+    }
+
+    fn kill(&mut self) -> Result<(), DeetError> {
+        let mut process = self.process();
+        process.check_alive()?;
+        process.exited = true;
+        process.status = Some(WaitStatus::Signaled(self.pid(), Signal::SIGKILL, false));
+        Ok(())
+    }
+
+    fn detach(&mut self, _tid: Pid) -> Result<(), DeetError> {
+        self.process().exited = true;
+        Ok(())
+    }
+
+    fn peek(&self, addr: usize) -> Result<usize, DeetError> {
+        let process = self.process();
+        process.check_alive()?;
+        let mut bytes = [0u8; 8];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = process.byte(addr + i);
+        }
+        Ok(usize::from_le_bytes(bytes))
+    }
+
+    fn poke(&mut self, addr: usize, word: usize) -> Result<(), DeetError> {
+        let mut process = self.process();
+        process.check_alive()?;
+        for (i, byte) in word.to_le_bytes().iter().enumerate() {
+            process.memory.insert(addr + i, *byte);
+        }
+        Ok(())
+    }
+
+    fn registers(&self, _tid: Pid) -> Result<Registers, DeetError> {
+        Ok(self.process().regs)
+    }
+
+    fn set_registers(&mut self, _tid: Pid, regs: Registers) -> Result<(), DeetError> {
+        self.process().regs = regs;
+        Ok(())
+    }
+
+    fn fp_registers(&self, _tid: Pid) -> Result<Vec<u8>, DeetError> {
+        Err(DeetError::Ptrace(nix::Error::UnsupportedOperation))
+    }
+
+    fn fault_address(&self, _tid: Pid) -> Result<usize, DeetError> {
+        Err(DeetError::Ptrace(nix::Error::UnsupportedOperation))
+    }
+
+    fn machine(&self) -> Result<u16, DeetError> {
+        Ok(NATIVE_MACHINE)
+    }
+
+    fn maps(&self) -> Result<String, DeetError> {
+        Ok(String::new())
+    }
+}
@@ -0,0 +1,153 @@
+//! Announcing that an inferior running in the background (`continue &`) has stopped. A waiter
+//! thread checks on the inferior's threads with waitid without reaping anything, so that the
+//! debugger's own waitpid still sees the stop, and prints a line above the prompt when one of
+//! them changes state. The debugger then picks up the details the next time it looks (before
+//! running a command, or when the user just presses enter).
+//!
+//! Only the debugger's own thread can use ptrace, so a thread that starts another one stays
+//! stopped until the debugger next looks and takes on the new thread. That is not announced.
+//!
+//! Stopped inferiors are watched for dying while the debugger waits for the user, as when
+//! something outside kills them. Stops would wake a tracer's waitid whatever it asked for, so
+//! that waits on a pidfd instead, which only becomes readable once the process has exited.
+
+use nix::unistd::{self, Pid};
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// How long the waiter lets pass between checks on the threads.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// What waitid reports as the status of a thread stopped at a PTRACE_EVENT_CLONE: SIGTRAP, with
+/// the event above it.
+const CLONE_EVENT_STATUS: i32 = libc::SIGTRAP | (libc::PTRACE_EVENT_CLONE << 8);
+
+/// Watches an inferior running in the background. Dropping it keeps it quiet about whatever
+/// happens afterwards, as when the debugger stops or kills the inferior itself.
+pub struct Waiter {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Waiter {
+    /// Starts watching threads `tids` of process `pid`, which is inferior `id`.
+    pub fn spawn(id: usize, pid: Pid, tids: Vec<Pid>) -> Waiter {
+        let cancelled = Arc::new(AtomicBool::new(false));
+        {
+            let cancelled = cancelled.clone();
+            thread::spawn(move || {
+                if wait_for_change(tids, &cancelled) && !cancelled.load(Ordering::SeqCst) {
+                    announce(&format!("[Inferior {} (process {}) stopped]", id, pid));
+                }
+            });
+        }
+        Waiter { cancelled }
+    }
+}
+
+impl Drop for Waiter {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Watches an inferior for exiting, for as long as it lives. Only exits that happen while the
+/// debugger is idle at the prompt are announced; the others the debugger sees for itself.
+pub struct ExitWatch {
+    idle: Arc<AtomicBool>,
+}
+
+impl ExitWatch {
+    /// Starts watching process `pid`, which is inferior `id`.
+    pub fn spawn(id: usize, pid: Pid) -> ExitWatch {
+        let idle = Arc::new(AtomicBool::new(false));
+        {
+            let idle = idle.clone();
+            thread::spawn(move || {
+                if wait_for_exit(pid) && idle.load(Ordering::SeqCst) {
+                    announce(&format!("[Inferior {} (process {}) exited]", id, pid));
+                }
+            });
+        }
+        ExitWatch { idle }
+    }
+
+    /// Tells the watch whether the debugger is waiting for the user rather than for the
+    /// inferior.
+    pub fn set_idle(&self, idle: bool) {
+        self.idle.store(idle, Ordering::SeqCst);
+    }
+}
+
+impl Drop for ExitWatch {
+    fn drop(&mut self) {
+        self.set_idle(false);
+    }
+}
+
+/// Blocks until one of threads `tids` stops (other than to start a thread) or exits, leaving
+/// the state change for waitpid to collect. waitid waits for one thread or for every child, so
+/// the threads are checked on in turns. Returns false if cancelled, or if there is nothing left
+/// to wait for.
+fn wait_for_change(mut tids: Vec<Pid>, cancelled: &AtomicBool) -> bool {
+    while !tids.is_empty() && !cancelled.load(Ordering::SeqCst) {
+        let mut i = 0;
+        while i < tids.len() {
+            let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+            let flags =
+                libc::WEXITED | libc::WSTOPPED | libc::WNOWAIT | libc::WNOHANG | libc::__WALL;
+            let tid = tids[i].as_raw() as libc::id_t;
+            let result = unsafe { libc::waitid(libc::P_PID, tid, &mut info, flags) };
+            if result < 0 && nix::errno::Errno::last() == nix::errno::Errno::EINTR {
+                continue;
+            }
+            // With WNOHANG, nothing has happened if no pid is filled in
+            let changed = result == 0 && unsafe { info.si_pid() } != 0;
+            if changed && unsafe { info.si_status() } != CLONE_EVENT_STATUS {
+                return true;
+            }
+            if result < 0 || changed {
+                tids.remove(i);
+            } else {
+                i += 1;
+            }
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+    false
+}
+
+/// Blocks until `pid` has exited. Returns false if that cannot be waited for, as on kernels
+/// without pidfds (before 5.3).
+fn wait_for_exit(pid: Pid) -> bool {
+    let pidfd = unsafe { libc::syscall(libc::SYS_pidfd_open, pid.as_raw(), 0) } as libc::c_int;
+    if pidfd < 0 {
+        return false;
+    }
+    let mut poll = libc::pollfd { fd: pidfd, events: libc::POLLIN, revents: 0 };
+    let exited = loop {
+        if unsafe { libc::poll(&mut poll, 1, -1) } >= 0 {
+            break true;
+        }
+        if nix::errno::Errno::last() != nix::errno::Errno::EINTR {
+            break false;
+        }
+    };
+    let _ = unistd::close(pidfd);
+    exited
+}
+
+/// Prints `message` on a line of its own. At a terminal, the prompt (and whatever has been typed
+/// after it) is cleared first and the prompt put back afterwards; the terminal is in raw mode
+/// while the line editor waits for input, so lines end in "\r\n".
+fn announce(message: &str) {
+    let mut stdout = std::io::stdout();
+    if unistd::isatty(libc::STDOUT_FILENO).unwrap_or(false) {
+        let _ = write!(stdout, "\r\x1b[K{}\r\n(deet) ", message);
+    } else {
+        let _ = writeln!(stdout, "{}", message);
+    }
+    let _ = stdout.flush();
+}
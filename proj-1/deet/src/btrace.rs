@@ -0,0 +1,741 @@
+//! Recording of execution by the processor, with Intel Processor Trace (`record btrace`). Rather
+//! than single-stepping, deet asks the kernel (through perf_event_open) to have the processor
+//! write out a compressed account of every branch the inferior's threads take: one bit for each
+//! conditional branch, and the target of each indirect jump, call and return. The threads run at
+//! close to full speed, and when they stop, the account is read back and replayed over the
+//! program's code (disassembled by objdump) to recover the instructions that ran.
+//!
+//! The trace is kept in a ring buffer that the processor overwrites, so it always holds the
+//! latest stretch of execution: what led up to a crash is there when the inferior stops.
+
+use crate::disassemble::Instruction;
+use crate::error::DeetError;
+use nix::unistd::Pid;
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::io;
+use std::os::unix::io::RawFd;
+use std::sync::atomic::{fence, Ordering};
+
+/// Where the kernel describes the processor's Intel PT support, if it has any.
+const PT_DEVICE: &str = "/sys/bus/event_source/devices/intel_pt";
+
+/// The size of the ring buffer the processor writes the trace of each thread to, in pages. It
+/// must be a power of two, and small enough for an unprivileged user to lock in memory.
+const AUX_PAGES: usize = 64;
+
+/// The size of the buffer the kernel writes its own records to, in pages. deet never reads
+/// them, and loses nothing when they overflow.
+const DATA_PAGES: usize = 1;
+
+/// Where the fields deet needs sit in the header page of the buffers (`perf_event_mmap_page`).
+const AUX_HEAD: usize = 1056;
+const AUX_OFFSET: usize = 1072;
+const AUX_SIZE: usize = 1080;
+
+const PERF_EVENT_IOC_ENABLE: libc::c_ulong = 0x2400;
+
+/// The most instructions followed without the trace saying anything, beyond which the code is
+/// assumed not to be what the processor ran.
+const MAX_WALK: usize = 1 << 20;
+
+/// A Packet Stream Boundary, which the processor writes out regularly so that a decoder can
+/// find its place in a trace it joins halfway through.
+const PSB: [u8; 16] = [
+    0x02, 0x82, 0x02, 0x82, 0x02, 0x82, 0x02, 0x82, 0x02, 0x82, 0x02, 0x82, 0x02, 0x82, 0x02, 0x82,
+];
+
+/// `struct perf_event_attr`, up to `aux_watermark` (PERF_ATTR_SIZE_VER5).
+#[repr(C)]
+#[derive(Default)]
+struct PerfEventAttr {
+    kind: u32,
+    size: u32,
+    config: u64,
+    sample_period: u64,
+    sample_type: u64,
+    read_format: u64,
+    flags: u64,
+    wakeup_events: u32,
+    bp_type: u32,
+    config1: u64,
+    config2: u64,
+    branch_sample_type: u64,
+    sample_regs_user: u64,
+    sample_stack_user: u32,
+    clockid: i32,
+    sample_regs_intr: u64,
+    aux_watermark: u32,
+    sample_max_stack: u16,
+    reserved: u16,
+}
+
+/// Bits of `PerfEventAttr::flags`.
+const DISABLED: u64 = 1 << 0;
+const EXCLUDE_KERNEL: u64 = 1 << 5;
+const EXCLUDE_HV: u64 = 1 << 6;
+
+/// Returns the perf event type of Intel PT, or None if the processor (or the kernel) lacks it.
+fn pt_type() -> Option<u32> {
+    fs::read_to_string(format!("{}/type", PT_DEVICE)).ok()?.trim().parse().ok()
+}
+
+/// Returns the bit of the event config that turns on the Intel PT option `name`, as the kernel
+/// describes it (`config:11`), or `default` where it does not.
+fn config_bit(name: &str, default: u32) -> u32 {
+    fs::read_to_string(format!("{}/format/{}", PT_DEVICE, name))
+        .ok()
+        .and_then(|format| format.trim().strip_prefix("config:")?.parse().ok())
+        .unwrap_or(default)
+}
+
+/// The trace of one thread, as the kernel hands it over: an event, and the two buffers mapped
+/// from it.
+struct ThreadTrace {
+    fd: RawFd,
+    /// The header page followed by the kernel's own records.
+    header: *mut u8,
+    /// The ring buffer the processor writes to. Mapping it read-only tells the kernel to let the
+    /// processor overwrite it rather than stop when it is full.
+    aux: *mut u8,
+}
+
+impl ThreadTrace {
+    fn open(tid: Pid, kind: u32, config: u64) -> Result<ThreadTrace, DeetError> {
+        let attr = PerfEventAttr {
+            kind,
+            size: std::mem::size_of::<PerfEventAttr>() as u32,
+            config,
+            flags: DISABLED | EXCLUDE_KERNEL | EXCLUDE_HV,
+            ..Default::default()
+        };
+        let fd = unsafe {
+            libc::syscall(libc::SYS_perf_event_open, &attr, tid.as_raw(), -1, -1, 0)
+        } as RawFd;
+        if fd < 0 {
+            let err = io::Error::last_os_error();
+            return Err(match err.raw_os_error() {
+                Some(libc::EACCES) | Some(libc::EPERM) => DeetError::Parse(String::from(
+                    "Not allowed to trace the process with Intel PT. Lowering \
+                     /proc/sys/kernel/perf_event_paranoid may help.",
+                )),
+                _ => DeetError::Io(err),
+            });
+        }
+        let mut trace = ThreadTrace {
+            fd,
+            header: std::ptr::null_mut(),
+            aux: std::ptr::null_mut(),
+        };
+        let page = page_size();
+        let header = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                (1 + DATA_PAGES) * page,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                fd,
+                0,
+            )
+        };
+        if header == libc::MAP_FAILED {
+            return Err(DeetError::Io(io::Error::last_os_error()));
+        }
+        trace.header = header as *mut u8;
+        unsafe {
+            std::ptr::write_volatile(trace.field(AUX_OFFSET), ((1 + DATA_PAGES) * page) as u64);
+            std::ptr::write_volatile(trace.field(AUX_SIZE), (AUX_PAGES * page) as u64);
+        }
+        let aux = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                AUX_PAGES * page,
+                libc::PROT_READ,
+                libc::MAP_SHARED,
+                fd,
+                ((1 + DATA_PAGES) * page) as libc::off_t,
+            )
+        };
+        if aux == libc::MAP_FAILED {
+            return Err(DeetError::Io(io::Error::last_os_error()));
+        }
+        trace.aux = aux as *mut u8;
+        if unsafe { libc::ioctl(fd, PERF_EVENT_IOC_ENABLE, 0) } < 0 {
+            return Err(DeetError::Io(io::Error::last_os_error()));
+        }
+        Ok(trace)
+    }
+
+    fn field(&self, offset: usize) -> *mut u64 {
+        unsafe { self.header.add(offset) as *mut u64 }
+    }
+
+    /// Returns what the ring buffer holds, oldest first. The thread must be stopped: the
+    /// processor only hands its trace to the kernel when the thread is switched out.
+    fn bytes(&self) -> Vec<u8> {
+        let size = AUX_PAGES * page_size();
+        let head = unsafe { std::ptr::read_volatile(self.field(AUX_HEAD)) } as usize;
+        fence(Ordering::Acquire);
+        let aux = unsafe { std::slice::from_raw_parts(self.aux, size) };
+        if head <= size {
+            return aux[..head].to_vec();
+        }
+        // The oldest bytes are those the processor is about to overwrite
+        let split = head % size;
+        [&aux[split..], &aux[..split]].concat()
+    }
+}
+
+impl Drop for ThreadTrace {
+    fn drop(&mut self) {
+        let page = page_size();
+        unsafe {
+            if !self.aux.is_null() {
+                libc::munmap(self.aux as *mut libc::c_void, AUX_PAGES * page);
+            }
+            if !self.header.is_null() {
+                libc::munmap(self.header as *mut libc::c_void, (1 + DATA_PAGES) * page);
+            }
+            libc::close(self.fd);
+        }
+    }
+}
+
+fn page_size() -> usize {
+    unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize }
+}
+
+/// The traces of an inferior's threads, and the code decoded to replay them.
+pub struct BranchTrace {
+    threads: HashMap<Pid, ThreadTrace>,
+    pub code: Code,
+}
+
+impl BranchTrace {
+    /// Starts tracing threads `tids`.
+    pub fn start(tids: &[Pid]) -> Result<BranchTrace, DeetError> {
+        let kind = pt_type().ok_or_else(|| {
+            DeetError::Parse(String::from(
+                "This processor does not support Intel Processor Trace (the kernel has no \
+                 intel_pt event source). Use \"record\" to record by single-stepping instead.",
+            ))
+        })?;
+        // Returns are made to show their target, like other indirect branches, rather than
+        // being left for the decoder to match up with their calls
+        let config = [("pt", 0), ("branch", 13), ("noretcomp", 11)]
+            .iter()
+            .fold(0u64, |config, (name, default)| config | 1 << config_bit(name, *default));
+        let mut threads = HashMap::new();
+        for tid in tids {
+            threads.insert(*tid, ThreadTrace::open(*tid, kind, config)?);
+        }
+        Ok(BranchTrace { threads, code: Code::default() })
+    }
+
+    /// Returns the trace of thread `tid`, if it is being traced.
+    pub fn trace(&self, tid: Pid) -> Option<Vec<u8>> {
+        Some(self.threads.get(&tid)?.bytes())
+    }
+}
+
+/// What an instruction does to the flow of execution.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Branch {
+    /// Nothing: execution goes on with the next instruction.
+    None,
+    /// A conditional branch to an address. The trace says whether it was taken.
+    Conditional(usize),
+    /// A jump or call to an address.
+    Direct(usize),
+    /// An indirect jump or call, a return, or a system call. The trace says where it went.
+    Indirect,
+}
+
+/// Works out from objdump's text of an instruction where it may send execution. The targets of
+/// direct branches are moved by `bias`, where the code was disassembled from a shared library.
+pub fn branch(text: &str, bias: usize) -> Branch {
+    let mut words = text
+        .split_whitespace()
+        .skip_while(|word| matches!(*word, "bnd" | "notrack" | "rep" | "repz" | "repe"));
+    let mnemonic = words.next().unwrap_or("");
+    let target = words
+        .next()
+        .and_then(|operand| usize::from_str_radix(operand, 16).ok())
+        .map(|target| target.wrapping_add(bias));
+    match mnemonic {
+        "ret" | "retq" | "retw" | "syscall" | "sysenter" | "int" | "int3" => Branch::Indirect,
+        "jmp" | "jmpq" | "call" | "callq" => target.map_or(Branch::Indirect, Branch::Direct),
+        _ if mnemonic.starts_with('j') || mnemonic.starts_with("loop") => {
+            target.map_or(Branch::None, Branch::Conditional)
+        }
+        _ => Branch::None,
+    }
+}
+
+/// The instructions decoded so far, by address.
+#[derive(Default)]
+pub struct Code {
+    instructions: HashMap<usize, (String, Branch, usize)>,
+    /// The address ranges that have been disassembled.
+    ranges: Vec<(usize, usize)>,
+}
+
+impl Code {
+    /// Adds `instructions`, disassembled from `[start, end)` of a file loaded `bias` bytes away
+    /// from the addresses it gives.
+    pub fn add(&mut self, instructions: &[Instruction], start: usize, end: usize, bias: usize) {
+        let next = instructions.iter().skip(1).map(|instruction| instruction.address + bias);
+        let next = next.chain(std::iter::once(end + bias));
+        for (instruction, next) in instructions.iter().zip(next) {
+            let branch = branch(&instruction.text, bias);
+            self.instructions
+                .insert(instruction.address + bias, (instruction.text.clone(), branch, next));
+        }
+        self.ranges.push((start + bias, end + bias));
+    }
+
+    /// Whether the code at `addr` has been disassembled, whether or not an instruction starts
+    /// there.
+    pub fn covers(&self, addr: usize) -> bool {
+        self.ranges.iter().any(|(start, end)| *start <= addr && addr < *end)
+    }
+
+    /// Returns the text of the instruction at `addr`, what it does to the flow of execution,
+    /// and the address of the instruction after it.
+    pub fn get(&self, addr: usize) -> Option<&(String, Branch, usize)> {
+        self.instructions.get(&addr)
+    }
+}
+
+/// The packets of a trace that matter for working out the flow of execution. Timing, power and
+/// paging packets are dropped.
+#[derive(Debug, PartialEq)]
+pub enum Packet {
+    Psb,
+    PsbEnd,
+    /// Whether each conditional branch was taken, oldest first.
+    Tnt(Vec<bool>),
+    /// The target of an indirect branch, or of an interrupt or exception.
+    Tip(Option<usize>),
+    /// Tracing starts (again) at an address.
+    TipPge(Option<usize>),
+    /// Tracing stops, for example because the thread entered the kernel.
+    TipPgd(Option<usize>),
+    /// The address of the instruction an interrupt or exception came at. Within a PSB, it is
+    /// where the thread is instead.
+    Fup(Option<usize>),
+    /// Part of the trace was lost, because the processor could not write it out fast enough or
+    /// because it could not be decoded.
+    Ovf,
+}
+
+/// Splits a trace into packets, starting from the first PSB: what comes before it cannot be
+/// told apart from the end of a packet overwritten in the ring buffer.
+pub fn decode(bytes: &[u8]) -> Vec<Packet> {
+    let mut packets = Vec::new();
+    let mut pos = match find_psb(bytes, 0) {
+        Some(pos) => pos,
+        None => return packets,
+    };
+    let mut last_ip = 0;
+    while pos < bytes.len() {
+        match packet(&bytes[pos..], &mut last_ip) {
+            Some((len, packet)) => {
+                packets.extend(packet);
+                pos += len;
+            }
+            // Pick the trace up again at the next PSB, unless it was merely cut off
+            None => match find_psb(bytes, pos + 1) {
+                Some(next) => {
+                    packets.push(Packet::Ovf);
+                    pos = next;
+                }
+                None => break,
+            },
+        }
+    }
+    packets
+}
+
+fn find_psb(bytes: &[u8], from: usize) -> Option<usize> {
+    let rest = bytes.get(from..)?;
+    Some(from + rest.windows(PSB.len()).position(|window| window == PSB)?)
+}
+
+/// Decodes the packet at the start of `bytes`, returning its length, or None if it is not one
+/// or is cut off. `last_ip` is the last address a packet carried, which later ones give only the
+/// low bytes of.
+fn packet(bytes: &[u8], last_ip: &mut u64) -> Option<(usize, Option<Packet>)> {
+    let header = bytes[0];
+    let (len, packet) = match header {
+        0x00 => (1, None),
+        0x02 => return extended(bytes, last_ip),
+        // Short TNT, whose stop bit is above bit 0
+        _ if header & 1 == 0 => (1, Some(Packet::Tnt(tnt_bits(u64::from(header >> 1))?))),
+        // CYC, continued while the low bit of each byte after the first is set
+        _ if header & 3 == 3 => {
+            let mut len = 1;
+            if header & 4 != 0 {
+                while bytes.get(len)? & 1 != 0 {
+                    len += 1;
+                }
+                len += 1;
+            }
+            (len, None)
+        }
+        _ => match header & 0x1f {
+            0x01 | 0x0d | 0x11 | 0x1d => {
+                let (len, ip) = ip(bytes, header >> 5, last_ip)?;
+                let packet = match header & 0x1f {
+                    0x01 => Packet::TipPgd(ip),
+                    0x0d => Packet::Tip(ip),
+                    0x11 => Packet::TipPge(ip),
+                    _ => Packet::Fup(ip),
+                };
+                (len, Some(packet))
+            }
+            _ => match header {
+                // TSC, MTC, MODE
+                0x19 => (8, None),
+                0x59 | 0x99 => (2, None),
+                _ => return None,
+            },
+        },
+    };
+    if bytes.len() < len {
+        return None;
+    }
+    Some((len, packet))
+}
+
+/// Decodes the packets that start with 0x02.
+fn extended(bytes: &[u8], last_ip: &mut u64) -> Option<(usize, Option<Packet>)> {
+    let (len, packet) = match *bytes.get(1)? {
+        0x82 => {
+            if !bytes.starts_with(&PSB) {
+                return None;
+            }
+            *last_ip = 0;
+            (PSB.len(), Some(Packet::Psb))
+        }
+        0x23 => (2, Some(Packet::PsbEnd)),
+        0xa3 => (8, Some(Packet::Tnt(tnt_bits(payload(bytes, 2, 6)?)?))),
+        0xf3 => (2, Some(Packet::Ovf)),
+        // TraceStop, EXSTOP
+        0x83 | 0x62 | 0xe2 => (2, None),
+        // CBR, PWRE
+        0x03 | 0x22 => (4, None),
+        // TMA, VMCS, PWRX
+        0x73 | 0xc8 | 0xa2 => (7, None),
+        // PIP
+        0x43 => (8, None),
+        // MWAIT
+        0xc2 => (10, None),
+        // MNT
+        0xc3 if *bytes.get(2)? == 0x88 => (11, None),
+        // PTWRITE, with a 4 or 8 byte payload
+        kind if kind & 0x1f == 0x12 => match (kind >> 5) & 3 {
+            0 => (6, None),
+            1 => (10, None),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    if bytes.len() < len {
+        return None;
+    }
+    Some((len, packet))
+}
+
+/// Reads the little-endian number `len` bytes long at `start` in `bytes`.
+fn payload(bytes: &[u8], start: usize, len: usize) -> Option<u64> {
+    let bytes = bytes.get(start..start + len)?;
+    Some(bytes.iter().rev().fold(0, |value, byte| value << 8 | u64::from(*byte)))
+}
+
+/// Returns the bits below the highest set bit of `value`, which marks where they start, oldest
+/// first.
+fn tnt_bits(value: u64) -> Option<Vec<bool>> {
+    if value == 0 {
+        return None;
+    }
+    let stop = 63 - value.leading_zeros();
+    Some((0..stop).rev().map(|bit| (value >> bit) & 1 == 1).collect())
+}
+
+/// Decodes the address of a TIP or FUP packet, whose header gives how much of it there is.
+/// Returns the packet's length and the address, or None for the address if it was suppressed.
+fn ip(bytes: &[u8], ip_bytes: u8, last_ip: &mut u64) -> Option<(usize, Option<usize>)> {
+    let len = match ip_bytes {
+        0 => return Some((1, None)),
+        1 => 2,
+        2 => 4,
+        3 | 4 => 6,
+        6 => 8,
+        _ => return None,
+    };
+    let value = payload(bytes, 1, len)?;
+    let ip = match ip_bytes {
+        1 => *last_ip & !0xffff | value,
+        2 => *last_ip & !0xffff_ffff | value,
+        // Sign-extended from 48 bits
+        3 => ((value << 16) as i64 >> 16) as u64,
+        4 => *last_ip & !0xffff_ffff_ffff | value,
+        _ => value,
+    };
+    *last_ip = ip;
+    Some((1 + len, Some(ip as usize)))
+}
+
+/// An instruction the thread executed, or a stretch of execution that could not be recovered.
+#[derive(Debug, PartialEq)]
+pub enum Entry {
+    Instruction(usize),
+    Gap,
+}
+
+/// Replays a trace over the code it was recorded from, returning the instructions executed,
+/// oldest first. `code` returns what the instruction at an address does to the flow of
+/// execution and the address of the next one, or None if there is no code there it knows.
+pub fn reconstruct(
+    packets: &[Packet],
+    code: &mut dyn FnMut(usize) -> Option<(Branch, usize)>,
+) -> Vec<Entry> {
+    let mut flow = Flow { ip: None, tnt: VecDeque::new(), entries: Vec::new() };
+    let mut in_psb = false;
+    // Set after an overflow, whose FUP says where the trace resumes, like those within a PSB
+    let mut resync = false;
+    // Set after a FUP for an interrupt or exception: the TIP that follows is where it went, and
+    // the instruction at the FUP's address did not run
+    let mut interrupted = false;
+    for packet in packets {
+        match packet {
+            Packet::Psb => in_psb = true,
+            Packet::PsbEnd => in_psb = false,
+            Packet::Tnt(bits) => {
+                flow.tnt.extend(bits);
+                flow.walk(code, None);
+            }
+            Packet::Fup(ip) if in_psb || resync => {
+                resync = false;
+                if flow.ip.is_none() {
+                    flow.ip = *ip;
+                    flow.tnt.clear();
+                }
+            }
+            Packet::Fup(ip) => {
+                if ip.is_some() {
+                    flow.walk(code, *ip);
+                }
+                interrupted = true;
+            }
+            Packet::Tip(target) | Packet::TipPgd(target) => {
+                if !interrupted {
+                    match flow.walk(code, None) {
+                        Some(ip) => flow.entries.push(Entry::Instruction(ip)),
+                        // The trace and the code disagree
+                        None if flow.ip.is_some() => flow.lose(),
+                        None => {}
+                    }
+                }
+                interrupted = false;
+                flow.tnt.clear();
+                flow.ip = match packet {
+                    Packet::Tip(_) => *target,
+                    _ => None,
+                };
+            }
+            Packet::TipPge(target) => {
+                flow.tnt.clear();
+                flow.ip = *target;
+            }
+            Packet::Ovf => {
+                flow.lose();
+                resync = true;
+            }
+        }
+    }
+    flow.entries
+}
+
+/// Where `reconstruct` has got to.
+struct Flow {
+    /// The address of the next instruction, if known.
+    ip: Option<usize>,
+    /// Outcomes of conditional branches not yet reached.
+    tnt: VecDeque<bool>,
+    entries: Vec<Entry>,
+}
+
+impl Flow {
+    /// Follows the code as far as the trace read so far allows: up to `stop`, or up to a branch
+    /// it has not yet said the outcome of. Returns the address of the indirect branch it stopped
+    /// at, if it did, for the caller to record once it knows where the branch went.
+    fn walk(
+        &mut self,
+        code: &mut dyn FnMut(usize) -> Option<(Branch, usize)>,
+        stop: Option<usize>,
+    ) -> Option<usize> {
+        for _ in 0..MAX_WALK {
+            let ip = match self.ip {
+                Some(ip) if Some(ip) != stop => ip,
+                _ => return None,
+            };
+            let (branch, next) = match code(ip) {
+                Some(instruction) => instruction,
+                None => {
+                    self.lose();
+                    return None;
+                }
+            };
+            self.ip = match branch {
+                Branch::None => Some(next),
+                Branch::Direct(target) => Some(target),
+                Branch::Conditional(target) => match self.tnt.pop_front() {
+                    Some(true) => Some(target),
+                    Some(false) => Some(next),
+                    None => return None,
+                },
+                Branch::Indirect => return Some(ip),
+            };
+            self.entries.push(Entry::Instruction(ip));
+        }
+        self.lose();
+        None
+    }
+
+    /// Gives up following the code until the trace says where the thread is again.
+    fn lose(&mut self) {
+        self.ip = None;
+        self.tnt.clear();
+        if self.entries.last() != Some(&Entry::Gap) {
+            self.entries.push(Entry::Gap);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A PSB whose FUP says the thread is at `ip`.
+    fn psb(ip: u64) -> Vec<u8> {
+        let mut bytes = PSB.to_vec();
+        bytes.push(0xdd);
+        bytes.extend_from_slice(&ip.to_le_bytes());
+        bytes.extend_from_slice(&[0x02, 0x23]);
+        bytes
+    }
+
+    #[test]
+    fn test_decode() {
+        let mut bytes = vec![0x5a, 0x99, 0x00];
+        bytes.extend(psb(0x401126));
+        // Short TNT of taken, not taken; TIP with the low two bytes; PAD; TSC; TIP.PGD
+        bytes.extend_from_slice(&[0x0c, 0x2d, 0x40, 0x11, 0x00]);
+        bytes.extend_from_slice(&[0x19, 1, 2, 3, 4, 5, 6, 7, 0x01]);
+        assert_eq!(
+            decode(&bytes),
+            vec![
+                Packet::Psb,
+                Packet::Fup(Some(0x401126)),
+                Packet::PsbEnd,
+                Packet::Tnt(vec![true, false]),
+                Packet::Tip(Some(0x401140)),
+                Packet::TipPgd(None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_decode_long_tnt_and_resync() {
+        let mut bytes = psb(0);
+        // A long TNT holding three bits, then something that is not a packet
+        bytes.extend_from_slice(&[0x02, 0xa3, 0b1011, 0, 0, 0, 0, 0, 0x02, 0xff]);
+        bytes.extend(psb(0x7fff_1234_5678));
+        // TIP.PGE with a sign-extended 48-bit address
+        bytes.extend_from_slice(&[0x71, 0x9a, 0x78, 0x56, 0x34, 0x12, 0x80]);
+        assert_eq!(
+            decode(&bytes),
+            vec![
+                Packet::Psb,
+                Packet::Fup(Some(0)),
+                Packet::PsbEnd,
+                Packet::Tnt(vec![false, true, true]),
+                Packet::Ovf,
+                Packet::Psb,
+                Packet::Fup(Some(0x7fff_1234_5678)),
+                Packet::PsbEnd,
+                Packet::TipPge(Some(0xffff_8012_3456_789a)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_branch() {
+        assert_eq!(branch("jne    401136 <main+0x20>", 0), Branch::Conditional(0x401136));
+        assert_eq!(branch("call   1040 <puts@plt>", 0x10000), Branch::Direct(0x11040));
+        assert_eq!(branch("bnd jmp *%r11", 0), Branch::Indirect);
+        assert_eq!(branch("call   *0x2fe2(%rip)", 0), Branch::Indirect);
+        assert_eq!(branch("ret", 0), Branch::Indirect);
+        assert_eq!(branch("syscall", 0), Branch::Indirect);
+        assert_eq!(branch("mov    %rsp,%rbp", 0), Branch::None);
+    }
+
+    #[test]
+    fn test_reconstruct() {
+        // 0x10: a loop that runs twice and calls 0x40 through a pointer, which returns
+        let program: HashMap<usize, (Branch, usize)> = vec![
+            (0x10, (Branch::None, 0x14)),
+            (0x14, (Branch::Indirect, 0x16)),
+            (0x16, (Branch::Conditional(0x10), 0x18)),
+            (0x18, (Branch::Direct(0x30), 0x1d)),
+            (0x30, (Branch::Indirect, 0x32)),
+            (0x40, (Branch::Indirect, 0x41)),
+        ]
+        .into_iter()
+        .collect();
+        let packets = vec![
+            Packet::Psb,
+            Packet::Fup(Some(0x10)),
+            Packet::PsbEnd,
+            Packet::Tip(Some(0x40)),
+            Packet::Tip(Some(0x16)),
+            Packet::Tnt(vec![true]),
+            Packet::Tip(Some(0x40)),
+            Packet::Tip(Some(0x16)),
+            Packet::Tnt(vec![false]),
+            // A fault at 0x30
+            Packet::Fup(Some(0x30)),
+            Packet::TipPgd(None),
+        ];
+        let entries = reconstruct(&packets, &mut |ip| program.get(&ip).copied());
+        let addresses: Vec<usize> = entries
+            .iter()
+            .map(|entry| match entry {
+                Entry::Instruction(ip) => *ip,
+                Entry::Gap => 0,
+            })
+            .collect();
+        assert_eq!(addresses, vec![0x10, 0x14, 0x40, 0x16, 0x10, 0x14, 0x40, 0x16, 0x18]);
+    }
+
+    #[test]
+    fn test_reconstruct_gap() {
+        let packets = vec![
+            Packet::Psb,
+            Packet::Fup(Some(0x10)),
+            Packet::PsbEnd,
+            Packet::Ovf,
+            Packet::Fup(Some(0x20)),
+            Packet::TipPgd(None),
+        ];
+        let program = |ip| match ip {
+            0x10 => Some((Branch::None, 0x12)),
+            0x20 => Some((Branch::Indirect, 0x22)),
+            _ => None,
+        };
+        let entries = reconstruct(&packets, &mut |ip| program(ip));
+        assert_eq!(entries, vec![Entry::Gap, Entry::Instruction(0x20)]);
+    }
+}
@@ -0,0 +1,212 @@
+//! Framing of the inferior's output. The inferior writes its stdout and stderr to a pty each (so
+//! that it still believes it is talking to a terminal, and line-buffers), and a relay thread
+//! copies whatever comes out to deet's own stdout, one labeled line at a time. Before deet reports
+//! a stop or shows the prompt, it drains the ptys itself, so that the program's output always
+//! comes first.
+
+use crate::error::DeetError;
+use crate::output;
+use nix::errno::Errno;
+use nix::fcntl::{fcntl, FcntlArg, FdFlag, OFlag};
+use nix::poll::{poll, PollFd, PollFlags};
+use nix::pty::openpty;
+use nix::sys::termios::{self, OutputFlags, SetArg};
+use nix::unistd;
+use std::os::unix::io::RawFd;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+const LABELS: [&str; 2] = ["out> ", "err> "];
+
+/// How long the relay thread sleeps between checks of whether it should stop, in milliseconds.
+const POLL_INTERVAL: i32 = 100;
+
+struct Stream {
+    master: RawFd,
+    /// Cleared once nothing holds the slave side open any more.
+    open: bool,
+}
+
+/// The state shared between the relay thread and the debugger.
+struct Relay {
+    streams: [Stream; 2],
+    /// The stream whose last line has not been finished yet, if any.
+    mid_line: Option<usize>,
+}
+
+impl Relay {
+    /// Reads whatever the streams have to offer and prints it. The streams take turns, a chunk
+    /// at a time, so that a flood on stdout does not hold back what was written to stderr.
+    fn drain(&mut self) {
+        let mut buf = [0u8; 4096];
+        let mut more = true;
+        while more {
+            more = false;
+            for i in 0..self.streams.len() {
+                if !self.streams[i].open {
+                    continue;
+                }
+                match unistd::read(self.streams[i].master, &mut buf) {
+                    Ok(0) => self.streams[i].open = false,
+                    Ok(len) => {
+                        output::program(self.frame(i, &buf[..len]));
+                        more = true;
+                    }
+                    Err(nix::Error::Sys(Errno::EAGAIN)) => {}
+                    Err(nix::Error::Sys(Errno::EINTR)) => more = true,
+                    // EIO: every process holding the slave side has gone
+                    Err(_) => self.streams[i].open = false,
+                }
+            }
+        }
+    }
+
+    /// Labels each line of `bytes`, which came out of stream `i`.
+    fn frame(&mut self, i: usize, bytes: &[u8]) -> String {
+        let mut text = String::new();
+        for line in String::from_utf8_lossy(bytes).split_inclusive('\n') {
+            if self.mid_line != Some(i) {
+                if self.mid_line.is_some() {
+                    text.push('\n');
+                }
+                text.push_str(LABELS[i]);
+            }
+            text.push_str(line);
+            self.mid_line = if line.ends_with('\n') { None } else { Some(i) };
+        }
+        text
+    }
+
+    /// Finishes off a line the program has left unterminated, such as a prompt for input.
+    fn end_line(&mut self) {
+        if self.mid_line.take().is_some() {
+            output::info("");
+        }
+    }
+}
+
+/// The ptys an inferior's output goes to, and the thread relaying it.
+pub struct OutputCapture {
+    relay: Arc<Mutex<Relay>>,
+    /// The slave ends, for the inferior to write to. Closed once it has been started.
+    slaves: Option<(RawFd, RawFd)>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl OutputCapture {
+    pub fn open() -> Result<OutputCapture, DeetError> {
+        let stdout = open_pty()?;
+        let stderr = open_pty()?;
+        let relay = Arc::new(Mutex::new(Relay {
+            streams: [
+                Stream { master: stdout.0, open: true },
+                Stream { master: stderr.0, open: true },
+            ],
+            mid_line: None,
+        }));
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let relay = relay.clone();
+            let stop = stop.clone();
+            thread::spawn(move || relay_output(&relay, &stop))
+        };
+        Ok(OutputCapture {
+            relay,
+            slaves: Some((stdout.1, stderr.1)),
+            stop,
+            thread: Some(thread),
+        })
+    }
+
+    /// The file descriptors the inferior's stdout and stderr should be pointed at.
+    pub fn slaves(&self) -> Option<(RawFd, RawFd)> {
+        self.slaves
+    }
+
+    /// Closes our copies of the slave ends, once the inferior has its own. The ptys then hang up
+    /// when the inferior (and anything it forks) is gone.
+    pub fn close_slaves(&mut self) {
+        if let Some((stdout, stderr)) = self.slaves.take() {
+            let _ = unistd::close(stdout);
+            let _ = unistd::close(stderr);
+        }
+    }
+
+    /// Prints everything the inferior has written so far.
+    pub fn flush(&self) {
+        let mut relay = self.relay.lock().unwrap();
+        relay.drain();
+        relay.end_line();
+    }
+}
+
+impl Drop for OutputCapture {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        self.flush();
+        self.close_slaves();
+        for stream in &self.relay.lock().unwrap().streams {
+            let _ = unistd::close(stream.master);
+        }
+    }
+}
+
+/// Opens a pty for the inferior to write to, returning its master and slave ends. Neither leaks
+/// into the programs the inferior execs, and newlines are left alone rather than turned into
+/// `\r\n`.
+fn open_pty() -> Result<(RawFd, RawFd), DeetError> {
+    let pty = openpty(None, None)?;
+    let mut attrs = termios::tcgetattr(pty.slave)?;
+    attrs.output_flags.remove(OutputFlags::ONLCR);
+    termios::tcsetattr(pty.slave, SetArg::TCSANOW, &attrs)?;
+    for fd in &[pty.master, pty.slave] {
+        fcntl(*fd, FcntlArg::F_SETFD(FdFlag::FD_CLOEXEC))?;
+    }
+    fcntl(pty.master, FcntlArg::F_SETFL(OFlag::O_NONBLOCK))?;
+    Ok((pty.master, pty.slave))
+}
+
+/// The relay thread: prints the inferior's output as it arrives, until told to stop or until
+/// both ptys have hung up.
+fn relay_output(relay: &Mutex<Relay>, stop: &AtomicBool) {
+    while !stop.load(Ordering::Relaxed) {
+        let mut fds: Vec<PollFd> = {
+            let relay = relay.lock().unwrap();
+            let open = relay.streams.iter().filter(|stream| stream.open);
+            open.map(|stream| PollFd::new(stream.master, PollFlags::POLLIN)).collect()
+        };
+        if fds.is_empty() {
+            break;
+        }
+        if let Ok(ready) = poll(&mut fds, POLL_INTERVAL) {
+            if ready > 0 {
+                relay.lock().unwrap().drain();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_frame() {
+        let mut relay = Relay {
+            streams: [Stream { master: -1, open: false }, Stream { master: -1, open: false }],
+            mid_line: None,
+        };
+        assert_eq!(relay.frame(0, b"hello\nworld\n"), "out> hello\nout> world\n");
+        assert_eq!(relay.frame(0, b"Name: "), "out> Name: ");
+        assert_eq!(relay.frame(0, b"deet\n"), "deet\n");
+        assert_eq!(relay.frame(0, b"partial"), "out> partial");
+        // Another stream breaks into an unfinished line on a line of its own
+        assert_eq!(relay.frame(1, b"oops\n"), "\nerr> oops\n");
+        assert_eq!(relay.mid_line, None);
+    }
+}
@@ -1,23 +1,68 @@
-use crate::debugger_command::DebuggerCommand;
-use crate::inferior::{Inferior, Status};
-use crate::dwarf_data::{DwarfData, Error as DwarfError};
+use crate::arch::{FpValue, Registers, Slot};
+use crate::background::{ExitWatch, Waiter};
+use crate::btrace::{self, BranchTrace, Entry};
+use crate::capture::OutputCapture;
+use crate::debugger_command::{parse_integer, DebuggerCommand};
+use crate::inferior::{
+    CallResult, ChildIo, FrameSetup, Inferior, Resume, SharedLibrary, Status, Thread, ThreadEvent,
+};
+use crate::location::{self, Machine, Place};
+use crate::disassemble;
+use crate::dwarf_data::{DwarfData, File, Location, Type, TypeKind, Variable};
+use crate::error::DeetError;
+use crate::format::{self, Format, FormatSpec};
+use crate::heap::HeapTrace;
+use crate::output::{self, Style};
+use crate::record::{self, Recording, Step, RECORD_LIMIT, STACK_WINDOW};
+use crate::script::{self, BreakpointInfo, Scripting, StopEvent};
+use crate::settings::{self, FollowForkMode, Settings};
+use crate::symbol_table::SymbolTable;
+use crate::terminal::InferiorTerminal;
+use crate::watchpoint::{self, RegisterWatch, Watchpoint};
+use nix::sys::signal::Signal;
+use nix::sys::wait::WaitPidFlag;
+use nix::unistd::Pid;
+use nix::sys::termios::{self, SetArg, Termios};
 use rustyline::error::ReadlineError;
 use rustyline::Editor;
 use std::collections::HashMap;
+use std::time::{Duration, Instant};
 
-#[derive(Clone, Copy)]
+#[derive(Clone)]
 pub struct BreakPoint {
     id: usize,
     addr: usize,
-    orig_byte: u8,
+    /// The instruction bytes the breakpoint instruction replaced, in each inferior (by pid) it
+    /// is patched into.
+    orig_bytes: HashMap<Pid, Vec<u8>>,
+    /// What the user typed to set the breakpoint, so that it can be looked up again when the
+    /// program's symbols change.
+    location: String,
+    /// Set by the debugger itself for the duration of one command, rather than by the user.
+    temporary: bool,
+    /// How many times an inferior has stopped here.
+    hits: usize,
+    /// How long inferiors have stayed stopped here altogether.
+    time_stopped: Duration,
 }
 
 impl BreakPoint {
-    fn new(id: usize, addr: usize) -> Self {
+    fn new(id: usize, addr: usize, location: &str) -> Self {
         BreakPoint {
             id,
             addr,
-            orig_byte: 0,
+            orig_bytes: HashMap::new(),
+            location: location.to_string(),
+            temporary: false,
+            hits: 0,
+            time_stopped: Duration::ZERO,
+        }
+    }
+
+    fn temporary(addr: usize) -> Self {
+        BreakPoint {
+            temporary: true,
+            ..BreakPoint::new(0, addr, "")
         }
     }
 
@@ -25,26 +70,309 @@ impl BreakPoint {
         self.addr
     }
 
-    pub fn set_byte(&mut self, orig_byte: u8) {
-        self.orig_byte = orig_byte
+    /// The instruction bytes the breakpoint replaced in process `pid`. Empty if it is not
+    /// patched in there.
+    pub fn orig_bytes(&self, pid: Pid) -> &[u8] {
+        self.orig_bytes.get(&pid).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Patches the breakpoint into each of `inferiors`, recording what it replaces in each. If
+    /// one of them cannot be patched, the others are restored before the error is returned, so
+    /// that no breakpoint instruction is left behind without its original bytes.
+    fn install<'a>(
+        &mut self,
+        inferiors: impl IntoIterator<Item = &'a mut Inferior>,
+    ) -> Result<(), DeetError> {
+        let mut patched: Vec<&mut Inferior> = Vec::new();
+        for inferior in inferiors {
+            match inferior.install_breakpoints(self.addr) {
+                Ok(orig_bytes) => {
+                    self.orig_bytes.insert(inferior.pid(), orig_bytes);
+                    patched.push(inferior);
+                }
+                Err(err) => {
+                    for inferior in patched {
+                        let orig_bytes = self.orig_bytes.remove(&inferior.pid()).unwrap();
+                        // The error that got us here is the one worth reporting
+                        let _ = inferior.write_bytes(self.addr, &orig_bytes);
+                    }
+                    return Err(err);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Records that `child` was forked from `parent`, whose memory it copied, breakpoint included.
+    fn inherit(&mut self, parent: Pid, child: Pid) {
+        if let Some(orig_bytes) = self.orig_bytes.get(&parent) {
+            self.orig_bytes.insert(child, orig_bytes.clone());
+        }
+    }
+
+    /// Puts the original instruction back in each of `inferiors` the breakpoint is patched into.
+    fn uninstall<'a>(
+        &mut self,
+        inferiors: impl IntoIterator<Item = &'a mut Inferior>,
+    ) -> Result<(), DeetError> {
+        for inferior in inferiors {
+            if let Some(orig_bytes) = self.orig_bytes.remove(&inferior.pid()) {
+                inferior.write_bytes(self.addr, &orig_bytes)?;
+            }
+        }
+        Ok(())
     }
 }
 
 impl std::fmt::Display for BreakPoint {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "ID: {} ", self.id).unwrap();
-        write!(f, "ADDR: {:#x} ", self.addr).unwrap();
-        write!(f, "ORIN_BYTE: {} ", self.orig_byte)
+        write!(f, "ID: {} ", self.id)?;
+        write!(f, "ADDR: {:#x} ", self.addr)?;
+        write!(f, "ORIG_BYTES: {:?} ", self.orig_bytes)
+    }
+}
+
+/// A stack frame found by walking the chain of saved frame pointers.
+struct Frame {
+    pc: usize,
+    /// Canonical frame address: the value the stack pointer had just before the call into this
+    /// frame. Frame-relative variable locations are offsets from it.
+    cfa: usize,
+    /// Where the frame saved its caller's frame pointer, with the return address right above it.
+    /// None while the innermost frame's prologue has yet to run.
+    record: Option<usize>,
+}
+
+/// A frame of the current inferior, as location expressions see it.
+struct FrameMachine<'a> {
+    debugger: &'a Debugger,
+    frame: Frame,
+    /// The pc to look things up by. A caller's pc is the return address, which may already be
+    /// past the range covering the call, so this is the byte before it.
+    pc: usize,
+    /// The registers, if this is the innermost frame. Those of its callers are not recovered.
+    regs: Option<Registers>,
+}
+
+impl Machine for FrameMachine<'_> {
+    fn register(&self, number: u16) -> Result<u64, DeetError> {
+        let regs = self.regs.as_ref().ok_or_else(|| {
+            DeetError::Parse(String::from("Registers are only known in the innermost frame"))
+        })?;
+        let inferior = self.debugger.inferior()?;
+        let arch = inferior.arch();
+        if let Some(value) = arch.dwarf_register(regs, number) {
+            return Ok(value);
+        }
+        // Floating point values live in the low lane of a vector register
+        let name = arch
+            .dwarf_vector_register(number)
+            .ok_or_else(|| DeetError::Parse(format!("Register {} is not supported", number)))?;
+        match inferior.fp_registers()?.into_iter().find(|(reg, _)| *reg == name) {
+            Some((_, FpValue::Vector(bytes))) => {
+                let mut low = [0u8; 8];
+                low.copy_from_slice(&bytes[..8]);
+                Ok(u64::from_le_bytes(low))
+            }
+            _ => Err(DeetError::Parse(format!("Register {} is not available", name))),
+        }
+    }
+
+    fn cfa(&self) -> usize {
+        self.frame.cfa
+    }
+
+    /// Evaluates the function's `DW_AT_frame_base`. A register there holds the base itself,
+    /// rather than the value of a variable.
+    fn frame_base(&self) -> Result<usize, DeetError> {
+        let func = self.debugger.debug_data.get_function_containing(self.pc);
+        match func.and_then(|func| func.frame_base.as_ref()) {
+            Some(Location::Expression(expr)) => {
+                let word = self.debugger.inferior()?.word_size();
+                match expr.evaluate(self.pc, word, self)? {
+                    Place::Memory(addr) => Ok(addr),
+                    Place::Value(bytes) => Ok(location::word_from_bytes(&bytes) as usize),
+                }
+            }
+            Some(Location::Address(addr)) => Ok(*addr),
+            Some(Location::FramePointerOffset(_)) => {
+                Err(DeetError::Parse(String::from("A frame base cannot refer to itself")))
+            }
+            None => Ok(self.frame.cfa),
+        }
+    }
+
+    fn read_memory(&self, addr: usize, len: usize) -> Result<Vec<u8>, DeetError> {
+        self.debugger.read_memory(addr, len)
+    }
+}
+
+/// How many of the innermost frames `where` shows.
+const WHERE_FRAMES: usize = 3;
+
+/// Give up unwinding after this many frames, in case the frame pointer chain is corrupted.
+const MAX_FRAMES: usize = 256;
+
+/// What an internal breakpoint set for `heap trace` is there for.
+#[derive(Clone, Copy, PartialEq)]
+enum HeapHook {
+    /// The program's entry point, by which time the shared libraries are loaded and the
+    /// allocator can be looked up.
+    Start,
+    Alloc,
+    Free,
+    /// Where a call to the allocator returns to.
+    Return,
+}
+
+/// Allocator entry points `heap trace` knows about, as (allocate, free) pairs. Rust's come first,
+/// since they call into malloc and would otherwise be counted twice.
+const ALLOCATORS: [(&str, &str); 2] = [("__rust_alloc", "__rust_dealloc"), ("malloc", "free")];
+
+/// How a call can be left without returning.
+#[derive(Clone, Copy, PartialEq)]
+enum NonLocalExit {
+    /// A jump back to a `setjmp` further up the stack.
+    Longjmp,
+    /// The start of unwinding, for a panic or a C++ exception.
+    Unwind,
+}
+
+/// The functions a call leaves through without returning, which `next` keeps an eye on.
+const NON_LOCAL_EXITS: [(&str, NonLocalExit); 5] = [
+    ("longjmp", NonLocalExit::Longjmp),
+    ("_longjmp", NonLocalExit::Longjmp),
+    ("siglongjmp", NonLocalExit::Longjmp),
+    ("__longjmp_chk", NonLocalExit::Longjmp),
+    ("_Unwind_RaiseException", NonLocalExit::Unwind),
+];
+
+/// How many differing ranges `compare-sections` lists per section.
+const MISMATCHES_SHOWN: usize = 10;
+
+/// How many instructions a longjmp is stepped through before giving up on seeing it land in code
+/// with line numbers.
+const LONGJMP_STEP_LIMIT: usize = 100_000;
+
+/// An expression that is printed every time the inferior stops.
+struct Display {
+    id: usize,
+    format: Format,
+    expr: String,
+}
+
+/// A process under the debugger's control, together with the state that only makes sense for
+/// that process.
+struct Tracee {
+    /// The number the user selects this inferior by.
+    id: usize,
+    inferior: Inferior,
+    watchpoints: Vec<Watchpoint>,
+    /// Pages we have write-protected for the watchpoints, with the protection they had before.
+    protected_pages: HashMap<usize, i32>,
+    register_watches: Vec<RegisterWatch>,
+    /// Set while the inferior runs in the background, after `continue &`.
+    background: Option<Waiter>,
+    /// Tells the user as soon as the inferior dies while stopped.
+    exit_watch: ExitWatch,
+    /// The instructions executed lately, if execution is being recorded (`record`).
+    record: Option<Recording>,
+    /// The trace the processor keeps of the inferior's threads, if execution is being recorded
+    /// with `record btrace`.
+    btrace: Option<BranchTrace>,
+    /// The number of the thread the user was last told the inferior is in, so that they can be
+    /// told when a stop happens in another.
+    reported_thread: usize,
+    /// Set once another inferior has exec'd a different program: the symbols and breakpoints
+    /// loaded no longer describe this one, so breakpoints are not patched into it.
+    other_program: bool,
+}
+
+impl Tracee {
+    fn new(id: usize, inferior: Inferior) -> Tracee {
+        Tracee {
+            id,
+            exit_watch: ExitWatch::spawn(id, inferior.pid()),
+            reported_thread: inferior.current_thread().number,
+            inferior,
+            watchpoints: Vec::new(),
+            protected_pages: HashMap::new(),
+            register_watches: Vec::new(),
+            background: None,
+            record: None,
+            btrace: None,
+            other_program: false,
+        }
     }
 }
 
+/// The inferiors running the program whose breakpoints are set, which is where they are
+/// patched in.
+fn breakpoint_inferiors(inferiors: &mut [Tracee]) -> impl Iterator<Item = &mut Inferior> {
+    inferiors.iter_mut().filter(|tracee| !tracee.other_program).map(|tracee| &mut tracee.inferior)
+}
+
 pub struct Debugger {
     target: String,
     history_path: String,
     readline: Editor<()>,
-    inferior: Option<Inferior>,
+    inferiors: Vec<Tracee>,
+    /// Index into `inferiors` of the one that commands act on.
+    current: Option<usize>,
+    next_inferior_id: usize,
+    /// Whether to stop when an inferior forks, keeping the child as a new inferior. Otherwise the
+    /// child is let go.
+    catch_fork: bool,
     debug_data: DwarfData,
     breakpoints: HashMap<usize, BreakPoint>,
+    /// Breakpoints whose location does not exist in the program currently loaded. They are
+    /// looked up again whenever other symbols are loaded.
+    pending_breakpoints: Vec<BreakPoint>,
+    next_breakpoint_id: usize,
+    /// The program `debug_data` was loaded from. This is not `target` once an inferior has
+    /// exec'd something else.
+    symbols_path: String,
+    /// Terminal settings from before any inferior ran, restored when an inferior is killed.
+    terminal: Option<Termios>,
+    displays: Vec<Display>,
+    next_display_id: usize,
+    /// Commands run whenever an inferior stops, after the displays are shown.
+    stop_hooks: Vec<String>,
+    /// The rhai engine scripts run in, which also keeps the stop hooks they set.
+    scripting: Scripting,
+    /// Set while the stop hooks run, so that a stop they cause does not run them again.
+    in_stop_hook: bool,
+    /// Index into the backtrace of the frame that variable lookups refer to.
+    selected_frame: usize,
+    next_watchpoint_id: usize,
+    /// How many `source`d scripts are being executed. Questions are not asked during scripts.
+    script_depth: usize,
+    /// Set when deet runs without a user at the terminal, so that nothing is asked either.
+    batch: bool,
+    quitting: bool,
+    /// The exit status of the last inferior to exit, with deaths by signal counted the way a
+    /// shell would (128 + signal number).
+    last_exit_code: Option<i32>,
+    /// The signal the current inferior last stopped with, if it was not a trap.
+    fault_signal: Option<Signal>,
+    /// Whether allocations are being traced, as set with `heap trace`.
+    heap_trace: bool,
+    /// What has been traced so far. Kept after the inferior exits, since that is when leaks show.
+    heap: HeapTrace,
+    /// The internal breakpoints `heap trace` uses, and what each of them is for.
+    heap_hooks: HashMap<usize, HeapHook>,
+    /// Where the output of the program last run is being captured.
+    capture: Option<OutputCapture>,
+    /// The terminal of the program last run, if it was given one of its own.
+    inferior_terminal: Option<InferiorTerminal>,
+    settings: Settings,
+    /// Where settings changed with `set` are saved. Only the interactive debugger has one, so
+    /// that tests leave the user's settings alone.
+    settings_path: Option<std::path::PathBuf>,
+    /// When the command being executed runs out of time, if there is a timeout.
+    deadline: Option<Instant>,
+    /// The breakpoint the current inferior is stopped at, and since when.
+    breakpoint_stop: Option<(usize, Instant)>,
 }
 
 enum BreakPointType<'a> {
@@ -65,22 +393,16 @@ fn get_breakpoint_type(breakpoint: &str) -> BreakPointType {
 
 impl Debugger {
     /// Initializes the debugger.
-    pub fn new(target: &str) -> Debugger {
-        // TODO (milestone 3): initialize the DwarfData
-        let debug_data = match DwarfData::from_file(target) {
-            Ok(val) => val,
-            Err(DwarfError::ErrorOpeningFile) => {
-                println!("Could not open file {}", target);
-                std::process::exit(1);
-            }
-            Err(DwarfError::DwarfFormatError(err)) => {
-                println!("Could not debugging symbols from {}: {:?}", target, err);
-                std::process::exit(1);
-            }
-        };
+    pub fn new(target: &str) -> Result<Debugger, DeetError> {
+        let debug_data = DwarfData::from_file(target)?;
         debug_data.print();
+        warn_if_no_debug_info(target, &debug_data);
+        Ok(Debugger::with_debug_data(target, debug_data))
+    }
 
-        let history_path = format!("{}/.deet_history", std::env::var("HOME").unwrap());
+    fn with_debug_data(target: &str, debug_data: DwarfData) -> Debugger {
+        let home = std::env::var("HOME").unwrap_or_else(|_| String::from("."));
+        let history_path = format!("{}/.deet_history", home);
         let mut readline = Editor::<()>::new();
         // Attempt to load history from ~/.deet_history if it exists
         let _ = readline.load_history(&history_path);
@@ -89,204 +411,3671 @@ impl Debugger {
             target: target.to_string(),
             history_path,
             readline,
-            inferior: None,
+            inferiors: Vec::new(),
+            current: None,
+            next_inferior_id: 1,
+            catch_fork: false,
             debug_data: debug_data,
             breakpoints: HashMap::new(),
+            pending_breakpoints: Vec::new(),
+            next_breakpoint_id: 1,
+            symbols_path: target.to_string(),
+            terminal: termios::tcgetattr(libc::STDIN_FILENO).ok(),
+            displays: Vec::new(),
+            next_display_id: 1,
+            stop_hooks: Vec::new(),
+            scripting: Scripting::new(),
+            in_stop_hook: false,
+            selected_frame: 0,
+            next_watchpoint_id: 1,
+            script_depth: 0,
+            batch: false,
+            quitting: false,
+            last_exit_code: None,
+            fault_signal: None,
+            heap_trace: false,
+            heap: HeapTrace::default(),
+            heap_hooks: HashMap::new(),
+            capture: None,
+            inferior_terminal: None,
+            settings: Settings::default(),
+            settings_path: None,
+            deadline: None,
+            breakpoint_stop: None,
+        }
+    }
+
+    /// Executes the commands in `~/.deetinit`, then those in `.deetinit` in the current
+    /// directory, if they exist. Errors are reported but do not stop the debugger from starting.
+    pub fn run_init_files(&mut self) {
+        self.load_settings();
+        let home = std::env::var("HOME").unwrap_or_else(|_| String::from("."));
+        let global = std::path::PathBuf::from(home).join(".deetinit");
+        let local = std::path::PathBuf::from(".deetinit");
+        let mut paths = vec![global.clone()];
+        // Running from the home directory should not execute the same file twice
+        if local.canonicalize().ok() != global.canonicalize().ok() {
+            paths.push(local);
+        }
+        for path in paths {
+            if path.is_file() {
+                if let Err(err) = self.source(&path.to_string_lossy()) {
+                    output::error(err);
+                }
+            }
+        }
+    }
+
+    /// Doesn't ask questions from now on, for when nobody is there to answer them.
+    pub fn set_batch(&mut self, batch: bool) {
+        self.batch = batch;
+    }
+
+    /// Whether a `quit` command has been executed.
+    pub fn quitting(&self) -> bool {
+        self.quitting
+    }
+
+    /// The status deet itself should exit with, if it is to pass on the inferior's.
+    pub fn exit_code(&self) -> Option<i32> {
+        if self.batch || self.settings.exit_code_passthrough {
+            self.last_exit_code
+        } else {
+            None
+        }
+    }
+
+    /// Parses and executes a single command line, as if it had been typed at the prompt.
+    pub fn execute_line(&mut self, line: &str) -> Result<(), DeetError> {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        if tokens.is_empty() {
+            return Ok(());
+        }
+        let command = DebuggerCommand::from_tokens(&tokens)?;
+        self.execute(command)
+    }
+
+    /// Kills whatever is still running before deet exits.
+    pub fn shut_down(&mut self) {
+        // A program that crashed and is only being held by the debugger would have died from
+        // the signal on its own
+        if let (Some(signal), Some(_)) = (self.fault_signal, self.current) {
+            self.last_exit_code = Some(128 + signal as i32);
+        }
+        if let Err(err) = self.kill_all_inferiors() {
+            output::error(err);
         }
     }
 
     pub fn run(&mut self) {
-        loop {
-            match self.get_next_command() {
-                DebuggerCommand::Run(args) => {
-                    if let Some(inferior) = Inferior::new(&self.target, &args, &self.breakpoints) {
-                        // Create the inferior
-                        self.inferior = Some(inferior);
-                        // TODO (milestone 1): make the inferior run
-                        // You may use self.inferior.as_mut().unwrap() to get a mutable reference
-                        // to the Inferior object
-                        match self.inferior.as_mut().unwrap().continue_run(None).unwrap() {
-                            Status::Exited(status) => {
-                                println!("Child exited (status {})", status);
-                            },
-                            Status::Signaled(signal) => {
-                                println!("Child exited with {}", signal);
-                            },
-                            Status::Stopped(signal, curr_addr) => {
-                                println!("Child stopped (signal {})", signal);
-                                let func = DwarfData::get_function_from_addr(&self.debug_data, curr_addr);
-                                let line = DwarfData::get_line_from_addr(&self.debug_data, curr_addr);
-                                match (func, line) {
-                                    (Some(func), Some(line)) => 
-                                        println!("Stopped at {} {}", func, line),
-                                    (_, _) => {
-                                        println!("Fail to resolve stopping function and line")
-                                    }
-                                }
-                            }
-                        }
-                    } else {
-                        println!("Error starting subprocess");
-                    }
+        while !self.quitting {
+            let command = self.get_next_command();
+            if let Err(err) = self.execute(command) {
+                output::error(err);
+            }
+        }
+    }
+
+    /// Changes one of the debugger's settings.
+    fn set(&mut self, setting: &str, value: &str) -> Result<(), DeetError> {
+        self.settings.set(setting, value)?;
+        if setting == "color" {
+            output::set_color(self.settings.color);
+        }
+        if let Some(path) = &self.settings_path {
+            std::fs::write(path, self.settings.to_file())?;
+        }
+        Ok(())
+    }
+
+    /// Prints the value of one setting, or of all of them.
+    fn show(&self, setting: Option<&str>) -> Result<(), DeetError> {
+        let names = match setting {
+            Some(name) => vec![name],
+            None => settings::NAMES.to_vec(),
+        };
+        for name in names {
+            let value = self
+                .settings
+                .get(name)
+                .ok_or_else(|| DeetError::Parse(format!("No setting named \"{}\".", name)))?;
+            output::info(format!("{:<24}{}", name, value));
+        }
+        Ok(())
+    }
+
+    /// Applies the settings saved in `~/.deet_settings`, and saves those changed from now on
+    /// there.
+    fn load_settings(&mut self) {
+        let home = std::env::var("HOME").unwrap_or_else(|_| String::from("."));
+        let path = std::path::PathBuf::from(home).join(".deet_settings");
+        if let Ok(text) = std::fs::read_to_string(&path) {
+            for (line, err) in self.settings.load(&text) {
+                output::error(format!("{}:{}: {}", path.display(), line, err));
+            }
+            output::set_color(self.settings.color);
+        }
+        self.settings_path = Some(path);
+    }
+
+    /// Leaves the debugger once the user has agreed to kill the running inferiors.
+    fn quit(&mut self) -> Result<(), DeetError> {
+        let question = match self.inferiors.len() {
+            0 => None,
+            1 => Some(String::from("Kill the running inferior?")),
+            n => Some(format!("Kill all {} running inferiors?", n)),
+        };
+        if let Some(question) = question {
+            if !self.confirm(&question) {
+                return Ok(());
+            }
+        }
+        if self.breakpoints.values().any(|breakpoint| breakpoint.hits > 0) {
+            self.breakpoint_stats();
+        }
+        self.quitting = true;
+        self.shut_down();
+        Ok(())
+    }
+
+    fn execute(&mut self, command: DebuggerCommand) -> Result<(), DeetError> {
+        self.check_background()?;
+        let running = self.current.is_some_and(|i| self.inferiors[i].background.is_some());
+        if running && !command.allowed_while_running() {
+            return Err(DeetError::Parse(String::from(
+                "The program is running. Use \"interrupt\" to stop it first.",
+            )));
+        }
+        self.deadline = self.settings.timeout.map(|limit| Instant::now() + limit);
+        match command {
+            DebuggerCommand::Run(args) => {
+                self.start_program(args)?;
+                self.resume(Resume::Continue)
+            }
+            DebuggerCommand::Start(args) => {
+                self.start_program(args)?;
+                self.run_to_main()
+            }
+            DebuggerCommand::StartInstruction(args) => {
+                self.start_program(args)?;
+                // The inferior is still stopped where the exec left it
+                self.selected_frame = 0;
+                let pc = self.inferior()?.get_pc()?;
+                self.report_stop(Status::Stopped(Signal::SIGTRAP, pc), false);
+                Ok(())
+            }
+            DebuggerCommand::Continue(false) => self.resume(Resume::Continue),
+            DebuggerCommand::Continue(true) => {
+                self.selected_frame = 0;
+                self.continue_in_background()
+            }
+            DebuggerCommand::Interrupt(false) => self.interrupt(),
+            DebuggerCommand::Interrupt(true) => self.interrupt_all(),
+            DebuggerCommand::StepInstruction => self.resume(Resume::Step),
+            DebuggerCommand::Next => self.next(),
+            DebuggerCommand::Record(on) => self.record(on),
+            DebuggerCommand::RecordBtrace => self.record_btrace(),
+            DebuggerCommand::RecordInstructionHistory(count) => self.instruction_history(count),
+            DebuggerCommand::RecordFunctionCallHistory(count) => self.function_call_history(count),
+            DebuggerCommand::ReverseStepInstruction => {
+                let i = self.current_index()?;
+                let recorded = self.inferiors[i].record.as_ref().map(|record| record.steps().len());
+                match recorded.ok_or_else(not_recording)? {
+                    0 => Err(DeetError::Parse(String::from("No more reverse-execution history."))),
+                    _ => self.undo_steps(1),
                 }
-                DebuggerCommand::Continue => {
-                    if self.inferior.is_none() {
-                        println!("The process is not running");
-                        continue;
-                    }
-                    let rip = self.inferior.as_ref().unwrap().get_previous_ins().unwrap();
-                    if self.breakpoints.contains_key(&rip) {
-                        println!(
-                            "Previously Stopped at breakpoint: {}\n",
-                            self.breakpoints.get(&rip).unwrap()
-                        );
-                        if !self
-                            .inferior
-                            .as_mut()
-                            .unwrap()
-                            .step_breakpoint(rip, self.breakpoints.get(&rip).unwrap().orig_byte)
-                        {
-                            println!("Failed to step by the breakpoint");
-                            continue;
-                        }
-                    }
-                    // let rip = self.inferior.as_ref().unwrap().get_previous_ins().unwrap();
-                    match self.inferior.as_mut().unwrap().continue_run(None).unwrap() {
-                        Status::Stopped(signal,  curr_addr) => {
-                            println!("Child stopped (signal {})", signal);
-                            let func = DwarfData::get_function_from_addr(&self.debug_data, curr_addr);
-                            let line = DwarfData::get_line_from_addr(&self.debug_data, curr_addr);
-                            match (func, line) {
-                                (Some(func), Some(line)) => 
-                                    println!("Stopped at {} {}", func, line),
-                                (_, _) => {
-                                        println!("Fail to resolve stopping function and line")
-                                }
-                            }
-                        }
-                        Status::Signaled(signal) => {
-                            println!("Child exited with {}", signal);
-                            self.inferior = None;
-                        }
-                        Status::Exited(exit_code) => {
-                            println!("Child exited (status {})", exit_code);
-                            self.inferior = None;
-                        }
-                    }
-                    self.inferior.as_mut().unwrap().continue_run(None).unwrap();
-                }
-                DebuggerCommand::Quit => {
-                    self.inferior.as_mut().unwrap().kill();
-                    return;
-                }
-                DebuggerCommand::Backtrace => {
-                    if self.inferior.is_some() {
-                        self.inferior.as_ref().unwrap().print_backtrace(&self.debug_data).ok();
-                    }   
-                }
-                DebuggerCommand::Break(args) => {
-                    let breakpoint = match get_breakpoint_type(&args) {
-                        BreakPointType::Raw(address) => parse_address(address).unwrap(),
-                        // unable to get lines info in dwarf file, don't know why
-                        BreakPointType::Line(line) => {
-                            match self.debug_data.get_addr_for_line(None, line) {
-                                Some(addr) => addr,
-                                None => {
-                                    println!("Failed to find the address of line {}", line);
-                                    continue;
-                                }
-                            }
-                        }
-                        BreakPointType::Func(func) => {
-                            match self.debug_data.get_addr_for_function(None, func) {
-                                Some(addr) => addr,
-                                None => {
-                                    println!("Failed to find the address of function {}", func);
-                                    continue;
-                                }
-                            }
-                        }
-                    };
-                    
-                    if !self.breakpoints.contains_key(&breakpoint) {
-                        // add breakpoint to global Hashmap, without knowing the orig_byte
-                        self.breakpoints.insert(
-                            breakpoint,
-                            BreakPoint::new(self.breakpoints.len() + 1, breakpoint),
-                        );
-                        // add breakpoint when process is stopped
-                        if self.inferior.is_some() {
-                            match self.inferior.as_mut().unwrap().install_breakpoints(breakpoint) {
-                                Ok(orig_byte) => self
-                                    .breakpoints
-                                    .get_mut(&breakpoint)
-                                    .unwrap()
-                                    .set_byte(orig_byte),
-                                Err(_) => {
-                                    println!("Fail to insert breakpoint at {:#x}", breakpoint);
-                                    continue;
-                                }
-                            }
+            }
+            DebuggerCommand::ReverseStep => self.reverse_line(false),
+            DebuggerCommand::ReverseNext => self.reverse_line(true),
+            DebuggerCommand::Backtrace => {
+                for (i, frame) in self.frames()?.iter().enumerate() {
+                    output::info(format!("#{} {}", i, self.describe_frame(frame)));
+                }
+                Ok(())
+            }
+            DebuggerCommand::Frame(index) => {
+                let index = index.unwrap_or(self.selected_frame);
+                self.select_frame(index as isize)
+            }
+            DebuggerCommand::Up => self.select_frame(self.selected_frame as isize + 1),
+            DebuggerCommand::Down => self.select_frame(self.selected_frame as isize - 1),
+            DebuggerCommand::InfoLocals => self.info_locals(),
+            DebuggerCommand::InfoRegisters(all) => self.info_registers(all),
+            DebuggerCommand::Where => self.where_summary(),
+            DebuggerCommand::Disassemble => self.disassemble_function(),
+            DebuggerCommand::Stack(words) => self.dump_stack(words),
+            DebuggerCommand::HeapTrace(on) => self.set_heap_trace(on),
+            DebuggerCommand::HeapReport => {
+                self.heap_report();
+                Ok(())
+            }
+            DebuggerCommand::Tty(None) => {
+                if self.settings.inferior_tty {
+                    output::info("Programs get a terminal of their own.");
+                } else if self.settings.raw_tty {
+                    output::info("Programs use the terminal directly.");
+                } else {
+                    output::info("Program output is captured and labeled.");
+                }
+                Ok(())
+            }
+            DebuggerCommand::Tty(Some(raw)) => {
+                self.set("tty", if raw { "on" } else { "off" })?;
+                if !self.inferiors.is_empty() {
+                    output::info("This takes effect the next time the program is run.");
+                }
+                Ok(())
+            }
+            DebuggerCommand::Break(location) => self.set_breakpoint(&location),
+            DebuggerCommand::Clear(location) => self.clear_breakpoint(&location),
+            DebuggerCommand::Return(value) => self.force_return(value),
+            DebuggerCommand::Jump(location) => self.jump(&location),
+            DebuggerCommand::Advance(location) => self.advance(&location),
+            DebuggerCommand::SaveBreakpoints(path) => self.save_breakpoints(&path),
+            DebuggerCommand::StatsBreakpoints => {
+                self.breakpoint_stats();
+                Ok(())
+            }
+            DebuggerCommand::Source(path) => self.source(&path),
+            DebuggerCommand::Rhai(code) => self.run_script("rhai", &code),
+            DebuggerCommand::Call(func, args) => self.call_function(&func, &args),
+            DebuggerCommand::Print(format, expr) => {
+                output::info(format!("{} = {}", expr, self.evaluate(&expr, format)?));
+                Ok(())
+            }
+            DebuggerCommand::Examine(spec, expr) => self.examine(spec, &expr),
+            DebuggerCommand::DumpMemory(path, start, end) => self.dump_memory(&path, &start, &end),
+            DebuggerCommand::Restore(path, addr) => self.restore_memory(&path, &addr),
+            DebuggerCommand::Whatis(expr) => {
+                output::info(format!("type = {}", self.lookup_type(&expr)?.name));
+                Ok(())
+            }
+            DebuggerCommand::Ptype(expr) => self.ptype(&expr),
+            DebuggerCommand::Display(format, Some(expr)) => {
+                let id = self.next_display_id;
+                self.next_display_id += 1;
+                self.displays.push(Display { id, format, expr });
+                if self.current.is_some() {
+                    self.show_display(self.displays.last().unwrap());
+                }
+                Ok(())
+            }
+            DebuggerCommand::Display(_, None) => {
+                for display in &self.displays {
+                    self.show_display(display);
+                }
+                Ok(())
+            }
+            DebuggerCommand::HookStop(Some(command)) => {
+                self.stop_hooks.push(command);
+                Ok(())
+            }
+            DebuggerCommand::HookStop(None) => {
+                for (i, command) in self.stop_hooks.iter().enumerate() {
+                    output::info(format!("{}: {}", i + 1, command));
+                }
+                let script_hooks = self.scripting.stop_hooks();
+                for (i, hook) in script_hooks.iter().enumerate() {
+                    output::info(format!(
+                        "{}: rhai function {}",
+                        self.stop_hooks.len() + i + 1, hook.name(),
+                    ));
+                }
+                if self.stop_hooks.is_empty() && script_hooks.is_empty() {
+                    output::info("No stop hooks.");
+                }
+                Ok(())
+            }
+            DebuggerCommand::UnhookStop => {
+                self.stop_hooks.clear();
+                self.scripting.clear_stop_hooks();
+                Ok(())
+            }
+            DebuggerCommand::Undisplay(id) => {
+                let before = self.displays.len();
+                self.displays.retain(|display| display.id != id);
+                if self.displays.len() == before {
+                    return Err(DeetError::Parse(format!("No display number {}", id)));
+                }
+                Ok(())
+            }
+            DebuggerCommand::Watch(expr, len) => self.set_watchpoint(&expr, len),
+            DebuggerCommand::Unwatch(id) => self.delete_watchpoint(id),
+            DebuggerCommand::Inferior(id) => self.switch_inferior(id),
+            DebuggerCommand::Thread(number) => self.switch_thread(number),
+            DebuggerCommand::InfoInferiors => {
+                for (i, tracee) in self.inferiors.iter().enumerate() {
+                    let marker = if self.current == Some(i) { '*' } else { ' ' };
+                    let inferior = &tracee.inferior;
+                    let arch = inferior.arch().name();
+                    let state = if tracee.background.is_some() { ", running" } else { "" };
+                    // The main thread's tid is the pid
+                    let name = inferior.thread_name(inferior.pid());
+                    let name = name.map_or_else(String::new, |name| format!(" '{}'", name));
+                    output::info(format!(
+                        "{} {} process {}{} ({}{})",
+                        marker,
+                        tracee.id,
+                        inferior.pid(),
+                        name,
+                        arch,
+                        state
+                    ));
+                    let threads = inferior.threads();
+                    if threads.len() > 1 {
+                        let current = inferior.current_thread();
+                        for thread in threads {
+                            let marker = if *thread == current { '*' } else { ' ' };
+                            output::info(format!(
+                                "    {} {}",
+                                marker, thread_label(inferior, *thread),
+                            ));
                         }
                     }
-                        println!(
-                            "Set breakpoint {} at {}",
-                            self.breakpoints.get(&breakpoint).as_ref().unwrap().id,
-                            args
-                        )
-                    }
-                                    
                 }
+                if self.inferiors.is_empty() {
+                    output::info("No inferiors.");
+                }
+                Ok(())
+            }
+            DebuggerCommand::InfoSymbol(expr) => self.info_symbol(&expr),
+            DebuggerCommand::InfoSharedLibrary => self.info_shared_libraries(),
+            DebuggerCommand::InfoFiles => {
+                self.info_files();
+                Ok(())
+            }
+            DebuggerCommand::MaintLineTable(file) => self.maint_line_table(file.as_deref()),
+            DebuggerCommand::CompareSections => self.compare_sections(),
+            DebuggerCommand::CatchFork => {
+                self.catch_fork = true;
+                output::info("Catchpoint set on fork");
+                Ok(())
             }
+            DebuggerCommand::Set(setting, value) => self.set(&setting, &value),
+            DebuggerCommand::Show(setting) => self.show(setting.as_deref()),
+            DebuggerCommand::Quit => self.quit(),
+        }
     }
 
-    /// This function prompts the user to enter a command, and continues re-prompting until the user
-    /// enters a valid command. It uses DebuggerCommand::from_tokens to do the command parsing.
-    ///
-    /// You don't need to read, understand, or modify this function.
-    fn get_next_command(&mut self) -> DebuggerCommand {
-        loop {
-            // Print prompt and get next line of user input
-            match self.readline.readline("(deet) ") {
-                Err(ReadlineError::Interrupted) => {
-                    // User pressed ctrl+c. We're going to ignore it
-                    println!("Type \"quit\" to exit");
+    /// Turns a user-supplied location (function name, line number, or `*address`) into an
+    /// address.
+    fn resolve_location(&self, location: &str) -> Result<usize, DeetError> {
+        Ok(match get_breakpoint_type(location) {
+            BreakPointType::Raw(address) => parse_address(address).ok_or_else(|| {
+                DeetError::Parse(format!("Invalid address \"{}\"", address))
+            })?,
+            // unable to get lines info in dwarf file, don't know why
+            BreakPointType::Line(line) => self
+                .debug_data
+                .get_addr_for_line(None, line)
+                .ok_or_else(|| {
+                    DeetError::Parse(format!("Failed to find the address of line {}", line))
+                })?,
+            BreakPointType::Func(func) => self
+                .debug_data
+                .get_addr_for_function(None, func)
+                .ok_or_else(|| {
+                    DeetError::Parse(format!("Failed to find the address of function {}", func))
+                })?,
+        })
+    }
+
+    fn set_breakpoint(&mut self, location: &str) -> Result<(), DeetError> {
+        let addr = match self.resolve_location(location) {
+            Ok(addr) => addr,
+            // A program exec'd later on may have it
+            Err(err) if !matches!(get_breakpoint_type(location), BreakPointType::Raw(_)) => {
+                output::error(err);
+                if !self.confirm("Make breakpoint pending on a future exec?") {
+                    return Ok(());
                 }
-                Err(ReadlineError::Eof) => {
-                    // User pressed ctrl+d, which is the equivalent of "quit" for our purposes
-                    return DebuggerCommand::Quit;
+                let breakpoint = BreakPoint::new(self.next_breakpoint_id, 0, location);
+                self.next_breakpoint_id += 1;
+                output::info(format!("Breakpoint {} at {} is pending", breakpoint.id, location));
+                self.pending_breakpoints.push(breakpoint);
+                return Ok(());
+            }
+            Err(err) => return Err(err),
+        };
+
+        if !self.breakpoints.contains_key(&addr) {
+            let mut breakpoint = BreakPoint::new(self.next_breakpoint_id, addr, location);
+            self.next_breakpoint_id += 1;
+            // The original bytes can only be recorded once there is a process to patch
+            breakpoint.install(breakpoint_inferiors(&mut self.inferiors))?;
+            self.breakpoints.insert(addr, breakpoint);
+        }
+        output::info(format!("Set breakpoint {} at {}", self.breakpoints[&addr].id, location));
+        Ok(())
+    }
+
+    /// Deletes the breakpoints at `location`, which is looked up the way `break` looks it up.
+    fn clear_breakpoint(&mut self, location: &str) -> Result<(), DeetError> {
+        // Pending breakpoints have no address yet, so they are matched by what was typed
+        let (mut deleted, pending): (Vec<BreakPoint>, Vec<BreakPoint>) = self
+            .pending_breakpoints
+            .drain(..)
+            .partition(|breakpoint| breakpoint.location == location);
+        self.pending_breakpoints = pending;
+        if let Ok(addr) = self.resolve_location(location) {
+            if self.breakpoints.get(&addr).is_some_and(|bp| !bp.temporary) {
+                if self.heap_hooks.contains_key(&addr) {
+                    // `heap trace` still needs to stop there
+                    let breakpoint = self.breakpoints.get_mut(&addr).unwrap();
+                    deleted.push(BreakPoint::new(breakpoint.id, addr, location));
+                    breakpoint.temporary = true;
+                } else {
+                    let mut breakpoint = self.breakpoints.remove(&addr).unwrap();
+                    breakpoint.uninstall(self.inferiors.iter_mut().map(|t| &mut t.inferior))?;
+                    deleted.push(breakpoint);
+                }
+            }
+        }
+        if deleted.is_empty() {
+            return Err(DeetError::Parse(format!("No breakpoint at {}.", location)));
+        }
+        deleted.sort_by_key(|breakpoint| breakpoint.id);
+        let ids: Vec<String> = deleted.iter().map(|bp| bp.id.to_string()).collect();
+        let plural = if ids.len() == 1 { "" } else { "s" };
+        output::info(format!("Deleted breakpoint{} {}", plural, ids.join(" ")));
+        Ok(())
+    }
+
+    /// Shows how often each breakpoint was hit and how long inferiors stayed stopped there.
+    fn breakpoint_stats(&mut self) {
+        self.end_breakpoint_stop();
+        let mut breakpoints: Vec<&BreakPoint> = self
+            .breakpoints
+            .values()
+            .chain(&self.pending_breakpoints)
+            .filter(|breakpoint| !breakpoint.temporary)
+            .collect();
+        if breakpoints.is_empty() {
+            output::info("No breakpoints.");
+            return;
+        }
+        breakpoints.sort_by_key(|breakpoint| breakpoint.id);
+        output::info(format!("{:<6}{:<8}{:<16}Location", "Num", "Hits", "Time stopped"));
+        for breakpoint in breakpoints {
+            let time = format!("{:.1}s", breakpoint.time_stopped.as_secs_f64());
+            let (id, hits, location) = (breakpoint.id, breakpoint.hits, &breakpoint.location);
+            output::info(format!("{:<6}{:<8}{:<16}{}", id, hits, time, location));
+        }
+    }
+
+    /// Adds the time since the current inferior stopped at a breakpoint to the breakpoint's
+    /// total, as it is about to run on (or to show the total so far).
+    fn end_breakpoint_stop(&mut self) {
+        if let Some((addr, since)) = self.breakpoint_stop.take() {
+            // The breakpoint may have been deleted since
+            if let Some(breakpoint) = self.breakpoints.get_mut(&addr) {
+                breakpoint.time_stopped += since.elapsed();
+            }
+        }
+    }
+
+    /// Writes the breakpoints out as commands that `source` can read back in.
+    fn save_breakpoints(&self, path: &str) -> Result<(), DeetError> {
+        let mut breakpoints: Vec<&BreakPoint> = self
+            .breakpoints
+            .values()
+            .chain(&self.pending_breakpoints)
+            .filter(|breakpoint| !breakpoint.temporary)
+            .collect();
+        breakpoints.sort_by_key(|breakpoint| breakpoint.id);
+        let mut script = String::new();
+        for breakpoint in &breakpoints {
+            script.push_str(&format!("break {}\n", breakpoint.location));
+        }
+        std::fs::write(path, script)?;
+        output::info(format!("Saved to file '{}'.", path));
+        Ok(())
+    }
+
+    /// Executes the commands in the file at `path`, one per line, stopping at the first one that
+    /// fails. Blank lines and lines starting with `#` are skipped. A `.rhai` file is run as a
+    /// script instead.
+    fn source(&mut self, path: &str) -> Result<(), DeetError> {
+        let script = std::fs::read_to_string(path)?;
+        if path.ends_with(".rhai") {
+            return self.run_script(path, &script);
+        }
+        self.script_depth += 1;
+        let mut result = Ok(());
+        for (i, line) in script.lines().enumerate() {
+            if line.trim_start().starts_with('#') {
+                continue;
+            }
+            result = self
+                .execute_line(line)
+                .map_err(|err| DeetError::Parse(format!("{}:{}: {}", path, i + 1, err)));
+            if result.is_err() || self.quitting {
+                break;
+            }
+        }
+        self.script_depth -= 1;
+        result
+    }
+
+    /// Runs the rhai script `code` (see `script`). `name` says where it came from, for errors.
+    fn run_script(&mut self, name: &str, code: &str) -> Result<(), DeetError> {
+        let scripting = self.scripting.clone();
+        self.script_depth += 1;
+        let result = scripting.run(self, name, code);
+        self.script_depth -= 1;
+        result
+    }
+
+    /// Switches over to the symbols of the program at `path` and moves the breakpoints along:
+    /// those set by name or line are looked up again, and those set at raw addresses are
+    /// dropped.
+    fn load_symbols(&mut self, path: &str) -> Result<(), DeetError> {
+        self.load_debug_data(path)?;
+        let mut breakpoints: Vec<BreakPoint> = self.breakpoints.drain().map(|(_, bp)| bp).collect();
+        breakpoints.append(&mut self.pending_breakpoints);
+        breakpoints.sort_by_key(|breakpoint| breakpoint.id);
+        for breakpoint in breakpoints {
+            if let BreakPointType::Raw(_) = get_breakpoint_type(&breakpoint.location) {
+                output::info(format!(
+                    "Deleted breakpoint {} at {}",
+                    breakpoint.id, breakpoint.location
+                ));
+            } else {
+                self.pending_breakpoints.push(breakpoint);
+            }
+        }
+        self.reinstall_breakpoints();
+        Ok(())
+    }
+
+    fn load_debug_data(&mut self, path: &str) -> Result<(), DeetError> {
+        self.debug_data = DwarfData::from_file(path)?;
+        warn_if_no_debug_info(path, &self.debug_data);
+        self.symbols_path = path.to_string();
+        Ok(())
+    }
+
+    /// Looks every breakpoint up again in the symbols loaded and installs it afresh in the
+    /// inferiors running that program, recording the instructions it replaces in each.
+    /// Breakpoints that cannot be found or patched in are reported and kept pending, to be tried
+    /// again with the next process or program. Temporary breakpoints are dropped.
+    fn reinstall_breakpoints(&mut self) {
+        let mut breakpoints: Vec<BreakPoint> = self.breakpoints.drain().map(|(_, bp)| bp).collect();
+        breakpoints.append(&mut self.pending_breakpoints);
+        breakpoints.sort_by_key(|breakpoint| breakpoint.id);
+        for mut breakpoint in breakpoints.into_iter().filter(|bp| !bp.temporary) {
+            match self.install_breakpoint(&mut breakpoint) {
+                Ok(()) => {
+                    self.breakpoints.insert(breakpoint.addr, breakpoint);
                 }
                 Err(err) => {
-                    panic!("Unexpected I/O error: {:?}", err);
+                    let (id, location) = (breakpoint.id, &breakpoint.location);
+                    output::warn(format!("Breakpoint {} at {} is pending: {}", id, location, err));
+                    self.pending_breakpoints.push(breakpoint);
                 }
-                Ok(line) => {
-                    if line.trim().len() == 0 {
-                        continue;
-                    }
-                    self.readline.add_history_entry(line.as_str());
-                    if let Err(err) = self.readline.save_history(&self.history_path) {
-                        println!(
-                            "Warning: failed to save history file at {}: {}",
-                            self.history_path, err
-                        );
-                    }
-                    let tokens: Vec<&str> = line.split_whitespace().collect();
-                    if let Some(cmd) = DebuggerCommand::from_tokens(&tokens) {
-                        return cmd;
-                    } else {
-                        println!("Unrecognized command.");
-                    }
+            }
+        }
+    }
+
+    /// Resolves `breakpoint`'s location and patches it into the inferiors running the program
+    /// loaded.
+    fn install_breakpoint(&mut self, breakpoint: &mut BreakPoint) -> Result<(), DeetError> {
+        let addr = self.resolve_location(&breakpoint.location)?;
+        if let Some(other) = self.breakpoints.get(&addr) {
+            return Err(DeetError::Parse(format!("breakpoint {} is already there", other.id)));
+        }
+        breakpoint.addr = addr;
+        breakpoint.orig_bytes.clear();
+        breakpoint.install(breakpoint_inferiors(&mut self.inferiors))
+    }
+
+    /// Deals with the current inferior having exec'd a new program: everything that pointed into
+    /// the old image is dropped, and the new image's symbols are loaded.
+    fn follow_exec(&mut self) -> Result<(), DeetError> {
+        let i = self.current_index()?;
+        let pid = self.inferiors[i].inferior.pid();
+        let path = std::fs::read_link(format!("/proc/{}/exe", pid))?;
+        let path = path.to_string_lossy().into_owned();
+        output::info(format!("process {} is executing new program: {}", pid, path));
+        let tracee = &mut self.inferiors[i];
+        for watchpoint in tracee.watchpoints.drain(..) {
+            output::info(format!(
+                "Watchpoint {} deleted because the program has exec'd.",
+                watchpoint.id
+            ));
+        }
+        tracee.protected_pages.clear();
+        if let Some(record) = &mut tracee.record {
+            record.reset();
+        }
+        tracee.inferior.refresh_arch()?;
+        // Other inferiors still run the old program, but the breakpoint table is about to stop
+        // describing it
+        for (j, other) in self.inferiors.iter_mut().enumerate() {
+            if j != i {
+                for breakpoint in self.breakpoints.values_mut() {
+                    breakpoint.uninstall(std::iter::once(&mut other.inferior))?;
                 }
+                other.other_program = true;
             }
         }
+        self.inferiors[i].other_program = false;
+        // The breakpoints `heap trace` used are dropped along with the old image's symbols
+        self.heap_hooks.clear();
+        self.heap = HeapTrace::default();
+        self.load_symbols(&path)?;
+        if self.heap_trace {
+            self.add_heap_hook(self.debug_data.entry_point(), HeapHook::Start)?;
+        }
+        Ok(())
     }
-}
 
-fn parse_address(addr: &str) -> Option<usize> {
-    let addr_without_0x = if addr.to_lowercase().starts_with("0x") {
-        &addr[2..]
-    } else {
-        &addr
-    };
-    usize::from_str_radix(addr_without_0x, 16).ok()
-}
\ No newline at end of file
+    fn set_heap_trace(&mut self, on: bool) -> Result<(), DeetError> {
+        self.heap_trace = on;
+        if !on {
+            return self.remove_heap_hooks();
+        }
+        if self.current.is_none() {
+            output::info("Heap allocations will be traced once the program runs.");
+            return Ok(());
+        }
+        if self.heap_hooks.is_empty() {
+            self.heap = HeapTrace::default();
+            self.hook_allocator()?;
+        }
+        Ok(())
+    }
+
+    /// Finds the allocator in the program or the libraries it has loaded, and stops on calls to
+    /// it from now on.
+    fn hook_allocator(&mut self) -> Result<(), DeetError> {
+        let (alloc, free, name) = match self.find_allocator()? {
+            Some(allocator) => allocator,
+            None => {
+                output::warn("Heap tracing: no allocator found in the program.");
+                return Ok(());
+            }
+        };
+        output::info(format!("Heap tracing: calls to {} traced.", name));
+        self.add_heap_hook(alloc, HeapHook::Alloc)?;
+        self.add_heap_hook(free, HeapHook::Free)
+    }
+
+    /// Looks up the first of `ALLOCATORS` present in the program, then in its shared libraries.
+    /// Returns the addresses of its allocate and free functions, and its name.
+    fn find_allocator(&self) -> Result<Option<(usize, usize, String)>, DeetError> {
+        let program = self.debug_data.symbol_table();
+        for (alloc, free) in &ALLOCATORS {
+            if let (Some(alloc_addr), Some(free_addr)) =
+                (program.address_of(alloc), program.address_of(free))
+            {
+                return Ok(Some((alloc_addr, free_addr, format!("{}/{}", alloc, free))));
+            }
+        }
+        let libraries: Vec<SharedLibrary> = self
+            .inferior()?
+            .shared_libraries(&self.symbols_path)?
+            .into_iter()
+            // The dynamic loader has a malloc of its own, which the program doesn't use
+            .filter(|library| !library.path.rsplit('/').next().unwrap_or("").starts_with("ld-"))
+            .collect();
+        for library in libraries {
+            let symbols = match SymbolTable::from_file(&library.path) {
+                Ok(symbols) => symbols,
+                Err(_) => continue,
+            };
+            let (alloc, free) = ALLOCATORS[1];
+            if let (Some(alloc_addr), Some(free_addr)) =
+                (symbols.address_of(alloc), symbols.address_of(free))
+            {
+                return Ok(Some((
+                    library.base + alloc_addr,
+                    library.base + free_addr,
+                    format!("{}/{} in {}", alloc, free, library.path),
+                )));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Sets an internal breakpoint at `addr`, unless the user already has one there.
+    fn add_heap_hook(&mut self, addr: usize, hook: HeapHook) -> Result<(), DeetError> {
+        self.add_temporary_breakpoints(Some(addr))?;
+        self.heap_hooks.insert(addr, hook);
+        Ok(())
+    }
+
+    fn remove_heap_hook(&mut self, addr: usize) -> Result<(), DeetError> {
+        self.heap_hooks.remove(&addr);
+        match self.breakpoints.get(&addr) {
+            Some(breakpoint) if breakpoint.temporary => {}
+            _ => return Ok(()),
+        }
+        let mut breakpoint = self.breakpoints.remove(&addr).unwrap();
+        if self.current.is_some() {
+            breakpoint.uninstall(std::iter::once(self.inferior_mut()?))?;
+        }
+        Ok(())
+    }
+
+    fn remove_heap_hooks(&mut self) -> Result<(), DeetError> {
+        let hooks: Vec<usize> = self.heap_hooks.keys().cloned().collect();
+        for addr in hooks {
+            self.remove_heap_hook(addr)?;
+        }
+        Ok(())
+    }
+
+    /// Updates the heap trace for the inferior having stopped on the heap hook at `addr`.
+    fn handle_heap_hook(&mut self, addr: usize) -> Result<(), DeetError> {
+        let inferior = self.inferior()?;
+        let arch = inferior.arch();
+        let regs = inferior.registers()?;
+        let sp = arch.sp(&regs);
+        match self.heap_hooks[&addr] {
+            HeapHook::Start => {
+                self.remove_heap_hook(addr)?;
+                self.hook_allocator()?;
+            }
+            HeapHook::Alloc => {
+                let call_site = inferior.read_slot(arch.return_address_at_entry(&regs))?;
+                let size = inferior.read_slot(arch.argument(&regs, 0))?;
+                self.heap.enter_alloc(size, call_site, sp + arch.entry_cfa_offset());
+                if !self.heap_hooks.contains_key(&call_site) {
+                    self.add_heap_hook(call_site, HeapHook::Return)?;
+                }
+            }
+            HeapHook::Free => {
+                let ptr = inferior.read_slot(arch.argument(&regs, 0))?;
+                self.heap.free(ptr);
+            }
+            HeapHook::Return => {
+                let ptr = arch.return_value(&regs) as usize;
+                self.heap.finish_alloc(addr, sp, ptr);
+                if !self.heap.is_waiting_at(addr) {
+                    self.remove_heap_hook(addr)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Summarizes the blocks allocated while tracing that have not been freed.
+    fn heap_report(&self) {
+        if !self.heap_trace && self.heap.allocations == 0 {
+            output::info("Heap tracing is off; turn it on with \"heap trace on\".");
+            return;
+        }
+        output::info(format!(
+            "{} bytes in {} blocks still allocated ({} allocations, {} frees traced)",
+            self.heap.live_bytes(),
+            self.heap.live_blocks(),
+            self.heap.allocations,
+            self.heap.frees
+        ));
+        for site in self.heap.by_call_site() {
+            let line = self.debug_data.get_line_from_addr(site.call_site);
+            output::info(format!(
+                "  {} bytes in {} blocks from {}{}",
+                site.bytes,
+                site.blocks,
+                self.describe_address(site.call_site),
+                line.map_or(String::new(), |line| format!(" ({})", output::source(line)))
+            ));
+        }
+    }
+
+    /// Watches `len` bytes at `expr` (a variable or `*address`) by write-protecting the pages
+    /// they live on. Without a length, a variable's whole value is watched. A `$register` is
+    /// watched by single-stepping instead.
+    fn set_watchpoint(&mut self, expr: &str, len: Option<usize>) -> Result<(), DeetError> {
+        if let Some(register) = expr.strip_prefix('$') {
+            if len.is_some() {
+                return Err(DeetError::Parse(String::from("A register has no length to watch.")));
+            }
+            return self.watch_register(register);
+        }
+        let (addr, default_len) = match expr.strip_prefix('*') {
+            Some(addr) => {
+                let addr = parse_address(addr)
+                    .ok_or_else(|| DeetError::Parse(format!("Invalid address \"{}\"", addr)))?;
+                (addr, self.word_size())
+            }
+            None => {
+                let (var, addr) = self.lookup_variable(expr.trim_start_matches('&'))?;
+                (addr, var.entity_type.size.max(1))
+            }
+        };
+        let len = len.unwrap_or(default_len);
+        let value = self.read_memory(addr, len)?;
+        let i = self.current_index()?;
+        let tracee = &mut self.inferiors[i];
+        for page in watchpoint::pages_covering(addr, len) {
+            if tracee.protected_pages.contains_key(&page) {
+                continue;
+            }
+            let prot = tracee.inferior.protection_at(page)?.ok_or_else(|| {
+                DeetError::Parse(format!("Cannot access memory at address {:#x}", page))
+            })?;
+            if prot & libc::PROT_WRITE == 0 {
+                return Err(DeetError::Parse(format!(
+                    "Memory at {:#x} is read-only and cannot change",
+                    page
+                )));
+            }
+            tracee.inferior.mprotect(page, watchpoint::page_size(), prot & !libc::PROT_WRITE)?;
+            tracee.protected_pages.insert(page, prot);
+        }
+        let id = self.next_watchpoint_id;
+        self.next_watchpoint_id += 1;
+        output::info(format!(
+            "Watchpoint {}: {} ({} bytes at {})",
+            id, expr, len, output::address(addr),
+        ));
+        tracee.watchpoints.push(Watchpoint {
+            id,
+            expr: expr.to_string(),
+            addr,
+            len,
+            value,
+        });
+        Ok(())
+    }
+
+    fn watch_register(&mut self, register: &str) -> Result<(), DeetError> {
+        let inferior = self.inferior()?;
+        let summary = inferior.arch().register_summary(&inferior.registers()?);
+        let value = register_value(&summary, register)
+            .ok_or_else(|| DeetError::Parse(format!("Invalid register \"${}\"", register)))?;
+        let id = self.next_watchpoint_id;
+        self.next_watchpoint_id += 1;
+        let note = "the program is single-stepped while it is set";
+        output::info(format!("Watchpoint {}: ${} ({})", id, register, note));
+        let i = self.current_index()?;
+        self.inferiors[i].register_watches.push(RegisterWatch {
+            id,
+            register: register.to_string(),
+            value,
+        });
+        Ok(())
+    }
+
+    /// Reports the watched registers of the current inferior whose value has changed since they
+    /// were last looked at. Returns whether there were any.
+    fn check_register_watches(&mut self) -> Result<bool, DeetError> {
+        let i = self.current_index()?;
+        let tracee = &mut self.inferiors[i];
+        let summary = tracee.inferior.arch().register_summary(&tracee.inferior.registers()?);
+        let mut triggered = false;
+        for watch in &mut tracee.register_watches {
+            let value = match register_value(&summary, &watch.register) {
+                Some(value) if value != watch.value => value,
+                _ => continue,
+            };
+            output::info(format!("\nWatchpoint {}: ${}\n", watch.id, watch.register));
+            output::info(format!("Old value = {:#x}", watch.value));
+            output::info(format!("New value = {:#x}", value));
+            watch.value = value;
+            triggered = true;
+        }
+        Ok(triggered)
+    }
+
+    /// Removes a watchpoint, giving back write access to pages no other watchpoint needs.
+    fn delete_watchpoint(&mut self, id: usize) -> Result<(), DeetError> {
+        let i = self.current_index()?;
+        let tracee = &mut self.inferiors[i];
+        let before = tracee.watchpoints.len() + tracee.register_watches.len();
+        tracee.watchpoints.retain(|watchpoint| watchpoint.id != id);
+        tracee.register_watches.retain(|watch| watch.id != id);
+        if tracee.watchpoints.len() + tracee.register_watches.len() == before {
+            return Err(DeetError::Parse(format!("No watchpoint number {}", id)));
+        }
+        let still_watched = watchpoint::watched_pages(&tracee.watchpoints, &tracee.protected_pages);
+        for (page, prot) in &tracee.protected_pages {
+            if !still_watched.contains_key(page) {
+                tracee.inferior.mprotect(*page, watchpoint::page_size(), *prot)?;
+            }
+        }
+        tracee.protected_pages = still_watched;
+        Ok(())
+    }
+
+    /// Sets the protection of every watched page, either back to what it originally was
+    /// (`writable`) or to that minus write access.
+    fn protect_watched_pages(tracee: &mut Tracee, writable: bool) -> Result<(), DeetError> {
+        for (page, prot) in &tracee.protected_pages {
+            let prot = if writable { *prot } else { *prot & !libc::PROT_WRITE };
+            tracee.inferior.mprotect(*page, watchpoint::page_size(), prot)?;
+        }
+        Ok(())
+    }
+
+    /// Handles a SIGSEGV that may have been caused by a write to a watched page: the write is
+    /// replayed with the pages unprotected, and the watchpoints whose bytes changed are reported.
+    /// Returns None if the fault had nothing to do with us, or otherwise the status after the
+    /// write and whether any watchpoint triggered.
+    fn step_over_watched_write(&mut self) -> Result<Option<(Status, bool)>, DeetError> {
+        let i = self.current_index()?;
+        let tracee = &mut self.inferiors[i];
+        let fault_page = tracee.inferior.fault_address()? & !(watchpoint::page_size() - 1);
+        if !tracee.protected_pages.contains_key(&fault_page) {
+            return Ok(None);
+        }
+        Debugger::protect_watched_pages(tracee, true)?;
+        let status = tracee.inferior.resume(Resume::Step, &self.breakpoints)?;
+        if let Status::Exited(_) | Status::Signaled(_) = status {
+            return Ok(Some((status, false)));
+        }
+        Debugger::protect_watched_pages(tracee, false)?;
+
+        let mut triggered = false;
+        for j in 0..self.inferiors[i].watchpoints.len() {
+            let watchpoint = &self.inferiors[i].watchpoints[j];
+            let value = self.read_memory(watchpoint.addr, watchpoint.len)?;
+            let (mut start, mut end) = match watchpoint::changed_range(&watchpoint.value, &value) {
+                Some(range) => range,
+                None => continue,
+            };
+            output::info(format!("\nWatchpoint {}: {}\n", watchpoint.id, watchpoint.expr));
+            if watchpoint.len <= 8 {
+                start = 0;
+                end = watchpoint.len;
+            } else {
+                // Only show the part of a big buffer that the write touched
+                let changed_addr = output::address(watchpoint.addr + start);
+                output::info(format!("Bytes {}..{} (at {}) changed", start, end, changed_addr));
+            }
+            let old = &watchpoint.value[start..end];
+            output::info(format!("Old value = {}", self.format_bytes(old, Format::Natural)?));
+            output::info(format!(
+                "New value = {}",
+                self.format_bytes(&value[start..end], Format::Natural)?
+            ));
+            self.inferiors[i].watchpoints[j].value = value;
+            triggered = true;
+        }
+        Ok(Some((status, triggered)))
+    }
+
+    /// Finds `name` among the current function's variables, falling back to the globals, and
+    /// returns it together with the address it lives at.
+    fn lookup_variable(&self, name: &str) -> Result<(Variable, usize), DeetError> {
+        let var = self.find_named_variable(name)?;
+        match self.locate(&var)? {
+            Place::Memory(addr) => Ok((var, addr)),
+            Place::Value(_) => Err(DeetError::Parse(format!("\"{}\" is not in memory.", name))),
+        }
+    }
+
+    fn find_named_variable(&self, name: &str) -> Result<Variable, DeetError> {
+        self.find_variable(name)?
+            .ok_or_else(|| DeetError::Parse(format!("No symbol \"{}\" in current context.", name)))
+    }
+
+    /// Works out where `var` is, as seen from the selected frame.
+    fn locate(&self, var: &Variable) -> Result<Place, DeetError> {
+        Ok(match &var.location {
+            Location::Address(addr) => Place::Memory(*addr),
+            Location::FramePointerOffset(offset) => {
+                let base = self.selected_machine()?.frame_base()?;
+                Place::Memory((base as isize + offset) as usize)
+            }
+            Location::Expression(expr) => {
+                let machine = self.selected_machine()?;
+                expr.evaluate(machine.pc, var.entity_type.size, &machine)?
+            }
+        })
+    }
+
+    /// The selected frame, as location expressions see it.
+    fn selected_machine(&self) -> Result<FrameMachine<'_>, DeetError> {
+        let frame = self.selected()?;
+        let pc = if self.selected_frame == 0 { frame.pc } else { frame.pc - 1 };
+        let regs = match self.selected_frame {
+            0 => Some(self.inferior()?.registers()?),
+            _ => None,
+        };
+        Ok(FrameMachine { debugger: self, frame, pc, regs })
+    }
+
+    /// Renders the value of `var` in the given format.
+    fn render_variable(&self, var: &Variable, format: Format) -> Result<String, DeetError> {
+        let dtype = &var.entity_type;
+        match (self.locate(var)?, format) {
+            (Place::Memory(addr), Format::Natural) => {
+                self.format_typed(&self.read_memory(addr, dtype.size)?, dtype)
+            }
+            (Place::Memory(addr), format) => self.render(addr, dtype.size, format),
+            (Place::Value(bytes), Format::Natural) => self.format_typed(&bytes, dtype),
+            (Place::Value(bytes), format) => self.format_bytes(&bytes, format),
+        }
+    }
+
+    /// Finds the variable `name` in scope: a local of the selected frame if the inferior is
+    /// running, otherwise a global. Inner blocks shadow outer ones.
+    fn find_variable(&self, name: &str) -> Result<Option<Variable>, DeetError> {
+        let local = match self.current {
+            Some(_) => {
+                let pc = self.selected()?.pc;
+                self.debug_data.get_function_containing(pc).and_then(|func| {
+                    func.variables
+                        .iter()
+                        .filter(|var| var.name == name && var.in_scope(pc))
+                        .min_by_key(|var| var.scope.map_or(usize::MAX, |(low, high)| high - low))
+                })
+            }
+            None => None,
+        };
+        Ok(local.or_else(|| self.debug_data.get_global_variable(name)).cloned())
+    }
+
+    /// Prints every local variable visible at the selected frame's pc.
+    fn info_locals(&self) -> Result<(), DeetError> {
+        let frame = self.selected()?;
+        let func = self
+            .debug_data
+            .get_function_containing(frame.pc)
+            .ok_or_else(|| DeetError::Parse(String::from("No symbol table info available.")))?;
+        let locals: Vec<&Variable> = func
+            .variables
+            .iter()
+            .filter(|var| !var.is_parameter && var.in_scope(frame.pc))
+            .collect();
+        if locals.is_empty() {
+            output::info("No locals.");
+        }
+        for var in locals {
+            match self.render_variable(var, Format::Natural) {
+                Ok(value) => output::info(format!("{} = {}", var.name, value)),
+                Err(err) => output::info(format!(
+                    "{} = <{}>",
+                    var.name, output::paint(Style::Error, err),
+                )),
+            }
+        }
+        Ok(())
+    }
+
+    /// Finds the type that ptype/whatis should describe: that of a variable, or a named type.
+    fn lookup_type(&self, expr: &str) -> Result<Type, DeetError> {
+        if let Some(var) = self.find_variable(expr)? {
+            return Ok(var.entity_type);
+        }
+        self.debug_data
+            .get_type_by_name(expr)
+            .cloned()
+            .ok_or_else(|| DeetError::Parse(format!("No symbol \"{}\" in current context.", expr)))
+    }
+
+    /// Prints the full definition of a type, looking through typedefs and expanding struct,
+    /// union, and enum bodies.
+    fn ptype(&self, expr: &str) -> Result<(), DeetError> {
+        let mut dtype = self.lookup_type(expr)?;
+        let mut header = String::from("type = ");
+        while let TypeKind::Typedef(Some(target)) = dtype.kind {
+            header.push_str(&format!("{} = ", dtype.name));
+            match self.debug_data.get_type(target) {
+                Some(target) => dtype = target.clone(),
+                None => break,
+            }
+        }
+        match &dtype.kind {
+            TypeKind::Struct(members) | TypeKind::Union(members) => {
+                output::info(format!("{}{} {{", header, dtype.name));
+                for member in members {
+                    let member_type = member.type_offset.and_then(|t| self.debug_data.get_type(t));
+                    let (type_name, size) = match member_type {
+                        Some(member_type) => (member_type.name.as_str(), member_type.size),
+                        None => ("void", 0),
+                    };
+                    output::info(format!(
+                        "    {};  /* offset {}, size {} */",
+                        declaration(type_name, &member.name),
+                        member.offset,
+                        size
+                    ));
+                }
+                output::info(format!("}}  /* size {} */", dtype.size));
+            }
+            TypeKind::Enum(enumerators) => {
+                let variants: Vec<String> = enumerators
+                    .iter()
+                    .map(|e| format!("{} = {}", e.name, e.value))
+                    .collect();
+                output::info(format!("{}{} {{{}}}", header, dtype.name, variants.join(", ")));
+            }
+            _ => output::info(format!("{}{}", header, dtype.name)),
+        }
+        Ok(())
+    }
+
+    /// Renders the variable `name` in the given format.
+    fn evaluate(&self, name: &str, format: Format) -> Result<String, DeetError> {
+        let var = self.find_named_variable(name)?;
+        self.render_variable(&var, format)
+    }
+
+    /// Renders the `size`-byte value stored at `addr`.
+    fn render(&self, addr: usize, size: usize, format: Format) -> Result<String, DeetError> {
+        let read_memory = |addr, len| self.read_memory(addr, len);
+        if format == Format::String && size != self.word_size() {
+            // A char array rather than a pointer to one
+            return Ok(format::read_c_string(addr, &read_memory)?.0);
+        }
+        let bytes = self.read_memory(addr, size)?;
+        self.format_bytes(&bytes, format)
+    }
+
+    /// Renders a value of type `dtype` the way the type suggests: floats as floats, bools as
+    /// true or false, characters as characters, and enums by name.
+    fn format_typed(&self, bytes: &[u8], dtype: &Type) -> Result<String, DeetError> {
+        let typed = match &dtype.kind {
+            TypeKind::Base(encoding) => format::format_base(bytes, *encoding),
+            TypeKind::Enum(enumerators) => format::format_enum(bytes, enumerators),
+            TypeKind::Typedef(Some(target)) | TypeKind::Qualified(_, Some(target)) => {
+                if let Some(target) = self.debug_data.get_type(*target) {
+                    return self.format_typed(bytes, target);
+                }
+                None
+            }
+            _ => None,
+        };
+        match typed {
+            Some(value) => Ok(value),
+            None => self.format_bytes(bytes, Format::Natural),
+        }
+    }
+
+    /// Renders a value already read from the inferior. Anything larger than a word is shown as
+    /// raw bytes, unless it is an extended precision float.
+    fn format_bytes(&self, bytes: &[u8], format: Format) -> Result<String, DeetError> {
+        let read_memory = |addr, len| self.read_memory(addr, len);
+        if format == Format::Float && bytes.len() > 8 {
+            if let Some(value) = format::format_float(bytes) {
+                return Ok(value);
+            }
+        }
+        if !bytes.is_empty() && bytes.len() <= 8 {
+            format::format_value(bytes, format, &read_memory)
+        } else {
+            let bytes: Vec<String> = bytes.iter().map(|byte| format!("{:#04x}", byte)).collect();
+            Ok(format!("{{{}}}", bytes.join(", ")))
+        }
+    }
+
+    fn show_display(&self, display: &Display) {
+        match self.evaluate(&display.expr, display.format) {
+            Ok(value) => output::info(format!("{}: {} = {}", display.id, display.expr, value)),
+            Err(err) => output::info(format!(
+                "{}: {} = <{}>",
+                display.id,
+                display.expr,
+                output::paint(Style::Error, err)
+            )),
+        }
+    }
+
+    /// Works out the address `expr` stands for: a number, `&variable`, a function, or a pointer
+    /// variable's value.
+    fn address_of(&self, expr: &str) -> Result<usize, DeetError> {
+        Ok(if let Some(name) = expr.strip_prefix('&') {
+            self.lookup_variable(name)?.1
+        } else if let Ok(addr) = parse_integer(expr) {
+            addr as usize
+        } else if let Some(addr) = self.debug_data.get_addr_for_function(None, expr) {
+            addr
+        } else {
+            let (_, var_addr) = self.lookup_variable(expr)?;
+            self.inferior()?.read_word(var_addr)?
+        })
+    }
+
+    /// Dumps memory starting at `expr`, which is an address, `&variable`, a function, or a pointer
+    /// variable.
+    fn examine(&self, spec: FormatSpec, expr: &str) -> Result<(), DeetError> {
+        let mut addr = self.address_of(expr)?;
+        let format = spec.format.unwrap_or(Format::Hex);
+        let count = spec.count.unwrap_or(1);
+        if format == Format::String {
+            let read_memory = |addr, len| self.read_memory(addr, len);
+            for _ in 0..count {
+                let (text, len) = format::read_c_string(addr, &read_memory)?;
+                output::info(format!("{}:\t{}", self.describe_address(addr), text));
+                addr += len;
+            }
+            return Ok(());
+        }
+        let size = spec.size.unwrap_or(if format == Format::Char { 1 } else { 4 });
+        let per_line = match size {
+            1 | 2 => 8,
+            4 => 4,
+            _ => 2,
+        };
+        for line_start in (0..count).step_by(per_line) {
+            let line_addr = addr + line_start * size;
+            let mut line = format!("{}:", self.describe_address(line_addr));
+            for i in line_start..count.min(line_start + per_line) {
+                line.push('\t');
+                line.push_str(&self.render(addr + i * size, size, format)?);
+            }
+            output::info(line);
+        }
+        Ok(())
+    }
+
+    /// Writes the memory from `start` up to (not including) `end` out to the file at `path`,
+    /// as it is without breakpoints.
+    fn dump_memory(&self, path: &str, start: &str, end: &str) -> Result<(), DeetError> {
+        let (start, end) = (self.address_of(start)?, self.address_of(end)?);
+        if end <= start {
+            return Err(DeetError::Parse(String::from("Invalid memory range: end before start.")));
+        }
+        std::fs::write(path, self.read_memory(start, end - start)?)?;
+        output::info(format!("Dumped {} bytes to file '{}'.", end - start, path));
+        Ok(())
+    }
+
+    /// Copies the contents of the file at `path` into memory at `addr`.
+    fn restore_memory(&mut self, path: &str, addr: &str) -> Result<(), DeetError> {
+        let addr = self.address_of(addr)?;
+        let bytes = std::fs::read(path)?;
+        output::info(format!(
+            "Restoring binary file {} into memory ({} to {})",
+            path,
+            output::address(addr),
+            output::address(addr + bytes.len())
+        ));
+        self.write_memory(addr, &bytes)
+    }
+
+    /// Prints the line numbers and addresses deet parsed out of the DWARF line programs, for all
+    /// the source files or just for `file`. This is for debugging deet itself: a line `break`
+    /// cannot find usually turns out to be missing here.
+    fn maint_line_table(&self, file: Option<&str>) -> Result<(), DeetError> {
+        let files: Vec<&File> = match file {
+            Some(name) => vec![self.debug_data.get_target_file(name).ok_or_else(|| {
+                DeetError::Parse(format!("No source file named \"{}\"", name))
+            })?],
+            None => self.debug_data.files().iter().collect(),
+        };
+        if files.is_empty() {
+            output::info("No debugging information.");
+        }
+        for file in files {
+            output::info(format!("Line table for {} ({} entries):", file.name, file.lines.len()));
+            if !file.lines.is_empty() {
+                output::info(format!("  {:>6}  ADDRESS", "LINE"));
+            }
+            for line in &file.lines {
+                output::info(format!("  {:>6}  {}", line.number, output::address(line.address)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Lists the sections of the program whose symbols are loaded, with where each one is in
+    /// memory and in the file, like gdb's `info files`.
+    fn info_files(&self) {
+        output::info(format!("Symbols from \"{}\".", self.symbols_path));
+        output::info(format!("Entry point: {}", output::address(self.debug_data.entry_point())));
+        for section in self.debug_data.symbol_table().sections() {
+            let end = section.address + section.size;
+            let file = match section.file_offset {
+                Some(offset) => format!("file {:#x} - {:#x}", offset, offset + section.size),
+                None => String::from("not in file"),
+            };
+            output::info(format!(
+                "\t{} - {} is {} ({})",
+                output::address(section.address),
+                output::address(end),
+                section.name,
+                file
+            ));
+        }
+    }
+
+    /// Compares the read-only sections of the program in memory with the file they were loaded
+    /// from, reporting where they differ. Breakpoints are seen through, so only changes deet
+    /// did not make itself show up.
+    fn compare_sections(&self) -> Result<(), DeetError> {
+        let file = std::fs::read(&self.symbols_path)?;
+        let mut mismatched = false;
+        for section in self.debug_data.symbol_table().sections() {
+            let on_disk = match section.file_offset {
+                Some(offset) if section.read_only => file.get(offset..offset + section.size),
+                _ => None,
+            };
+            let on_disk = match on_disk {
+                Some(bytes) => bytes,
+                None => continue,
+            };
+            let in_memory = self.read_memory(section.address, section.size)?;
+            let ranges = differing_ranges(on_disk, &in_memory);
+            output::info(format!(
+                "Section {}, range {} -- {}: {}",
+                section.name,
+                output::address(section.address),
+                output::address(section.address + section.size),
+                if ranges.is_empty() { "matched." } else { "MIS-MATCHED!" }
+            ));
+            for (start, end) in ranges.iter().take(MISMATCHES_SHOWN) {
+                let len = end - start;
+                let bytes = if len == 1 { "byte differs" } else { "bytes differ" };
+                output::info(format!(
+                    "  {} -- {}: {} {}",
+                    output::address(section.address + start),
+                    output::address(section.address + end),
+                    len,
+                    bytes
+                ));
+            }
+            if ranges.len() > MISMATCHES_SHOWN {
+                output::info(format!("  ... and {} more", ranges.len() - MISMATCHES_SHOWN));
+            }
+            mismatched |= !ranges.is_empty();
+        }
+        if mismatched {
+            output::info(format!(
+                "The program in memory differs from {}; it may modify its own code, or the file \
+                 may have changed since it was started.",
+                self.symbols_path
+            ));
+        }
+        Ok(())
+    }
+
+    /// Lists the shared objects mapped into the current inferior, with the addresses their code
+    /// occupies and whether deet can read their symbols (for `info symbol` and `heap trace`).
+    fn info_shared_libraries(&self) -> Result<(), DeetError> {
+        let libraries = match self.current {
+            Some(_) => self.inferior()?.shared_libraries(&self.symbols_path)?,
+            None => Vec::new(),
+        };
+        if libraries.is_empty() {
+            output::info("No shared libraries loaded at this time.");
+            return Ok(());
+        }
+        output::info(format!(
+            "{:<18}  {:<18}  {:<9}  Shared Object Library",
+            "From", "To", "Syms Read"
+        ));
+        for library in libraries {
+            let (from, to) = match library.text {
+                Some((start, end)) => {
+                    let column = |addr| output::paint(Style::Address, format!("{:#018x}", addr));
+                    (column(start), column(end))
+                }
+                None => (String::new(), String::new()),
+            };
+            let symbols = SymbolTable::from_file(&library.path).is_ok_and(|t| !t.is_empty());
+            let symbols = if symbols { "Yes" } else { "No" };
+            output::info(format!("{:<18}  {:<18}  {:<9}  {}", from, to, symbols, library.path));
+        }
+        Ok(())
+    }
+
+    /// Names the symbol `expr` (an address or `&variable`) points into, and the section and
+    /// library that contains it.
+    fn info_symbol(&self, expr: &str) -> Result<(), DeetError> {
+        let addr = match expr.strip_prefix('&') {
+            Some(name) => self.lookup_variable(name)?.1,
+            None => parse_integer(expr)? as usize,
+        };
+        let symbol_table = self.debug_data.symbol_table();
+        let symbol = match self.debug_data.get_function_containing(addr) {
+            Some(func) => Some((func.name.clone(), addr - func.address)),
+            None => symbol_table
+                .lookup(addr)
+                .map(|(symbol, offset)| (symbol.name.clone(), offset)),
+        };
+        if let Some((name, offset)) = symbol {
+            let section = symbol_table.section_containing(addr).map_or("??", |s| s.name.as_str());
+            output::info(format!("{} in section {}", describe_symbol(&name, offset), section));
+            return Ok(());
+        }
+
+        // Perhaps a shared library has it
+        let mapping = match self.current {
+            Some(_) => self.inferior()?.mapping_containing(addr)?,
+            None => None,
+        };
+        let mapping = match mapping {
+            Some(mapping) if mapping.path.starts_with('/') => mapping,
+            Some(mapping) if !mapping.path.is_empty() => {
+                output::info(format!("No symbol matches {} (in {}).", expr, mapping.path));
+                return Ok(());
+            }
+            _ => {
+                output::info(format!("No symbol matches {}.", expr));
+                return Ok(());
+            }
+        };
+        // Assumes the library's segments sit at the same distance from each other in memory as
+        // in the file, which is how the dynamic loader maps them
+        let file_addr = addr - mapping.start + mapping.offset;
+        let library = SymbolTable::from_file(&mapping.path)?;
+        match library.lookup(file_addr) {
+            Some((symbol, offset)) => {
+                let section = library.section_containing(file_addr);
+                let section = section.map_or("??", |s| s.name.as_str());
+                output::info(format!(
+                    "{} in section {} of {}",
+                    describe_symbol(&symbol.name, offset),
+                    section,
+                    mapping.path
+                ));
+            }
+            None => output::info(format!("No symbol matches {} (in {}).", expr, mapping.path)),
+        }
+        Ok(())
+    }
+
+    /// Prints everything needed to get one's bearings after a stop: where the inferior is, the
+    /// source around it, the innermost frames, and the registers.
+    fn where_summary(&self) -> Result<(), DeetError> {
+        let inferior = self.inferior()?;
+        let arch = inferior.arch();
+        let regs = inferior.registers()?;
+        let rip = arch.pc(&regs);
+        output::info(format!("{} = {}", arch.pc_name(), self.describe_address(rip)));
+
+        if let Some(line) = self.debug_data.get_line_from_addr(rip) {
+            output::info(output::source(&line));
+            let context = (self.settings.listsize - 1) / 2;
+            match source_snippet(&line.file, line.number, context) {
+                Ok(snippet) => {
+                    for (number, text) in snippet {
+                        let marker = if number == line.number { "=>" } else { "  " };
+                        output::info(format!("{} {:>4}  {}", marker, number, text));
+                    }
+                }
+                Err(err) => output::info(format!("(source not available: {})", err)),
+            }
+        }
+
+        output::info("");
+        let frames = self.frames()?;
+        for (i, frame) in frames.iter().enumerate().take(WHERE_FRAMES) {
+            output::info(format!("#{} {}", i, self.describe_frame(frame)));
+        }
+        if frames.len() > WHERE_FRAMES {
+            output::info(format!("(+{} more frames)", frames.len() - WHERE_FRAMES));
+        }
+
+        output::info("");
+        print_register_grid(&arch.register_summary(&regs));
+        Ok(())
+    }
+
+    /// Prints the general purpose registers, followed by the floating point and vector ones if
+    /// `all` is set.
+    fn info_registers(&self, all: bool) -> Result<(), DeetError> {
+        let inferior = self.inferior()?;
+        print_register_grid(&inferior.arch().register_summary(&inferior.registers()?));
+        if all {
+            for (name, value) in inferior.fp_registers()? {
+                output::info(format!("{:<6} {}", name, format::format_fp_register(&value)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Disassembles the function the selected frame is in, marking the instruction about to run
+    /// with `=>` and those with a breakpoint on them with `*`.
+    fn disassemble_function(&self) -> Result<(), DeetError> {
+        let pc = self.selected()?.pc;
+        let (name, start, end) = self.code_range_containing(pc).ok_or_else(|| {
+            DeetError::Parse(format!("No function contains the program counter ({:#x}).", pc))
+        })?;
+        let instructions = disassemble::disassemble(&self.symbols_path, start, end)?;
+        output::info(format!("Dump of assembler code for function {}:", output::function(&name)));
+        for instruction in instructions {
+            let marker = if instruction.address == pc { "=>" } else { "  " };
+            let breakpoint = if self.breakpoints.contains_key(&instruction.address) {
+                "*"
+            } else {
+                " "
+            };
+            output::info(format!(
+                "{}{} {} <+{}>:\t{}",
+                marker,
+                breakpoint,
+                output::address(instruction.address),
+                instruction.address - start,
+                instruction.text
+            ));
+        }
+        output::info("End of assembler dump.");
+        Ok(())
+    }
+
+    /// Dumps `words` words of the stack, starting at the stack pointer, pointing out which slots
+    /// hold a frame's return address or saved frame pointer.
+    fn dump_stack(&self, words: usize) -> Result<(), DeetError> {
+        let inferior = self.inferior()?;
+        let arch = inferior.arch();
+        let regs = inferior.registers()?;
+        let (sp, fp) = (arch.sp(&regs), arch.frame_pointer(&regs));
+        let word = arch.word_size();
+        let saved_fp = format!("saved {}", arch.frame_pointer_name());
+        let mut slots = HashMap::new();
+        for (i, frame) in self.frames()?.iter().enumerate() {
+            match frame.record {
+                Some(record) => {
+                    slots.insert(record + word, (i, "return address"));
+                    slots.insert(record, (i, saved_fp.as_str()));
+                }
+                // Until its prologue has run, the innermost frame has no saved frame pointer
+                None => {
+                    if let Slot::Stack(addr) = arch.return_address_at_entry(&regs) {
+                        slots.insert(addr, (i, "return address"));
+                    }
+                }
+            }
+        }
+        for addr in (sp..).step_by(word).take(words) {
+            let value = inferior.read_word(addr)?;
+            let mut line =
+                format!("{}: {:#0width$x}", output::address(addr), value, width = 2 + 2 * word);
+            let mut registers = Vec::new();
+            if addr == sp {
+                registers.push(arch.sp_name());
+            }
+            if addr == fp {
+                registers.push(arch.frame_pointer_name());
+            }
+            if !registers.is_empty() {
+                line.push_str(&format!("  <- {}", registers.join(", ")));
+            }
+            match slots.get(&addr) {
+                Some((frame, "return address")) => line.push_str(&format!(
+                    "  #{} return address: {}",
+                    frame,
+                    self.describe_address(value)
+                )),
+                Some((frame, what)) => line.push_str(&format!("  #{} {}", frame, what)),
+                None => {}
+            }
+            output::info(line);
+        }
+        Ok(())
+    }
+
+    /// Formats `addr` as `0x401136 <func3+4>` when it falls inside a known function.
+    fn describe_address(&self, addr: usize) -> String {
+        match self.debug_data.get_function_containing(addr) {
+            Some(func) if addr == func.address => {
+                format!("{} <{}>", output::address(addr), output::function(&func.name))
+            }
+            Some(func) => format!(
+                "{} <{}+{}>",
+                output::address(addr),
+                output::function(&func.name),
+                addr - func.address
+            ),
+            None => output::address(addr),
+        }
+    }
+
+    /// Walks the stack from the current instruction up to `main`.
+    fn frames(&self) -> Result<Vec<Frame>, DeetError> {
+        let inferior = self.inferior()?;
+        let arch = inferior.arch();
+        let regs = inferior.registers()?;
+        let (pc, sp, fp) = (arch.pc(&regs), arch.sp(&regs), arch.frame_pointer(&regs));
+        let word = arch.word_size();
+        let (cfa, record) = match self.frame_setup()? {
+            FrameSetup::NotStarted => (sp + arch.entry_cfa_offset(), None),
+            FrameSetup::RbpPushed => (sp + self.frame_size_at(pc)?, Some(sp)),
+            FrameSetup::Complete => (fp + self.frame_size_at(pc)?, Some(fp)),
+        };
+        // Call frame information is right even where the frame pointer is used for something
+        // else, as in optimized code
+        let cfi_cfa = self.debug_data.cfa_rule(pc).and_then(|(register, offset)| {
+            Some((arch.dwarf_register(&regs, register)? as i64 + offset) as usize)
+        });
+        let cfa = cfi_cfa.unwrap_or(cfa);
+        // Returns the return address and saved frame pointer in a frame record
+        let read_record = |record: usize| -> Option<(usize, usize)> {
+            Some((inferior.read_word(record + word).ok()?, inferior.read_word(record).ok()?))
+        };
+        // `caller` tracks the pc and frame record of the frame above the last one found
+        let mut caller = match record {
+            Some(record) => read_record(record),
+            None => Some((inferior.read_slot(arch.return_address_at_entry(&regs))?, fp)),
+        };
+        let mut frames = vec![Frame { pc, cfa, record }];
+        while frames.len() < MAX_FRAMES {
+            let frame = frames.last().unwrap();
+            match self.debug_data.get_function_from_addr(frame.pc) {
+                Some(name) if name != "main" => {}
+                _ => break,
+            }
+            let (pc, record) = match caller {
+                Some(caller) => caller,
+                None => break,
+            };
+            let cfa = record + self.frame_size_at(pc)?;
+            caller = read_record(record);
+            frames.push(Frame { pc, cfa, record: Some(record) });
+        }
+        Ok(frames)
+    }
+
+    fn selected(&self) -> Result<Frame, DeetError> {
+        let mut frames = self.frames()?;
+        if self.selected_frame >= frames.len() {
+            return Err(DeetError::Parse(String::from("The selected frame no longer exists.")));
+        }
+        Ok(frames.swap_remove(self.selected_frame))
+    }
+
+    fn select_frame(&mut self, index: isize) -> Result<(), DeetError> {
+        let frames = self.frames()?;
+        if index < 0 {
+            return Err(DeetError::Parse(String::from(
+                "Bottom (innermost) frame selected; you cannot go down.",
+            )));
+        }
+        let frame = frames.get(index as usize).ok_or_else(|| {
+            DeetError::Parse(String::from("Initial frame selected; you cannot go up."))
+        })?;
+        self.selected_frame = index as usize;
+        output::info(format!("#{} {}", index, self.describe_frame(frame)));
+        Ok(())
+    }
+
+    fn describe_frame(&self, frame: &Frame) -> String {
+        let func = self.debug_data.get_function_from_addr(frame.pc);
+        let line = self.debug_data.get_line_from_addr(frame.pc);
+        match (func, line) {
+            (Some(func), Some(line)) => {
+                format!("{} ({})", output::function(func), output::source(line))
+            }
+            (Some(func), None) => {
+                format!("{} ({})", output::function(func), output::address(frame.pc))
+            }
+            (None, _) => format!("?? ({})", output::address(frame.pc)),
+        }
+    }
+
+    /// Pops the current frame so that execution picks up in the caller, with `value` (if any) as
+    /// the return value.
+    fn force_return(&mut self, value: Option<i64>) -> Result<(), DeetError> {
+        let setup = self.frame_setup()?;
+        let rip = self.inferior()?.get_pc()?;
+        let func = self
+            .debug_data
+            .get_function_from_addr(rip)
+            .unwrap_or_else(|| output::address(rip));
+        if !self.confirm(&format!("Make {} return now?", func)) {
+            return Ok(());
+        }
+        let cfa = self.frames()?[0].cfa;
+        self.selected_frame = 0;
+        let inferior = self.inferior_mut()?;
+        inferior.pop_frame(setup, cfa, value.map(|value| value as u64))?;
+        let rip = inferior.get_pc()?;
+        self.print_location(rip);
+        Ok(())
+    }
+
+    /// Moves execution to `location` and continues from there.
+    fn jump(&mut self, location: &str) -> Result<(), DeetError> {
+        let target = self.resolve_location(location)?;
+        let rip = self.inferior()?.get_pc()?;
+        let current_func = self.debug_data.get_function_containing(rip).map(|f| f.name.clone());
+        let target_func = self.debug_data.get_function_containing(target).map(|f| f.name.clone());
+        if current_func != target_func {
+            // The stack frame belongs to the current function; the target will misread it
+            output::warn(format!(
+                "{} is not in the current function {}.",
+                location,
+                current_func.as_deref().unwrap_or("??")
+            ));
+            if !self.confirm("Jump anyway?") {
+                return Ok(());
+            }
+        }
+        output::info(format!("Continuing at {}.", output::address(target)));
+        self.inferior_mut()?.set_pc(target)?;
+        self.resume(Resume::Continue)
+    }
+
+    /// Steps to the next source line of the current frame, running the calls it makes to
+    /// completion. The stack pointer tells this frame apart from recursive invocations of the
+    /// same function, so lines the recursion passes through are not mistaken for this frame's.
+    fn next(&mut self) -> Result<(), DeetError> {
+        let pc = self.inferior()?.get_pc()?;
+        let no_line_info = || {
+            DeetError::Parse(String::from("The current function has no line number information."))
+        };
+        let line = self.debug_data.get_line_from_addr(pc).ok_or_else(no_line_info)?;
+        let (start, end) = self
+            .debug_data
+            .get_function_containing(pc)
+            .map(|func| (func.address, func.address + func.text_length))
+            .ok_or_else(no_line_info)?;
+        let cfa = self.frames()?[0].cfa;
+        self.selected_frame = 0;
+        loop {
+            let (status, watch_triggered) = self.run_until_stop(Resume::Step)?;
+            let mut addr = match status {
+                Status::Stopped(Signal::SIGTRAP, addr) if !watch_triggered => addr,
+                status => {
+                    self.report_stop(status, watch_triggered);
+                    return Ok(());
+                }
+            };
+            let inferior = self.inferior()?;
+            let regs = inferior.registers()?;
+            let sp = inferior.arch().sp(&regs);
+            let outside = addr < start || addr >= end;
+            if outside && sp >= cfa {
+                // This frame has returned
+                break;
+            }
+            if outside || addr == start {
+                // A call, possibly a recursive one: let it return to this frame first
+                let slot = inferior.arch().return_address_at_entry(&regs);
+                let return_addr = inferior.read_slot(slot)?;
+                if let Some((status, watch_triggered)) = self.finish_call(return_addr, sp)? {
+                    self.report_stop(status, watch_triggered);
+                    return Ok(());
+                }
+                addr = return_addr;
+                if addr < start || addr >= end {
+                    break;
+                }
+            }
+            match self.debug_data.get_line_from_addr(addr) {
+                Some(now) if now.file == line.file && now.number == line.number => {}
+                _ => break,
+            }
+        }
+        let pc = self.inferior()?.get_pc()?;
+        self.report_stop(Status::Stopped(Signal::SIGTRAP, pc), false);
+        Ok(())
+    }
+
+    /// Lets the function the current inferior has just called run until it returns to
+    /// `return_addr`. `entry_sp`, the stack pointer on entry, tells that return apart from those
+    /// of deeper recursive calls passing the same place. Returns how the inferior stopped
+    /// instead, if something else stopped it first.
+    fn finish_call(
+        &mut self,
+        return_addr: usize,
+        entry_sp: usize,
+    ) -> Result<Option<(Status, bool)>, DeetError> {
+        // While recording, the call is stepped through, so that it can be undone as well
+        let i = self.current_index()?;
+        let mode = match self.inferiors[i].record {
+            Some(_) => Resume::Step,
+            None => Resume::Continue,
+        };
+        // Without these, a call that never returns would let the program run away
+        let exits = self.find_non_local_exits()?;
+        if let Some(exit) = exits.get(&self.inferior()?.get_pc()?) {
+            return self.leave_call(*exit).map(Some);
+        }
+        let mut temporary = Vec::new();
+        if mode == Resume::Continue {
+            let addrs = std::iter::once(return_addr).chain(exits.keys().copied());
+            temporary = self.add_temporary_breakpoints(addrs)?;
+        }
+        let thread = self.inferior()?.current_thread();
+        let mut left_through = None;
+        let result = loop {
+            let (status, watch_triggered) = match self.run_until_stop(mode) {
+                Ok(result) => result,
+                Err(err) => break Err(err),
+            };
+            let addr = match status {
+                Status::Stopped(Signal::SIGTRAP, addr) if !watch_triggered => addr,
+                status => break Ok(Some((status, watch_triggered))),
+            };
+            let user_breakpoint = self.breakpoints.get(&addr).is_some_and(|bp| !bp.temporary);
+            // Other threads running the same code run into the temporary breakpoints too
+            let other_thread =
+                self.inferior().is_ok_and(|inferior| inferior.current_thread() != thread);
+            if other_thread && !user_breakpoint {
+                continue;
+            }
+            if addr == return_addr {
+                let sp = self
+                    .inferior()
+                    .and_then(|inferior| Ok(inferior.arch().sp(&inferior.registers()?)));
+                match sp {
+                    Ok(sp) if sp >= entry_sp => break Ok(None),
+                    // A deeper invocation returning; the user's own breakpoint still counts
+                    Ok(_) if !user_breakpoint => continue,
+                    Ok(_) => {}
+                    Err(err) => break Err(err),
+                }
+            } else if let (Some(exit), false) = (exits.get(&addr), user_breakpoint) {
+                left_through = Some(*exit);
+                break Ok(None);
+            } else if mode == Resume::Step && !user_breakpoint {
+                continue;
+            }
+            break Ok(Some((status, watch_triggered)));
+        };
+
+        let alive = match &result {
+            Ok(Some((Status::Exited(_), _))) | Ok(Some((Status::Signaled(_), _))) => false,
+            _ => self.current.is_some(),
+        };
+        self.remove_temporary_breakpoints(temporary, alive)?;
+        match left_through {
+            Some(exit) => self.leave_call(exit).map(Some),
+            None => result,
+        }
+    }
+
+    /// Finds where the functions in `NON_LOCAL_EXITS` start, in the program and in its shared
+    /// libraries.
+    fn find_non_local_exits(&self) -> Result<HashMap<usize, NonLocalExit>, DeetError> {
+        let mut tables = vec![(0, self.debug_data.symbol_table())];
+        let libraries = self.inferior()?.shared_libraries(&self.symbols_path)?;
+        let library_tables: Vec<(usize, SymbolTable)> = libraries
+            .iter()
+            .filter_map(|library| Some((library.base, SymbolTable::from_file(&library.path).ok()?)))
+            .collect();
+        tables.extend(library_tables.iter().map(|(base, table)| (*base, table)));
+        let mut exits = HashMap::new();
+        for (base, table) in tables {
+            for (name, exit) in &NON_LOCAL_EXITS {
+                if let Some(addr) = table.address_of(name) {
+                    exits.insert(base + addr, *exit);
+                }
+            }
+        }
+        Ok(exits)
+    }
+
+    /// Follows the inferior out of a call it is leaving through `exit`. A longjmp is stepped
+    /// through to where it lands; unwinding is stopped where it starts, as following it through
+    /// the unwinder one instruction at a time would take far too long.
+    fn leave_call(&mut self, exit: NonLocalExit) -> Result<(Status, bool), DeetError> {
+        if exit == NonLocalExit::Unwind {
+            output::info("The call is being unwound (a panic or an exception was raised).");
+            let pc = self.inferior()?.get_pc()?;
+            return Ok((Status::Stopped(Signal::SIGTRAP, pc), false));
+        }
+        output::info("The call was left through longjmp.");
+        let mut steps = 0;
+        loop {
+            let (status, watch_triggered) = self.run_until_stop(Resume::Step)?;
+            steps += 1;
+            match status {
+                Status::Stopped(Signal::SIGTRAP, addr)
+                    if !watch_triggered
+                        && steps < LONGJMP_STEP_LIMIT
+                        && self.debug_data.get_line_from_addr(addr).is_none() => {}
+                status => return Ok((status, watch_triggered)),
+            }
+        }
+    }
+
+    /// Sets breakpoints for deet's own use in the current inferior, at those of `addrs` that
+    /// have none yet. Returns where it set them, to be passed to `remove_temporary_breakpoints`
+    /// afterwards. If one cannot be set, those already set are taken back out.
+    fn add_temporary_breakpoints(
+        &mut self,
+        addrs: impl IntoIterator<Item = usize>,
+    ) -> Result<Vec<usize>, DeetError> {
+        let mut added = Vec::new();
+        for addr in addrs {
+            if self.breakpoints.contains_key(&addr) {
+                continue;
+            }
+            let mut breakpoint = BreakPoint::temporary(addr);
+            let installed = self
+                .inferior_mut()
+                .and_then(|inferior| breakpoint.install(std::iter::once(inferior)));
+            if let Err(err) = installed {
+                self.remove_temporary_breakpoints(added, true)?;
+                return Err(err);
+            }
+            self.breakpoints.insert(addr, breakpoint);
+            added.push(addr);
+        }
+        Ok(added)
+    }
+
+    /// Takes out the breakpoints `add_temporary_breakpoints` set at `addrs`, unless something
+    /// (like an exec replacing the breakpoint table) got there first. `alive` says whether there
+    /// is still a process to patch.
+    fn remove_temporary_breakpoints(
+        &mut self,
+        addrs: Vec<usize>,
+        alive: bool,
+    ) -> Result<(), DeetError> {
+        for addr in addrs {
+            if !self.breakpoints.get(&addr).is_some_and(|bp| bp.temporary) {
+                continue;
+            }
+            let mut breakpoint = self.breakpoints.remove(&addr).unwrap();
+            if alive {
+                breakpoint.uninstall(std::iter::once(self.inferior_mut()?))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn record(&mut self, on: bool) -> Result<(), DeetError> {
+        let i = self.current_index()?;
+        let tracee = &mut self.inferiors[i];
+        if tracee.btrace.is_some() {
+            if on {
+                return Err(already_recording());
+            }
+            tracee.btrace = None;
+            output::info("Process record is stopped and all execution logs are deleted.");
+            return Ok(());
+        }
+        match (on, tracee.record.is_some()) {
+            (true, false) => {
+                tracee.record = Some(Recording::new());
+                let limit = RECORD_LIMIT;
+                output::info(format!(
+                    "Recording execution while stepping (the last {} instructions).",
+                    limit
+                ));
+                if !disassemble::objdump_available() {
+                    output::warn(
+                        "objdump not found: without it, deet cannot tell what memory an \
+                         instruction writes, and only saves the stack around it.",
+                    );
+                }
+            }
+            (true, true) => return Err(already_recording()),
+            (false, true) => {
+                tracee.record = None;
+                output::info("Process record is stopped and all execution logs are deleted.");
+            }
+            (false, false) => return Err(not_recording()),
+        }
+        Ok(())
+    }
+
+    /// Starts recording the current inferior with Intel Processor Trace, which lets it run at
+    /// full speed. Only the threads it has now are recorded.
+    fn record_btrace(&mut self) -> Result<(), DeetError> {
+        let i = self.current_index()?;
+        let tracee = &mut self.inferiors[i];
+        if tracee.record.is_some() || tracee.btrace.is_some() {
+            return Err(already_recording());
+        }
+        if !disassemble::objdump_available() {
+            return Err(DeetError::ToolNotFound("objdump"));
+        }
+        let tids: Vec<Pid> = tracee.inferior.threads().iter().map(|thread| thread.tid).collect();
+        tracee.btrace = Some(BranchTrace::start(&tids)?);
+        output::info("Recording execution with Intel Processor Trace.");
+        Ok(())
+    }
+
+    /// Replays the processor's trace of the current thread over the code of the program and its
+    /// shared libraries, returning the instructions the thread executed, oldest first.
+    fn btrace_entries(&mut self, libraries: &[SharedLibrary]) -> Result<Vec<Entry>, DeetError> {
+        let i = self.current_index()?;
+        let thread = self.inferiors[i].inferior.current_thread();
+        let (bytes, mut code) = match &mut self.inferiors[i].btrace {
+            Some(btrace) => {
+                let bytes = btrace.trace(thread.tid).ok_or_else(|| {
+                    DeetError::Parse(format!("Thread {} is not being recorded.", thread.number))
+                })?;
+                (bytes, std::mem::take(&mut btrace.code))
+            }
+            None => return Err(not_recording()),
+        };
+        let packets = btrace::decode(&bytes);
+        let symbol_table = self.debug_data.symbol_table();
+        let entries = btrace::reconstruct(&packets, &mut |addr| {
+            if !code.covers(addr) {
+                // Disassemble the whole section or library the code is in, once
+                let (path, start, end, bias) = match symbol_table.section_containing(addr) {
+                    Some(section) => {
+                        let end = section.address + section.size;
+                        (self.symbols_path.as_str(), section.address, end, 0)
+                    }
+                    None => {
+                        let library = library_containing(libraries, addr)?;
+                        let (start, end) = library.text?;
+                        let base = library.base;
+                        (library.path.as_str(), start - base, end - base, base)
+                    }
+                };
+                let instructions = disassemble::disassemble(path, start, end).unwrap_or_default();
+                code.add(&instructions, start, end, bias);
+            }
+            code.get(addr).map(|(_, branch, next)| (*branch, *next))
+        });
+        if let Some(btrace) = &mut self.inferiors[i].btrace {
+            btrace.code = code;
+        }
+        Ok(entries)
+    }
+
+    /// Names the function `addr` is in, and how far into it it is. The symbols of shared
+    /// libraries are read into `tables` as they are needed.
+    fn function_at(
+        &self,
+        addr: usize,
+        libraries: &[SharedLibrary],
+        tables: &mut HashMap<String, Option<SymbolTable>>,
+    ) -> Option<(String, usize)> {
+        if let Some(func) = self.debug_data.get_function_containing(addr) {
+            return Some((func.name.clone(), addr - func.address));
+        }
+        if let Some((symbol, offset)) = self.debug_data.symbol_table().lookup(addr) {
+            return Some((symbol.name.clone(), offset));
+        }
+        let library = library_containing(libraries, addr)?;
+        let table = tables
+            .entry(library.path.clone())
+            .or_insert_with(|| SymbolTable::from_file(&library.path).ok());
+        let (symbol, offset) = table.as_ref()?.lookup(addr - library.base)?;
+        Some((symbol.name.clone(), offset))
+    }
+
+    /// Shows the last `count` instructions the processor recorded the current thread executing,
+    /// numbered from the oldest one recorded.
+    fn instruction_history(&mut self, count: usize) -> Result<(), DeetError> {
+        let libraries = self.inferior()?.shared_libraries(&self.symbols_path)?;
+        let entries = self.btrace_entries(&libraries)?;
+        if entries.is_empty() {
+            output::info("No instruction history has been recorded.");
+            return Ok(());
+        }
+        let i = self.current_index()?;
+        let mut tables = HashMap::new();
+        let mut numbered = Vec::new();
+        let mut number = 0;
+        for entry in &entries {
+            if let Entry::Instruction(_) = entry {
+                number += 1;
+            }
+            numbered.push((number, entry));
+        }
+        for (number, entry) in &numbered[numbered.len().saturating_sub(count)..] {
+            let addr = match entry {
+                Entry::Instruction(addr) => *addr,
+                Entry::Gap => {
+                    output::warn("[part of the trace was lost or could not be decoded]");
+                    continue;
+                }
+            };
+            let location = match self.function_at(addr, &libraries, &mut tables) {
+                Some((name, 0)) => {
+                    format!("{} <{}>", output::address(addr), output::function(&name))
+                }
+                Some((name, offset)) => {
+                    format!("{} <{}+{}>", output::address(addr), output::function(&name), offset)
+                }
+                None => output::address(addr),
+            };
+            let code = self.inferiors[i].btrace.as_ref().map(|btrace| &btrace.code);
+            let text = code.and_then(|code| code.get(addr)).map_or("", |(text, _, _)| text);
+            output::info(format!("{}\t{}:\t{}", number, location, text));
+        }
+        Ok(())
+    }
+
+    /// Shows the functions the current thread ran in over the last of the processor's trace, in
+    /// order, with the numbers (as `record instruction-history` gives them) of the first and last
+    /// instruction executed in each stretch.
+    fn function_call_history(&mut self, count: usize) -> Result<(), DeetError> {
+        let libraries = self.inferior()?.shared_libraries(&self.symbols_path)?;
+        let entries = self.btrace_entries(&libraries)?;
+        let mut tables = HashMap::new();
+        // The function of each stretch (None for a gap in the trace) and its instructions
+        let mut stretches: Vec<(Option<String>, usize, usize)> = Vec::new();
+        let mut number = 0;
+        for entry in &entries {
+            let name = match entry {
+                Entry::Instruction(addr) => {
+                    number += 1;
+                    let name = self.function_at(*addr, &libraries, &mut tables);
+                    Some(name.map_or_else(|| String::from("??"), |(name, _)| name))
+                }
+                Entry::Gap => None,
+            };
+            match stretches.last_mut() {
+                Some((last, _, end)) if *last == name && name.is_some() => *end = number,
+                _ => stretches.push((name, number, number)),
+            }
+        }
+        if stretches.is_empty() {
+            output::info("No function call history has been recorded.");
+        }
+        for (index, (name, start, end)) in stretches.iter().enumerate() {
+            if index + count < stretches.len() {
+                continue;
+            }
+            match name {
+                Some(name) => output::info(format!(
+                    "{}\t{}\tinst {},{}",
+                    index + 1,
+                    output::function(name),
+                    start,
+                    end
+                )),
+                None => output::warn("[part of the trace was lost or could not be decoded]"),
+            }
+        }
+        Ok(())
+    }
+
+    /// Saves what the instruction the current inferior is about to execute may destroy, if its
+    /// execution is being recorded. Running on without stepping leaves a gap that the recording
+    /// cannot reach back across, so it starts over.
+    fn record_step(&mut self, mode: Resume) -> Result<(), DeetError> {
+        let i = self.current_index()?;
+        match &mut self.inferiors[i].record {
+            None => return Ok(()),
+            Some(record) if mode == Resume::Continue => {
+                record.clear();
+                return Ok(());
+            }
+            Some(_) => {}
+        }
+        let regs = self.inferiors[i].inferior.registers()?;
+        let pc = self.inferiors[i].inferior.arch().pc(&regs);
+        if self.inferiors[i].record.as_ref().and_then(|record| record.instruction(pc)).is_none() {
+            let instructions = self.decode_function(pc);
+            if let Some(record) = &mut self.inferiors[i].record {
+                record.add_instructions(instructions.into_iter());
+            }
+        }
+
+        let tracee = &mut self.inferiors[i];
+        let (inferior, record) = match (&tracee.inferior, &mut tracee.record) {
+            (inferior, Some(record)) => (inferior, record),
+            _ => return Ok(()),
+        };
+        let arch = inferior.arch();
+        let summary = arch.register_summary(&regs);
+        let register = |name: &str| register_value(&summary, name);
+        let ranges = record
+            .instruction(pc)
+            .and_then(|(text, next)| record::written_ranges(text, *next, &register));
+        let exact = ranges.is_some();
+        let ranges = ranges.unwrap_or_else(|| {
+            let sp = arch.sp(&regs);
+            vec![(sp.saturating_sub(STACK_WINDOW), 2 * STACK_WINDOW)]
+        });
+        let memory = ranges
+            .into_iter()
+            .filter_map(|(addr, len)| Some((addr, inferior.read_bytes(addr, len).ok()?)))
+            .collect();
+        record.push(Step { regs, memory, exact });
+        Ok(())
+    }
+
+    /// Disassembles the function of the program that `pc` is in, as (address, text, address of
+    /// the next instruction) triples. Code outside the program, such as that of shared
+    /// libraries, is not decoded.
+    fn decode_function(&self, pc: usize) -> Vec<(usize, String, usize)> {
+        let (_, start, end) = match self.code_range_containing(pc) {
+            Some(range) => range,
+            None => return Vec::new(),
+        };
+        let instructions =
+            disassemble::disassemble(&self.symbols_path, start, end).unwrap_or_default();
+        let next = instructions.iter().skip(1).map(|instruction| instruction.address);
+        instructions
+            .iter()
+            .zip(next.chain(std::iter::once(end)))
+            .map(|(instruction, next)| (instruction.address, instruction.text.clone(), next))
+            .collect()
+    }
+
+    /// Undoes the last `count` recorded instructions of the current inferior, and shows where
+    /// that leaves it.
+    fn undo_steps(&mut self, count: usize) -> Result<(), DeetError> {
+        let i = self.current_index()?;
+        let tracee = &mut self.inferiors[i];
+        let record = tracee.record.as_mut().ok_or_else(not_recording)?;
+        let mut exact = true;
+        for _ in 0..count {
+            let step = match record.pop() {
+                Some(step) => step,
+                None => break,
+            };
+            for (addr, bytes) in &step.memory {
+                // Most of what was saved is unchanged, and writing is done a byte at a time
+                let now = tracee.inferior.read_bytes(*addr, bytes.len())?;
+                for (offset, byte) in bytes.iter().enumerate() {
+                    if now[offset] != *byte {
+                        tracee.inferior.write_bytes(addr + offset, &[*byte])?;
+                    }
+                }
+            }
+            tracee.inferior.set_registers(step.regs)?;
+            exact &= step.exact;
+        }
+        if !exact {
+            output::warn("Some instructions could not be decoded; only the stack was restored.");
+        }
+        self.selected_frame = 0;
+        let pc = self.inferior()?.get_pc()?;
+        self.print_location(pc);
+        for display in &self.displays {
+            self.show_display(display);
+        }
+        Ok(())
+    }
+
+    /// Steps back to the start of the previous source line. With `over_calls` (reverse-next),
+    /// only lines of the current frame count, so that the calls it made are undone as a whole.
+    fn reverse_line(&mut self, over_calls: bool) -> Result<(), DeetError> {
+        let count = {
+            let tracee = &self.inferiors[self.current_index()?];
+            let record = tracee.record.as_ref().ok_or_else(not_recording)?;
+            let arch = tracee.inferior.arch();
+            let regs = tracee.inferior.registers()?;
+            let (pc, sp) = (arch.pc(&regs), arch.sp(&regs));
+            let line_of = |pc: usize| {
+                let line = self.debug_data.get_line_from_addr(pc)?;
+                Some((line.file, line.number))
+            };
+            let function = self.code_range_containing(pc).map(|(_, start, end)| (start, end));
+            // Deeper frames, recursive invocations of the function included, are further down
+            // the stack
+            let in_frame = |step: &Step| {
+                let step_pc = arch.pc(&step.regs);
+                !over_calls
+                    || function.is_some_and(|(start, end)| start <= step_pc && step_pc < end)
+                        && arch.sp(&step.regs) >= sp
+            };
+            let steps = record.steps();
+            let current = line_of(pc);
+            // The last instruction of the line before this one...
+            let found = (0..steps.len()).rev().find(|&index| {
+                let line = line_of(arch.pc(&steps[index].regs));
+                in_frame(&steps[index]) && line.is_some() && line != current
+            });
+            match found {
+                Some(mut index) => {
+                    // ...and back to the first instruction of that line
+                    let line = line_of(arch.pc(&steps[index].regs));
+                    for earlier in (0..index).rev() {
+                        if !in_frame(&steps[earlier]) {
+                            continue;
+                        }
+                        if line_of(arch.pc(&steps[earlier].regs)) != line {
+                            break;
+                        }
+                        index = earlier;
+                    }
+                    steps.len() - index
+                }
+                None => {
+                    output::info("No more reverse-execution history.");
+                    steps.len()
+                }
+            }
+        };
+        self.undo_steps(count)
+    }
+
+    /// Continues until `location` is reached or the current function returns, whichever comes
+    /// first.
+    fn advance(&mut self, location: &str) -> Result<(), DeetError> {
+        let target = self.resolve_location(location)?;
+        let frames = self.frames()?;
+        let guard = frames.get(1).map(|caller| (caller.pc, frames[0].cfa));
+        let addrs = std::iter::once(target).chain(guard.map(|(pc, _)| pc));
+        let temporary = self.add_temporary_breakpoints(addrs)?;
+
+        self.selected_frame = 0;
+        let result = loop {
+            let (status, watch_triggered) = match self.run_until_stop(Resume::Continue) {
+                Ok(result) => result,
+                Err(err) => break Err(err),
+            };
+            // Returning to the caller only counts once this frame is gone, not when a recursive
+            // call further down returns to the same place
+            if let (Status::Stopped(Signal::SIGTRAP, addr), Some((return_addr, cfa))) =
+                (&status, guard)
+            {
+                if *addr == return_addr && *addr != target && temporary.contains(addr) {
+                    let sp = self
+                        .inferior()
+                        .and_then(|inferior| Ok(inferior.arch().sp(&inferior.registers()?)));
+                    match sp {
+                        Ok(sp) if sp < cfa && !watch_triggered => continue,
+                        Ok(_) => {}
+                        Err(err) => break Err(err),
+                    }
+                }
+            }
+            break Ok((status, watch_triggered));
+        };
+
+        let alive = match &result {
+            Ok((Status::Exited(_), _)) | Ok((Status::Signaled(_), _)) => false,
+            _ => self.current.is_some(),
+        };
+        self.remove_temporary_breakpoints(temporary, alive)?;
+        let (status, watch_triggered) = result?;
+        self.report_stop(status, watch_triggered);
+        Ok(())
+    }
+
+    /// Runs `func(args...)` inside the inferior and prints what it returned.
+    fn call_function(&mut self, func: &str, args: &[i64]) -> Result<(), DeetError> {
+        let func_addr = self.debug_data.get_addr_for_function(None, func).ok_or_else(|| {
+            DeetError::Parse(format!("Failed to find the address of function {}", func))
+        })?;
+        let trampoline = self.debug_data.entry_point();
+        let args: Vec<u64> = args.iter().map(|arg| *arg as u64).collect();
+        let i = self.current_index()?;
+        let inferior = &mut self.inferiors[i].inferior;
+        let result = inferior.call_function(func_addr, &args, trampoline, &self.breakpoints)?;
+        self.flush_inferior_output();
+        match result {
+            CallResult::Returned(value) => {
+                output::info(format!("{} returned {} ({:#x})", func, value as i64, value));
+            }
+            CallResult::Interrupted(Status::Stopped(signal, addr))
+            | CallResult::Interrupted(Status::GroupStopped(signal, addr)) => {
+                output::info(format!(
+                    "The program received {} at {} while in {}; its state has been restored.",
+                    signal,
+                    output::address(addr),
+                    func
+                ));
+            }
+            CallResult::Interrupted(Status::Forked(child, _)) => {
+                // The child is in the middle of the call, with no way back
+                self.release_fork(child)?;
+                output::info(format!(
+                    "The program forked (process {}) while in {}; its state has been restored.",
+                    child, func
+                ));
+            }
+            CallResult::Interrupted(Status::Execed(_)) => {
+                output::info(format!("The program exec'd while in {}", func));
+                self.follow_exec()?;
+            }
+            CallResult::Interrupted(Status::Exited(exit_code)) => {
+                output::info(format!("Child exited (status {}) while in {}", exit_code, func));
+                self.last_exit_code = Some(exit_code);
+                self.remove_current();
+            }
+            CallResult::Interrupted(Status::Signaled(signal)) => {
+                output::info(format!("Child exited with {} while in {}", signal, func));
+                self.last_exit_code = Some(128 + signal as i32);
+                self.remove_current();
+            }
+        }
+        Ok(())
+    }
+
+    /// Works out how much of the current function's prologue has run. Code with no debug info is
+    /// assumed to have a complete frame.
+    fn frame_setup(&self) -> Result<FrameSetup, DeetError> {
+        let inferior = self.inferior()?;
+        let pc = inferior.get_pc()?;
+        let prologue = match self.function_start(pc) {
+            Some(start) => self.prologue_start(start)?,
+            None => return Ok(FrameSetup::Complete),
+        };
+        Ok(if pc <= prologue {
+            FrameSetup::NotStarted
+        } else if pc == prologue + inferior.arch().frame_save_len() {
+            FrameSetup::RbpPushed
+        } else {
+            FrameSetup::Complete
+        })
+    }
+
+    /// Where the function containing `pc` starts, from the debugging information or, for code
+    /// built without it, the ELF symbols.
+    fn function_start(&self, pc: usize) -> Option<usize> {
+        match self.debug_data.get_function_containing(pc) {
+            Some(func) => Some(func.address),
+            None => self.debug_data.symbol_table().lookup(pc).map(|(_, offset)| pc - offset),
+        }
+    }
+
+    /// Returns the address of the instruction that saves the frame pointer in the function
+    /// starting at `func_start`, skipping instructions such as endbr64 that
+    /// -fcf-protection (or -mbranch-protection) puts before it.
+    fn prologue_start(&self, func_start: usize) -> Result<usize, DeetError> {
+        let mut addr = func_start;
+        for prefix in self.inferior()?.arch().prologue_prefixes() {
+            if self.read_memory(addr, prefix.len())? == *prefix {
+                addr += prefix.len();
+            }
+        }
+        Ok(addr)
+    }
+
+    /// How far above its frame record the canonical frame address of the function containing
+    /// `pc` is.
+    fn frame_size_at(&self, pc: usize) -> Result<usize, DeetError> {
+        let arch = self.inferior()?.arch();
+        let prologue = match self.function_start(pc) {
+            Some(start) => {
+                let start = self.prologue_start(start)?;
+                self.read_memory(start, 4).unwrap_or_default()
+            }
+            None => Vec::new(),
+        };
+        Ok(arch.frame_size(&prologue))
+    }
+
+    /// Reads the inferior's memory with our own breakpoints masked out.
+    fn read_memory(&self, addr: usize, len: usize) -> Result<Vec<u8>, DeetError> {
+        let inferior = self.inferior()?;
+        let mut bytes = inferior.read_bytes(addr, len)?;
+        for (bp_addr, breakpoint) in &self.breakpoints {
+            for (i, byte) in breakpoint.orig_bytes(inferior.pid()).iter().enumerate() {
+                if bp_addr + i >= addr && bp_addr + i < addr + len {
+                    bytes[bp_addr + i - addr] = *byte;
+                }
+            }
+        }
+        Ok(bytes)
+    }
+
+    /// Writes `bytes` at `addr` in the current inferior, leaving breakpoints in place: the bytes
+    /// under a breakpoint become the instruction it replaced.
+    fn write_memory(&mut self, addr: usize, bytes: &[u8]) -> Result<(), DeetError> {
+        let i = self.current_index()?;
+        let mut bytes = bytes.to_vec();
+        let pid = self.inferiors[i].inferior.pid();
+        let patched = self.inferiors[i].inferior.read_bytes(addr, bytes.len())?;
+        for (bp_addr, breakpoint) in &mut self.breakpoints {
+            let orig_bytes = breakpoint.orig_bytes.get_mut(&pid).into_iter().flatten();
+            for (j, byte) in orig_bytes.enumerate() {
+                if bp_addr + j >= addr && bp_addr + j < addr + bytes.len() {
+                    *byte = bytes[bp_addr + j - addr];
+                    bytes[bp_addr + j - addr] = patched[bp_addr + j - addr];
+                }
+            }
+        }
+        self.inferiors[i].inferior.write_bytes(addr, &bytes)?;
+        Ok(())
+    }
+
+    /// Returns the name and extent of the function of the program that contains `pc`, from the
+    /// debugging information or else the symbol table.
+    fn code_range_containing(&self, pc: usize) -> Option<(String, usize, usize)> {
+        match self.debug_data.get_function_containing(pc) {
+            Some(func) => Some((func.name.clone(), func.address, func.address + func.text_length)),
+            None => match self.debug_data.symbol_table().lookup(pc) {
+                Some((symbol, _)) if symbol.size != 0 => {
+                    Some((symbol.name.clone(), symbol.address, symbol.address + symbol.size))
+                }
+                _ => None,
+            },
+        }
+    }
+
+    /// Prints the function and source line for `addr`, or just the address if it has no debug
+    /// info.
+    fn print_location(&self, addr: usize) {
+        let func = self.debug_data.get_function_from_addr(addr);
+        let line = self.debug_data.get_line_from_addr(addr);
+        match (func, line) {
+            (Some(func), Some(line)) => {
+                output::info(format!(
+                    "Stopped at {} {}",
+                    output::function(func), output::source(line),
+                ))
+            }
+            (_, _) => output::info(format!("Stopped at {}", output::address(addr))),
+        }
+    }
+
+    /// Kills and reaps every inferior, and puts the terminal back the way it was before the
+    /// inferiors got hold of it.
+    fn kill_all_inferiors(&mut self) -> Result<(), DeetError> {
+        if self.inferiors.is_empty() {
+            return Ok(());
+        }
+        self.end_breakpoint_stop();
+        self.current = None;
+        for mut tracee in self.inferiors.drain(..) {
+            // Killing it is not worth announcing
+            tracee.background = None;
+            tracee.inferior.kill()?;
+            for breakpoint in self.breakpoints.values_mut() {
+                breakpoint.orig_bytes.remove(&tracee.inferior.pid());
+            }
+        }
+        if let Some(terminal) = &self.terminal {
+            termios::tcsetattr(libc::STDIN_FILENO, SetArg::TCSANOW, terminal)?;
+        }
+        Ok(())
+    }
+
+    fn add_inferior(&mut self, tracee: Tracee) {
+        self.next_inferior_id = tracee.id + 1;
+        self.inferiors.push(tracee);
+        if self.current.is_none() {
+            self.current = Some(self.inferiors.len() - 1);
+        }
+    }
+
+    /// Forgets the current inferior once its process is gone, along with its watchpoints, and
+    /// switches to another inferior if there is one left.
+    fn remove_current(&mut self) {
+        let tracee = match self.current.take() {
+            Some(i) => self.inferiors.remove(i),
+            None => return,
+        };
+        self.forget_breakpoints(tracee.inferior.pid());
+        // Watchpoints refer to the process's memory, so they go away with it
+        for watchpoint in &tracee.watchpoints {
+            output::info(format!(
+                "Watchpoint {} deleted because the program has exited.",
+                watchpoint.id
+            ));
+        }
+        if let Some(next) = self.inferiors.first() {
+            output::info(format!(
+                "[Switching to inferior {} (process {})]",
+                next.id, next.inferior.pid(),
+            ));
+            self.current = Some(0);
+        }
+    }
+
+    /// Makes inferior `id` the one that commands act on.
+    fn switch_inferior(&mut self, id: usize) -> Result<(), DeetError> {
+        let i = self
+            .inferiors
+            .iter()
+            .position(|tracee| tracee.id == id)
+            .ok_or_else(|| DeetError::Parse(format!("Inferior ID {} not known.", id)))?;
+        self.current = Some(i);
+        self.selected_frame = 0;
+        output::info(format!(
+            "[Switching to inferior {} (process {})]",
+            id, self.inferiors[i].inferior.pid(),
+        ));
+        let rip = self.inferior()?.get_pc()?;
+        self.print_location(rip);
+        Ok(())
+    }
+
+    /// Makes thread `number` of the current inferior the one that commands act on.
+    fn switch_thread(&mut self, number: usize) -> Result<(), DeetError> {
+        let i = self.current_index()?;
+        let tracee = &mut self.inferiors[i];
+        let thread = tracee.inferior.select_thread(number)?;
+        tracee.reported_thread = thread.number;
+        self.selected_frame = 0;
+        output::info(format!("[Switching to {}]", thread_label(&tracee.inferior, thread)));
+        let rip = tracee.inferior.get_pc()?;
+        self.print_location(rip);
+        Ok(())
+    }
+
+    /// Takes on the child the current inferior just forked as a new inferior. Its memory is a
+    /// copy of the parent's, breakpoints and watched pages included.
+    fn adopt_fork(&mut self, child: Pid) -> Result<usize, DeetError> {
+        let inferior = Inferior::attach_forked(child)?;
+        let parent = &self.inferiors[self.current_index()?];
+        for breakpoint in self.breakpoints.values_mut() {
+            breakpoint.inherit(parent.inferior.pid(), child);
+        }
+        let tracee = Tracee {
+            id: self.next_inferior_id,
+            exit_watch: ExitWatch::spawn(self.next_inferior_id, inferior.pid()),
+            reported_thread: inferior.current_thread().number,
+            inferior,
+            watchpoints: parent.watchpoints.clone(),
+            protected_pages: parent.protected_pages.clone(),
+            register_watches: Vec::new(),
+            background: None,
+            record: None,
+            btrace: None,
+            other_program: parent.other_program,
+        };
+        let id = tracee.id;
+        self.add_inferior(tracee);
+        Ok(id)
+    }
+
+    /// Makes the child the current inferior just forked the current one in its place, and lets
+    /// the parent run free after removing the breakpoints and page protections from it.
+    fn follow_fork_child(&mut self, child: Pid) -> Result<(), DeetError> {
+        let i = self.current_index()?;
+        self.adopt_fork(child)?;
+        let mut parent = self.inferiors.remove(i);
+        self.current = Some(self.inferiors.len() - 1);
+        let pid = parent.inferior.pid();
+        output::info(format!("[Attaching after process {} fork to child process {}]", pid, child));
+        for (page, prot) in &parent.protected_pages {
+            parent.inferior.mprotect(*page, watchpoint::page_size(), *prot)?;
+        }
+        parent.inferior.detach(&self.breakpoints)?;
+        self.forget_breakpoints(pid);
+        output::info(format!("[Detaching after fork from parent process {}]", pid));
+        Ok(())
+    }
+
+    /// Lets a freshly forked child run free, after removing the breakpoints and page protections
+    /// it inherited from the parent.
+    fn release_fork(&mut self, child: Pid) -> Result<(), DeetError> {
+        let mut inferior = Inferior::attach_forked(child)?;
+        let parent = &self.inferiors[self.current_index()?];
+        for breakpoint in self.breakpoints.values_mut() {
+            breakpoint.inherit(parent.inferior.pid(), child);
+        }
+        for (page, prot) in &parent.protected_pages {
+            inferior.mprotect(*page, watchpoint::page_size(), *prot)?;
+        }
+        let result = inferior.detach(&self.breakpoints);
+        self.forget_breakpoints(child);
+        result
+    }
+
+    /// Drops what the breakpoints recorded about process `pid`, which deet no longer traces.
+    fn forget_breakpoints(&mut self, pid: Pid) {
+        for breakpoint in self.breakpoints.values_mut() {
+            breakpoint.orig_bytes.remove(&pid);
+        }
+    }
+
+    /// Resumes the inferior and reports how it stopped. Forgets the inferior once it is gone.
+    fn resume(&mut self, mode: Resume) -> Result<(), DeetError> {
+        self.selected_frame = 0;
+        let (status, watch_triggered) = self.run_until_stop(mode)?;
+        self.report_stop(status, watch_triggered);
+        Ok(())
+    }
+
+    /// Launches the program afresh, leaving it stopped before its first instruction.
+    fn start_program(&mut self, args: Vec<String>) -> Result<(), DeetError> {
+        // Stale inferiors would otherwise linger as stopped, traced processes
+        self.kill_all_inferiors()?;
+        // Breakpoints set at raw addresses were dropped along with the symbols of the
+        // program exec'd, so there is nothing to move back
+        if self.symbols_path != self.target {
+            let target = self.target.clone();
+            self.load_debug_data(&target)?;
+        }
+        // The old process's allocator is not where the new one's will be
+        self.remove_heap_hooks()?;
+        let mut capture = None;
+        let mut terminal = None;
+        if self.settings.inferior_tty {
+            terminal = Some(InferiorTerminal::open()?);
+        } else if !self.settings.raw_tty {
+            capture = Some(OutputCapture::open()?);
+        }
+        let io = match (
+            capture.as_ref().and_then(OutputCapture::slaves),
+            terminal.as_ref().and_then(InferiorTerminal::slave),
+        ) {
+            (Some((stdout, stderr)), _) => ChildIo::Capture(stdout, stderr),
+            (_, Some(tty)) => ChildIo::Terminal(tty),
+            _ => ChildIo::Inherit,
+        };
+        let inferior = Inferior::new(&self.target, &args, io)?;
+        if let Some(capture) = &mut capture {
+            capture.close_slaves();
+        }
+        if let Some(terminal) = &mut terminal {
+            terminal.close_slave();
+        }
+        self.capture = capture;
+        self.inferior_terminal = terminal;
+        self.add_inferior(Tracee::new(self.next_inferior_id, inferior));
+        self.reinstall_breakpoints();
+        if self.heap_trace {
+            self.heap = HeapTrace::default();
+            self.add_heap_hook(self.debug_data.entry_point(), HeapHook::Start)?;
+        }
+        Ok(())
+    }
+
+    /// Continues the freshly started inferior to `main`, through a temporary breakpoint there
+    /// unless the user has one of their own.
+    fn run_to_main(&mut self) -> Result<(), DeetError> {
+        let main = self.resolve_location("main")?;
+        let temporary = self.add_temporary_breakpoints(Some(main))?;
+        self.selected_frame = 0;
+        let result = self.run_until_stop(Resume::Continue);
+        let alive = match &result {
+            Ok((Status::Exited(_), _)) | Ok((Status::Signaled(_), _)) => false,
+            _ => self.current.is_some(),
+        };
+        self.remove_temporary_breakpoints(temporary, alive)?;
+        let (status, watch_triggered) = result?;
+        self.report_stop(status, watch_triggered);
+        Ok(())
+    }
+
+    /// Resumes the inferior until something the user should hear about happens, and returns
+    /// that, along with whether it was a watchpoint triggering.
+    fn run_until_stop(&mut self, mode: Resume) -> Result<(Status, bool), DeetError> {
+        // Registers change without faulting, so watching one means looking after every step
+        let watching_registers = !self.inferiors[self.current_index()?].register_watches.is_empty();
+        let step = if watching_registers { Resume::Step } else { mode };
+        self.end_breakpoint_stop();
+        // Keystrokes go to the program for as long as it runs
+        let _foreground = self.inferior_terminal.as_ref().map(InferiorTerminal::foreground);
+        loop {
+            // Following a fork's child changes which inferior is current
+            let i = self.current_index()?;
+            // Commands that step many times, like `next`, are timed as a whole
+            let deadline = self.deadline;
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                self.report_timeout();
+                let pc = self.inferiors[i].inferior.get_pc()?;
+                return Ok((Status::GroupStopped(Signal::SIGTRAP, pc), false));
+            }
+            self.record_step(step)?;
+            let status = match deadline {
+                Some(deadline) if step == Resume::Continue => self.resume_until(deadline)?,
+                _ => self.inferiors[i].inferior.resume(step, &self.breakpoints)?,
+            };
+            let result = self.handle_status(status, mode)?;
+            if !watching_registers {
+                match result {
+                    Some(result) => return Ok(result),
+                    None => continue,
+                }
+            }
+            let addr = match result {
+                None => None,
+                Some((Status::Stopped(Signal::SIGTRAP, addr), false)) => Some(addr),
+                Some(result) => return Ok(result),
+            };
+            if self.check_register_watches()? {
+                let pc = self.inferior()?.get_pc()?;
+                return Ok((Status::Stopped(Signal::SIGTRAP, pc), true));
+            }
+            // Stepping onto a breakpoint stops there just as running into it would
+            match addr {
+                Some(addr) if mode == Resume::Step || self.breakpoints.contains_key(&addr) => {
+                    return Ok((Status::Stopped(Signal::SIGTRAP, addr), false));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Continues the current inferior like `Inferior::resume` does, but interrupts it if it is
+    /// still running at `deadline`.
+    fn resume_until(&mut self, deadline: Instant) -> Result<Status, DeetError> {
+        let i = self.current_index()?;
+        let mode = Resume::Continue;
+        let inferior = &mut self.inferiors[i].inferior;
+        if let Some(status) = inferior.start(mode, &self.breakpoints)? {
+            return Ok(status);
+        }
+        while Instant::now() < deadline {
+            if let Some(status) = inferior.collect(Some(WaitPidFlag::WNOHANG), &self.breakpoints)? {
+                return Ok(status);
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        self.report_timeout();
+        let inferior = &mut self.inferiors[i].inferior;
+        inferior.interrupt()?;
+        // Whatever stopped it first is reported instead, and the stop the interrupt causes is
+        // dropped the next time the debugger looks
+        let status = inferior.collect(None, &self.breakpoints)?;
+        status.ok_or(DeetError::Parse(String::from("The program did not stop.")))
+    }
+
+    fn report_timeout(&self) {
+        let seconds = self.settings.timeout.map_or(0, |limit| limit.as_secs());
+        let plural = if seconds == 1 { "" } else { "s" };
+        output::warn(format!(
+            "Timed out after {} second{}; interrupting the program.",
+            seconds, plural
+        ));
+    }
+
+    /// Deals with the current inferior having stopped with `status` after being resumed in
+    /// `mode`. Returns what the user should hear about, along with whether it was a watchpoint
+    /// triggering, or None if the inferior should just carry on. Faults caused by writes to
+    /// watched pages, forks nobody asked to catch, and execs are dealt with here and never
+    /// reported as such.
+    fn handle_status(
+        &mut self,
+        status: Status,
+        mode: Resume,
+    ) -> Result<Option<(Status, bool)>, DeetError> {
+        let i = self.current_index()?;
+        Ok(match status {
+            Status::Stopped(Signal::SIGSEGV, addr)
+                if !self.inferiors[i].protected_pages.is_empty() =>
+            {
+                match self.step_over_watched_write()? {
+                    // A write to a watched page that left the watched bytes alone is invisible
+                    // to the user
+                    Some((Status::Stopped(Signal::SIGTRAP, _), false))
+                        if mode == Resume::Continue => None,
+                    Some(result) => Some(result),
+                    None => Some((Status::Stopped(Signal::SIGSEGV, addr), false)),
+                }
+            }
+            Status::Forked(child, addr) => {
+                if self.catch_fork {
+                    return Ok(Some((Status::Forked(child, addr), false)));
+                }
+                match self.settings.follow_fork_mode {
+                    FollowForkMode::Parent => self.release_fork(child)?,
+                    FollowForkMode::Child => self.follow_fork_child(child)?,
+                }
+                match mode {
+                    Resume::Step => Some((Status::Stopped(Signal::SIGTRAP, addr), false)),
+                    Resume::Continue => None,
+                }
+            }
+            Status::Stopped(Signal::SIGTRAP, addr) if self.heap_hooks.contains_key(&addr) => {
+                self.handle_heap_hook(addr)?;
+                let user_breakpoint = self.breakpoints.get(&addr).is_some_and(|bp| !bp.temporary);
+                if mode == Resume::Step || user_breakpoint {
+                    Some((status, false))
+                } else {
+                    None
+                }
+            }
+            Status::Execed(_) => {
+                self.follow_exec()?;
+                match mode {
+                    Resume::Step => Some((status, false)),
+                    Resume::Continue => None,
+                }
+            }
+            status => Some((status, false)),
+        })
+    }
+
+    /// Lets the current inferior run in the background, giving the prompt back straight away.
+    /// Stops it runs into that need no attention are dealt with as usual.
+    fn continue_in_background(&mut self) -> Result<(), DeetError> {
+        let i = self.current_index()?;
+        self.end_breakpoint_stop();
+        if !self.inferiors[i].register_watches.is_empty() {
+            return Err(DeetError::Parse(String::from(
+                "Registers can only be watched in the foreground. Use \"continue\" instead.",
+            )));
+        }
+        let mode = Resume::Continue;
+        while let Some(status) = self.inferiors[i].inferior.start(mode, &self.breakpoints)? {
+            if let Some((status, watch_triggered)) = self.handle_status(status, mode)? {
+                self.report_stop(status, watch_triggered);
+                return Ok(());
+            }
+        }
+        self.watch_background(i);
+        Ok(())
+    }
+
+    /// Has the inferior at index `i`, which runs in the background, watched for stopping.
+    fn watch_background(&mut self, i: usize) {
+        let tracee = &mut self.inferiors[i];
+        let inferior = &tracee.inferior;
+        let tids = inferior.threads().iter().map(|thread| thread.tid).collect();
+        tracee.background = Some(Waiter::spawn(tracee.id, inferior.pid(), tids));
+    }
+
+    /// Reports on any inferior running in the background that has stopped since the last look,
+    /// or any stopped one that has died since (killed from outside, say), making it the current
+    /// one.
+    fn check_background(&mut self) -> Result<(), DeetError> {
+        let mut i = 0;
+        while i < self.inferiors.len() {
+            // Once the debugger looks for itself, there is nothing left for the waiter to
+            // announce, and it would take the other threads being stopped for news
+            let waiter = self.inferiors[i].background.take();
+            let running = waiter.is_some();
+            let inferior = &mut self.inferiors[i].inferior;
+            let status = match inferior.collect(Some(WaitPidFlag::WNOHANG), &self.breakpoints) {
+                Ok(Some(status)) => status,
+                Ok(None) => {
+                    // Watched afresh, as it may have started threads the waiter knows nothing
+                    // about
+                    if running {
+                        self.watch_background(i);
+                    }
+                    i += 1;
+                    continue;
+                }
+                Err(err) => {
+                    self.inferiors[i].background = waiter;
+                    return Err(err);
+                }
+            };
+            if !running && !matches!(status, Status::Exited(_) | Status::Signaled(_)) {
+                // A stop of one that is stopped already, like an interrupt that came too late
+                i += 1;
+                continue;
+            }
+            if self.current != Some(i) {
+                let tracee = &self.inferiors[i];
+                let pid = tracee.inferior.pid();
+                output::info(format!("[Switching to inferior {} (process {})]", tracee.id, pid));
+                self.current = Some(i);
+            }
+            self.selected_frame = 0;
+            match self.handle_status(status, Resume::Continue)? {
+                Some((status, watch_triggered)) => self.report_stop(status, watch_triggered),
+                None => self.continue_in_background()?,
+            }
+            // Inferiors may have come or gone
+            i = 0;
+        }
+        Ok(())
+    }
+
+    /// Stops the current inferior, which is running in the background, and reports where.
+    fn interrupt(&mut self) -> Result<(), DeetError> {
+        let i = self.current_index()?;
+        let tracee = &mut self.inferiors[i];
+        if tracee.background.take().is_none() {
+            return Err(DeetError::Parse(String::from("The program is not running.")));
+        }
+        tracee.inferior.interrupt()?;
+        let status = tracee.inferior.collect(None, &self.breakpoints)?;
+        let status = status.ok_or(DeetError::Parse(String::from("The program did not stop.")))?;
+        self.selected_frame = 0;
+        match self.handle_status(status, Resume::Continue)? {
+            Some((status, watch_triggered)) => self.report_stop(status, watch_triggered),
+            // Something else stopped it first. The interrupt is still on its way, and stops it
+            // again straight away.
+            None => self.resume(Resume::Continue)?,
+        }
+        Ok(())
+    }
+
+    /// Stops every inferior running in the background and reports where each one stopped,
+    /// leaving the last of them current.
+    fn interrupt_all(&mut self) -> Result<(), DeetError> {
+        let running: Vec<usize> = self
+            .inferiors
+            .iter()
+            .filter(|tracee| tracee.background.is_some())
+            .map(|tracee| tracee.id)
+            .collect();
+        if running.is_empty() {
+            return Err(DeetError::Parse(String::from("No program is running.")));
+        }
+        for id in running {
+            // Inferiors may have come or gone while the previous one was dealt with
+            let i = match self.inferiors.iter().position(|tracee| tracee.id == id) {
+                Some(i) if self.inferiors[i].background.is_some() => i,
+                _ => continue,
+            };
+            if self.current != Some(i) {
+                let pid = self.inferiors[i].inferior.pid();
+                output::info(format!("[Switching to inferior {} (process {})]", id, pid));
+                self.current = Some(i);
+            }
+            self.interrupt()?;
+        }
+        Ok(())
+    }
+
+    /// Announces the threads the current inferior has started and those that have exited since
+    /// it last stopped, and unless it is `gone`, which thread it has stopped in if that is not
+    /// the one the user was last told about.
+    fn report_thread_events(&mut self, gone: bool) {
+        let i = match self.current {
+            Some(i) => i,
+            None => return,
+        };
+        let tracee = &mut self.inferiors[i];
+        let inferior = &mut tracee.inferior;
+        for event in inferior.take_thread_events() {
+            match event {
+                ThreadEvent::Started(thread) => {
+                    output::info(format!("[New {}]", thread_label(inferior, thread)))
+                }
+                ThreadEvent::Exited(thread, code) => {
+                    output::info(format!(
+                        "[{} exited (status {})]",
+                        thread_label(inferior, thread), code
+                    ))
+                }
+            }
+        }
+        let thread = inferior.current_thread();
+        if !gone && thread.number != tracee.reported_thread {
+            output::info(format!("[Switching to {}]", thread_label(inferior, thread)));
+            tracee.reported_thread = thread.number;
+        }
+    }
+
+    /// How stop reports refer to the thread the current inferior stopped in: "Child" while it
+    /// has a single thread, and by its `thread_label` otherwise.
+    fn stopped_thread(&self) -> String {
+        match self.inferior() {
+            Ok(inferior) if inferior.threads().len() > 1 => {
+                thread_label(inferior, inferior.current_thread())
+            }
+            _ => String::from("Child"),
+        }
+    }
+
+    /// Prints what the inferior has written and deet has not shown yet, so that it does not end
+    /// up after (or in the middle of) deet's own messages.
+    fn flush_inferior_output(&self) {
+        if let Some(capture) = &self.capture {
+            capture.flush();
+        }
+        if let Some(terminal) = &self.inferior_terminal {
+            terminal.flush();
+        }
+    }
+
+    /// Prints how the inferior stopped, forgetting it if it is gone.
+    fn report_stop(&mut self, status: Status, watch_triggered: bool) {
+        self.flush_inferior_output();
+        let gone = matches!(status, Status::Exited(_) | Status::Signaled(_));
+        let event = self.stop_event(&status);
+        self.report_thread_events(gone);
+        match status {
+            Status::Stopped(signal, curr_addr) => {
+                if !watch_triggered {
+                    output::info(format!("{} stopped (signal {})", self.stopped_thread(), signal));
+                }
+                match self.breakpoints.get_mut(&curr_addr) {
+                    Some(breakpoint) if signal == Signal::SIGTRAP && !breakpoint.temporary => {
+                        breakpoint.hits += 1;
+                        self.breakpoint_stop = Some((curr_addr, Instant::now()));
+                    }
+                    _ => {}
+                }
+                self.fault_signal = Some(signal).filter(|signal| *signal != Signal::SIGTRAP);
+                self.print_location(curr_addr);
+                for display in &self.displays {
+                    self.show_display(display);
+                }
+            }
+            Status::GroupStopped(signal, curr_addr) => {
+                let thread = self.stopped_thread();
+                match signal {
+                    Signal::SIGTRAP => output::info(format!("{} interrupted", thread)),
+                    signal => output::info(format!(
+                        "{} stopped by job control (signal {})",
+                        thread, signal
+                    )),
+                }
+                self.fault_signal = None;
+                self.print_location(curr_addr);
+                for display in &self.displays {
+                    self.show_display(display);
+                }
+            }
+            Status::Forked(child, curr_addr) => {
+                output::info(format!("Catchpoint (forked process {})", child));
+                match self.adopt_fork(child) {
+                    Ok(id) => output::info(format!("[New inferior {} (process {})]", id, child)),
+                    Err(err) => output::error(format!("Could not take on the child: {}", err)),
+                }
+                self.print_location(curr_addr);
+                for display in &self.displays {
+                    self.show_display(display);
+                }
+            }
+            Status::Execed(curr_addr) => {
+                self.print_location(curr_addr);
+                for display in &self.displays {
+                    self.show_display(display);
+                }
+            }
+            Status::Exited(exit_code) => {
+                output::info(format!("Child exited (status {})", exit_code));
+                self.last_exit_code = Some(exit_code);
+                self.remove_current();
+            }
+            Status::Signaled(signal) => {
+                output::info(format!("Child exited with {}", signal));
+                self.last_exit_code = Some(128 + signal as i32);
+                self.remove_current();
+            }
+        }
+        if let Some(event) = event {
+            self.run_stop_hooks(&event);
+        }
+    }
+
+    /// What stop hooks are told about `status`, or None if the inferior is gone.
+    fn stop_event(&self, status: &Status) -> Option<StopEvent> {
+        let (pc, signal) = match *status {
+            Status::Stopped(signal, pc) | Status::GroupStopped(signal, pc) => (pc, signal),
+            Status::Forked(_, pc) | Status::Execed(pc) => (pc, Signal::SIGTRAP),
+            Status::Exited(_) | Status::Signaled(_) => return None,
+        };
+        let breakpoint = match status {
+            Status::Stopped(Signal::SIGTRAP, _) => self
+                .breakpoints
+                .get(&pc)
+                .filter(|breakpoint| !breakpoint.temporary)
+                .map(|breakpoint| breakpoint.id),
+            _ => None,
+        };
+        Some(StopEvent { pc, signal: signal.to_string(), breakpoint })
+    }
+
+    /// Executes the stop hooks, unless this stop is one of theirs (from a `continue`, say).
+    fn run_stop_hooks(&mut self, event: &StopEvent) {
+        if self.in_stop_hook {
+            return;
+        }
+        self.in_stop_hook = true;
+        for command in self.stop_hooks.clone() {
+            if let Err(err) = self.execute_line(&command) {
+                output::error(err);
+            }
+            // A hook may have let the program run to its end
+            if self.current.is_none() || self.quitting {
+                break;
+            }
+        }
+        let scripting = self.scripting.clone();
+        for hook in scripting.stop_hooks() {
+            if self.current.is_none() || self.quitting {
+                break;
+            }
+            self.script_depth += 1;
+            if let Err(err) = scripting.call_stop_hook(self, &hook, event) {
+                output::error(err);
+            }
+            self.script_depth -= 1;
+        }
+        self.in_stop_hook = false;
+    }
+
+    /// The size of a pointer in the current inferior, assumed to be 8 bytes when there is none.
+    fn word_size(&self) -> usize {
+        self.inferior().map_or(8, |inferior| inferior.word_size())
+    }
+
+    fn current_index(&self) -> Result<usize, DeetError> {
+        self.current.ok_or(DeetError::NoInferior)
+    }
+
+    /// The inferior that commands act on.
+    fn inferior(&self) -> Result<&Inferior, DeetError> {
+        Ok(&self.inferiors[self.current_index()?].inferior)
+    }
+
+    fn inferior_mut(&mut self) -> Result<&mut Inferior, DeetError> {
+        let i = self.current_index()?;
+        Ok(&mut self.inferiors[i].inferior)
+    }
+
+    /// Asks the user a yes/no question, asking again until the answer is y, yes, n or no. Ctrl+c
+    /// answers no, and ctrl+d (or any other end of input) answers yes, so that it still gets the
+    /// user out. Scripts and batch runs are not asked; the answer is always yes.
+    fn confirm(&mut self, question: &str) -> bool {
+        if !self.settings.confirm {
+            return true;
+        }
+        if self.batch || self.script_depth > 0 {
+            output::info(format!("{} (y/n) [answered Y; input not from terminal]", question));
+            return true;
+        }
+        loop {
+            match self.readline.readline(&format!("{} (y/n) ", question)) {
+                Ok(answer) => match answer.trim().to_lowercase().as_str() {
+                    "y" | "yes" => return true,
+                    "n" | "no" => return false,
+                    _ => output::info("Please answer y or n."),
+                },
+                Err(ReadlineError::Interrupted) => return false,
+                Err(_) => return true,
+            }
+        }
+    }
+
+    /// This function prompts the user to enter a command, and continues re-prompting until the user
+    /// enters a valid command. It uses DebuggerCommand::from_tokens to do the command parsing.
+    ///
+    /// You don't need to read, understand, or modify this function.
+    fn get_next_command(&mut self) -> DebuggerCommand {
+        loop {
+            if let Err(err) = self.check_background() {
+                output::error(err);
+            }
+            self.flush_inferior_output();
+            // Background inferiors have a waiter of their own
+            for tracee in &self.inferiors {
+                tracee.exit_watch.set_idle(tracee.background.is_none());
+            }
+            // Print prompt and get next line of user input
+            let line = self.readline.readline("(deet) ");
+            for tracee in &self.inferiors {
+                tracee.exit_watch.set_idle(false);
+            }
+            match line {
+                Err(ReadlineError::Interrupted) => {
+                    // User pressed ctrl+c. We're going to ignore it
+                    output::info("Type \"quit\" to exit");
+                }
+                Err(ReadlineError::Eof) => {
+                    // User pressed ctrl+d, which is the equivalent of "quit" for our purposes
+                    return DebuggerCommand::Quit;
+                }
+                Err(err) => {
+                    output::error(format!("Unexpected I/O error: {:?}", err));
+                    return DebuggerCommand::Quit;
+                }
+                Ok(line) => {
+                    if line.trim().len() == 0 {
+                        continue;
+                    }
+                    self.readline.add_history_entry(line.as_str());
+                    if let Err(err) = self.readline.save_history(&self.history_path) {
+                        output::warn(format!(
+                            "Failed to save history file at {}: {}",
+                            self.history_path, err
+                        ));
+                    }
+                    let tokens: Vec<&str> = line.split_whitespace().collect();
+                    match DebuggerCommand::from_tokens(&tokens) {
+                        Ok(cmd) => return cmd,
+                        Err(err) => output::error(err),
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Prints registers four to a line.
+fn print_register_grid(registers: &[(&str, u64)]) {
+    for row in registers.chunks(4) {
+        let cells: Vec<String> = row
+            .iter()
+            .map(|(name, value)| format!("{:<6} {:<18}", name, format!("{:#x}", value)))
+            .collect();
+        output::info(cells.join(" ").trim_end());
+    }
+}
+
+/// Writes a C declaration of `name` with the given type, e.g. `char buf[16]` or `int *next`.
+fn declaration(type_name: &str, name: &str) -> String {
+    match type_name.find(" [") {
+        Some(dims) => format!("{} {}{}", &type_name[..dims], name, &type_name[dims + 1..]),
+        None if type_name.ends_with('*') => format!("{}{}", type_name, name),
+        None => format!("{} {}", type_name, name),
+    }
+}
+
+
+/// Returns lines `line - context` through `line + context` of the file at `path`, numbered from 1.
+fn source_snippet(path: &str, line: usize, context: usize) -> std::io::Result<Vec<(usize, String)>> {
+    let text = std::fs::read_to_string(path)?;
+    let first = line.saturating_sub(context).max(1);
+    Ok(text
+        .lines()
+        .enumerate()
+        .map(|(i, text)| (i + 1, text.to_string()))
+        .skip(first - 1)
+        .take(line + context + 1 - first)
+        .collect())
+}
+
+/// Writes `name + offset` the way `info symbol` shows it.
+fn describe_symbol(name: &str, offset: usize) -> String {
+    if offset == 0 {
+        output::function(name)
+    } else {
+        format!("{} + {}", output::function(name), offset)
+    }
+}
+
+/// Writes one of `inferior`'s threads the way thread listings and stop reports show it:
+/// "Thread 2 (tid 1235) 'worker-1'".
+fn thread_label(inferior: &Inferior, thread: Thread) -> String {
+    match inferior.thread_name(thread.tid) {
+        Some(name) => format!("Thread {} (tid {}) '{}'", thread.number, thread.tid, name),
+        None => format!("Thread {} (tid {})", thread.number, thread.tid),
+    }
+}
+
+/// Says so when `path` was built without -g, since only addresses, registers and memory (and
+/// the ELF symbols) are then there to work with.
+fn warn_if_no_debug_info(path: &str, debug_data: &DwarfData) {
+    if !debug_data.has_debug_info() {
+        output::error(format!(
+            "No debugging symbols found in {} (was it compiled with -g?). Breakpoints can still \
+             be set on functions and addresses, and backtraces show raw addresses.",
+            path
+        ));
+    }
+}
+
+/// The ranges of offsets at which `a` and `b` differ, as `(start, end)` pairs.
+fn differing_ranges(a: &[u8], b: &[u8]) -> Vec<(usize, usize)> {
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for i in (0..a.len().min(b.len())).filter(|i| a[*i] != b[*i]) {
+        match ranges.last_mut() {
+            Some((_, end)) if *end == i => *end = i + 1,
+            _ => ranges.push((i, i + 1)),
+        }
+    }
+    ranges
+}
+
+impl script::Host for Debugger {
+    fn execute(&mut self, line: &str) -> Result<(), DeetError> {
+        self.execute_line(line)
+    }
+
+    fn break_at(&mut self, location: &str) -> Result<usize, DeetError> {
+        self.set_breakpoint(location)?;
+        let set = match self.resolve_location(location) {
+            Ok(addr) => self.breakpoints.get(&addr),
+            Err(_) => self.pending_breakpoints.iter().rev().find(|bp| bp.location == location),
+        };
+        set.map(|breakpoint| breakpoint.id)
+            .ok_or_else(|| DeetError::Parse(format!("No breakpoint was set at {}.", location)))
+    }
+
+    fn delete_breakpoint(&mut self, id: usize) -> Result<(), DeetError> {
+        let location = self
+            .breakpoints
+            .values()
+            .chain(&self.pending_breakpoints)
+            .find(|breakpoint| breakpoint.id == id && !breakpoint.temporary)
+            .map(|breakpoint| breakpoint.location.clone())
+            .ok_or_else(|| DeetError::Parse(format!("No breakpoint number {}.", id)))?;
+        self.clear_breakpoint(&location)
+    }
+
+    fn breakpoints(&self) -> Vec<BreakpointInfo> {
+        let mut breakpoints: Vec<BreakpointInfo> = self
+            .breakpoints
+            .values()
+            .chain(&self.pending_breakpoints)
+            .filter(|breakpoint| !breakpoint.temporary)
+            .map(|breakpoint| BreakpointInfo {
+                id: breakpoint.id,
+                addr: breakpoint.addr,
+                location: breakpoint.location.clone(),
+                hits: breakpoint.hits,
+            })
+            .collect();
+        breakpoints.sort_by_key(|breakpoint| breakpoint.id);
+        breakpoints
+    }
+
+    fn read_memory(&self, addr: usize, len: usize) -> Result<Vec<u8>, DeetError> {
+        Debugger::read_memory(self, addr, len)
+    }
+
+    fn write_memory(&mut self, addr: usize, bytes: &[u8]) -> Result<(), DeetError> {
+        Debugger::write_memory(self, addr, bytes)
+    }
+
+    fn word_size(&self) -> usize {
+        Debugger::word_size(self)
+    }
+
+    fn register(&self, name: &str) -> Result<u64, DeetError> {
+        let inferior = self.inferior()?;
+        let summary = inferior.arch().register_summary(&inferior.registers()?);
+        register_value(&summary, name)
+            .ok_or_else(|| DeetError::Parse(format!("Invalid register \"${}\"", name)))
+    }
+
+    fn set_register(&mut self, name: &str, value: u64) -> Result<(), DeetError> {
+        let inferior = self.inferior_mut()?;
+        let mut regs = inferior.registers()?;
+        *inferior
+            .arch()
+            .register_mut(&mut regs, name)
+            .ok_or_else(|| DeetError::Parse(format!("Invalid register \"${}\"", name)))? = value;
+        inferior.set_registers(regs)
+    }
+
+    fn pc(&self) -> Result<usize, DeetError> {
+        let inferior = self.inferior()?;
+        Ok(inferior.arch().pc(&inferior.registers()?))
+    }
+
+    fn evaluate(&self, expr: &str) -> Result<String, DeetError> {
+        Debugger::evaluate(self, expr, Format::Natural)
+    }
+}
+
+/// Looks `name` up among the registers `register_summary` returned.
+fn register_value(summary: &[(&str, u64)], name: &str) -> Option<u64> {
+    summary.iter().find(|(register, _)| *register == name).map(|(_, value)| *value)
+}
+
+/// Returns the shared library whose code `addr` is in.
+fn library_containing(libraries: &[SharedLibrary], addr: usize) -> Option<&SharedLibrary> {
+    libraries
+        .iter()
+        .find(|library| library.text.is_some_and(|(start, end)| start <= addr && addr < end))
+}
+
+fn already_recording() -> DeetError {
+    DeetError::Parse(String::from("The process is already being recorded."))
+}
+
+fn not_recording() -> DeetError {
+    DeetError::Parse(String::from("Process record is not started. Use \"record\" first."))
+}
+
+fn parse_address(addr: &str) -> Option<usize> {
+    let addr_without_0x = if addr.to_lowercase().starts_with("0x") {
+        &addr[2..]
+    } else {
+        &addr
+    };
+    usize::from_str_radix(addr_without_0x, 16).ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::backend::mock::{MockBackend, MOCK_PID};
+    use crate::backend::InferiorBackend;
+
+    /// A debugger with one inferior, backed by a mock process that runs through `trace`.
+    fn debugger_with_mock(trace: Vec<usize>) -> (Debugger, MockBackend) {
+        let mock = MockBackend::new(trace);
+        let inferior = Inferior::with_backend(mock.pid(), Box::new(mock.clone())).unwrap();
+        let mut debugger = Debugger::with_debug_data("mock", DwarfData::empty());
+        debugger.set_batch(true);
+        debugger.add_inferior(Tracee::new(1, inferior));
+        (debugger, mock)
+    }
+
+    #[test]
+    fn test_breakpoint_bookkeeping() {
+        let (mut debugger, mock) = debugger_with_mock(vec![0x1000, 0x1004, 0x1008, 0x100c]);
+        mock.process().memory.insert(0x1008, 0x55);
+        mock.process().exit_code = 3;
+        debugger.execute_line("break *0x1008").unwrap();
+        assert_eq!(debugger.breakpoints[&0x1008].orig_bytes(mock.pid()), &[0x55][..]);
+        assert_ne!(mock.process().memory[&0x1008], 0x55);
+
+        debugger.execute_line("continue").unwrap();
+        // Stopped on the breakpoint, with the program counter moved back onto it
+        assert_eq!(debugger.inferior().unwrap().get_pc().unwrap(), 0x1008);
+
+        debugger.execute_line("stepi").unwrap();
+        assert_eq!(debugger.inferior().unwrap().get_pc().unwrap(), 0x100c);
+        // Stepping off the breakpoint puts it back
+        assert_ne!(mock.process().memory[&0x1008], 0x55);
+        // Memory reads see through breakpoints
+        assert_eq!(debugger.read_memory(0x1008, 1).unwrap(), vec![0x55]);
+
+        debugger.execute_line("clear *0x1008").unwrap();
+        assert!(debugger.breakpoints.is_empty());
+        assert_eq!(mock.process().memory[&0x1008], 0x55);
+        assert!(debugger.execute_line("clear *0x1008").is_err());
+
+        debugger.execute_line("continue").unwrap();
+        assert!(debugger.inferiors.is_empty());
+        assert_eq!(debugger.last_exit_code, Some(3));
+    }
+
+    #[test]
+    fn test_reinstall_breakpoints() {
+        let (mut debugger, mock) = debugger_with_mock(vec![0x1000, 0x1008]);
+        mock.process().memory.insert(0x1008, 0x55);
+        debugger.execute_line("break *0x1008").unwrap();
+        debugger.execute_line("continue").unwrap();
+        debugger.execute_line("continue").unwrap();
+        assert!(debugger.inferiors.is_empty());
+
+        // A new process records its own instructions
+        let mock = MockBackend::new(vec![0x1000, 0x1008]);
+        mock.process().memory.insert(0x1008, 0x66);
+        let inferior = Inferior::with_backend(mock.pid(), Box::new(mock.clone())).unwrap();
+        debugger.add_inferior(Tracee::new(2, inferior));
+        debugger.reinstall_breakpoints();
+        assert_eq!(debugger.breakpoints[&0x1008].orig_bytes(mock.pid()), &[0x66][..]);
+        assert_ne!(mock.process().memory[&0x1008], 0x66);
+
+        // One that cannot be patched in is kept for later
+        mock.clone().detach(mock.pid()).unwrap();
+        debugger.reinstall_breakpoints();
+        assert!(debugger.breakpoints.is_empty());
+        assert_eq!(debugger.pending_breakpoints[0].location, "*0x1008");
+    }
+
+    #[test]
+    fn test_breakpoint_in_each_inferior() {
+        let (mut debugger, mock) = debugger_with_mock(vec![0x1000, 0x1008]);
+        mock.process().memory.insert(0x1008, 0x55);
+        let other = MockBackend::new(vec![0x1000, 0x1008]);
+        other.process().memory.insert(0x1008, 0x66);
+        let pid = Pid::from_raw(MOCK_PID + 1);
+        let inferior = Inferior::with_backend(pid, Box::new(other.clone())).unwrap();
+        debugger.add_inferior(Tracee::new(2, inferior));
+
+        // Each inferior gets its own instruction back
+        debugger.execute_line("break *0x1008").unwrap();
+        assert_eq!(debugger.breakpoints[&0x1008].orig_bytes(pid), &[0x66][..]);
+        debugger.execute_line("clear *0x1008").unwrap();
+        assert_eq!(mock.process().memory[&0x1008], 0x55);
+        assert_eq!(other.process().memory[&0x1008], 0x66);
+
+        // One that cannot be patched takes the breakpoint back out of the others
+        other.clone().detach(pid).unwrap();
+        assert!(debugger.execute_line("break *0x1008").is_err());
+        assert!(debugger.breakpoints.is_empty());
+        assert_eq!(mock.process().memory[&0x1008], 0x55);
+    }
+
+    #[test]
+    fn test_continue_over_breakpoint() {
+        // A loop that passes the breakpoint twice
+        let (mut debugger, _mock) = debugger_with_mock(vec![0x1000, 0x1004, 0x1000, 0x1004]);
+        debugger.execute_line("break *0x1004").unwrap();
+        debugger.execute_line("continue").unwrap();
+        debugger.execute_line("continue").unwrap();
+        assert_eq!(debugger.inferior().unwrap().get_pc().unwrap(), 0x1004);
+        assert_eq!(debugger.breakpoints[&0x1004].hits, 2);
+        assert!(debugger.breakpoint_stop.is_some());
+        debugger.execute_line("continue").unwrap();
+        assert!(debugger.breakpoint_stop.is_none());
+        assert_eq!(debugger.last_exit_code, Some(0));
+    }
+
+    #[test]
+    fn test_dump_and_restore_memory() {
+        let (mut debugger, mock) = debugger_with_mock(vec![0x1000, 0x1004]);
+        for (i, byte) in [0x11, 0x22, 0x33, 0x44].iter().enumerate() {
+            mock.process().memory.insert(0x2000 + i, *byte);
+        }
+        debugger.execute_line("break *0x2002").unwrap();
+        let path = std::env::temp_dir().join(format!("deet-dump-{}", std::process::id()));
+        let path = path.to_string_lossy();
+        debugger.execute_line(&format!("dump memory {} 0x2000 0x2004", path)).unwrap();
+        // The breakpoint is not part of what the program has there
+        assert_eq!(std::fs::read(&*path).unwrap(), vec![0x11, 0x22, 0x33, 0x44]);
+
+        std::fs::write(&*path, [0x55, 0x66, 0x77]).unwrap();
+        debugger.execute_line(&format!("restore {} 0x2001", path)).unwrap();
+        std::fs::remove_file(&*path).unwrap();
+        assert_eq!(debugger.read_memory(0x2000, 4).unwrap(), vec![0x11, 0x55, 0x66, 0x77]);
+        // The breakpoint stays, now replacing the restored byte
+        assert_eq!(debugger.breakpoints[&0x2002].orig_bytes(mock.pid()), &[0x66][..]);
+        assert_ne!(mock.process().memory[&0x2002], 0x66);
+    }
+
+    #[test]
+    fn test_killed_while_stopped() {
+        let (mut debugger, mock) = debugger_with_mock(vec![0x1000, 0x1004]);
+        // Something outside kills the process while the debugger waits for the user
+        mock.clone().kill().unwrap();
+        debugger.execute_line("info inferiors").unwrap();
+        assert!(debugger.inferiors.is_empty());
+        assert_eq!(debugger.last_exit_code, Some(128 + Signal::SIGKILL as i32));
+    }
+
+    #[test]
+    fn test_stop_hooks() {
+        let (mut debugger, _mock) = debugger_with_mock(vec![0x1000, 0x1004, 0x1008, 0x100c]);
+        debugger.execute_line("break *0x1004").unwrap();
+        debugger.execute_line("break *0x1008").unwrap();
+        // The stop the hook causes does not run it again
+        debugger.execute_line("hook stop stepi").unwrap();
+        debugger.execute_line("continue").unwrap();
+        assert_eq!(debugger.inferior().unwrap().get_pc().unwrap(), 0x1008);
+        debugger.execute_line("unhook stop").unwrap();
+        debugger.execute_line("stepi").unwrap();
+        assert_eq!(debugger.inferior().unwrap().get_pc().unwrap(), 0x100c);
+    }
+
+    #[test]
+    fn test_set_timeout() {
+        let (mut debugger, _mock) = debugger_with_mock(vec![0x1000]);
+        debugger.execute_line("set timeout 5").unwrap();
+        assert_eq!(debugger.settings.timeout, Some(Duration::from_secs(5)));
+        assert!(debugger.execute_line("set timeout soon").is_err());
+        debugger.execute_line("set timeout 0").unwrap();
+        assert_eq!(debugger.settings.timeout, None);
+    }
+
+    #[test]
+    fn test_watch_register() {
+        let (mut debugger, _mock) = debugger_with_mock(vec![0x1000, 0x1004, 0x1008, 0x100c]);
+        assert!(debugger.execute_line("watch $nonsense").is_err());
+        debugger.execute_line("break *0x100c").unwrap();
+        debugger.execute_line("watch $rip").unwrap();
+        // Every instruction moves the program counter
+        debugger.execute_line("continue").unwrap();
+        assert_eq!(debugger.inferior().unwrap().get_pc().unwrap(), 0x1004);
+        assert_eq!(debugger.inferiors[0].register_watches[0].value, 0x1004);
+        assert!(debugger.execute_line("continue &").is_err());
+
+        debugger.execute_line("unwatch 1").unwrap();
+        debugger.execute_line("continue").unwrap();
+        assert_eq!(debugger.inferior().unwrap().get_pc().unwrap(), 0x100c);
+    }
+
+    #[test]
+    fn test_differing_ranges() {
+        assert!(differing_ranges(&[1, 2, 3], &[1, 2, 3]).is_empty());
+        assert_eq!(differing_ranges(&[1, 2, 3, 4, 5], &[0, 2, 0, 0, 5]), vec![(0, 1), (2, 4)]);
+    }
+
+    #[test]
+    fn test_continue_in_background() {
+        let (mut debugger, _mock) = debugger_with_mock(vec![0x1000, 0x1004, 0x1008, 0x100c]);
+        debugger.execute_line("break *0x1004").unwrap();
+        debugger.execute_line("continue &").unwrap();
+        assert!(debugger.inferiors[0].background.is_some());
+        // The stop at the breakpoint is picked up before the next command runs
+        debugger.execute_line("stepi").unwrap();
+        assert!(debugger.inferiors[0].background.is_none());
+        assert_eq!(debugger.inferior().unwrap().get_pc().unwrap(), 0x1008);
+        assert!(debugger.execute_line("interrupt -a").is_err());
+    }
+}
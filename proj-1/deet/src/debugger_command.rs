@@ -1,32 +1,437 @@
+use crate::error::DeetError;
+use crate::format::{Format, FormatSpec};
+
+/// How many words `stack` shows when not told otherwise.
+const DEFAULT_STACK_WORDS: usize = 16;
+
+/// How many entries `record instruction-history` and `record function-call-history` show when
+/// not told otherwise.
+const DEFAULT_HISTORY: usize = 10;
+
 pub enum DebuggerCommand {
     Quit,
     Run(Vec<String>),
-    Continue,
+    /// Runs the program up to the start of `main`.
+    Start(Vec<String>),
+    /// Runs the program, stopping at its very first instruction.
+    StartInstruction(Vec<String>),
+    /// Continues, in the background if asked to with `&`.
+    Continue(bool),
+    /// Stops the current inferior running in the background, or all of them (`-a`).
+    Interrupt(bool),
+    StepInstruction,
+    Next,
+    /// Starts recording execution while stepping, or stops and forgets the recording.
+    Record(bool),
+    /// Asks for execution to be recorded by the processor (Intel Processor Trace) rather than by
+    /// single-stepping.
+    RecordBtrace,
+    /// Shows the last instructions the processor recorded the current thread executing.
+    RecordInstructionHistory(usize),
+    /// Shows the functions the last instructions the processor recorded ran in, in order.
+    RecordFunctionCallHistory(usize),
+    ReverseStepInstruction,
+    ReverseStep,
+    ReverseNext,
     Backtrace,
     Break(String),
+    Clear(String),
+    Return(Option<i64>),
+    Jump(String),
+    Advance(String),
+    SaveBreakpoints(String),
+    /// Shows how often each breakpoint was hit, and for how long the program stayed there.
+    StatsBreakpoints,
+    Source(String),
+    /// Runs a line of rhai script.
+    Rhai(String),
+    Set(String, String),
+    /// Shows one setting, or all of them.
+    Show(Option<String>),
+    Call(String, Vec<i64>),
+    Print(Format, String),
+    Examine(FormatSpec, String),
+    /// Copies the memory from one address up to another into a file.
+    DumpMemory(String, String, String),
+    /// Copies a file into memory at an address.
+    Restore(String, String),
+    Display(Format, Option<String>),
+    /// Adds a command to run whenever the inferior stops, lists them (None), or deletes them all.
+    HookStop(Option<String>),
+    UnhookStop,
+    Undisplay(usize),
+    Whatis(String),
+    Ptype(String),
+    Frame(Option<usize>),
+    Up,
+    Down,
+    InfoLocals,
+    /// Shows the general purpose registers, or all of them (floating point and vector ones
+    /// included).
+    InfoRegisters(bool),
+    Where,
+    Disassemble,
+    Stack(usize),
+    HeapTrace(bool),
+    HeapReport,
+    Tty(Option<bool>),
+    Watch(String, Option<usize>),
+    Unwatch(usize),
+    Inferior(usize),
+    /// Selects one of the current inferior's threads, by number.
+    Thread(usize),
+    InfoInferiors,
+    InfoSymbol(String),
+    InfoSharedLibrary,
+    /// Shows the sections of the program being debugged, and its entry point.
+    InfoFiles,
+    /// Dumps the parsed line table, of one source file or of all of them.
+    MaintLineTable(Option<String>),
+    /// Checks the program's code and constants in memory against the file they came from.
+    CompareSections,
+    CatchFork,
 }
 
 impl DebuggerCommand {
-    pub fn from_tokens(tokens: &Vec<&str>) -> Option<DebuggerCommand> {
-        match tokens[0] {
-            "q" | "quit" => Some(DebuggerCommand::Quit),
+    pub fn from_tokens(tokens: &Vec<&str>) -> Result<DebuggerCommand, DeetError> {
+        // Commands like print/x carry a format after the slash
+        let (name, spec) = match tokens[0].find('/') {
+            Some(slash) => (&tokens[0][..slash], Some(FormatSpec::parse(&tokens[0][slash + 1..])?)),
+            None => (tokens[0], None),
+        };
+        let format = spec.and_then(|spec| spec.format).unwrap_or(Format::Natural);
+        match name {
+            "q" | "quit" => Ok(DebuggerCommand::Quit),
             "r" | "run" => {
                 let args = tokens[1..].to_vec();
-                Some(DebuggerCommand::Run(
+                Ok(DebuggerCommand::Run(
                     args.iter().map(|s| s.to_string()).collect(),
                 ))
             },
-            "c" | "cont" | "continue" => {
-                Some(DebuggerCommand::Continue)
+            "start" => Ok(DebuggerCommand::Start(
+                tokens[1..].iter().map(|s| s.to_string()).collect(),
+            )),
+            "starti" => Ok(DebuggerCommand::StartInstruction(
+                tokens[1..].iter().map(|s| s.to_string()).collect(),
+            )),
+            "c" | "cont" | "continue" => match tokens.get(1) {
+                None => Ok(DebuggerCommand::Continue(false)),
+                Some(&"&") => Ok(DebuggerCommand::Continue(true)),
+                _ => Err(DeetError::Parse(String::from("Usage: continue [&]"))),
+            },
+            "interrupt" => match tokens.get(1) {
+                None => Ok(DebuggerCommand::Interrupt(false)),
+                Some(&"-a") => Ok(DebuggerCommand::Interrupt(true)),
+                _ => Err(DeetError::Parse(String::from("Usage: interrupt [-a]"))),
+            },
+            "si" | "stepi" => {
+                Ok(DebuggerCommand::StepInstruction)
+            }
+            "n" | "next" => Ok(DebuggerCommand::Next),
+            "record" => match tokens.get(1) {
+                None => Ok(DebuggerCommand::Record(true)),
+                Some(&"stop") => Ok(DebuggerCommand::Record(false)),
+                Some(&"btrace") => Ok(DebuggerCommand::RecordBtrace),
+                Some(&"instruction-history") => {
+                    let count = match tokens.get(2) {
+                        Some(count) => parse_integer(count)? as usize,
+                        None => DEFAULT_HISTORY,
+                    };
+                    Ok(DebuggerCommand::RecordInstructionHistory(count))
+                }
+                Some(&"function-call-history") => {
+                    let count = match tokens.get(2) {
+                        Some(count) => parse_integer(count)? as usize,
+                        None => DEFAULT_HISTORY,
+                    };
+                    Ok(DebuggerCommand::RecordFunctionCallHistory(count))
+                }
+                _ => Err(DeetError::Parse(String::from(
+                    "Usage: record [stop|btrace|instruction-history [n]|function-call-history [n]]",
+                ))),
             },
+            "rsi" | "reverse-stepi" => Ok(DebuggerCommand::ReverseStepInstruction),
+            "rs" | "reverse-step" => Ok(DebuggerCommand::ReverseStep),
+            "rn" | "reverse-next" => Ok(DebuggerCommand::ReverseNext),
             "bt" | "back" | "backtrace" => {
-                Some(DebuggerCommand::Backtrace)
+                Ok(DebuggerCommand::Backtrace)
             }
             "b" | "break" => {
-                Some(DebuggerCommand::Break(tokens[1].to_string()))
+                let location = tokens.get(1).ok_or_else(|| {
+                    DeetError::Parse(String::from("Usage: break <function|line|*address>"))
+                })?;
+                Ok(DebuggerCommand::Break(location.to_string()))
+            }
+            "clear" => {
+                let location = tokens.get(1).ok_or_else(|| {
+                    DeetError::Parse(String::from("Usage: clear <function|line|*address>"))
+                })?;
+                Ok(DebuggerCommand::Clear(location.to_string()))
+            }
+            "return" => {
+                let value = match tokens.get(1) {
+                    Some(value) => Some(parse_integer(value)?),
+                    None => None,
+                };
+                Ok(DebuggerCommand::Return(value))
+            }
+            "j" | "jump" => {
+                let location = tokens.get(1).ok_or_else(|| {
+                    DeetError::Parse(String::from("Usage: jump <function|line|*address>"))
+                })?;
+                Ok(DebuggerCommand::Jump(location.to_string()))
+            }
+            "advance" => {
+                let location = tokens.get(1).ok_or_else(|| {
+                    DeetError::Parse(String::from("Usage: advance <function|line|*address>"))
+                })?;
+                Ok(DebuggerCommand::Advance(location.to_string()))
+            }
+            "save" => match (tokens.get(1), tokens.get(2)) {
+                (Some(&"breakpoints"), Some(path)) => {
+                    Ok(DebuggerCommand::SaveBreakpoints(path.to_string()))
+                }
+                _ => Err(DeetError::Parse(String::from("Usage: save breakpoints <file>"))),
+            },
+            "stats" => match tokens.get(1) {
+                Some(&"breakpoints") => Ok(DebuggerCommand::StatsBreakpoints),
+                _ => Err(DeetError::Parse(String::from("Usage: stats breakpoints"))),
+            },
+            "source" => {
+                let path = tokens.get(1).ok_or_else(|| {
+                    DeetError::Parse(String::from("Usage: source <file>"))
+                })?;
+                Ok(DebuggerCommand::Source(path.to_string()))
+            }
+            "rhai" => {
+                if tokens.len() < 2 {
+                    return Err(DeetError::Parse(String::from("Usage: rhai <code>")));
+                }
+                Ok(DebuggerCommand::Rhai(tokens[1..].join(" ")))
+            }
+            "set" => match (tokens.get(1), tokens.get(2)) {
+                (Some(setting), Some(value)) => {
+                    Ok(DebuggerCommand::Set(setting.to_string(), value.to_string()))
+                }
+                _ => Err(DeetError::Parse(String::from("Usage: set <setting> <value>"))),
+            },
+            "show" => Ok(DebuggerCommand::Show(tokens.get(1).map(|setting| setting.to_string()))),
+            "call" => {
+                let expr = tokens[1..].join(" ");
+                let (func, args) = parse_call(&expr).ok_or_else(|| {
+                    DeetError::Parse(String::from("Usage: call <function>(<arg>, ...)"))
+                })?;
+                let args = args
+                    .iter()
+                    .map(|arg| parse_integer(arg))
+                    .collect::<Result<Vec<i64>, DeetError>>()?;
+                Ok(DebuggerCommand::Call(func.to_string(), args))
+            }
+            "p" | "print" => {
+                let expr = tokens.get(1).ok_or_else(|| {
+                    DeetError::Parse(String::from("Usage: print[/f] <variable>"))
+                })?;
+                Ok(DebuggerCommand::Print(format, expr.to_string()))
+            }
+            "x" => {
+                let expr = tokens.get(1).ok_or_else(|| {
+                    DeetError::Parse(String::from("Usage: x[/nfu] <address|&variable>"))
+                })?;
+                Ok(DebuggerCommand::Examine(spec.unwrap_or_default(), expr.to_string()))
+            }
+            "dump" => match (tokens.get(1), tokens.get(2), tokens.get(3), tokens.get(4)) {
+                (Some(&"memory"), Some(path), Some(start), Some(end)) => {
+                    let (path, start, end) = (path.to_string(), start.to_string(), end.to_string());
+                    Ok(DebuggerCommand::DumpMemory(path, start, end))
+                }
+                _ => Err(DeetError::Parse(String::from("Usage: dump memory <file> <start> <end>"))),
+            },
+            "restore" => match (tokens.get(1), tokens.get(2)) {
+                (Some(path), Some(addr)) => {
+                    Ok(DebuggerCommand::Restore(path.to_string(), addr.to_string()))
+                }
+                _ => Err(DeetError::Parse(String::from("Usage: restore <file> <address>"))),
+            },
+            "display" => Ok(DebuggerCommand::Display(
+                format,
+                tokens.get(1).map(|expr| expr.to_string()),
+            )),
+            "hook" => match tokens.get(1) {
+                Some(&"stop") if tokens.len() == 2 => Ok(DebuggerCommand::HookStop(None)),
+                Some(&"stop") => Ok(DebuggerCommand::HookStop(Some(tokens[2..].join(" ")))),
+                _ => Err(DeetError::Parse(String::from("Usage: hook stop [command]"))),
+            },
+            "unhook" => match tokens.get(1) {
+                Some(&"stop") => Ok(DebuggerCommand::UnhookStop),
+                _ => Err(DeetError::Parse(String::from("Usage: unhook stop"))),
+            },
+            "undisplay" => {
+                let id = tokens.get(1).and_then(|id| id.parse().ok()).ok_or_else(|| {
+                    DeetError::Parse(String::from("Usage: undisplay <display number>"))
+                })?;
+                Ok(DebuggerCommand::Undisplay(id))
+            }
+            "whatis" | "ptype" => {
+                if tokens.len() < 2 {
+                    return Err(DeetError::Parse(format!("Usage: {} <variable|type>", name)));
+                }
+                let expr = tokens[1..].join(" ");
+                Ok(if name == "whatis" {
+                    DebuggerCommand::Whatis(expr)
+                } else {
+                    DebuggerCommand::Ptype(expr)
+                })
+            }
+            "f" | "frame" => {
+                let index = match tokens.get(1) {
+                    Some(index) => Some(index.parse().map_err(|_| {
+                        DeetError::Parse(format!("Invalid frame number \"{}\"", index))
+                    })?),
+                    None => None,
+                };
+                Ok(DebuggerCommand::Frame(index))
+            }
+            "where" | "context" => Ok(DebuggerCommand::Where),
+            "disas" | "disassemble" => Ok(DebuggerCommand::Disassemble),
+            "heap" => match (tokens.get(1), tokens.get(2)) {
+                (Some(&"trace"), Some(&"on")) => Ok(DebuggerCommand::HeapTrace(true)),
+                (Some(&"trace"), Some(&"off")) => Ok(DebuggerCommand::HeapTrace(false)),
+                (Some(&"report"), None) => Ok(DebuggerCommand::HeapReport),
+                _ => Err(DeetError::Parse(String::from("Usage: heap trace on|off, heap report"))),
+            },
+            "tty" => match tokens.get(1) {
+                None => Ok(DebuggerCommand::Tty(None)),
+                Some(&"on") => Ok(DebuggerCommand::Tty(Some(true))),
+                Some(&"off") => Ok(DebuggerCommand::Tty(Some(false))),
+                _ => Err(DeetError::Parse(String::from("Usage: tty [on|off]"))),
+            },
+            "stack" => {
+                let words = match tokens.get(1) {
+                    Some(words) => parse_integer(words)? as usize,
+                    None => DEFAULT_STACK_WORDS,
+                };
+                Ok(DebuggerCommand::Stack(words))
+            }
+            "up" => Ok(DebuggerCommand::Up),
+            "down" => Ok(DebuggerCommand::Down),
+            "i" | "info" => match tokens.get(1) {
+                Some(&"locals") => Ok(DebuggerCommand::InfoLocals),
+                Some(&"registers") => Ok(DebuggerCommand::InfoRegisters(false)),
+                Some(&"all-registers") => Ok(DebuggerCommand::InfoRegisters(true)),
+                Some(&"inferiors") => Ok(DebuggerCommand::InfoInferiors),
+                Some(&"sharedlibrary") => Ok(DebuggerCommand::InfoSharedLibrary),
+                Some(&"files") | Some(&"target") => Ok(DebuggerCommand::InfoFiles),
+                Some(&"symbol") => match tokens.get(2) {
+                    Some(addr) => Ok(DebuggerCommand::InfoSymbol(addr.to_string())),
+                    None => Err(DeetError::Parse(String::from("Usage: info symbol <address>"))),
+                },
+                _ => Err(DeetError::Parse(String::from(
+                    "Usage: info locals|registers|all-registers|inferiors|sharedlibrary|files|\
+                     symbol",
+                ))),
+            },
+            "compare-sections" => Ok(DebuggerCommand::CompareSections),
+            "maint" | "maintenance" => match (tokens.get(1), tokens.get(2)) {
+                (Some(&"info"), Some(&"line-table")) => {
+                    Ok(DebuggerCommand::MaintLineTable(tokens.get(3).map(|file| file.to_string())))
+                }
+                _ => Err(DeetError::Parse(String::from("Usage: maint info line-table [file]"))),
+            },
+            "inferior" => {
+                let id = tokens.get(1).and_then(|id| id.parse().ok()).ok_or_else(|| {
+                    DeetError::Parse(String::from("Usage: inferior <inferior number>"))
+                })?;
+                Ok(DebuggerCommand::Inferior(id))
+            }
+            "thread" => {
+                let number = tokens.get(1).and_then(|number| number.parse().ok()).ok_or_else(|| {
+                    DeetError::Parse(String::from("Usage: thread <thread number>"))
+                })?;
+                Ok(DebuggerCommand::Thread(number))
+            }
+            "catch" => match tokens.get(1) {
+                Some(&"fork") => Ok(DebuggerCommand::CatchFork),
+                _ => Err(DeetError::Parse(String::from("Usage: catch fork"))),
+            },
+            "watch" => {
+                let expr = tokens.get(1).ok_or_else(|| {
+                    let usage = "Usage: watch <variable|*address|$register> [length]";
+                    DeetError::Parse(String::from(usage))
+                })?;
+                let len = match tokens.get(2) {
+                    Some(len) => Some(parse_integer(len)? as usize),
+                    None => None,
+                };
+                Ok(DebuggerCommand::Watch(expr.to_string(), len))
+            }
+            "unwatch" => {
+                let id = tokens.get(1).and_then(|id| id.parse().ok()).ok_or_else(|| {
+                    DeetError::Parse(String::from("Usage: unwatch <watchpoint number>"))
+                })?;
+                Ok(DebuggerCommand::Unwatch(id))
             }
             // Default case:
-            _ => None,
+            _ => Err(DeetError::Parse(format!("Unrecognized command \"{}\".", name))),
         }
     }
+
+    /// Whether the command can be used while the current inferior runs in the background. The
+    /// others need it stopped, since they read or change its registers or memory.
+    pub fn allowed_while_running(&self) -> bool {
+        matches!(
+            self,
+            DebuggerCommand::Quit
+                | DebuggerCommand::Run(_)
+                | DebuggerCommand::Start(_)
+                | DebuggerCommand::StartInstruction(_)
+                | DebuggerCommand::Interrupt(_)
+                | DebuggerCommand::Inferior(_)
+                | DebuggerCommand::InfoInferiors
+                | DebuggerCommand::InfoSharedLibrary
+                | DebuggerCommand::InfoFiles
+                | DebuggerCommand::Tty(_)
+                | DebuggerCommand::Set(_, _)
+                | DebuggerCommand::Show(_)
+                | DebuggerCommand::SaveBreakpoints(_)
+                | DebuggerCommand::StatsBreakpoints
+                | DebuggerCommand::Source(_)
+                | DebuggerCommand::HeapReport
+                | DebuggerCommand::CatchFork
+                | DebuggerCommand::Undisplay(_)
+                | DebuggerCommand::HookStop(_)
+                | DebuggerCommand::UnhookStop
+                | DebuggerCommand::MaintLineTable(_)
+        )
+    }
+}
+
+/// Splits `name(arg1, arg2)` into the function name and its (trimmed) arguments.
+fn parse_call(expr: &str) -> Option<(&str, Vec<&str>)> {
+    let open = expr.find('(')?;
+    let args = expr[open + 1..].trim_end().strip_suffix(')')?;
+    let name = expr[..open].trim();
+    if name.is_empty() {
+        return None;
+    }
+    let args = if args.trim().is_empty() {
+        Vec::new()
+    } else {
+        args.split(',').map(|arg| arg.trim()).collect()
+    };
+    Some((name, args))
+}
+
+/// Parses a decimal or 0x-prefixed hexadecimal integer, optionally negative.
+pub fn parse_integer(token: &str) -> Result<i64, DeetError> {
+    let (negative, digits) = match token.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, token),
+    };
+    let value = if digits.to_lowercase().starts_with("0x") {
+        i64::from_str_radix(&digits[2..], 16)
+    } else {
+        digits.parse::<i64>()
+    }
+    .map_err(|_| DeetError::Parse(format!("Invalid number \"{}\"", token)))?;
+    Ok(if negative { -value } else { value })
 }
@@ -0,0 +1,80 @@
+//! Disassembly of the target's machine code. Rather than carry an x86 decoder around, deet asks
+//! `objdump` (from GNU binutils) to do the work and picks its output apart. Since objdump reads the
+//! executable file and not the inferior's memory, the int3 bytes of breakpoints never show up.
+//!
+//! objdump is therefore a runtime dependency of `disas`, and of reverse-stepping, which decodes
+//! instructions to learn what memory they write. Everything else works without it.
+
+use crate::error::DeetError;
+use std::io;
+use std::process::Command;
+
+pub struct Instruction {
+    pub address: usize,
+    /// The mnemonic and operands, in AT&T syntax.
+    pub text: String,
+}
+
+/// Disassembles the instructions of the file at `path` that lie in `[start, end)`.
+pub fn disassemble(path: &str, start: usize, end: usize) -> Result<Vec<Instruction>, DeetError> {
+    let output = Command::new("objdump")
+        .arg("--disassemble")
+        .arg("--no-show-raw-insn")
+        .arg(format!("--start-address={:#x}", start))
+        .arg(format!("--stop-address={:#x}", end))
+        .arg(path)
+        .output()
+        .map_err(|err| match err.kind() {
+            io::ErrorKind::NotFound => DeetError::ToolNotFound("objdump"),
+            _ => DeetError::Io(err),
+        })?;
+    if !output.status.success() {
+        return Err(DeetError::Io(io::Error::other(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        )));
+    }
+    Ok(parse_objdump(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Whether objdump can be run at all.
+pub fn objdump_available() -> bool {
+    Command::new("objdump").arg("--version").output().is_ok()
+}
+
+/// Picks the instructions out of objdump's output, where each one looks like
+/// `  401126:\tpush   %rbp`. Headers and symbol labels are skipped.
+fn parse_objdump(output: &str) -> Vec<Instruction> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (address, text) = line.split_at(line.find(":\t")?);
+            let address = usize::from_str_radix(address.trim(), 16).ok()?;
+            // Drop objdump's own comments, such as `# 404028 <counter>`
+            let text = text[2..].split(" #").next().unwrap_or("").trim_end();
+            Some(Instruction {
+                address,
+                text: text.to_string(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_objdump() {
+        let output = "\n/tmp/recur:     file format elf64-x86-64\n\n\n\
+            Disassembly of section .text:\n\n\
+            0000000000401126 <leaf>:\n  \
+            401126:\tpush   %rbp\n  \
+            401127:\tmov    %rsp,%rbp\n  \
+            40112a:\tmov    0x2ef8(%rip),%eax        # 404028 <counter>\n";
+        let instructions = parse_objdump(output);
+        assert_eq!(instructions.len(), 3);
+        assert_eq!(instructions[0].address, 0x401126);
+        assert_eq!(instructions[1].text, "mov    %rsp,%rbp");
+        assert_eq!(instructions[2].text, "mov    0x2ef8(%rip),%eax");
+    }
+}
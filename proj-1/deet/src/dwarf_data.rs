@@ -1,6 +1,11 @@
 use crate::gimli_wrapper;
+use crate::location::LocationExpression;
+use crate::output;
+use crate::symbol_table::SymbolTable;
 use addr2line::Context;
-use object::Object;
+use gimli::UnwindSection;
+use object::{Object, ObjectSection};
+use std::collections::HashMap;
 use std::convert::TryInto;
 use std::{fmt, fs};
 
@@ -12,7 +17,21 @@ pub enum Error {
 
 pub struct DwarfData {
     files: Vec<File>,
+    types: HashMap<usize, Type>,
+    entry_point: usize,
+    symbol_table: SymbolTable,
     addr2line: Context<addr2line::gimli::EndianRcSlice<addr2line::gimli::RunTimeEndian>>,
+    call_frame_info: Option<CallFrameInfo>,
+}
+
+/// The target's `.eh_frame`, which says where the canonical frame address is at every
+/// instruction, whether or not the function keeps a frame pointer.
+struct CallFrameInfo {
+    data: Vec<u8>,
+    endian: gimli::RunTimeEndian,
+    /// Where `.eh_frame` and `.text` are loaded, which pointers in the former are relative to.
+    address: u64,
+    text: u64,
 }
 
 impl fmt::Debug for DwarfData {
@@ -38,14 +57,68 @@ impl DwarfData {
         } else {
             gimli::RunTimeEndian::Big
         };
+        let (files, types) = gimli_wrapper::load_file(&object, endian)?;
+        let call_frame_info = object.section_by_name(".eh_frame").and_then(|section| {
+            let (offset, size) = section.file_range()?;
+            Some(CallFrameInfo {
+                data: mmap.get(offset as usize..(offset + size) as usize)?.to_vec(),
+                endian,
+                address: section.address(),
+                text: object.section_by_name(".text").map_or(0, |text| text.address()),
+            })
+        });
         Ok(DwarfData {
-            files: gimli_wrapper::load_file(&object, endian)?,
+            files,
+            types,
+            entry_point: object.entry() as usize,
+            symbol_table: SymbolTable::from_object(&object),
             addr2line: Context::new(&object).or_else(|e| Err(gimli_wrapper::Error::from(e)))?,
+            call_frame_info,
         })
     }
 
-    #[allow(dead_code)]
-    fn get_target_file(&self, file: &str) -> Option<&File> {
+    /// Debugging information for a target that has none, for tests that need no symbols.
+    #[cfg(test)]
+    pub fn empty() -> DwarfData {
+        use addr2line::gimli::{Dwarf, EndianRcSlice, Error, RunTimeEndian, SectionId};
+        fn empty_section(_: SectionId) -> Result<EndianRcSlice<RunTimeEndian>, Error> {
+            Ok(EndianRcSlice::new(std::rc::Rc::from(&[][..]), RunTimeEndian::Little))
+        }
+        DwarfData {
+            files: Vec::new(),
+            types: HashMap::new(),
+            entry_point: 0,
+            symbol_table: SymbolTable::empty(),
+            addr2line: Context::from_dwarf(Dwarf::load(empty_section, empty_section).unwrap())
+                .unwrap(),
+            call_frame_info: None,
+        }
+    }
+
+    /// Returns the address of the first instruction the target executes.
+    pub fn entry_point(&self) -> usize {
+        self.entry_point
+    }
+
+    /// The ELF symbols of the target, which cover code and data the debugging information
+    /// doesn't.
+    pub fn symbol_table(&self) -> &SymbolTable {
+        &self.symbol_table
+    }
+
+    /// Whether the target was built with debugging information (-g). Without it, only the ELF
+    /// symbols are there to go by.
+    pub fn has_debug_info(&self) -> bool {
+        !self.files.is_empty()
+    }
+
+    /// The compilation units, one per source file.
+    pub fn files(&self) -> &[File] {
+        &self.files
+    }
+
+    /// Finds the compilation unit for `file`, given either its full path or its name alone.
+    pub fn get_target_file(&self, file: &str) -> Option<&File> {
         self.files.iter().find(|f| {
             f.name == file || (!file.contains("/") && f.name.ends_with(&format!("/{}", file)))
         })
@@ -68,25 +141,81 @@ impl DwarfData {
 
     #[allow(dead_code)]
     pub fn get_addr_for_function(&self, file: Option<&str>, func_name: &str) -> Option<usize> {
+        // Prototypes of external functions (such as printf) show up with no code at all
+        let is_match = |func: &&Function| func.name == func_name && func.text_length > 0;
         match file {
             Some(filename) => Some(
                 self.get_target_file(filename)?
                     .functions
                     .iter()
-                    .find(|func| func.name == func_name)?
+                    .find(is_match)?
                     .address,
             ),
             None => {
                 for file in &self.files {
-                    if let Some(func) = file.functions.iter().find(|func| func.name == func_name) {
+                    if let Some(func) = file.functions.iter().find(is_match) {
                         return Some(func.address);
                     }
                 }
-                None
+                self.symbol_table.address_of(func_name)
             }
         }
     }
 
+    /// Returns the function whose machine code contains `addr`.
+    pub fn get_function_containing(&self, addr: usize) -> Option<&Function> {
+        self.files
+            .iter()
+            .flat_map(|file| file.functions.iter())
+            .find(|func| func.address <= addr && addr < func.address + func.text_length)
+    }
+
+    /// How to find the canonical frame address while executing the instruction at `pc`: the
+    /// DWARF number of a register, and the offset from its value. None if the call frame
+    /// information does not cover `pc`, or needs an expression evaluated.
+    pub fn cfa_rule(&self, pc: usize) -> Option<(u16, i64)> {
+        let cfi = self.call_frame_info.as_ref()?;
+        let eh_frame = gimli::EhFrame::new(&cfi.data, cfi.endian);
+        let bases = gimli::BaseAddresses::default().set_eh_frame(cfi.address).set_text(cfi.text);
+        let mut ctx = gimli::UninitializedUnwindContext::new();
+        let row = eh_frame
+            .unwind_info_for_address(&bases, &mut ctx, pc as u64, gimli::EhFrame::cie_from_offset)
+            .ok()?;
+        match row.cfa() {
+            gimli::CfaRule::RegisterAndOffset { register, offset } => Some((register.0, *offset)),
+            gimli::CfaRule::Expression(_) => None,
+        }
+    }
+
+    /// Returns the type at the given .debug_info offset, as referenced from `Member`s and from
+    /// derived types.
+    pub fn get_type(&self, offset: usize) -> Option<&Type> {
+        self.types.get(&offset)
+    }
+
+    /// Looks up a type by name, accepting either `point` or `struct point` for tagged types.
+    pub fn get_type_by_name(&self, name: &str) -> Option<&Type> {
+        self.types
+            .values()
+            .find(|dtype| dtype.name == name)
+            .or_else(|| {
+                self.types.values().find(|dtype| match dtype.kind {
+                    TypeKind::Struct(_) | TypeKind::Union(_) | TypeKind::Enum(_) => {
+                        dtype.name.split_once(' ').map(|(_, rest)| rest) == Some(name)
+                    }
+                    _ => false,
+                })
+            })
+    }
+
+    /// Looks up a global variable by name.
+    pub fn get_global_variable(&self, name: &str) -> Option<&Variable> {
+        self.files
+            .iter()
+            .flat_map(|file| file.global_variables.iter())
+            .find(|var| var.name == name)
+    }
+
     #[allow(dead_code)]
     pub fn get_line_from_addr(&self, curr_addr: usize) -> Option<Line> {
         let location = self
@@ -100,49 +229,56 @@ impl DwarfData {
         })
     }
 
-    #[allow(dead_code)]
+    /// Names the function containing `curr_addr`, falling back on the ELF symbols for code
+    /// without debugging information.
     pub fn get_function_from_addr(&self, curr_addr: usize) -> Option<String> {
-        let frame = self
-            .addr2line
-            .find_frames(curr_addr.try_into().unwrap())
-            .ok()?
-            .next()
-            .ok()??;
-        Some(frame.function?.raw_name().ok()?.to_string())
+        let name = || -> Option<String> {
+            let frame = self
+                .addr2line
+                .find_frames(curr_addr.try_into().unwrap())
+                .ok()?
+                .next()
+                .ok()??;
+            Some(frame.function?.raw_name().ok()?.to_string())
+        };
+        name().or_else(|| Some(self.symbol_table.lookup(curr_addr)?.0.name.clone()))
     }
 
     #[allow(dead_code)]
     pub fn print(&self) {
         for file in &self.files {
-            println!("------");
-            println!("{}", file.name);
-            println!("------");
+            output::info("------");
+            output::info(&file.name);
+            output::info("------");
 
-            println!("Global variables:");
+            output::info("Global variables:");
             for var in &file.global_variables {
-                println!(
+                output::info(format!(
                     "  * {} ({}, located at {}, declared at line {})",
                     var.name, var.entity_type.name, var.location, var.line_number
-                );
+                ));
             }
 
-            println!("Functions:");
+            output::info("Functions:");
             for func in &file.functions {
-                println!(
-                    "  * {} (declared on line {}, located at {:#x}, {} bytes long)",
-                    func.name, func.line_number, func.address, func.text_length
-                );
+                output::info(format!(
+                    "  * {} (declared on line {}, located at {}, {} bytes long)",
+                    func.name,
+                    func.line_number,
+                    output::address(func.address),
+                    func.text_length
+                ));
                 for var in &func.variables {
-                    println!(
+                    output::info(format!(
                         "    * Variable: {} ({}, located at {}, declared at line {})",
                         var.name, var.entity_type.name, var.location, var.line_number
-                    );
+                    ));
                 }
             }
 
-            println!("Line numbers:");
+            output::info("Line numbers:");
             for line in &file.lines {
-                println!("  * {} (at {:#x})", line.number, line.address);
+                output::info(format!("  * {} (at {})", line.number, output::address(line.address)));
             }
         }
     }
@@ -152,6 +288,7 @@ impl DwarfData {
 pub struct Type {
     pub name: String,
     pub size: usize,
+    pub kind: TypeKind,
 }
 
 impl Type {
@@ -159,14 +296,81 @@ impl Type {
         Type {
             name: name,
             size: size,
+            kind: TypeKind::Base(Encoding::Signed),
+        }
+    }
+}
+
+/// What sort of type a `Type` is. Other types are referred to by their .debug_info offset (see
+/// `DwarfData::get_type`); `None` means void.
+#[derive(Debug, Clone)]
+pub enum TypeKind {
+    Base(Encoding),
+    Pointer(Option<usize>),
+    Typedef(Option<usize>),
+    /// A const or volatile version of another type.
+    Qualified(&'static str, Option<usize>),
+    Struct(Vec<Member>),
+    Union(Vec<Member>),
+    Enum(Vec<Enumerator>),
+    /// Element type and the length of each dimension.
+    Array(Option<usize>, Vec<usize>),
+    Function,
+}
+
+impl Default for TypeKind {
+    fn default() -> Self {
+        TypeKind::Base(Encoding::Signed)
+    }
+}
+
+/// How the bits of a base type are to be read, as given by its DW_AT_encoding.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Encoding {
+    Signed,
+    Unsigned,
+    Float,
+    Bool,
+    SignedChar,
+    UnsignedChar,
+    /// A Unicode code point, like Rust's `char`.
+    Utf,
+}
+
+impl Encoding {
+    pub fn from_dwarf(encoding: gimli::DwAte) -> Encoding {
+        match encoding {
+            gimli::DW_ATE_unsigned => Encoding::Unsigned,
+            gimli::DW_ATE_float => Encoding::Float,
+            gimli::DW_ATE_boolean => Encoding::Bool,
+            gimli::DW_ATE_signed_char => Encoding::SignedChar,
+            gimli::DW_ATE_unsigned_char => Encoding::UnsignedChar,
+            gimli::DW_ATE_UTF => Encoding::Utf,
+            _ => Encoding::Signed,
         }
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct Member {
+    pub name: String,
+    pub type_offset: Option<usize>,
+    /// Byte offset from the start of the enclosing struct.
+    pub offset: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct Enumerator {
+    pub name: String,
+    pub value: i64,
+}
+
 #[derive(Clone)]
 pub enum Location {
     Address(usize),
     FramePointerOffset(isize),
+    /// Anything more involved, such as a register or a location list.
+    Expression(LocationExpression),
 }
 
 impl fmt::Display for Location {
@@ -174,6 +378,8 @@ impl fmt::Display for Location {
         match *self {
             Location::Address(addr) => write!(f, "Address({:#x})", addr),
             Location::FramePointerOffset(offset) => write!(f, "FramePointerOffset({})", offset),
+            Location::Expression(ref expr) if expr.is_list() => write!(f, "LocationList"),
+            Location::Expression(_) => write!(f, "Expression"),
         }
     }
 }
@@ -188,9 +394,24 @@ impl fmt::Debug for Location {
 #[derive(Debug, Clone)]
 pub struct Variable {
     pub name: String,
+    pub type_offset: usize,
     pub entity_type: Type,
     pub location: Location,
     pub line_number: usize, // Line number in source file
+    pub is_parameter: bool,
+    /// The address range of the innermost lexical block declaring this variable, or None if it
+    /// is visible throughout its function.
+    pub scope: Option<(usize, usize)>,
+}
+
+impl Variable {
+    /// Returns whether the variable is visible when executing the instruction at `pc`.
+    pub fn in_scope(&self, pc: usize) -> bool {
+        match self.scope {
+            Some((low, high)) => low <= pc && pc < high,
+            None => true,
+        }
+    }
 }
 
 #[derive(Debug, Default, Clone)]
@@ -200,6 +421,9 @@ pub struct Function {
     pub text_length: usize,
     pub line_number: usize, // Line number in source file
     pub variables: Vec<Variable>,
+    /// What `DW_OP_fbreg` offsets are from (`DW_AT_frame_base`). GCC always makes this the
+    /// canonical frame address; Clang uses the frame or stack pointer.
+    pub frame_base: Option<Location>,
 }
 
 #[derive(Debug, Default, Clone)]
@@ -222,5 +446,3 @@ impl fmt::Display for Line {
         write!(f, "{}:{}", self.file, self.number)
     }
 }
-
-
@@ -0,0 +1,59 @@
+use crate::dwarf_data::Error as DwarfError;
+use std::fmt;
+
+/// Everything that can go wrong while executing a debugger command. Errors are reported at the
+/// prompt and never take the debugger down.
+#[derive(Debug)]
+pub enum DeetError {
+    /// A ptrace or waitpid call on the inferior failed.
+    Ptrace(nix::Error),
+    /// The target's debugging symbols could not be loaded.
+    Dwarf(DwarfError),
+    /// The user typed something we could not make sense of.
+    Parse(String),
+    /// Reading or writing a file (or spawning the target) failed.
+    Io(std::io::Error),
+    /// The command needs a running inferior, but there is none.
+    NoInferior,
+    /// The command needs an external program (named here) that could not be found.
+    ToolNotFound(&'static str),
+    /// A rhai script failed to compile or run.
+    Script(String),
+}
+
+impl fmt::Display for DeetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeetError::Ptrace(err) => write!(f, "Error controlling the inferior: {}", err),
+            DeetError::Dwarf(DwarfError::ErrorOpeningFile) => write!(f, "Could not open file"),
+            DeetError::Dwarf(DwarfError::DwarfFormatError(err)) => {
+                write!(f, "Could not load debugging symbols: {:?}", err)
+            }
+            DeetError::Parse(msg) => write!(f, "{}", msg),
+            DeetError::Io(err) => write!(f, "I/O error: {}", err),
+            DeetError::NoInferior => write!(f, "The program is not being run."),
+            DeetError::Script(msg) => write!(f, "Script error: {}", msg),
+            DeetError::ToolNotFound(tool) => {
+                write!(f, "{} not found. This command needs it installed and on the PATH.", tool)
+            }
+        }
+    }
+}
+
+impl From<nix::Error> for DeetError {
+    fn from(err: nix::Error) -> Self {
+        DeetError::Ptrace(err)
+    }
+}
+
+impl From<DwarfError> for DeetError {
+    fn from(err: DwarfError) -> Self {
+        DeetError::Dwarf(err)
+    }
+}
+
+impl From<std::io::Error> for DeetError {
+    fn from(err: std::io::Error) -> Self {
+        DeetError::Io(err)
+    }
+}
@@ -0,0 +1,344 @@
+use crate::arch::FpValue;
+use crate::dwarf_data::{Encoding, Enumerator};
+use crate::error::DeetError;
+
+/// How a value is rendered. Selected with a `/f` suffix on `print`, `display` and `x`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Format {
+    /// Whatever suits the value's type best; signed decimal when the type is not known.
+    Natural,
+    Hex,
+    Decimal,
+    Char,
+    Binary,
+    /// The value is a pointer to a NUL-terminated string.
+    String,
+    Float,
+}
+
+impl Format {
+    pub fn from_letter(letter: char) -> Option<Format> {
+        match letter {
+            'x' => Some(Format::Hex),
+            'd' => Some(Format::Decimal),
+            'c' => Some(Format::Char),
+            't' => Some(Format::Binary),
+            's' => Some(Format::String),
+            'f' => Some(Format::Float),
+            _ => None,
+        }
+    }
+}
+
+/// Everything that can follow the slash in `x/4xw`: a repeat count, a format letter, and a unit
+/// size letter (b, h, w, g), in any order.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct FormatSpec {
+    pub count: Option<usize>,
+    pub format: Option<Format>,
+    pub size: Option<usize>,
+}
+
+impl FormatSpec {
+    pub fn parse(spec: &str) -> Result<FormatSpec, DeetError> {
+        let mut result = FormatSpec::default();
+        let digits: String = spec.chars().take_while(|c| c.is_ascii_digit()).collect();
+        if !digits.is_empty() {
+            result.count = digits.parse().ok();
+        }
+        for letter in spec[digits.len()..].chars() {
+            if let Some(format) = Format::from_letter(letter) {
+                result.format = Some(format);
+                continue;
+            }
+            result.size = Some(match letter {
+                'b' => 1,
+                'h' => 2,
+                'w' => 4,
+                'g' => 8,
+                _ => return Err(DeetError::Parse(format!("Undefined format letter \"{}\"", letter))),
+            });
+        }
+        Ok(result)
+    }
+}
+
+/// Strings are cut off after this many bytes, in case the pointer leads to garbage.
+const MAX_STRING_LEN: usize = 200;
+
+/// Renders the little-endian integer in `bytes` (1 to 8 bytes long). `read_memory(addr, len)` is
+/// only consulted for `Format::String`, to follow the pointer.
+pub fn format_value(
+    bytes: &[u8],
+    format: Format,
+    read_memory: &dyn Fn(usize, usize) -> Result<Vec<u8>, DeetError>,
+) -> Result<String, DeetError> {
+    let (unsigned, signed) = integer(bytes);
+    Ok(match format {
+        Format::Natural | Format::Decimal => signed.to_string(),
+        Format::Hex => format!("{:#x}", unsigned),
+        Format::Binary => format!("{:b}", unsigned),
+        Format::Char => format!("{} {}", signed as i8, quote_char(unsigned as u8)),
+        Format::String => {
+            let (text, _) = read_c_string(unsigned as usize, read_memory)?;
+            format!("{:#x} {}", unsigned, text)
+        }
+        Format::Float => format_float(bytes).unwrap_or_else(|| signed.to_string()),
+    })
+}
+
+/// Reads the little-endian integer in `bytes` (1 to 8 bytes long), both as unsigned and as
+/// signed.
+fn integer(bytes: &[u8]) -> (u64, i64) {
+    let mut raw = [0u8; 8];
+    raw[..bytes.len()].copy_from_slice(bytes);
+    let unsigned = u64::from_le_bytes(raw);
+    let bits = bytes.len() * 8;
+    // Sign-extend from the value's real width
+    let signed = if bits == 64 {
+        unsigned as i64
+    } else {
+        ((unsigned << (64 - bits)) as i64) >> (64 - bits)
+    };
+    (unsigned, signed)
+}
+
+/// Renders the value of a base type the way its encoding says to read it. Returns None if
+/// `bytes` is not a size values of that encoding come in.
+pub fn format_base(bytes: &[u8], encoding: Encoding) -> Option<String> {
+    if encoding == Encoding::Float {
+        return format_float(bytes);
+    }
+    if bytes.is_empty() || bytes.len() > 8 {
+        return None;
+    }
+    let (unsigned, signed) = integer(bytes);
+    Some(match encoding {
+        Encoding::Signed | Encoding::Float => signed.to_string(),
+        Encoding::Unsigned => unsigned.to_string(),
+        Encoding::Bool => match unsigned {
+            0 => String::from("false"),
+            1 => String::from("true"),
+            _ => unsigned.to_string(),
+        },
+        Encoding::SignedChar => format!("{} {}", signed, quote_char(unsigned as u8)),
+        Encoding::UnsignedChar => format!("{} {}", unsigned, quote_char(unsigned as u8)),
+        Encoding::Utf => match std::char::from_u32(unsigned as u32) {
+            Some(c) => format!("{} '{}'", unsigned, c.escape_default()),
+            None => unsigned.to_string(),
+        },
+    })
+}
+
+/// Renders the value of an enum as the name of the enumerator it matches, or as a number if it
+/// matches none.
+pub fn format_enum(bytes: &[u8], enumerators: &[Enumerator]) -> Option<String> {
+    if bytes.is_empty() || bytes.len() > 8 {
+        return None;
+    }
+    let (unsigned, signed) = integer(bytes);
+    let mask = if bytes.len() == 8 { !0 } else { (1u64 << (bytes.len() * 8)) - 1 };
+    // Enumerator values may have been recorded either sign- or zero-extended
+    let name = enumerators.iter().find(|e| e.value as u64 & mask == unsigned);
+    Some(name.map_or_else(|| signed.to_string(), |e| e.name.clone()))
+}
+
+/// Renders `bytes` as a float, if they are the size of one: single or double precision, or x87
+/// extended precision (a `long double`, which takes up 16 bytes on x86-64 but only uses 10).
+pub fn format_float(bytes: &[u8]) -> Option<String> {
+    Some(match bytes.len() {
+        4 => float_to_string(f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])),
+        8 => {
+            let mut raw = [0u8; 8];
+            raw.copy_from_slice(bytes);
+            float_to_string(f64::from_le_bytes(raw))
+        }
+        10..=16 => {
+            let mut raw = [0u8; 10];
+            raw.copy_from_slice(&bytes[..10]);
+            float_to_string(extended_to_f64(&raw))
+        }
+        _ => return None,
+    })
+}
+
+/// Converts an 80-bit x87 extended precision value to the nearest double.
+pub fn extended_to_f64(bytes: &[u8; 10]) -> f64 {
+    let mut mantissa = [0u8; 8];
+    mantissa.copy_from_slice(&bytes[..8]);
+    let mantissa = u64::from_le_bytes(mantissa);
+    let sign_exponent = u16::from_le_bytes([bytes[8], bytes[9]]);
+    let sign = if sign_exponent & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = (sign_exponent & 0x7fff) as i32;
+    let magnitude = match exponent {
+        0x7fff if mantissa << 1 == 0 => f64::INFINITY,
+        0x7fff => f64::NAN,
+        // The mantissa has an explicit integer bit, so it is worth 2^-63 of its face value
+        _ => mantissa as f64 * 2f64.powi(exponent.max(1) - 16383 - 63),
+    };
+    sign * magnitude
+}
+
+/// Writes a float the way C programmers expect to see it: plainly when it is of a reasonable
+/// size, in exponent notation otherwise.
+fn float_to_string<F>(value: F) -> String
+where
+    F: Copy + Into<f64> + std::fmt::Display + std::fmt::LowerExp,
+{
+    let magnitude = value.into().abs();
+    if magnitude.is_finite() && magnitude != 0.0 && !(1e-4..1e16).contains(&magnitude) {
+        format!("{:e}", value)
+    } else {
+        format!("{}", value)
+    }
+}
+
+/// Renders the contents of a floating point or vector register. Vector registers are shown as
+/// both single and double precision lanes, followed by their raw value.
+pub fn format_fp_register(value: &FpValue) -> String {
+    match value {
+        FpValue::Extended(bytes) => {
+            let raw: String = bytes.iter().rev().map(|byte| format!("{:02x}", byte)).collect();
+            format!("{:<22} (raw 0x{})", float_to_string(extended_to_f64(bytes)), raw)
+        }
+        FpValue::Vector(bytes) => {
+            let lanes = |size: usize| -> String {
+                let lanes: Vec<String> =
+                    bytes.chunks(size).filter_map(format_float).collect();
+                lanes.join(", ")
+            };
+            let raw: String = bytes.iter().rev().map(|byte| format!("{:02x}", byte)).collect();
+            format!("{{f32 = [{}], f64 = [{}]}} 0x{}", lanes(4), lanes(8), raw)
+        }
+        FpValue::Control(value) => format!("{:#x}", value),
+    }
+}
+
+/// Reads the NUL-terminated string at `addr` and returns it quoted, along with the number of
+/// bytes of memory it spans (counting the NUL, when one was found before the length limit).
+pub fn read_c_string(
+    addr: usize,
+    read_memory: &dyn Fn(usize, usize) -> Result<Vec<u8>, DeetError>,
+) -> Result<(String, usize), DeetError> {
+    let mut bytes = Vec::new();
+    while bytes.len() < MAX_STRING_LEN {
+        let chunk = read_memory(addr + bytes.len(), 8)?;
+        match chunk.iter().position(|b| *b == 0) {
+            Some(end) => {
+                bytes.extend_from_slice(&chunk[..end]);
+                return Ok((format!("\"{}\"", escape(&bytes)), bytes.len() + 1));
+            }
+            None => bytes.extend_from_slice(&chunk),
+        }
+    }
+    Ok((format!("\"{}\"...", escape(&bytes[..MAX_STRING_LEN])), MAX_STRING_LEN))
+}
+
+fn quote_char(byte: u8) -> String {
+    format!("'{}'", escape(&[byte]))
+}
+
+fn escape(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|byte| match *byte {
+            b'\n' => String::from("\\n"),
+            b'\t' => String::from("\\t"),
+            b'\\' => String::from("\\\\"),
+            b'"' => String::from("\\\""),
+            0x20..=0x7e => (*byte as char).to_string(),
+            _ => format!("\\{:03o}", byte),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn no_memory(_: usize, _: usize) -> Result<Vec<u8>, DeetError> {
+        Err(DeetError::NoInferior)
+    }
+
+    #[test]
+    fn test_format_integers() {
+        let minus_two = (-2i32).to_le_bytes();
+        assert_eq!(format_value(&minus_two, Format::Natural, &no_memory).unwrap(), "-2");
+        assert_eq!(format_value(&minus_two, Format::Hex, &no_memory).unwrap(), "0xfffffffe");
+        assert_eq!(format_value(&[5], Format::Binary, &no_memory).unwrap(), "101");
+        assert_eq!(format_value(b"a", Format::Char, &no_memory).unwrap(), "97 'a'");
+        assert_eq!(format_value(b"\n", Format::Char, &no_memory).unwrap(), "10 '\\n'");
+    }
+
+    #[test]
+    fn test_format_floats() {
+        let bytes = 1.5f64.to_le_bytes();
+        assert_eq!(format_value(&bytes, Format::Float, &no_memory).unwrap(), "1.5");
+        assert_eq!(format_float(&(-0.25f32).to_le_bytes()).unwrap(), "-0.25");
+        assert_eq!(format_float(&1e20f64.to_le_bytes()).unwrap(), "1e20");
+        assert_eq!(format_float(&0.1f32.to_le_bytes()).unwrap(), "0.1");
+        // 1.5 and -2 in extended precision
+        let one_and_a_half = [0, 0, 0, 0, 0, 0, 0, 0xc0, 0xff, 0x3f];
+        assert_eq!(extended_to_f64(&one_and_a_half), 1.5);
+        let minus_two = [0, 0, 0, 0, 0, 0, 0, 0x80, 0x00, 0xc0];
+        assert_eq!(extended_to_f64(&minus_two), -2.0);
+    }
+
+    #[test]
+    fn test_format_typed() {
+        assert_eq!(format_base(&[1], Encoding::Bool).unwrap(), "true");
+        assert_eq!(format_base(&[0], Encoding::Bool).unwrap(), "false");
+        assert_eq!(format_base(b"x", Encoding::SignedChar).unwrap(), "120 'x'");
+        assert_eq!(format_base(&0xe9u32.to_le_bytes(), Encoding::Utf).unwrap(), "233 '\\u{e9}'");
+        assert_eq!(format_base(&[0xff; 4], Encoding::Unsigned).unwrap(), "4294967295");
+        assert_eq!(format_base(&[0xff; 4], Encoding::Signed).unwrap(), "-1");
+        assert_eq!(format_base(&2.5f64.to_le_bytes(), Encoding::Float).unwrap(), "2.5");
+
+        let enumerators = vec![
+            Enumerator { name: String::from("Red"), value: 0 },
+            Enumerator { name: String::from("Unknown"), value: -1 },
+        ];
+        assert_eq!(format_enum(&[0, 0, 0, 0], &enumerators).unwrap(), "Red");
+        assert_eq!(format_enum(&[0xff; 4], &enumerators).unwrap(), "Unknown");
+        assert_eq!(format_enum(&[7, 0, 0, 0], &enumerators).unwrap(), "7");
+    }
+
+    #[test]
+    fn test_format_string() {
+        let memory = |addr: usize, len: usize| -> Result<Vec<u8>, DeetError> {
+            let text = b"hello, world\0garbage";
+            Ok(text[addr - 0x1000..addr - 0x1000 + len].to_vec())
+        };
+        assert_eq!(
+            format_value(&0x1000u64.to_le_bytes(), Format::String, &memory).unwrap(),
+            "0x1000 \"hello, world\""
+        );
+    }
+
+    #[test]
+    fn test_read_c_string_length() {
+        // The length counts raw bytes, not the escaped text, which runs longer than the string
+        let memory = |addr: usize, len: usize| -> Result<Vec<u8>, DeetError> {
+            let mut text = b"hi\n\x01\0next\0".to_vec();
+            text.resize(32, 0);
+            Ok(text[addr..addr + len].to_vec())
+        };
+        assert_eq!(read_c_string(0, &memory).unwrap(), (String::from("\"hi\\n\\001\""), 5));
+        assert_eq!(read_c_string(5, &memory).unwrap(), (String::from("\"next\""), 5));
+
+        let endless = |_: usize, len: usize| -> Result<Vec<u8>, DeetError> { Ok(vec![b'a'; len]) };
+        let (text, len) = read_c_string(0, &endless).unwrap();
+        assert!(text.ends_with("\"..."));
+        assert_eq!(len, MAX_STRING_LEN);
+    }
+
+    #[test]
+    fn test_parse_format_spec() {
+        let spec = FormatSpec::parse("4xw").unwrap();
+        assert_eq!(spec.count, Some(4));
+        assert_eq!(spec.format, Some(Format::Hex));
+        assert_eq!(spec.size, Some(4));
+        assert_eq!(FormatSpec::parse("s").unwrap().format, Some(Format::String));
+        assert!(FormatSpec::parse("q").is_err());
+    }
+}
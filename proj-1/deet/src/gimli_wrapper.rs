@@ -10,13 +10,21 @@ use gimli::{UnitOffset, UnitSectionOffset};
 use object::Object;
 use std::borrow;
 //use std::io::{BufWriter, Write};
-use crate::dwarf_data::{File, Function, Line, Location, Type, Variable};
+use crate::dwarf_data::{
+    Encoding, Enumerator, File, Function, Line, Location, Member, Type, TypeKind, Variable,
+};
+use crate::location::LocationExpression;
 use std::collections::HashMap;
 use std::convert::TryInto;
 use std::fmt::Write;
 use std::{io, path};
 
-pub fn load_file(object: &object::File, endian: gimli::RunTimeEndian) -> Result<Vec<File>, Error> {
+/// Loads every compilation unit in `object`, along with a table of all the types they define,
+/// keyed by the types' offsets in .debug_info.
+pub fn load_file(
+    object: &object::File,
+    endian: gimli::RunTimeEndian,
+) -> Result<(Vec<File>, HashMap<usize, Type>), Error> {
     // Load a section and return as `Cow<[u8]>`.
     let load_section = |id: gimli::SectionId| -> Result<borrow::Cow<[u8]>, gimli::Error> {
         Ok(object
@@ -51,9 +59,25 @@ pub fn load_file(object: &object::File, endian: gimli::RunTimeEndian) -> Result<
 
         // Iterate over the Debugging Information Entries (DIEs) in the unit.
         let mut depth = 0;
+        // Enclosing struct/union/enum/array types, so their children (members, enumerators, and
+        // subranges) can be attached to the right parent
+        let mut type_stack: Vec<(isize, usize)> = Vec::new();
+        // Enclosing lexical blocks, as (depth, low pc, high pc), so variables know their scope
+        let mut block_stack: Vec<(isize, usize, usize)> = Vec::new();
         let mut entries = unit.entries();
         while let Some((delta_depth, entry)) = entries.next_dfs()? {
             depth += delta_depth;
+            while type_stack.last().is_some_and(|(d, _)| *d >= depth) {
+                type_stack.pop();
+            }
+            while block_stack.last().is_some_and(|(d, _, _)| *d >= depth) {
+                block_stack.pop();
+            }
+            let parent_type = match type_stack.last() {
+                Some((d, offset)) if *d == depth - 1 => Some(*offset),
+                _ => None,
+            };
+            let type_offset = unit_offset_to_usize(entry.offset(), &unit);
             // Update the offset_to_type mapping for types
             // Update the variable list for formal params/variables
             match entry.tag() {
@@ -97,9 +121,94 @@ pub fn load_file(object: &object::File, endian: gimli::RunTimeEndian) -> Result<
                         // TODO: report error?
                         0
                     };
-                    let type_offset = entry.offset().0;
-                    offset_to_type
-                        .insert(type_offset, Type::new(name, byte_size.try_into().unwrap()));
+                    let mut base_type = Type::new(name, byte_size.try_into().unwrap());
+                    if let Ok(Some(gimli::AttributeValue::Encoding(encoding))) =
+                        entry.attr_value(gimli::DW_AT_encoding)
+                    {
+                        base_type.kind = TypeKind::Base(Encoding::from_dwarf(encoding));
+                    }
+                    offset_to_type.insert(type_offset, base_type);
+                }
+                gimli::DW_TAG_pointer_type
+                | gimli::DW_TAG_reference_type
+                | gimli::DW_TAG_typedef
+                | gimli::DW_TAG_const_type
+                | gimli::DW_TAG_volatile_type
+                | gimli::DW_TAG_structure_type
+                | gimli::DW_TAG_class_type
+                | gimli::DW_TAG_union_type
+                | gimli::DW_TAG_enumeration_type
+                | gimli::DW_TAG_array_type
+                | gimli::DW_TAG_subroutine_type => {
+                    let target = attr_ref(entry, gimli::DW_AT_type, &unit, &dwarf);
+                    let kind = match entry.tag() {
+                        gimli::DW_TAG_pointer_type | gimli::DW_TAG_reference_type => {
+                            TypeKind::Pointer(target)
+                        }
+                        gimli::DW_TAG_typedef => TypeKind::Typedef(target),
+                        gimli::DW_TAG_const_type => TypeKind::Qualified("const", target),
+                        gimli::DW_TAG_volatile_type => TypeKind::Qualified("volatile", target),
+                        gimli::DW_TAG_structure_type | gimli::DW_TAG_class_type => {
+                            TypeKind::Struct(Vec::new())
+                        }
+                        gimli::DW_TAG_union_type => TypeKind::Union(Vec::new()),
+                        gimli::DW_TAG_enumeration_type => TypeKind::Enum(Vec::new()),
+                        gimli::DW_TAG_array_type => TypeKind::Array(target, Vec::new()),
+                        _ => TypeKind::Function,
+                    };
+                    let mut dtype = Type::new(
+                        attr_string(entry, gimli::DW_AT_name, &unit, &dwarf).unwrap_or_default(),
+                        attr_uint(entry, gimli::DW_AT_byte_size, &unit, &dwarf).unwrap_or(0)
+                            as usize,
+                    );
+                    dtype.kind = kind;
+                    offset_to_type.insert(type_offset, dtype);
+                    type_stack.push((depth, type_offset));
+                }
+                gimli::DW_TAG_member => {
+                    let member = Member {
+                        name: attr_string(entry, gimli::DW_AT_name, &unit, &dwarf)
+                            .unwrap_or_default(),
+                        type_offset: attr_ref(entry, gimli::DW_AT_type, &unit, &dwarf),
+                        offset: attr_uint(entry, gimli::DW_AT_data_member_location, &unit, &dwarf)
+                            .unwrap_or(0) as usize,
+                    };
+                    if let Some(TypeKind::Struct(members)) | Some(TypeKind::Union(members)) =
+                        parent_type.and_then(|p| offset_to_type.get_mut(&p)).map(|t| &mut t.kind)
+                    {
+                        members.push(member);
+                    }
+                }
+                gimli::DW_TAG_enumerator => {
+                    let value = match entry.attr(gimli::DW_AT_const_value)? {
+                        Some(attr) => match get_attr_value(&attr, &unit, &dwarf) {
+                            Ok(DebugValue::Int(value)) => value,
+                            Ok(DebugValue::Uint(value)) => value as i64,
+                            _ => 0,
+                        },
+                        None => 0,
+                    };
+                    let enumerator = Enumerator {
+                        name: attr_string(entry, gimli::DW_AT_name, &unit, &dwarf)
+                            .unwrap_or_default(),
+                        value,
+                    };
+                    if let Some(TypeKind::Enum(enumerators)) =
+                        parent_type.and_then(|p| offset_to_type.get_mut(&p)).map(|t| &mut t.kind)
+                    {
+                        enumerators.push(enumerator);
+                    }
+                }
+                gimli::DW_TAG_subrange_type => {
+                    let count = attr_uint(entry, gimli::DW_AT_count, &unit, &dwarf).or_else(|| {
+                        attr_uint(entry, gimli::DW_AT_upper_bound, &unit, &dwarf).map(|ub| ub + 1)
+                    });
+                    if let Some(TypeKind::Array(_, dims)) =
+                        parent_type.and_then(|p| offset_to_type.get_mut(&p)).map(|t| &mut t.kind)
+                    {
+                        // Flexible array members have no bound at all
+                        dims.push(count.unwrap_or(0) as usize);
+                    }
                 }
                 gimli::DW_TAG_subprogram => {
                     let mut func: Function = Default::default();
@@ -129,14 +238,25 @@ pub fn load_file(object: &object::File, endian: gimli::RunTimeEndian) -> Result<
                                     func.line_number = line_number.try_into().unwrap();
                                 }
                             }
+                            gimli::DW_AT_frame_base => {
+                                func.frame_base = get_location(&attr, &unit, &dwarf, endian);
+                            }
                             _ => {}
                         }
                     }
                     compilation_units.last_mut().unwrap().functions.push(func);
                 }
+                gimli::DW_TAG_lexical_block => {
+                    let low_pc = attr_uint(entry, gimli::DW_AT_low_pc, &unit, &dwarf);
+                    let length = attr_uint(entry, gimli::DW_AT_high_pc, &unit, &dwarf);
+                    if let (Some(low_pc), Some(length)) = (low_pc, length) {
+                        let low_pc = low_pc as usize;
+                        block_stack.push((depth, low_pc, low_pc + length as usize));
+                    }
+                }
                 gimli::DW_TAG_formal_parameter | gimli::DW_TAG_variable => {
                     let mut name = String::new();
-                    let mut entity_type: Option<Type> = None;
+                    let mut entity_type: Option<usize> = None;
                     let mut location: Option<Location> = None;
                     let mut line_number = 0;
                     let mut attrs = entry.attrs();
@@ -151,13 +271,11 @@ pub fn load_file(object: &object::File, endian: gimli::RunTimeEndian) -> Result<
                             }
                             gimli::DW_AT_type => {
                                 if let Ok(DebugValue::Size(offset)) = val {
-                                    if let Some(dtype) = offset_to_type.get(&offset).clone() {
-                                        entity_type = Some(dtype.clone());
-                                    }
+                                    entity_type = Some(offset);
                                 }
                             }
                             gimli::DW_AT_location => {
-                                if let Some(loc) = get_location(&attr, &unit) {
+                                if let Some(loc) = get_location(&attr, &unit, &dwarf, endian) {
                                     location = Some(loc);
                                 }
                             }
@@ -169,12 +287,16 @@ pub fn load_file(object: &object::File, endian: gimli::RunTimeEndian) -> Result<
                             _ => {}
                         }
                     }
-                    if entity_type.is_some() && location.is_some() {
+                    if let (Some(type_offset), Some(location)) = (entity_type, location) {
+                        // The type may well be defined further down; it is filled in below
                         let var = Variable {
                             name,
-                            entity_type: entity_type.unwrap(),
-                            location: location.unwrap(),
+                            type_offset,
+                            entity_type: Type::default(),
+                            location,
                             line_number: line_number.try_into().unwrap(),
+                            is_parameter: entry.tag() == gimli::DW_TAG_formal_parameter,
+                            scope: block_stack.last().map(|(_, low, high)| (*low, *high)),
                         };
                         if depth == 1 {
                             compilation_units
@@ -240,7 +362,131 @@ pub fn load_file(object: &object::File, endian: gimli::RunTimeEndian) -> Result<
             }
         }
     }
-    Ok(compilation_units)
+    resolve_types(&mut offset_to_type);
+    for file in compilation_units.iter_mut() {
+        let variables = file
+            .global_variables
+            .iter_mut()
+            .chain(file.functions.iter_mut().flat_map(|func| func.variables.iter_mut()));
+        for var in variables {
+            if let Some(dtype) = offset_to_type.get(&var.type_offset) {
+                var.entity_type = dtype.clone();
+            }
+        }
+    }
+    Ok((compilation_units, offset_to_type))
+}
+
+/// Gives derived types (pointers, arrays, typedefs, ...) their full C-style names and sizes now
+/// that every type they refer to has been loaded.
+fn resolve_types(types: &mut HashMap<usize, Type>) {
+    let offsets: Vec<usize> = types.keys().cloned().collect();
+    for offset in offsets {
+        let name = type_name(types, Some(offset), 0);
+        let size = type_size(types, Some(offset), 0);
+        let dtype = types.get_mut(&offset).unwrap();
+        dtype.name = name;
+        dtype.size = size;
+    }
+}
+
+/// Guards against cycles (a struct containing a pointer to itself is fine, but malformed DWARF
+/// should not send us into infinite recursion).
+const MAX_TYPE_DEPTH: usize = 16;
+
+fn type_name(types: &HashMap<usize, Type>, offset: Option<usize>, depth: usize) -> String {
+    let dtype = match offset.and_then(|offset| types.get(&offset)) {
+        Some(dtype) if depth < MAX_TYPE_DEPTH => dtype,
+        Some(_) => return String::from("..."),
+        None => return String::from("void"),
+    };
+    let tagged = |tag: &str| {
+        if dtype.name.is_empty() {
+            format!("{} {{...}}", tag)
+        } else {
+            format!("{} {}", tag, dtype.name)
+        }
+    };
+    match &dtype.kind {
+        TypeKind::Base(_) | TypeKind::Typedef(_) => dtype.name.clone(),
+        TypeKind::Pointer(target) => format!("{} *", type_name(types, *target, depth + 1)),
+        TypeKind::Qualified(qualifier, target) => {
+            format!("{} {}", qualifier, type_name(types, *target, depth + 1))
+        }
+        TypeKind::Struct(_) => tagged("struct"),
+        TypeKind::Union(_) => tagged("union"),
+        TypeKind::Enum(_) => tagged("enum"),
+        TypeKind::Array(elem, dims) => {
+            let dims: String = dims.iter().map(|dim| format!("[{}]", dim)).collect();
+            format!("{} {}", type_name(types, *elem, depth + 1), dims)
+        }
+        TypeKind::Function => String::from("<function>"),
+    }
+}
+
+fn type_size(types: &HashMap<usize, Type>, offset: Option<usize>, depth: usize) -> usize {
+    let dtype = match offset.and_then(|offset| types.get(&offset)) {
+        Some(dtype) if depth < MAX_TYPE_DEPTH => dtype,
+        _ => return 0,
+    };
+    if dtype.size != 0 {
+        return dtype.size;
+    }
+    match &dtype.kind {
+        TypeKind::Typedef(target) | TypeKind::Qualified(_, target) => {
+            type_size(types, *target, depth + 1)
+        }
+        TypeKind::Array(elem, dims) => {
+            type_size(types, *elem, depth + 1) * dims.iter().product::<usize>()
+        }
+        _ => 0,
+    }
+}
+
+/// Converts a unit-relative DIE offset into the .debug_info offset that `DW_AT_type` references
+/// resolve to, so that types from different compilation units don't collide.
+fn unit_offset_to_usize<R: Reader>(offset: UnitOffset<usize>, unit: &gimli::Unit<R>) -> usize {
+    match offset.to_unit_section_offset(unit) {
+        UnitSectionOffset::DebugInfoOffset(goff) => goff.0,
+        UnitSectionOffset::DebugTypesOffset(goff) => goff.0,
+    }
+}
+
+fn attr_string<R: Reader>(
+    entry: &gimli::DebuggingInformationEntry<R>,
+    name: gimli::DwAt,
+    unit: &gimli::Unit<R>,
+    dwarf: &gimli::Dwarf<R>,
+) -> Option<String> {
+    match get_attr_value(&entry.attr(name).ok()??, unit, dwarf) {
+        Ok(DebugValue::Str(value)) => Some(value),
+        _ => None,
+    }
+}
+
+fn attr_uint<R: Reader>(
+    entry: &gimli::DebuggingInformationEntry<R>,
+    name: gimli::DwAt,
+    unit: &gimli::Unit<R>,
+    dwarf: &gimli::Dwarf<R>,
+) -> Option<u64> {
+    match get_attr_value(&entry.attr(name).ok()??, unit, dwarf) {
+        Ok(DebugValue::Uint(value)) => Some(value),
+        Ok(DebugValue::Int(value)) if value >= 0 => Some(value as u64),
+        _ => None,
+    }
+}
+
+fn attr_ref<R: Reader>(
+    entry: &gimli::DebuggingInformationEntry<R>,
+    name: gimli::DwAt,
+    unit: &gimli::Unit<R>,
+    dwarf: &gimli::Dwarf<R>,
+) -> Option<usize> {
+    match get_attr_value(&entry.attr(name).ok()??, unit, dwarf) {
+        Ok(DebugValue::Size(offset)) => Some(offset),
+        _ => None,
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -291,12 +537,18 @@ impl<'input, Endian> Reader for gimli::EndianSlice<'input, Endian> where
 
 trait Reader: gimli::Reader<Offset = usize> + Send + Sync {}
 
-fn get_location<R: Reader>(attr: &gimli::Attribute<R>, unit: &gimli::Unit<R>) -> Option<Location> {
+fn get_location<R: Reader>(
+    attr: &gimli::Attribute<R>,
+    unit: &gimli::Unit<R>,
+    dwarf: &gimli::Dwarf<R>,
+    endian: gimli::RunTimeEndian,
+) -> Option<Location> {
+    let encoding = unit.encoding();
     if let gimli::AttributeValue::Exprloc(ref data) = attr.value() {
-        let encoding = unit.encoding();
+        // A lone DW_OP_fbreg or DW_OP_addr needs no evaluating
         let mut pc = data.0.clone();
-        if pc.len() > 0 {
-            if let Ok(op) = gimli::Operation::parse(&mut pc, encoding) {
+        if let Ok(op) = gimli::Operation::parse(&mut pc, encoding) {
+            if pc.is_empty() {
                 match op {
                     gimli::Operation::FrameOffset { offset } => {
                         return Some(Location::FramePointerOffset(offset.try_into().unwrap()));
@@ -308,8 +560,22 @@ fn get_location<R: Reader>(attr: &gimli::Attribute<R>, unit: &gimli::Unit<R>) ->
                 }
             }
         }
+        let bytes = data.0.to_slice().ok()?.to_vec();
+        return Some(Location::Expression(LocationExpression::new(
+            encoding,
+            endian,
+            vec![(None, bytes)],
+        )));
+    }
+    // Otherwise it may be a location list, with the variable moving around as the function runs
+    let offset = dwarf.attr_locations_offset(unit, attr.value()).ok()??;
+    let mut locations = dwarf.locations(unit, offset).ok()?;
+    let mut entries = Vec::new();
+    while let Ok(Some(entry)) = locations.next() {
+        let range = (entry.range.begin as usize, entry.range.end as usize);
+        entries.push((Some(range), entry.data.0.to_slice().ok()?.to_vec()));
     }
-    None
+    Some(Location::Expression(LocationExpression::new(encoding, endian, entries)))
 }
 
 // based on dwarf_dump.rs
@@ -347,6 +613,10 @@ fn get_attr_value<R: Reader>(
         gimli::AttributeValue::Sdata(data) => Ok(DebugValue::Int(data)),
         gimli::AttributeValue::Addr(data) => Ok(DebugValue::Uint(data)),
         gimli::AttributeValue::Udata(data) => Ok(DebugValue::Uint(data)),
+        gimli::AttributeValue::Data1(data) => Ok(DebugValue::Uint(data.into())),
+        gimli::AttributeValue::Data2(data) => Ok(DebugValue::Uint(data.into())),
+        gimli::AttributeValue::Data4(data) => Ok(DebugValue::Uint(data.into())),
+        gimli::AttributeValue::Data8(data) => Ok(DebugValue::Uint(data)),
 
         gimli::AttributeValue::String(s) => {
             Ok(DebugValue::Str(format!("{}", s.to_string_lossy()?)))
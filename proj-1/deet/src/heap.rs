@@ -0,0 +1,136 @@
+//! Bookkeeping for `heap trace`. The debugger stops the inferior on entry to the allocator and,
+//! once an allocation returns, records the block it handed out along with the call site; frees
+//! take blocks back out. Whatever is left is either still in use or leaked.
+
+use std::collections::HashMap;
+
+pub struct Allocation {
+    pub size: usize,
+    /// The return address of the call to the allocator.
+    pub call_site: usize,
+}
+
+/// An allocation the allocator has been asked for but has not returned yet.
+struct PendingAllocation {
+    size: usize,
+    call_site: usize,
+    /// rsp once the allocator has returned, to tell that return apart from a recursive call
+    /// returning to the same place.
+    rsp: usize,
+}
+
+/// Live blocks allocated from one call site.
+pub struct CallSiteSummary {
+    pub call_site: usize,
+    pub bytes: usize,
+    pub blocks: usize,
+}
+
+#[derive(Default)]
+pub struct HeapTrace {
+    live: HashMap<usize, Allocation>,
+    pending: Vec<PendingAllocation>,
+    pub allocations: usize,
+    pub frees: usize,
+}
+
+impl HeapTrace {
+    /// Records a call to the allocator for `size` bytes, made from `call_site` with rsp ending up
+    /// at `rsp` once it returns.
+    pub fn enter_alloc(&mut self, size: usize, call_site: usize, rsp: usize) {
+        self.pending.push(PendingAllocation {
+            size,
+            call_site,
+            rsp,
+        });
+    }
+
+    /// Records the allocator returning `ptr` to `addr`, if that is a return we were waiting for.
+    pub fn finish_alloc(&mut self, addr: usize, rsp: usize, ptr: usize) {
+        let index = match self
+            .pending
+            .iter()
+            .rposition(|pending| pending.call_site == addr && pending.rsp == rsp)
+        {
+            Some(index) => index,
+            None => return,
+        };
+        let pending = self.pending.remove(index);
+        // A failed allocation hands nothing out
+        if ptr != 0 {
+            self.allocations += 1;
+            self.live.insert(
+                ptr,
+                Allocation {
+                    size: pending.size,
+                    call_site: pending.call_site,
+                },
+            );
+        }
+    }
+
+    /// Whether an allocation is still to return to `addr`.
+    pub fn is_waiting_at(&self, addr: usize) -> bool {
+        self.pending.iter().any(|pending| pending.call_site == addr)
+    }
+
+    pub fn free(&mut self, ptr: usize) {
+        // Blocks allocated before tracing started are not known, and don't count
+        if self.live.remove(&ptr).is_some() {
+            self.frees += 1;
+        }
+    }
+
+    pub fn live_bytes(&self) -> usize {
+        self.live.values().map(|allocation| allocation.size).sum()
+    }
+
+    pub fn live_blocks(&self) -> usize {
+        self.live.len()
+    }
+
+    /// Groups the live blocks by where they were allocated, biggest users first.
+    pub fn by_call_site(&self) -> Vec<CallSiteSummary> {
+        let mut sites: HashMap<usize, CallSiteSummary> = HashMap::new();
+        for allocation in self.live.values() {
+            let site = sites.entry(allocation.call_site).or_insert(CallSiteSummary {
+                call_site: allocation.call_site,
+                bytes: 0,
+                blocks: 0,
+            });
+            site.bytes += allocation.size;
+            site.blocks += 1;
+        }
+        let mut sites: Vec<CallSiteSummary> = sites.into_values().collect();
+        sites.sort_by(|a, b| (b.bytes, a.call_site).cmp(&(a.bytes, b.call_site)));
+        sites
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_heap_trace() {
+        let mut heap = HeapTrace::default();
+        heap.enter_alloc(16, 0x401000, 0x7000);
+        assert!(heap.is_waiting_at(0x401000));
+        // A deeper call returning to the same place is not the one being waited for
+        heap.finish_alloc(0x401000, 0x6f00, 0x1234);
+        assert_eq!(heap.live_blocks(), 0);
+        heap.finish_alloc(0x401000, 0x7000, 0x5000);
+        assert!(!heap.is_waiting_at(0x401000));
+        heap.enter_alloc(8, 0x401000, 0x7000);
+        heap.finish_alloc(0x401000, 0x7000, 0x5020);
+        heap.enter_alloc(100, 0x402000, 0x7000);
+        heap.finish_alloc(0x402000, 0x7000, 0x6000);
+        heap.free(0x6000);
+        heap.free(0x9999);
+        assert_eq!((heap.allocations, heap.frees), (3, 1));
+        assert_eq!((heap.live_blocks(), heap.live_bytes()), (2, 24));
+        let sites = heap.by_call_site();
+        assert_eq!(sites.len(), 1);
+        assert_eq!((sites[0].call_site, sites[0].bytes, sites[0].blocks), (0x401000, 24, 2));
+    }
+}
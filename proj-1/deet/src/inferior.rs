@@ -1,13 +1,18 @@
 use nix::sys::ptrace;
 use nix::sys::signal;
-use nix::sys::wait::{waitpid, WaitPidFlag, WaitStatus};
-use nix::unistd::Pid;
-use std::process::{Child, Command};
-use std::os::unix::process::CommandExt;
+use nix::sys::wait::{WaitPidFlag, WaitStatus};
+use nix::unistd::{self, ForkResult, Pid};
+use std::ffi::{CStr, CString};
 use std::mem::size_of;
+use std::os::unix::io::RawFd;
 use std::collections::HashMap;
+use std::time::Duration;
+use crate::arch::{self, Arch, FpValue, Registers, Slot};
+use crate::backend::{InferiorBackend, Ptrace};
 use crate::debugger::BreakPoint;
-use crate::dwarf_data::DwarfData;
+use crate::error::DeetError;
+use crate::output;
+use crate::watchpoint;
 
 pub enum Status {
     /// Indicates inferior stopped. Contains the signal that stopped the process, as well as the
@@ -17,150 +22,950 @@ pub enum Status {
     /// Indicates inferior exited normally. Contains the exit status code.
     Exited(i32),
 
+    /// Indicates the inferior forked and is stopped in the fork call. Contains the child's pid and
+    /// the parent's instruction pointer.
+    Forked(Pid, usize),
+
+    /// Indicates the inferior replaced itself with a new program through execve. Contains the
+    /// instruction pointer it is stopped at, in the new image.
+    Execed(usize),
+
     /// Indicates the inferior exited due to a signal. Contains the signal that killed the
     /// process.
     Signaled(signal::Signal),
+
+    /// Indicates the inferior is in a group-stop (stopped by job control, with SIGSTOP or
+    /// SIGTSTP for instance) or was stopped with PTRACE_INTERRUPT, in which case the signal is
+    /// SIGTRAP. Unlike `Stopped`, no signal is waiting to be delivered. Contains the signal and
+    /// the current instruction pointer.
+    GroupStopped(signal::Signal, usize),
 }
 
-/// This function calls ptrace with PTRACE_TRACEME to enable debugging on a process. You should use
-/// pre_exec with Command to call this in the child process.
-fn child_traceme() -> Result<(), std::io::Error> {
-    ptrace::traceme().or(Err(std::io::Error::new(
-        std::io::ErrorKind::Other,
-        "ptrace TRACEME failed",
-    )))
+/// The event a seized tracee reports group-stops and PTRACE_INTERRUPT stops with. nix leaves it
+/// out, since older glibc versions don't define it.
+const PTRACE_EVENT_STOP: i32 = 128;
+
+/// How long to wait between checks on running threads, when there are several to wait for.
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// How `Inferior::resume` lets the inferior proceed.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Resume {
+    /// Run until the inferior stops on a signal or breakpoint, or exits.
+    Continue,
+    /// Execute a single machine instruction.
+    Step,
 }
 
-pub struct Inferior {
-    child: Child,
+/// How far the current function has got through its prologue (`push rbp; mov rbp, rsp` on x86,
+/// `stp x29, x30, [sp, #-N]!; mov x29, sp` on aarch64), which decides where its return address
+/// and the caller's frame pointer live.
+#[derive(Clone, Copy, PartialEq)]
+pub enum FrameSetup {
+    /// Nothing has been saved: the return address is where the call left it (on the stack, or in
+    /// the link register) and the frame pointer still belongs to the caller.
+    NotStarted,
+    /// The caller's frame pointer has been saved at the stack pointer, with the return address
+    /// right above it, but the frame pointer has not been pointed at it yet.
+    RbpPushed,
+    /// The frame pointer points at the saved frame pointer, with the return address right above
+    /// it.
+    Complete,
+}
+
+/// How a function called with `Inferior::call_function` finished.
+pub enum CallResult {
+    /// The function returned normally, with this value.
+    Returned(u64),
+    /// Something else stopped the inferior first (a signal, a breakpoint, or the process dying).
+    Interrupted(Status),
+}
+
+/// A line of /proc/<pid>/maps: a range of the inferior's address space and what backs it.
+pub struct Mapping {
+    pub start: usize,
+    pub end: usize,
+    pub perms: String,
+    /// Offset into `path` of the start of the mapping.
+    pub offset: usize,
+    /// The mapped file, a pseudo-path such as `[stack]`, or empty for anonymous memory.
+    pub path: String,
+}
+
+impl Mapping {
+    fn parse(line: &str) -> Option<Mapping> {
+        let mut fields = line.split_whitespace();
+        let mut bounds = fields.next()?.split('-');
+        let start = usize::from_str_radix(bounds.next()?, 16).ok()?;
+        let end = usize::from_str_radix(bounds.next()?, 16).ok()?;
+        let perms = fields.next()?.to_string();
+        let offset = usize::from_str_radix(fields.next()?, 16).ok()?;
+        // Skip the device and inode; the path may contain spaces
+        let path = fields.skip(2).collect::<Vec<&str>>().join(" ");
+        Some(Mapping {
+            start,
+            end,
+            perms,
+            offset,
+            path,
+        })
+    }
+}
+
+/// A shared object mapped into the inferior, pieced together from its mappings.
+pub struct SharedLibrary {
+    pub path: String,
+    /// Where the library's first mapping, which covers the start of the file, is. Addresses in
+    /// the file are relative to it.
+    pub base: usize,
+    /// The range of addresses the library's code occupies, if any of it is mapped executable.
+    pub text: Option<(usize, usize)>,
+}
+
+/// One of the inferior's threads. They are numbered from 1 (the main thread) in the order they
+/// started.
+#[derive(Clone, Copy, PartialEq)]
+pub struct Thread {
+    pub number: usize,
+    pub tid: Pid,
 }
 
+/// A thread starting or exiting, for the debugger to tell the user about.
+pub enum ThreadEvent {
+    Started(Thread),
+    /// Contains the exit code the kernel reports for the thread, which for threads that go down
+    /// with the whole process is the process's, or 128 + the number of the signal that killed it.
+    Exited(Thread, i32),
+}
 
+pub struct Inferior {
+    pid: Pid,
+    /// The architecture of the program being run.
+    arch: Box<dyn Arch>,
+    /// What the process is controlled through.
+    backend: Box<dyn InferiorBackend>,
+    /// Every thread of the process, all of them traced, in the order they started.
+    threads: Vec<Thread>,
+    next_thread_number: usize,
+    /// The thread registers are read from and single steps apply to: the one that stopped last,
+    /// unless another has been selected since.
+    thread: Pid,
+    /// Whether the current thread is single stepping, with the others left stopped, as opposed
+    /// to all of them running.
+    stepping: bool,
+    /// Stops that threads ran into while the others were being stopped, to be reported before
+    /// anything is resumed again.
+    pending: Vec<(Pid, WaitStatus)>,
+    /// Threads that something else stopped before PTRACE_INTERRUPT got to them. The interrupt
+    /// still stops them as soon as they are resumed; that stop is not worth reporting.
+    interrupts_due: Vec<Pid>,
+    /// What has happened to the threads since the debugger last looked.
+    events: Vec<ThreadEvent>,
+}
 
+/// Aligns `addr` down to a ptrace word. PTRACE_PEEKDATA and PTRACE_POKEDATA always move a word
+/// of the debugger's own size, 8 bytes, even when the inferior is a 32-bit program.
 fn align_addr_to_word(addr: usize) -> usize {
     addr & (-(size_of::<usize>() as isize) as usize)
 }
 
+/// Where a new inferior's standard streams go.
+pub enum ChildIo {
+    /// To deet's own.
+    Inherit,
+    /// Stdout and stderr to these file descriptors; stdin is still deet's.
+    Capture(RawFd, RawFd),
+    /// All three to this pty slave, which also becomes the inferior's controlling terminal.
+    Terminal(RawFd),
+}
+
 impl Inferior {
-    /// Attempts to start a new inferior process. Returns Some(Inferior) if successful, or None if
-    /// an error is encountered.
-    pub fn new(target: &str, args: &Vec<String>, breakpoints: &HashMap<usize, BreakPoint>) -> Option<Inferior> {
-        // TODO: implement me!
-        // println!(
-        //     "Inferior::new not implemented! target={}, args={:?}",
-        //     target, args
-        // );
-        let mut command = Command::new(target);
-        command.args(args);
-        unsafe{
-            command.pre_exec(child_traceme);
-        }
-        let child = command.spawn().ok()?;
-        let mut inferior = Inferior{child};
-        for (breakpoint, _) in breakpoints {
-            inferior.install_breakpoints(*breakpoint);
-        }
-        Some(inferior)
+    /// Attempts to start a new inferior process, stopped at its first instruction so that
+    /// breakpoints can be installed. Its standard streams go where `io` says.
+    pub fn new(target: &str, args: &[String], io: ChildIo) -> Result<Inferior, DeetError> {
+        // The child may only make async-signal-safe calls between fork and exec, so everything
+        // it needs is allocated up front
+        let nul_error = |_| DeetError::Parse(String::from("Arguments cannot contain NUL bytes"));
+        let path = CString::new(target).map_err(nul_error)?;
+        let argv = std::iter::once(target)
+            .chain(args.iter().map(String::as_str))
+            .map(CString::new)
+            .collect::<Result<Vec<CString>, _>>()
+            .map_err(nul_error)?;
+        let argv: Vec<&CStr> = argv.iter().map(CString::as_c_str).collect();
+        // The child holds off exec'ing until we close our end of this pipe, so that it is traced
+        // by the time it does
+        let (go_read, go_write) = unistd::pipe()?;
+        let pid = match unistd::fork()? {
+            ForkResult::Child => {
+                let _ = unistd::close(go_write);
+                match io {
+                    ChildIo::Inherit => {}
+                    ChildIo::Capture(stdout, stderr) => {
+                        let _ = unistd::dup2(stdout, libc::STDOUT_FILENO);
+                        let _ = unistd::dup2(stderr, libc::STDERR_FILENO);
+                    }
+                    ChildIo::Terminal(tty) => {
+                        // A session of its own, so that the pty can be its controlling terminal
+                        let _ = unistd::setsid();
+                        unsafe { libc::ioctl(tty, libc::TIOCSCTTY, 0) };
+                        for fd in &[libc::STDIN_FILENO, libc::STDOUT_FILENO, libc::STDERR_FILENO] {
+                            let _ = unistd::dup2(tty, *fd);
+                        }
+                    }
+                }
+                let _ = unistd::read(go_read, &mut [0]);
+                let _ = unistd::execvp(&path, &argv);
+                unsafe { libc::_exit(127) }
+            }
+            ForkResult::Parent { child } => child,
+        };
+        unistd::close(go_read)?;
+        let mut backend = Box::new(Ptrace::new(pid));
+        // Children and threads are traced from birth, so the debugger gets to decide what happens
+        // to them. Execs are reported as events rather than a plain SIGTRAP that looks like a
+        // breakpoint.
+        let options = ptrace::Options::PTRACE_O_TRACEFORK
+            | ptrace::Options::PTRACE_O_TRACECLONE
+            | ptrace::Options::PTRACE_O_TRACEEXEC;
+        if let Err(err) = backend.seize(options) {
+            let _ = backend.kill();
+            let _ = unistd::close(go_write);
+            let _ = backend.wait(pid, None);
+            return Err(err);
+        }
+        unistd::close(go_write)?;
+        // The exec event leaves the child stopped at the first instruction of the target, where
+        // breakpoints can be patched in before anything runs
+        match backend.wait(pid, None)? {
+            WaitStatus::PtraceEvent(_, _, event)
+                if event == ptrace::Event::PTRACE_EVENT_EXEC as i32 => {}
+            WaitStatus::Exited(_, 127) => {
+                let message = format!("Could not execute {}", target);
+                return Err(std::io::Error::new(std::io::ErrorKind::NotFound, message).into());
+            }
+            _ => return Err(DeetError::Ptrace(nix::Error::UnsupportedOperation)),
+        }
+        Inferior::with_backend(pid, backend)
+    }
+
+    /// Takes control of a child that a traced inferior forked. The kernel seizes it
+    /// automatically (with the parent's options, so its threads are traced too); this waits for
+    /// the PTRACE_EVENT_STOP it starts out in.
+    pub fn attach_forked(pid: Pid) -> Result<Inferior, DeetError> {
+        let mut backend = Box::new(Ptrace::new(pid));
+        match backend.wait(pid, None)? {
+            WaitStatus::PtraceEvent(_, _, PTRACE_EVENT_STOP) => {}
+            _ => return Err(DeetError::Ptrace(nix::Error::UnsupportedOperation)),
+        }
+        Inferior::with_backend(pid, backend)
+    }
+
+    /// Wraps a process that is already stopped and under `backend`'s control.
+    pub fn with_backend(
+        pid: Pid,
+        backend: Box<dyn InferiorBackend>,
+    ) -> Result<Inferior, DeetError> {
+        let arch = arch::for_machine(backend.machine()?)?;
+        Ok(Inferior {
+            pid,
+            arch,
+            backend,
+            threads: vec![Thread { number: 1, tid: pid }],
+            next_thread_number: 2,
+            thread: pid,
+            stepping: false,
+            pending: Vec::new(),
+            interrupts_due: Vec::new(),
+            events: Vec::new(),
+        })
     }
 
     /// Returns the pid of this inferior.
     pub fn pid(&self) -> Pid {
-        nix::unistd::Pid::from_raw(self.child.id() as i32)
+        self.pid
+    }
+
+    pub fn arch(&self) -> &dyn Arch {
+        self.arch.as_ref()
+    }
+
+    /// Returns the size in bytes of the inferior's pointers.
+    pub fn word_size(&self) -> usize {
+        self.arch.word_size()
+    }
+
+    /// Looks up the architecture again, after the inferior has exec'd a program that may be built
+    /// for another one.
+    pub fn refresh_arch(&mut self) -> Result<(), DeetError> {
+        self.arch = arch::for_machine(self.backend.machine()?)?;
+        Ok(())
+    }
+
+    /// The inferior's threads, in the order they started.
+    pub fn threads(&self) -> &[Thread] {
+        &self.threads
+    }
+
+    /// The thread registers are read from and single steps apply to.
+    pub fn current_thread(&self) -> Thread {
+        let thread = self.threads.iter().find(|thread| thread.tid == self.thread);
+        // The main thread goes last, when the whole process exits
+        thread.copied().unwrap_or(self.threads[0])
+    }
+
+    /// Makes thread `number` the current one.
+    pub fn select_thread(&mut self, number: usize) -> Result<Thread, DeetError> {
+        let thread = self
+            .threads
+            .iter()
+            .find(|thread| thread.number == number)
+            .copied()
+            .ok_or_else(|| DeetError::Parse(format!("Thread ID {} not known.", number)))?;
+        self.thread = thread.tid;
+        Ok(thread)
+    }
+
+    /// Returns the threads that have started and exited since the last call, in order.
+    pub fn take_thread_events(&mut self) -> Vec<ThreadEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    /// Waits for one of the inferior's threads to stop and returns how it did. That thread
+    /// becomes the current one.
+    ///
+    /// A seized tracee stops in one of three ways: a signal-delivery-stop (a plain
+    /// `WaitStatus::Stopped`, which includes breakpoint traps), a group-stop or
+    /// PTRACE_INTERRUPT stop (PTRACE_EVENT_STOP), or another ptrace event (fork, clone, exec).
+    fn wait(&mut self) -> Result<Status, DeetError> {
+        match self.wait_thread(None)? {
+            Some(status) => self.decode(status),
+            None => Err(DeetError::Parse(String::from("The program did not stop."))),
+        }
+    }
+
+    /// Waits for a thread to stop in a way the debugger needs to hear about, makes it the current
+    /// thread and stops the others too, so that nothing runs while the user looks around.
+    /// Threads starting and exiting along the way are taken care of here. With WNOHANG, returns
+    /// None if nothing worth reporting has happened yet.
+    fn wait_thread(
+        &mut self,
+        options: Option<WaitPidFlag>,
+    ) -> Result<Option<WaitStatus>, DeetError> {
+        loop {
+            let (tid, status) = match self.poll(options)? {
+                Some(change) => change,
+                None => return Ok(None),
+            };
+            match status {
+                // The main thread only reports exiting once every other thread has, when the
+                // whole process is gone
+                WaitStatus::Exited(_, code) if tid != self.pid => self.thread_exited(tid, code)?,
+                WaitStatus::Signaled(_, signal, _) if tid != self.pid => {
+                    self.thread_exited(tid, 128 + signal as i32)?
+                }
+                WaitStatus::Exited(..) | WaitStatus::Signaled(..) => return Ok(Some(status)),
+                WaitStatus::PtraceEvent(_, _, event)
+                    if event == ptrace::Event::PTRACE_EVENT_CLONE as i32 =>
+                {
+                    let new_thread = self.thread_started(tid)?;
+                    self.resume_thread(new_thread)?;
+                    self.resume_thread(tid)?;
+                }
+                WaitStatus::PtraceEvent(_, _, PTRACE_EVENT_STOP)
+                    if self.interrupts_due.contains(&tid) =>
+                {
+                    self.interrupts_due.retain(|due| *due != tid);
+                    self.resume_thread(tid)?;
+                }
+                WaitStatus::PtraceEvent(_, _, event)
+                    if event == ptrace::Event::PTRACE_EVENT_EXEC as i32 =>
+                {
+                    // execve has done away with every other thread, and whichever one called it
+                    // now goes by the pid
+                    let pid = self.pid;
+                    self.threads.retain(|thread| thread.tid == pid);
+                    self.thread = pid;
+                    self.pending.clear();
+                    self.interrupts_due.clear();
+                    return Ok(Some(status));
+                }
+                status => {
+                    self.thread = tid;
+                    self.stop_others()?;
+                    return Ok(Some(status));
+                }
+            }
+        }
+    }
+
+    /// Waits for the next change of state of one of the threads that are running: the current
+    /// one while it single steps, and all of them otherwise. waitpid waits for one thread or for
+    /// every child (other inferiors included), so several threads are checked on in turns.
+    fn poll(
+        &mut self,
+        options: Option<WaitPidFlag>,
+    ) -> Result<Option<(Pid, WaitStatus)>, DeetError> {
+        let running: Vec<Pid> = if self.stepping {
+            vec![self.thread]
+        } else {
+            self.threads.iter().map(|thread| thread.tid).collect()
+        };
+        if let [tid] = running[..] {
+            return Ok(match self.backend.wait(tid, options)? {
+                WaitStatus::StillAlive => None,
+                status => Some((tid, status)),
+            });
+        }
+        let nohang = options.unwrap_or_else(WaitPidFlag::empty) | WaitPidFlag::WNOHANG;
+        loop {
+            for tid in &running {
+                match self.backend.wait(*tid, Some(nohang))? {
+                    WaitStatus::StillAlive => {}
+                    status => return Ok(Some((*tid, status))),
+                }
+            }
+            if options.is_some_and(|options| options.contains(WaitPidFlag::WNOHANG)) {
+                return Ok(None);
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    /// Takes on the thread that thread `parent` has just started, which the kernel traces from
+    /// birth, and returns its tid. It is left in the PTRACE_EVENT_STOP it starts out in.
+    fn thread_started(&mut self, parent: Pid) -> Result<Pid, DeetError> {
+        let tid = Pid::from_raw(self.backend.event_message(parent)? as i32);
+        self.backend.wait(tid, None)?;
+        let thread = Thread { number: self.next_thread_number, tid };
+        self.next_thread_number += 1;
+        self.threads.push(thread);
+        self.events.push(ThreadEvent::Started(thread));
+        Ok(tid)
     }
 
-    /// Calls waitpid on this inferior and returns a Status to indicate the state of the process
-    /// after the waitpid call.
-    pub fn wait(&self, options: Option<WaitPidFlag>) -> Result<Status, nix::Error> {
-        Ok(match waitpid(self.pid(), options)? {
+    /// Forgets thread `tid`, which has exited with `code`. If it was the current thread, the
+    /// main thread takes over, and since a single step it was taking can never finish now, every
+    /// thread runs instead.
+    fn thread_exited(&mut self, tid: Pid, code: i32) -> Result<(), DeetError> {
+        if let Some(i) = self.threads.iter().position(|thread| thread.tid == tid) {
+            let thread = self.threads.remove(i);
+            self.events.push(ThreadEvent::Exited(thread, code));
+        }
+        self.pending.retain(|(pending, _)| *pending != tid);
+        self.interrupts_due.retain(|due| *due != tid);
+        if tid == self.thread {
+            self.thread = self.pid;
+            if self.stepping {
+                self.cont_all()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Stops every thread but the current one, which has just stopped, unless they were stopped
+    /// already while it single stepped. A thread that runs into a breakpoint meanwhile is put
+    /// back in front of it, to run into it again later; one that something else stops keeps
+    /// that stop pending.
+    fn stop_others(&mut self) -> Result<(), DeetError> {
+        if self.stepping {
+            return Ok(());
+        }
+        let mut others = Vec::new();
+        for thread in &self.threads {
+            // A main thread that has exited while the others run on can be neither stopped nor
+            // waited for
+            if thread.tid != self.thread && self.backend.interrupt(thread.tid).is_ok() {
+                others.push(thread.tid);
+            }
+        }
+        for tid in others {
+            loop {
+                match self.backend.wait(tid, None)? {
+                    WaitStatus::PtraceEvent(_, _, PTRACE_EVENT_STOP) => break,
+                    WaitStatus::PtraceEvent(_, _, event)
+                        if event == ptrace::Event::PTRACE_EVENT_CLONE as i32 =>
+                    {
+                        // The new thread stays in the stop it starts out in
+                        self.thread_started(tid)?;
+                        self.backend.cont(tid)?;
+                    }
+                    WaitStatus::Exited(_, code) if tid != self.pid => {
+                        self.thread_exited(tid, code)?;
+                        break;
+                    }
+                    WaitStatus::Signaled(_, signal, _) if tid != self.pid => {
+                        self.thread_exited(tid, 128 + signal as i32)?;
+                        break;
+                    }
+                    WaitStatus::Stopped(_, signal::Signal::SIGTRAP) if self.rewind_trap(tid)? => {
+                        self.interrupts_due.push(tid);
+                        break;
+                    }
+                    status => {
+                        self.pending.push((tid, status));
+                        self.interrupts_due.push(tid);
+                        break;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Moves thread `tid`, which has stopped with a SIGTRAP, back onto the breakpoint instruction
+    /// it has just executed, if that is what trapped. Returns whether it did.
+    fn rewind_trap(&mut self, tid: Pid) -> Result<bool, DeetError> {
+        let mut regs = self.backend.registers(tid)?;
+        let addr = self.arch.pc(&regs) - self.arch.breakpoint_pc_offset();
+        let breakpoint = self.arch.breakpoint();
+        let trapped =
+            self.read_bytes(addr, breakpoint.len()).is_ok_and(|bytes| bytes == breakpoint);
+        if trapped {
+            self.arch.set_pc(&mut regs, addr);
+            self.backend.set_registers(tid, regs)?;
+        }
+        Ok(trapped)
+    }
+
+    /// Lets thread `tid`, which has stopped for something not worth reporting, carry on the way
+    /// the inferior was last resumed.
+    fn resume_thread(&mut self, tid: Pid) -> Result<(), DeetError> {
+        if !self.stepping {
+            self.backend.cont(tid)
+        } else if tid == self.thread {
+            self.backend.step(tid)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Has the current thread execute a single instruction, with the others left stopped.
+    fn step_thread(&mut self) -> Result<(), DeetError> {
+        self.stepping = true;
+        self.backend.step(self.thread)
+    }
+
+    /// Lets every thread run.
+    fn cont_all(&mut self) -> Result<(), DeetError> {
+        self.stepping = false;
+        for thread in &self.threads {
+            self.backend.cont(thread.tid)?;
+        }
+        Ok(())
+    }
+
+    fn decode(&self, status: WaitStatus) -> Result<Status, DeetError> {
+        Ok(match status {
             WaitStatus::Exited(_pid, exit_code) => Status::Exited(exit_code),
             WaitStatus::Signaled(_pid, signal, _core_dumped) => Status::Signaled(signal),
-            WaitStatus::Stopped(_pid, signal) => {
-                let regs = ptrace::getregs(self.pid())?;
-                Status::Stopped(signal, regs.rip as usize)
+            WaitStatus::Stopped(_pid, signal) => Status::Stopped(signal, self.get_pc()?),
+            WaitStatus::PtraceEvent(_pid, signal, PTRACE_EVENT_STOP) => {
+                Status::GroupStopped(signal, self.get_pc()?)
+            }
+            WaitStatus::PtraceEvent(_pid, _signal, event)
+                if event == ptrace::Event::PTRACE_EVENT_FORK as i32 =>
+            {
+                let child = Pid::from_raw(self.backend.event_message(self.thread)? as i32);
+                Status::Forked(child, self.get_pc()?)
+            }
+            WaitStatus::PtraceEvent(_pid, _signal, event)
+                if event == ptrace::Event::PTRACE_EVENT_EXEC as i32 =>
+            {
+                Status::Execed(self.get_pc()?)
+            }
+            status => {
+                return Err(DeetError::Parse(format!("Unexpected wait status: {:?}", status)))
             }
-            other => panic!("waitpid returned unexpected status: {:?}", other),
         })
     }
 
-    pub fn continue_run(&self, signal: Option<signal::Signal>) -> Result<Status, nix::Error> {
-        ptrace::cont(self.pid(), None)?;
-        self.wait(None)
+    /// Lets the inferior proceed, either until the next stop or for a single instruction.
+    /// Continuing runs every thread; a single step only moves the current one.
+    ///
+    /// If the inferior is parked on one of `breakpoints`, the original instruction is executed
+    /// first with the breakpoint temporarily removed, and the breakpoint is re-armed afterwards.
+    /// When a breakpoint is hit, the program counter is rewound onto the breakpoint address (on
+    /// architectures that leave it past the trap), so the reported address (and the next call to
+    /// `resume`) always refer to the instruction that has yet to run.
+    pub fn resume(
+        &mut self,
+        mode: Resume,
+        breakpoints: &HashMap<usize, BreakPoint>,
+    ) -> Result<Status, DeetError> {
+        if let Some(status) = self.start(mode, breakpoints)? {
+            return Ok(status);
+        }
+        let status = self.wait()?;
+        self.rewind_breakpoint(status, breakpoints)
     }
 
-    pub fn kill(&mut self) {  
-        match self.child.kill().ok() {
-            Some(_) => {
-                println!("Killing running inferior (pid {})", self.pid());
-                self.wait(None).unwrap();
-            },
-            None => {} 
+    /// Sets the inferior going the way `resume` does, without waiting for it to stop. Returns
+    /// the status it has stopped with already (after a single step, if something stopped it
+    /// while it stepped off a breakpoint, or if another thread has a stop pending that continuing
+    /// would have reported), or None if it is running.
+    pub fn start(
+        &mut self,
+        mode: Resume,
+        breakpoints: &HashMap<usize, BreakPoint>,
+    ) -> Result<Option<Status>, DeetError> {
+        if mode == Resume::Continue && !self.pending.is_empty() {
+            let (tid, status) = self.pending.remove(0);
+            self.thread = tid;
+            let status = self.decode(status)?;
+            return Ok(Some(self.rewind_breakpoint(status, breakpoints)?));
+        }
+        let rip = self.get_pc()?;
+        if let Some(breakpoint) = breakpoints.get(&rip) {
+            self.write_bytes(rip, breakpoint.orig_bytes(self.pid))?;
+            self.step_thread()?;
+            let status = self.wait()?;
+            match status {
+                Status::Stopped(signal::Signal::SIGTRAP, _) => {
+                    self.install_breakpoints(rip)?;
+                    if mode == Resume::Step {
+                        return Ok(Some(status));
+                    }
+                }
+                Status::Stopped(_, _) | Status::GroupStopped(_, _) | Status::Forked(_, _) => {
+                    self.install_breakpoints(rip)?;
+                    return Ok(Some(status));
+                }
+                _ => return Ok(Some(status)),
+            }
+        } else if mode == Resume::Step {
+            self.step_thread()?;
+            return Ok(Some(self.wait()?));
         }
+        self.cont_all()?;
+        Ok(None)
     }
 
-    pub fn print_backtrace(&self, debug_data: &DwarfData) -> Result<(), nix::Error> {
-        match ptrace::getregs(self.pid()) {
-            Ok(regs) => {
-                // Ok(println!("%rip register: {:#x}", regs.rip))
-                let mut instruction_ptr = regs.rip as usize;
-                let mut base_ptr = regs.rbp as usize;
-                loop {
-                    let line = DwarfData::get_line_from_addr(debug_data, instruction_ptr).unwrap();
-                    let function =  DwarfData::get_function_from_addr(debug_data, instruction_ptr).unwrap();
-                    println!("{} ({})", function, line);
-                    if function == "main" {
-                        break;
-                    }
-                    instruction_ptr = ptrace::read(self.pid(), (base_ptr + 8) as ptrace::AddressType)? as usize;
-                    base_ptr = ptrace::read(self.pid(), base_ptr as ptrace::AddressType)? as usize;
+    /// Waits for an inferior set going with `start` to stop, and returns how it did. With
+    /// WNOHANG, returns None straight away if it is still running.
+    pub fn collect(
+        &mut self,
+        options: Option<WaitPidFlag>,
+        breakpoints: &HashMap<usize, BreakPoint>,
+    ) -> Result<Option<Status>, DeetError> {
+        let status = match self.wait_thread(options)? {
+            Some(status) => self.decode(status)?,
+            None => return Ok(None),
+        };
+        Ok(Some(self.rewind_breakpoint(status, breakpoints)?))
+    }
+
+    /// Moves the program counter back onto the breakpoint the inferior has stopped at, if that is
+    /// what `status` is.
+    fn rewind_breakpoint(
+        &mut self,
+        status: Status,
+        breakpoints: &HashMap<usize, BreakPoint>,
+    ) -> Result<Status, DeetError> {
+        let offset = self.arch.breakpoint_pc_offset();
+        match status {
+            Status::Stopped(signal::Signal::SIGTRAP, addr)
+                if breakpoints.contains_key(&(addr - offset)) =>
+            {
+                self.set_pc(addr - offset)?;
+                Ok(Status::Stopped(signal::Signal::SIGTRAP, addr - offset))
+            }
+            status => Ok(status),
+        }
+    }
+
+    /// Stops the current thread while it runs, and with it the others. The stop is reported by
+    /// `collect` as a `GroupStopped` with SIGTRAP.
+    pub fn interrupt(&mut self) -> Result<(), DeetError> {
+        self.backend.interrupt(self.thread)
+    }
+
+    pub fn kill(&mut self) -> Result<(), DeetError> {
+        output::info(format!("Killing running inferior (pid {})", self.pid()));
+        self.backend.kill()?;
+        // Every thread reports its death, the main thread last
+        let mut tids: Vec<Pid> = self.threads.iter().map(|thread| thread.tid).collect();
+        tids.rotate_left(1);
+        for tid in tids {
+            loop {
+                match self.backend.wait(tid, None)? {
+                    WaitStatus::Exited(..) | WaitStatus::Signaled(..) => break,
+                    _ => {}
                 }
-                Ok(())
-            },
-            Err(err) => {
-                Err(err)
             }
         }
+        Ok(())
+    }
+
+    /// Takes `breakpoints` back out and lets the inferior run on its own.
+    pub fn detach(mut self, breakpoints: &HashMap<usize, BreakPoint>) -> Result<(), DeetError> {
+        for (addr, breakpoint) in breakpoints {
+            self.write_bytes(*addr, breakpoint.orig_bytes(self.pid))?;
+        }
+        for thread in &self.threads {
+            self.backend.detach(thread.tid)?;
+        }
+        Ok(())
     }
 
-    pub fn install_breakpoints(&mut self, breakpoint: usize) -> Result<u8, nix::Error> {
-        self.write_byte(breakpoint, 0xcc)
+    /// Writes a breakpoint instruction at `breakpoint`, returning the bytes it replaced.
+    pub fn install_breakpoints(&mut self, breakpoint: usize) -> Result<Vec<u8>, DeetError> {
+        self.write_bytes(breakpoint, self.arch.breakpoint())
     }
 
-    pub fn get_previous_ins(&self) -> Result<usize, nix::Error> {
-        let regs = ptrace::getregs(self.pid()).unwrap();
-        Ok(regs.rip as usize - 1)
+    /// The current thread's registers.
+    pub fn registers(&self) -> Result<Registers, DeetError> {
+        self.backend.registers(self.thread)
     }
 
-    pub fn write_byte(&mut self, addr: usize, val: u8) -> Result<u8, nix::Error> {
+    pub fn set_registers(&mut self, regs: Registers) -> Result<(), DeetError> {
+        self.backend.set_registers(self.thread, regs)
+    }
+
+    /// The current thread's floating point and vector registers, by name.
+    pub fn fp_registers(&self) -> Result<Vec<(String, FpValue)>, DeetError> {
+        Ok(self.arch.fp_registers(&self.backend.fp_registers(self.thread)?))
+    }
+
+    pub fn get_pc(&self) -> Result<usize, DeetError> {
+        Ok(self.arch.pc(&self.registers()?))
+    }
+
+    pub fn set_pc(&mut self, pc: usize) -> Result<(), DeetError> {
+        let mut regs = self.registers()?;
+        self.arch.set_pc(&mut regs, pc);
+        self.set_registers(regs)
+    }
+
+    /// Reads the value in `slot`.
+    pub fn read_slot(&self, slot: Slot) -> Result<usize, DeetError> {
+        match slot {
+            Slot::Register(value) => Ok(value),
+            Slot::Stack(addr) => self.read_word(addr),
+        }
+    }
+
+    /// Reads one of the inferior's words (a pointer's worth of memory) at `addr`.
+    pub fn read_word(&self, addr: usize) -> Result<usize, DeetError> {
+        let word = self.peek(addr)?;
+        Ok(if self.word_size() == 4 { word & 0xffff_ffff } else { word })
+    }
+
+    /// Reads a ptrace word (8 bytes) at `addr`.
+    fn peek(&self, addr: usize) -> Result<usize, DeetError> {
+        self.backend.peek(addr)
+    }
+
+    /// Reads `len` bytes of the inferior's memory, exactly as they are (breakpoints included).
+    pub fn read_bytes(&self, addr: usize, len: usize) -> Result<Vec<u8>, DeetError> {
+        let mut bytes = Vec::with_capacity(len);
+        let mut word_addr = align_addr_to_word(addr);
+        while word_addr < addr + len {
+            let word = self.peek(word_addr)?;
+            bytes.extend_from_slice(&word.to_le_bytes());
+            word_addr += size_of::<usize>();
+        }
+        let start = addr - align_addr_to_word(addr);
+        Ok(bytes[start..start + len].to_vec())
+    }
+
+    /// Discards the current stack frame, as though the function had just returned, and
+    /// optionally sets its return value. `cfa` is the frame's canonical frame address, which is
+    /// where the stack pointer goes back to.
+    pub fn pop_frame(
+        &mut self,
+        setup: FrameSetup,
+        cfa: usize,
+        value: Option<u64>,
+    ) -> Result<(), DeetError> {
+        let mut regs = self.registers()?;
+        let arch = self.arch.as_ref();
+        let word = arch.word_size();
+        let (return_addr, caller_fp) = match setup {
+            FrameSetup::NotStarted => (
+                self.read_slot(arch.return_address_at_entry(&regs))?,
+                arch.frame_pointer(&regs),
+            ),
+            FrameSetup::RbpPushed => {
+                let sp = arch.sp(&regs);
+                (self.read_word(sp + word)?, self.read_word(sp)?)
+            }
+            FrameSetup::Complete => {
+                let fp = arch.frame_pointer(&regs);
+                (self.read_word(fp + word)?, self.read_word(fp)?)
+            }
+        };
+        arch.set_pc(&mut regs, return_addr);
+        arch.set_frame_pointer(&mut regs, caller_fp);
+        arch.set_sp(&mut regs, cfa);
+        if let Some(value) = value {
+            arch.set_return_value(&mut regs, value);
+        }
+        self.set_registers(regs)
+    }
+
+    /// Makes the inferior execute a system call, as if it had done so itself at the current
+    /// instruction, and returns the raw result (a negated errno on failure). Registers and code
+    /// are left as they were.
+    pub fn syscall(&mut self, number: i64, args: &[u64]) -> Result<i64, DeetError> {
+        let saved_regs = self.registers()?;
+        let pc = self.arch.pc(&saved_regs);
+        let saved_code = self.write_bytes(pc, self.arch.syscall_instruction())?;
+        let mut regs = saved_regs;
+        self.arch.prepare_syscall(&mut regs, number, args);
+        self.set_registers(regs)?;
+        self.step_thread()?;
+        let status = self.wait()?;
+        if let Status::Exited(_) | Status::Signaled(_) = status {
+            return Err(DeetError::Ptrace(nix::Error::Sys(nix::errno::Errno::ESRCH)));
+        }
+        let result = self.arch.syscall_result(&self.registers()?);
+        self.write_bytes(pc, &saved_code)?;
+        self.set_registers(saved_regs)?;
+        Ok(result)
+    }
+
+    /// Changes the protection of the inferior's pages in `[addr, addr + len)`.
+    pub fn mprotect(&mut self, addr: usize, len: usize, prot: i32) -> Result<(), DeetError> {
+        let number = self.arch.mprotect_number();
+        let result = self.syscall(number, &[addr as u64, len as u64, prot as u64])?;
+        if result < 0 {
+            let errno = nix::errno::Errno::from_i32(-result as i32);
+            return Err(DeetError::Ptrace(nix::Error::Sys(errno)));
+        }
+        Ok(())
+    }
+
+    /// Returns the inferior's memory mappings, according to /proc/<pid>/maps.
+    pub fn mappings(&self) -> Result<Vec<Mapping>, DeetError> {
+        let maps = self.backend.maps()?;
+        Ok(maps.lines().filter_map(Mapping::parse).collect())
+    }
+
+    /// Returns the name of thread `tid` of the inferior, as set with prctl(PR_SET_NAME) or
+    /// pthread_setname_np (and otherwise the program's name), or None if it has no such thread.
+    pub fn thread_name(&self, tid: Pid) -> Option<String> {
+        let path = format!("/proc/{}/task/{}/comm", self.pid, tid);
+        let name = std::fs::read_to_string(path).ok()?;
+        Some(name.trim_end_matches('\n').to_string())
+    }
+
+    /// Returns the shared objects the inferior has mapped, in address order. `program` is the
+    /// executable itself, which is left out.
+    pub fn shared_libraries(&self, program: &str) -> Result<Vec<SharedLibrary>, DeetError> {
+        let program = std::fs::canonicalize(program)
+            .map_or_else(|_| program.to_string(), |path| path.to_string_lossy().into_owned());
+        let mut libraries: Vec<SharedLibrary> = Vec::new();
+        for mapping in self.mappings()? {
+            if !mapping.path.starts_with('/') || mapping.path == program {
+                continue;
+            }
+            let index = match libraries.iter().position(|library| library.path == mapping.path) {
+                Some(index) => index,
+                None if mapping.offset == 0 => {
+                    let path = mapping.path.clone();
+                    libraries.push(SharedLibrary { path, base: mapping.start, text: None });
+                    libraries.len() - 1
+                }
+                // Not mapped the way the dynamic loader maps libraries
+                None => continue,
+            };
+            if mapping.perms.contains('x') {
+                let library = &mut libraries[index];
+                library.text = Some(match library.text {
+                    Some((start, end)) => (start.min(mapping.start), end.max(mapping.end)),
+                    None => (mapping.start, mapping.end),
+                });
+            }
+        }
+        Ok(libraries)
+    }
+
+    /// Returns the memory mapping containing `addr`.
+    pub fn mapping_containing(&self, addr: usize) -> Result<Option<Mapping>, DeetError> {
+        Ok(self
+            .mappings()?
+            .into_iter()
+            .find(|mapping| mapping.start <= addr && addr < mapping.end))
+    }
+
+    /// Returns the protection of the mapping containing `addr`.
+    pub fn protection_at(&self, addr: usize) -> Result<Option<i32>, DeetError> {
+        Ok(self
+            .mapping_containing(addr)?
+            .map(|mapping| watchpoint::parse_protection(&mapping.perms)))
+    }
+
+    /// Returns the address whose access caused the SIGSEGV the current thread is stopped with.
+    pub fn fault_address(&self) -> Result<usize, DeetError> {
+        self.backend.fault_address(self.thread)
+    }
+
+    /// Writes one of the inferior's words at `addr`, leaving the memory after it alone.
+    pub fn write_word(&mut self, addr: usize, val: usize) -> Result<(), DeetError> {
+        let val = if self.word_size() == 4 {
+            (self.peek(addr)? & !0xffff_ffff) | (val & 0xffff_ffff)
+        } else {
+            val
+        };
+        self.backend.poke(addr, val)
+    }
+
+    /// Calls the function at `func_addr` with integer `args`, following the platform's calling
+    /// convention, and puts every register back afterwards.
+    ///
+    /// The function returns to `trampoline`, which is temporarily patched with a breakpoint so
+    /// that we get control back. It must be an address the function cannot reach on its own, such
+    /// as the program's entry point.
+    pub fn call_function(
+        &mut self,
+        func_addr: usize,
+        args: &[u64],
+        trampoline: usize,
+        breakpoints: &HashMap<usize, BreakPoint>,
+    ) -> Result<CallResult, DeetError> {
+        let saved_regs = self.registers()?;
+        let mut regs = saved_regs;
+        for (addr, value) in self.arch.prepare_call(&mut regs, func_addr, args, trampoline)? {
+            self.write_word(addr, value)?;
+        }
+        self.set_registers(regs)?;
+
+        let orig_bytes = self.install_breakpoints(trampoline)?;
+        let status = self.resume(Resume::Continue, breakpoints)?;
+        // After an exec, there is nothing left to restore
+        if let Status::Exited(_) | Status::Signaled(_) | Status::Execed(_) = status {
+            return Ok(CallResult::Interrupted(status));
+        }
+        self.write_bytes(trampoline, &orig_bytes)?;
+        let result = self.arch.return_value(&self.registers()?);
+        self.set_registers(saved_regs)?;
+        let returned = trampoline + self.arch.breakpoint_pc_offset();
+        Ok(match status {
+            Status::Stopped(signal::Signal::SIGTRAP, pc) if pc == returned => {
+                CallResult::Returned(result)
+            }
+            status => CallResult::Interrupted(status),
+        })
+    }
+
+    /// Overwrites the memory at `addr` with `bytes`, returning what was there before.
+    pub fn write_bytes(&mut self, addr: usize, bytes: &[u8]) -> Result<Vec<u8>, DeetError> {
+        bytes
+            .iter()
+            .enumerate()
+            .map(|(i, byte)| self.write_byte(addr + i, *byte))
+            .collect()
+    }
+
+    pub fn write_byte(&mut self, addr: usize, val: u8) -> Result<u8, DeetError> {
         let aligned_addr = align_addr_to_word(addr);
         let byte_offset = addr - aligned_addr;
-        let word = ptrace::read(self.pid(), aligned_addr as ptrace::AddressType)? as u64;
+        let word = self.peek(aligned_addr)? as u64;
         let orig_byte = (word >> 8 * byte_offset) & 0xff;
         let masked_word = word & !(0xff << 8 * byte_offset);
         let updated_word = masked_word | ((val as u64) << 8 * byte_offset);
-        ptrace::write(
-            self.pid(),
-            aligned_addr as ptrace::AddressType,
-            updated_word as *mut std::ffi::c_void,
-        )?;
+        self.backend.poke(aligned_addr, updated_word as usize)?;
         Ok(orig_byte as u8)
     }
-
-    pub fn step_breakpoint(&mut self, rip: usize, orin_byte: u8) -> bool {
-        // restore instruction
-        self.write_byte(rip, orin_byte).unwrap();
-        // rewind rip to the stopped instruction
-        let mut regs = ptrace::getregs(self.pid()).unwrap();
-        regs.rip = rip as u64;
-        ptrace::setregs(self.pid(), regs).unwrap();
-        // step one the original instruction
-        ptrace::step(self.pid(), None).unwrap();
-        // restore the breakpoint and return to resume the normal execution
-        match self.wait(None).unwrap() {
-            Status::Stopped(s, _) if s == signal::Signal::SIGTRAP => {
-                self.install_breakpoints(rip).unwrap();
-                true
-            }
-            _ => false,
-        }
-    }
 }
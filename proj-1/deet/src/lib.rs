@@ -0,0 +1,26 @@
+//! deet, the debugger. `Debugger` holds a session and executes command lines the way they are
+//! typed at its prompt; `Inferior` is a single traced process, for tools that want to drive one
+//! directly.
+
+mod arch;
+mod backend;
+mod background;
+mod btrace;
+mod capture;
+pub mod debugger;
+pub mod debugger_command;
+mod disassemble;
+pub mod error;
+mod format;
+mod heap;
+pub mod inferior;
+mod location;
+pub mod output;
+mod record;
+mod script;
+mod settings;
+mod dwarf_data;
+mod gimli_wrapper;
+mod symbol_table;
+mod terminal;
+mod watchpoint;
@@ -0,0 +1,227 @@
+//! Evaluation of DWARF location descriptions: the little stack programs (`DW_OP_*`) that say
+//! where a variable lives when that is not simply a fixed address or frame offset. Optimized code
+//! keeps variables in registers, splits them into pieces, and moves them around as the function
+//! runs (which location lists describe, one expression per range of program counters).
+
+use crate::error::DeetError;
+use gimli::{EvaluationResult, Piece};
+use std::iter;
+
+/// One expression of a location description, with the range of program counters it applies to
+/// (None for everywhere).
+pub type LocationEntry = (Option<(usize, usize)>, Vec<u8>);
+
+/// A location that has to be worked out against the stopped program.
+#[derive(Clone)]
+pub struct LocationExpression {
+    encoding: gimli::Encoding,
+    endian: gimli::RunTimeEndian,
+    entries: Vec<LocationEntry>,
+}
+
+/// Where a variable's value was found.
+#[derive(Debug, PartialEq)]
+pub enum Place {
+    /// In memory, at this address.
+    Memory(usize),
+    /// Somewhere with no address (registers, or pieces of several places); this is the value.
+    Value(Vec<u8>),
+}
+
+/// What evaluating a location needs to know about the stopped program, as seen from one frame.
+pub trait Machine {
+    /// The value of the register that DWARF numbers `number`.
+    fn register(&self, number: u16) -> Result<u64, DeetError>;
+
+    /// The frame's canonical frame address.
+    fn cfa(&self) -> usize;
+
+    /// What `DW_OP_fbreg` offsets are from in this frame.
+    fn frame_base(&self) -> Result<usize, DeetError>;
+
+    fn read_memory(&self, addr: usize, len: usize) -> Result<Vec<u8>, DeetError>;
+}
+
+impl LocationExpression {
+    pub fn new(
+        encoding: gimli::Encoding,
+        endian: gimli::RunTimeEndian,
+        entries: Vec<LocationEntry>,
+    ) -> LocationExpression {
+        LocationExpression { encoding, endian, entries }
+    }
+
+    /// Whether this is a location list, rather than a single expression.
+    pub fn is_list(&self) -> bool {
+        self.entries.iter().any(|(range, _)| range.is_some())
+    }
+
+    /// Works out where the `size`-byte variable is while the frame is executing the instruction
+    /// at `pc`.
+    pub fn evaluate(
+        &self,
+        pc: usize,
+        size: usize,
+        machine: &dyn Machine,
+    ) -> Result<Place, DeetError> {
+        let (_, bytes) = self
+            .entries
+            .iter()
+            .find(|(range, _)| range.is_none_or(|(low, high)| low <= pc && pc < high))
+            .ok_or_else(optimized_out)?;
+        let expression = gimli::Expression(gimli::EndianSlice::new(bytes, self.endian));
+        let mut evaluation = expression.evaluation(self.encoding);
+        let mut result = evaluation.evaluate().map_err(unsupported)?;
+        loop {
+            result = match result {
+                EvaluationResult::Complete => break,
+                EvaluationResult::RequiresMemory { address, size, .. } => {
+                    let bytes = machine.read_memory(address as usize, size as usize)?;
+                    let value = gimli::Value::Generic(word_from_bytes(&bytes));
+                    evaluation.resume_with_memory(value)
+                }
+                EvaluationResult::RequiresRegister { register, .. } => {
+                    let value = gimli::Value::Generic(machine.register(register.0)?);
+                    evaluation.resume_with_register(value)
+                }
+                EvaluationResult::RequiresFrameBase => {
+                    evaluation.resume_with_frame_base(machine.frame_base()? as u64)
+                }
+                EvaluationResult::RequiresCallFrameCfa => {
+                    evaluation.resume_with_call_frame_cfa(machine.cfa() as u64)
+                }
+                EvaluationResult::RequiresRelocatedAddress(address) => {
+                    evaluation.resume_with_relocated_address(address)
+                }
+                _ => return Err(DeetError::Parse(String::from("Unsupported location expression"))),
+            }
+            .map_err(unsupported)?;
+        }
+        assemble(evaluation.result(), size, machine)
+    }
+}
+
+/// Puts together the value described by `pieces`. A whole variable in memory is left where it is.
+fn assemble<R: gimli::Reader>(
+    pieces: Vec<Piece<R>>,
+    size: usize,
+    machine: &dyn Machine,
+) -> Result<Place, DeetError> {
+    if let [Piece { size_in_bits: None, location: gimli::Location::Address { address }, .. }] =
+        pieces[..]
+    {
+        return Ok(Place::Memory(address as usize));
+    }
+    let mut value = Vec::new();
+    for piece in pieces {
+        if piece.bit_offset.is_some_and(|offset| offset != 0)
+            || piece.size_in_bits.is_some_and(|bits| bits % 8 != 0)
+        {
+            return Err(DeetError::Parse(String::from("Bit fields are not supported")));
+        }
+        let len = match piece.size_in_bits {
+            Some(bits) => bits as usize / 8,
+            None => size.saturating_sub(value.len()),
+        };
+        let bytes = match piece.location {
+            gimli::Location::Empty => return Err(optimized_out()),
+            gimli::Location::Register { register } => {
+                machine.register(register.0)?.to_le_bytes().to_vec()
+            }
+            gimli::Location::Address { address } => machine.read_memory(address as usize, len)?,
+            gimli::Location::Value { value } => {
+                value.to_u64(!0).map_err(unsupported)?.to_le_bytes().to_vec()
+            }
+            gimli::Location::Bytes { value } => value.to_slice().map_err(unsupported)?.to_vec(),
+            gimli::Location::ImplicitPointer { .. } => {
+                return Err(DeetError::Parse(String::from("Implicit pointers are not supported")))
+            }
+        };
+        value.extend(bytes.into_iter().chain(iter::repeat(0)).take(len));
+    }
+    Ok(Place::Value(value))
+}
+
+/// Reads a little-endian value of up to 8 bytes.
+pub fn word_from_bytes(bytes: &[u8]) -> u64 {
+    let mut word = [0u8; 8];
+    let len = bytes.len().min(word.len());
+    word[..len].copy_from_slice(&bytes[..len]);
+    u64::from_le_bytes(word)
+}
+
+fn optimized_out() -> DeetError {
+    DeetError::Parse(String::from("value has been optimized out"))
+}
+
+fn unsupported(err: gimli::Error) -> DeetError {
+    DeetError::Parse(format!("Could not evaluate location expression: {}", err))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashMap;
+
+    struct TestMachine {
+        registers: HashMap<u16, u64>,
+        memory: HashMap<usize, u8>,
+    }
+
+    impl Machine for TestMachine {
+        fn register(&self, number: u16) -> Result<u64, DeetError> {
+            self.registers
+                .get(&number)
+                .copied()
+                .ok_or_else(|| DeetError::Parse(format!("No register {}", number)))
+        }
+
+        fn cfa(&self) -> usize {
+            0x7000
+        }
+
+        fn frame_base(&self) -> Result<usize, DeetError> {
+            Ok(self.cfa())
+        }
+
+        fn read_memory(&self, addr: usize, len: usize) -> Result<Vec<u8>, DeetError> {
+            Ok((addr..addr + len).map(|a| self.memory.get(&a).copied().unwrap_or(0)).collect())
+        }
+    }
+
+    fn expression(entries: Vec<LocationEntry>) -> LocationExpression {
+        let encoding =
+            gimli::Encoding { format: gimli::Format::Dwarf32, version: 4, address_size: 8 };
+        LocationExpression::new(encoding, gimli::RunTimeEndian::Little, entries)
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let mut machine = TestMachine { registers: HashMap::new(), memory: HashMap::new() };
+        machine.registers.insert(3, 0x1122_3344_5566_7788);
+        machine.registers.insert(6, 0x6000);
+        machine.memory.insert(0x5ff0, 0x20);
+
+        let (reg3, fbreg, breg6, deref) = (0x53, 0x91, 0x76, 0x06);
+        let (piece, stack_value, lit5) = (0x93, 0x9f, 0x35);
+        // DW_OP_reg3
+        let place = expression(vec![(None, vec![reg3])]).evaluate(0, 4, &machine).unwrap();
+        assert_eq!(place, Place::Value(vec![0x88, 0x77, 0x66, 0x55]));
+        // DW_OP_fbreg -16
+        let place = expression(vec![(None, vec![fbreg, 0x70])]).evaluate(0, 4, &machine).unwrap();
+        assert_eq!(place, Place::Memory(0x6ff0));
+        // DW_OP_breg6 -16; DW_OP_deref
+        let bytes = vec![breg6, 0x70, deref];
+        let place = expression(vec![(None, bytes)]).evaluate(0, 8, &machine).unwrap();
+        assert_eq!(place, Place::Memory(0x20));
+        // DW_OP_reg3; DW_OP_piece 2; DW_OP_lit5; DW_OP_stack_value; DW_OP_piece 2
+        let bytes = vec![reg3, piece, 2, lit5, stack_value, piece, 2];
+        let place = expression(vec![(None, bytes)]).evaluate(0, 4, &machine).unwrap();
+        assert_eq!(place, Place::Value(vec![0x88, 0x77, 5, 0]));
+
+        // A location list only covers some of the function
+        let list = expression(vec![(Some((0x10, 0x20)), vec![reg3])]);
+        assert!(list.evaluate(0x18, 8, &machine).is_ok());
+        assert!(list.evaluate(0x20, 8, &machine).is_err());
+    }
+}
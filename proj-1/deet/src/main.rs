@@ -1,24 +1,63 @@
-mod debugger;
-mod debugger_command;
-mod inferior;
-mod dwarf_data;
-mod gimli_wrapper;
-
-use crate::debugger::Debugger;
+use deet::debugger::Debugger;
+use deet::output;
 use nix::sys::signal::{signal, SigHandler, Signal};
 use std::env;
 
+fn usage(program: &str) -> ! {
+    output::error(format!("Usage: {} <target program> [--batch] [-ex <command>]...", program));
+    std::process::exit(1);
+}
+
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    if args.len() != 2 {
-        println!("Usage: {} <target program>", args[0]);
-        std::process::exit(1);
+    let mut args = env::args();
+    let program = args.next().unwrap_or_else(|| String::from("deet"));
+    let mut target = None;
+    let mut commands = Vec::new();
+    let mut batch = false;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-batch" | "--batch" => batch = true,
+            "-ex" | "--ex" => match args.next() {
+                Some(command) => commands.push(command),
+                None => usage(&program),
+            },
+            _ if target.is_none() => target = Some(arg),
+            _ => usage(&program),
+        }
     }
-    let target = &args[1];
+    let target = match target {
+        Some(target) => target,
+        None => usage(&program),
+    };
 
     // Disable handling of ctrl+c in this process (so that ctrl+c only gets delivered to child
     // processes)
     unsafe { signal(Signal::SIGINT, SigHandler::SigIgn) }.expect("Error disabling SIGINT handling");
 
-    Debugger::new(target).run();
+    output::set_color(output::ColorMode::Auto);
+    let mut debugger = match Debugger::new(&target) {
+        Ok(debugger) => debugger,
+        Err(err) => {
+            output::error(format!("Could not debug {}: {}", target, err));
+            std::process::exit(1);
+        }
+    };
+    debugger.set_batch(batch);
+    debugger.run_init_files();
+    for command in &commands {
+        if debugger.quitting() {
+            break;
+        }
+        if let Err(err) = debugger.execute_line(command) {
+            output::error(err);
+        }
+    }
+    if batch {
+        debugger.shut_down();
+    } else {
+        debugger.run();
+    }
+    if let Some(code) = debugger.exit_code() {
+        std::process::exit(code);
+    }
 }
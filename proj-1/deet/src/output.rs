@@ -0,0 +1,130 @@
+//! Everything deet prints goes through here. Addresses, function names, source locations,
+//! warnings and errors are colored when the output goes to a terminal (or when the user asks for
+//! it with `set color`). Warnings and errors go to stderr, everything else to stdout.
+
+use std::fmt;
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Whether stdout and stderr, respectively, are colored.
+static COLOR: AtomicBool = AtomicBool::new(false);
+static COLOR_STDERR: AtomicBool = AtomicBool::new(false);
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ColorMode {
+    On,
+    Off,
+    /// Color each of stdout and stderr only if it is a terminal.
+    Auto,
+}
+
+impl ColorMode {
+    pub fn parse(value: &str) -> Option<ColorMode> {
+        match value {
+            "on" => Some(ColorMode::On),
+            "off" => Some(ColorMode::Off),
+            "auto" => Some(ColorMode::Auto),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ColorMode::On => "on",
+            ColorMode::Off => "off",
+            ColorMode::Auto => "auto",
+        }
+    }
+}
+
+pub fn set_color(mode: ColorMode) {
+    let enabled = |fd| match mode {
+        ColorMode::On => true,
+        ColorMode::Off => false,
+        ColorMode::Auto => nix::unistd::isatty(fd).unwrap_or(false),
+    };
+    COLOR.store(enabled(libc::STDOUT_FILENO), Ordering::Relaxed);
+    COLOR_STDERR.store(enabled(libc::STDERR_FILENO), Ordering::Relaxed);
+}
+
+#[derive(Clone, Copy)]
+pub enum Style {
+    Address,
+    Function,
+    Source,
+    Warning,
+    Error,
+}
+
+impl Style {
+    fn ansi_code(self) -> &'static str {
+        match self {
+            Style::Address => "34",
+            Style::Function => "33",
+            Style::Source => "32",
+            Style::Warning => "35",
+            Style::Error => "31",
+        }
+    }
+}
+
+/// Renders `text` in the given style, or as is if coloring of stdout is off.
+pub fn paint<T: fmt::Display>(style: Style, text: T) -> String {
+    paint_if(COLOR.load(Ordering::Relaxed), style, text)
+}
+
+fn paint_if<T: fmt::Display>(color: bool, style: Style, text: T) -> String {
+    if color {
+        format!("\x1b[{}m{}\x1b[0m", style.ansi_code(), text)
+    } else {
+        text.to_string()
+    }
+}
+
+pub fn address(addr: usize) -> String {
+    paint(Style::Address, format!("{:#x}", addr))
+}
+
+pub fn function<T: fmt::Display>(name: T) -> String {
+    paint(Style::Function, name)
+}
+
+pub fn source<T: fmt::Display>(location: T) -> String {
+    paint(Style::Source, location)
+}
+
+/// Prints a line of ordinary output, such as the answer to a command.
+pub fn info<T: fmt::Display>(text: T) {
+    println!("{}", text);
+}
+
+/// Prints what the inferior wrote, which need not end in a newline, as soon as it comes.
+pub fn program<T: fmt::Display>(text: T) {
+    print!("{}", text);
+    let _ = std::io::stdout().flush();
+}
+
+/// Warns the user of something that did not go as asked, without being an error.
+pub fn warn<T: fmt::Display>(text: T) {
+    eprintln!("{}", paint_if(COLOR_STDERR.load(Ordering::Relaxed), Style::Warning, text));
+}
+
+/// Reports an error to the user.
+pub fn error<T: fmt::Display>(err: T) {
+    eprintln!("{}", paint_if(COLOR_STDERR.load(Ordering::Relaxed), Style::Error, err));
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_paint() {
+        set_color(ColorMode::On);
+        assert_eq!(address(0x401136), "\x1b[34m0x401136\x1b[0m");
+        set_color(ColorMode::Off);
+        assert_eq!(address(0x401136), "0x401136");
+        assert_eq!(ColorMode::parse("auto"), Some(ColorMode::Auto));
+        assert_eq!(ColorMode::parse("maybe"), None);
+    }
+}
@@ -0,0 +1,243 @@
+//! Recording of execution, so that it can be stepped backwards. Before each instruction the
+//! debugger single-steps, it saves the registers and the bytes of memory the instruction may
+//! write; undoing the instruction means writing them back. Which memory an instruction writes is
+//! worked out from objdump's disassembly of it (see `disassemble`), erring on the side of saving
+//! too much, since restoring bytes the instruction left alone changes nothing.
+
+use crate::arch::Registers;
+use std::collections::{HashMap, VecDeque};
+
+/// How many instructions a recording keeps. Older ones are forgotten.
+pub const RECORD_LIMIT: usize = 10_000;
+
+/// How many bytes are saved at each address an instruction writes to, enough for the widest
+/// vector store.
+const WRITE_SIZE: usize = 32;
+
+/// The most a string instruction with a `rep` prefix is assumed to write.
+const MAX_STRING_WRITE: usize = 1 << 16;
+
+/// How much of the stack on either side of the stack pointer is saved for an instruction that
+/// could not be decoded.
+pub const STACK_WINDOW: usize = 512;
+
+/// What executing one instruction destroyed.
+pub struct Step {
+    /// The registers before the instruction ran.
+    pub regs: Registers,
+    /// The bytes at each address the instruction may have written, as they were before.
+    pub memory: Vec<(usize, Vec<u8>)>,
+    /// Whether all the memory the instruction may have written was saved. If not, only the stack
+    /// around the stack pointer was.
+    pub exact: bool,
+}
+
+pub struct Recording {
+    steps: VecDeque<Step>,
+    /// The text of each instruction decoded so far, and the address of the one after it.
+    instructions: HashMap<usize, (String, usize)>,
+}
+
+impl Recording {
+    pub fn new() -> Recording {
+        Recording { steps: VecDeque::new(), instructions: HashMap::new() }
+    }
+
+    pub fn push(&mut self, step: Step) {
+        if self.steps.len() == RECORD_LIMIT {
+            self.steps.pop_front();
+        }
+        self.steps.push_back(step);
+    }
+
+    pub fn pop(&mut self) -> Option<Step> {
+        self.steps.pop_back()
+    }
+
+    /// The recorded instructions, oldest first.
+    pub fn steps(&self) -> &VecDeque<Step> {
+        &self.steps
+    }
+
+    /// Forgets the recorded instructions, as when the program has run on without being recorded.
+    pub fn clear(&mut self) {
+        self.steps.clear();
+    }
+
+    /// Forgets the decoded instructions too, as when the program has exec'd another.
+    pub fn reset(&mut self) {
+        self.steps.clear();
+        self.instructions.clear();
+    }
+
+    pub fn instruction(&self, addr: usize) -> Option<&(String, usize)> {
+        self.instructions.get(&addr)
+    }
+
+    pub fn add_instructions(&mut self, instructions: impl Iterator<Item = (usize, String, usize)>) {
+        for (addr, text, next) in instructions {
+            self.instructions.insert(addr, (text, next));
+        }
+    }
+}
+
+/// Works out which memory the instruction `text` (AT&T syntax, as objdump prints it) may write
+/// when run with the registers `register` looks up by name. `next` is the address of the
+/// instruction after it, which %rip-relative operands count from. Returns the address and
+/// length of each range, or None if the instruction is not understood.
+pub fn written_ranges(
+    text: &str,
+    next: usize,
+    register: &dyn Fn(&str) -> Option<u64>,
+) -> Option<Vec<(usize, usize)>> {
+    let mut words = text.split_whitespace();
+    let mut mnemonic = words.next()?;
+    let repeated = mnemonic.starts_with("rep");
+    if repeated {
+        mnemonic = words.next().unwrap_or("");
+    }
+    let operands: String = words.collect::<Vec<&str>>().join(" ");
+    let operands = split_operands(&operands);
+
+    if mnemonic.starts_with("lea") || mnemonic.starts_with("nop") || mnemonic.starts_with('j') {
+        return Some(Vec::new());
+    }
+    // Without a prefix, movs* is more likely one of the sign-extending or SSE moves
+    if mnemonic.starts_with("stos") || (mnemonic.starts_with("movs") && repeated) {
+        let dest = register("rdi").or_else(|| register("edi"))? as usize;
+        let count = if repeated {
+            register("rcx").or_else(|| register("ecx"))? as usize
+        } else {
+            1
+        };
+        return Some(vec![(dest, count.saturating_mul(8).clamp(8, MAX_STRING_WRITE))]);
+    }
+    let mut ranges = Vec::new();
+    if mnemonic.starts_with("push") || mnemonic.starts_with("call") {
+        let sp = register("rsp").or_else(|| register("esp"))? as usize;
+        ranges.push((sp.checked_sub(WRITE_SIZE)?, WRITE_SIZE));
+        if mnemonic.starts_with("call") {
+            // The operand is the target, which is only read
+            return Some(ranges);
+        }
+    }
+    // Whatever an instruction writes is its last operand
+    if let Some(dest) = operands.last() {
+        if is_memory(dest) {
+            ranges.push((effective_address(dest, next, register)?, WRITE_SIZE));
+        }
+    }
+    Some(ranges)
+}
+
+/// Splits operands at the commas that are not inside parentheses.
+fn split_operands(operands: &str) -> Vec<&str> {
+    let mut result = Vec::new();
+    let (mut depth, mut start) = (0, 0);
+    for (i, c) in operands.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                result.push(operands[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if !operands[start..].trim().is_empty() {
+        result.push(operands[start..].trim());
+    }
+    result
+}
+
+/// Whether the operand refers to memory, rather than being a register or an immediate.
+fn is_memory(operand: &str) -> bool {
+    let register = operand.starts_with('%') && !operand.contains(':');
+    !operand.starts_with('$') && !register
+}
+
+/// Computes the address an operand of the form `segment:displacement(base,index,scale)` refers
+/// to, any part of which may be missing.
+fn effective_address(
+    operand: &str,
+    next: usize,
+    register: &dyn Fn(&str) -> Option<u64>,
+) -> Option<usize> {
+    let operand = operand.trim_start_matches('*');
+    let (segment, operand) = match operand.find(':') {
+        Some(colon) => (Some(&operand[..colon]), &operand[colon + 1..]),
+        None => (None, operand),
+    };
+    let (displacement, registers) = match operand.find('(') {
+        Some(open) => (&operand[..open], operand[open + 1..].strip_suffix(')')?),
+        None => (operand, ""),
+    };
+    let mut address = match displacement {
+        "" => 0,
+        displacement => parse_displacement(displacement)?,
+    };
+    if let Some(segment) = segment {
+        let segment = segment.trim_start_matches('%');
+        address = address.wrapping_add(register(&format!("{}_base", segment))?);
+    }
+    let mut parts = registers.split(',').map(str::trim);
+    let value = |name: &str| match name.trim_start_matches('%') {
+        "rip" | "eip" => Some(next as u64),
+        name => register(name),
+    };
+    if let Some(base) = parts.next().filter(|base| !base.is_empty()) {
+        address = address.wrapping_add(value(base)?);
+    }
+    if let Some(index) = parts.next().filter(|index| !index.is_empty()) {
+        let scale = match parts.next() {
+            Some(scale) => scale.parse::<u64>().ok()?,
+            None => 1,
+        };
+        address = address.wrapping_add(value(index)?.wrapping_mul(scale));
+    }
+    Some(address as usize)
+}
+
+/// Parses a displacement such as `-0x18` or `0x404028`.
+fn parse_displacement(text: &str) -> Option<u64> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text),
+    };
+    let value = match digits.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok()?,
+        None => digits.parse().ok()?,
+    };
+    Some(if negative { value.wrapping_neg() } else { value })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_written_ranges() {
+        let register = |name: &str| match name {
+            "rbp" => Some(0x7000),
+            "rsp" => Some(0x6f00),
+            "rax" => Some(0x10),
+            "rdi" => Some(0x9000),
+            "rcx" => Some(4),
+            "fs_base" => Some(0x2000),
+            _ => None,
+        };
+        let ranges = |text| written_ranges(text, 0x401000, &register);
+        assert_eq!(ranges("mov    %edi,-0x14(%rbp)"), Some(vec![(0x6fec, WRITE_SIZE)]));
+        assert_eq!(ranges("mov    -0x14(%rbp),%eax"), Some(vec![]));
+        assert_eq!(ranges("movl   $0x1,0x2ef8(%rip)"), Some(vec![(0x403ef8, WRITE_SIZE)]));
+        assert_eq!(ranges("mov    %eax,(%rbp,%rax,4)"), Some(vec![(0x7040, WRITE_SIZE)]));
+        assert_eq!(ranges("mov    %rax,%fs:0x28"), Some(vec![(0x2028, WRITE_SIZE)]));
+        assert_eq!(ranges("push   %rbp"), Some(vec![(0x6f00 - WRITE_SIZE, WRITE_SIZE)]));
+        assert_eq!(ranges("call   401126 <leaf>"), Some(vec![(0x6f00 - WRITE_SIZE, WRITE_SIZE)]));
+        assert_eq!(ranges("lea    -0x20(%rbp),%rax"), Some(vec![]));
+        assert_eq!(ranges("rep stos %rax,%es:(%rdi)"), Some(vec![(0x9000, 32)]));
+        // Registers it cannot look up make the instruction unknown
+        assert_eq!(ranges("mov    %eax,(%r9)"), None);
+    }
+}
@@ -0,0 +1,416 @@
+//! Scripting deet in rhai (https://rhai.rs), to automate triage the way gdb's Python API does.
+//! `rhai <code>` runs a line of it and `source <file>.rhai` a whole script. On top of rhai's own
+//! functions, scripts can call:
+//!
+//! - `execute(command)`, which runs a deet command as if it had been typed at the prompt.
+//! - `break_at(location)`, which sets a breakpoint and returns its number,
+//!   `delete_breakpoint(number)`, and `breakpoints()`, which lists them as maps with an `id`,
+//!   `address`, `location` and `hits`.
+//! - `read_memory(address, length)`, which returns a blob, `read_word(address)`, and
+//!   `write_memory(address, blob)`. Breakpoints are masked out of what is read, as usual.
+//! - `register(name)` and `set_register(name, value)`, for the registers `info registers` shows,
+//!   and `pc()`.
+//! - `evaluate(variable)`, which renders a variable the way `print` does.
+//! - `on_stop(function)`, which has `function` called whenever the inferior stops, with a map
+//!   holding the `pc`, the `signal` it stopped with, and the number of the `breakpoint` it
+//!   stopped at (or `()`).
+//!
+//! The bindings reach the debugger through the `Host` trait, which `Debugger` implements.
+
+use crate::error::DeetError;
+use crate::output;
+use rhai::{Array, Blob, Dynamic, Engine, EvalAltResult, FnPtr, Map, AST, INT};
+use std::cell::{Cell, RefCell};
+use std::marker::PhantomData;
+use std::rc::Rc;
+
+/// What scripts can ask of the debugger.
+pub trait Host {
+    fn execute(&mut self, line: &str) -> Result<(), DeetError>;
+    /// Sets a breakpoint the way `break` does, returning its number.
+    fn break_at(&mut self, location: &str) -> Result<usize, DeetError>;
+    fn delete_breakpoint(&mut self, id: usize) -> Result<(), DeetError>;
+    fn breakpoints(&self) -> Vec<BreakpointInfo>;
+    fn read_memory(&self, addr: usize, len: usize) -> Result<Vec<u8>, DeetError>;
+    fn write_memory(&mut self, addr: usize, bytes: &[u8]) -> Result<(), DeetError>;
+    fn word_size(&self) -> usize;
+    fn register(&self, name: &str) -> Result<u64, DeetError>;
+    fn set_register(&mut self, name: &str, value: u64) -> Result<(), DeetError>;
+    fn pc(&self) -> Result<usize, DeetError>;
+    fn evaluate(&self, expr: &str) -> Result<String, DeetError>;
+}
+
+pub struct BreakpointInfo {
+    pub id: usize,
+    pub addr: usize,
+    pub location: String,
+    pub hits: usize,
+}
+
+/// How the inferior stopped, as stop hooks are told.
+pub struct StopEvent {
+    pub pc: usize,
+    pub signal: String,
+    /// The number of the breakpoint it stopped at, if any.
+    pub breakpoint: Option<usize>,
+}
+
+/// A function a script asked to have called at every stop, and the script it came from (which
+/// it may call into).
+#[derive(Clone)]
+pub struct StopHook {
+    function: FnPtr,
+    script: Rc<AST>,
+}
+
+impl StopHook {
+    pub fn name(&self) -> &str {
+        self.function.fn_name()
+    }
+}
+
+thread_local! {
+    /// The debugger running the script, for the bindings to act on.
+    static HOST: Cell<Option<*mut dyn Host>> = Cell::new(None);
+}
+
+/// Makes `host` the one the bindings act on for as long as the guard lives, which is also as long
+/// as `host` stays borrowed. A binding that has the host run something (stop hooks, say) hands
+/// its own borrow on, and the outer host is put back once that is done.
+struct HostGuard<'a> {
+    outer: Option<*mut dyn Host>,
+    host: PhantomData<&'a mut dyn Host>,
+}
+
+impl<'a> HostGuard<'a> {
+    fn enter<H: Host + 'static>(host: &'a mut H) -> HostGuard<'a> {
+        let host: *mut dyn Host = host;
+        HostGuard {
+            outer: HOST.with(|current| current.replace(Some(host))),
+            host: PhantomData,
+        }
+    }
+}
+
+impl Drop for HostGuard<'_> {
+    fn drop(&mut self) {
+        HOST.with(|current| current.set(self.outer));
+    }
+}
+
+fn with_host<T>(
+    f: impl FnOnce(&mut dyn Host) -> Result<T, DeetError>,
+) -> Result<T, Box<EvalAltResult>> {
+    let host = HOST.with(Cell::get).ok_or("deet is not running a script")?;
+    // The pointer came from a mutable borrow that `HostGuard` holds on to while scripts run, and
+    // nothing else touches the host until this returns
+    unsafe { f(&mut *host) }.map_err(|err| err.to_string().into())
+}
+
+/// The rhai engine with deet's bindings, and the stop hooks scripts have set. Cloning it is cheap
+/// and shares both, which lets the debugger hold on to a copy while it hands itself to a script.
+#[derive(Clone)]
+pub struct Scripting {
+    engine: Rc<Engine>,
+    stop_hooks: Rc<RefCell<Vec<StopHook>>>,
+    /// The scripts being run, innermost last. `on_stop` takes its functions from the innermost.
+    running: Rc<RefCell<Vec<Rc<AST>>>>,
+}
+
+impl Scripting {
+    pub fn new() -> Scripting {
+        let stop_hooks = Rc::new(RefCell::new(Vec::new()));
+        let running: Rc<RefCell<Vec<Rc<AST>>>> = Rc::new(RefCell::new(Vec::new()));
+        let mut engine = Engine::new();
+        engine.on_print(|text| output::info(text));
+        engine.on_debug(|text, _, _| output::info(text));
+
+        engine.register_fn("execute", |line: &str| with_host(|host| host.execute(line)));
+        engine.register_fn("break_at", |location: &str| {
+            with_host(|host| host.break_at(location)).map(|id| id as INT)
+        });
+        engine.register_fn("delete_breakpoint", |id: INT| {
+            with_host(|host| host.delete_breakpoint(id as usize))
+        });
+        engine.register_fn("breakpoints", || {
+            with_host(|host| Ok(host.breakpoints())).map(|breakpoints| {
+                breakpoints
+                    .into_iter()
+                    .map(breakpoint_map)
+                    .collect::<Array>()
+            })
+        });
+        engine.register_fn("read_memory", |addr: INT, len: INT| -> Result<Blob, _> {
+            with_host(|host| host.read_memory(addr as usize, len as usize))
+        });
+        engine.register_fn("read_word", |addr: INT| {
+            with_host(|host| {
+                let bytes = host.read_memory(addr as usize, host.word_size())?;
+                let mut word = [0u8; 8];
+                word[..bytes.len()].copy_from_slice(&bytes);
+                Ok(u64::from_le_bytes(word) as INT)
+            })
+        });
+        engine.register_fn("write_memory", |addr: INT, bytes: Blob| {
+            with_host(|host| host.write_memory(addr as usize, &bytes))
+        });
+        engine.register_fn("register", |name: &str| {
+            with_host(|host| host.register(name)).map(|value| value as INT)
+        });
+        engine.register_fn("set_register", |name: &str, value: INT| {
+            with_host(|host| host.set_register(name, value as u64))
+        });
+        engine.register_fn("pc", || with_host(|host| host.pc()).map(|pc| pc as INT));
+        engine.register_fn("evaluate", |expr: &str| {
+            with_host(|host| host.evaluate(expr))
+        });
+        let (hooks, scripts) = (Rc::clone(&stop_hooks), Rc::clone(&running));
+        engine.register_fn("on_stop", move |function: FnPtr| {
+            let script = scripts
+                .borrow()
+                .last()
+                .cloned()
+                .ok_or("no script is running")?;
+            hooks.borrow_mut().push(StopHook { function, script });
+            Ok::<(), Box<EvalAltResult>>(())
+        });
+
+        Scripting {
+            engine: Rc::new(engine),
+            stop_hooks,
+            running,
+        }
+    }
+
+    /// Runs the script `code` against `host`. `name` says where it came from, for errors.
+    pub fn run<H: Host + 'static>(
+        &self,
+        host: &mut H,
+        name: &str,
+        code: &str,
+    ) -> Result<(), DeetError> {
+        let script = self
+            .engine
+            .compile(code)
+            .map_err(|err| script_error(name, err))?;
+        let script = Rc::new(script);
+        self.running.borrow_mut().push(Rc::clone(&script));
+        let result = {
+            let _guard = HostGuard::enter(host);
+            self.engine.run_ast(&script)
+        };
+        self.running.borrow_mut().pop();
+        result.map_err(|err| script_error(name, err))
+    }
+
+    pub fn stop_hooks(&self) -> Vec<StopHook> {
+        self.stop_hooks.borrow().clone()
+    }
+
+    pub fn clear_stop_hooks(&self) {
+        self.stop_hooks.borrow_mut().clear();
+    }
+
+    /// Calls one of the stop hooks with `event`.
+    pub fn call_stop_hook<H: Host + 'static>(
+        &self,
+        host: &mut H,
+        hook: &StopHook,
+        event: &StopEvent,
+    ) -> Result<(), DeetError> {
+        let mut map = Map::new();
+        map.insert("pc".into(), Dynamic::from(event.pc as INT));
+        map.insert("signal".into(), Dynamic::from(event.signal.clone()));
+        let breakpoint = event
+            .breakpoint
+            .map_or(Dynamic::UNIT, |id| Dynamic::from(id as INT));
+        map.insert("breakpoint".into(), breakpoint);
+        let _guard = HostGuard::enter(host);
+        hook.function
+            .call::<Dynamic>(&self.engine, &hook.script, (map,))
+            .map(|_| ())
+            .map_err(|err| script_error(hook.name(), err))
+    }
+}
+
+impl Default for Scripting {
+    fn default() -> Self {
+        Scripting::new()
+    }
+}
+
+fn breakpoint_map(breakpoint: BreakpointInfo) -> Dynamic {
+    let mut map = Map::new();
+    map.insert("id".into(), Dynamic::from(breakpoint.id as INT));
+    map.insert("address".into(), Dynamic::from(breakpoint.addr as INT));
+    map.insert("location".into(), Dynamic::from(breakpoint.location));
+    map.insert("hits".into(), Dynamic::from(breakpoint.hits as INT));
+    Dynamic::from_map(map)
+}
+
+fn script_error(name: &str, err: impl std::fmt::Display) -> DeetError {
+    DeetError::Script(format!("{}: {}", name, err))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[derive(Default)]
+    struct FakeHost {
+        memory: Vec<u8>,
+        registers: HashMap<String, u64>,
+        breakpoints: Vec<BreakpointInfo>,
+        executed: Vec<String>,
+    }
+
+    impl Host for FakeHost {
+        fn execute(&mut self, line: &str) -> Result<(), DeetError> {
+            self.executed.push(line.to_string());
+            Ok(())
+        }
+
+        fn break_at(&mut self, location: &str) -> Result<usize, DeetError> {
+            let id = self.breakpoints.len() + 1;
+            let location = location.to_string();
+            self.breakpoints.push(BreakpointInfo {
+                id,
+                addr: 0x401000 + id,
+                location,
+                hits: 0,
+            });
+            Ok(id)
+        }
+
+        fn delete_breakpoint(&mut self, id: usize) -> Result<(), DeetError> {
+            let before = self.breakpoints.len();
+            self.breakpoints.retain(|breakpoint| breakpoint.id != id);
+            if self.breakpoints.len() == before {
+                return Err(DeetError::Parse(format!("No breakpoint number {}.", id)));
+            }
+            Ok(())
+        }
+
+        fn breakpoints(&self) -> Vec<BreakpointInfo> {
+            self.breakpoints
+                .iter()
+                .map(|b| BreakpointInfo {
+                    location: b.location.clone(),
+                    ..*b
+                })
+                .collect()
+        }
+
+        fn read_memory(&self, addr: usize, len: usize) -> Result<Vec<u8>, DeetError> {
+            Ok(self.memory[addr..addr + len].to_vec())
+        }
+
+        fn write_memory(&mut self, addr: usize, bytes: &[u8]) -> Result<(), DeetError> {
+            self.memory[addr..addr + bytes.len()].copy_from_slice(bytes);
+            Ok(())
+        }
+
+        fn word_size(&self) -> usize {
+            8
+        }
+
+        fn register(&self, name: &str) -> Result<u64, DeetError> {
+            self.registers
+                .get(name)
+                .copied()
+                .ok_or_else(|| DeetError::Parse(format!("Invalid register \"${}\"", name)))
+        }
+
+        fn set_register(&mut self, name: &str, value: u64) -> Result<(), DeetError> {
+            self.registers.insert(name.to_string(), value);
+            Ok(())
+        }
+
+        fn pc(&self) -> Result<usize, DeetError> {
+            Ok(self.register("rip")? as usize)
+        }
+
+        fn evaluate(&self, expr: &str) -> Result<String, DeetError> {
+            Ok(format!("<{}>", expr))
+        }
+    }
+
+    #[test]
+    fn test_bindings() {
+        let scripting = Scripting::new();
+        let mut host = FakeHost {
+            memory: vec![0; 16],
+            ..FakeHost::default()
+        };
+        host.registers.insert(String::from("rip"), 0x401136);
+        let script = r#"
+            let id = break_at("main");
+            break_at("func");
+            delete_breakpoint(id);
+            let bytes = read_memory(0, 2);
+            bytes.push(1);
+            bytes.push(2);
+            write_memory(8, bytes);
+            set_register("rax", pc() + read_word(8));
+            execute("print " + evaluate("x"));
+        "#;
+        scripting.run(&mut host, "test", script).unwrap();
+        assert_eq!(host.breakpoints.len(), 1);
+        assert_eq!(host.breakpoints[0].location, "func");
+        assert_eq!(host.memory[8..12], [0, 0, 1, 2]);
+        assert_eq!(host.registers["rax"], 0x401136 + 0x0201_0000);
+        assert_eq!(host.executed, vec!["print <x>"]);
+    }
+
+    #[test]
+    fn test_errors() {
+        let scripting = Scripting::new();
+        let mut host = FakeHost::default();
+        let err = scripting
+            .run(&mut host, "bad.rhai", "delete_breakpoint(3);")
+            .unwrap_err();
+        assert!(err.to_string().contains("No breakpoint number 3"));
+        assert!(scripting.run(&mut host, "bad.rhai", "let = ;").is_err());
+        // Without a script running, there is no debugger to act on
+        assert!(with_host(|host| host.pc()).is_err());
+    }
+
+    #[test]
+    fn test_stop_hooks() {
+        let scripting = Scripting::new();
+        let mut host = FakeHost::default();
+        let script = r#"
+            fn report(event) {
+                execute("stopped at " + event.pc + " by " + event.signal + " " + event.breakpoint);
+            }
+            on_stop(Fn("report"));
+            on_stop(|event| if event.breakpoint == () { execute("no breakpoint") });
+        "#;
+        scripting.run(&mut host, "hooks.rhai", script).unwrap();
+        let hooks = scripting.stop_hooks();
+        assert_eq!(hooks.len(), 2);
+        assert_eq!(hooks[0].name(), "report");
+        let event = StopEvent {
+            pc: 4198710,
+            signal: String::from("SIGTRAP"),
+            breakpoint: Some(1),
+        };
+        for hook in &hooks {
+            scripting.call_stop_hook(&mut host, hook, &event).unwrap();
+        }
+        let event = StopEvent {
+            breakpoint: None,
+            ..event
+        };
+        scripting
+            .call_stop_hook(&mut host, &hooks[1], &event)
+            .unwrap();
+        assert_eq!(
+            host.executed,
+            vec!["stopped at 4198710 by SIGTRAP 1", "no breakpoint"]
+        );
+        scripting.clear_stop_hooks();
+        assert!(scripting.stop_hooks().is_empty());
+    }
+}
@@ -0,0 +1,187 @@
+//! The debugger's settings, changed with `set <name> <value>` and shown with `show [name]`. The
+//! values are kept in `~/.deet_settings`, one `name value` pair per line, so that they last from
+//! one session to the next.
+
+use crate::error::DeetError;
+use crate::output::ColorMode;
+use std::time::Duration;
+
+/// The names of the settings, in the order `show` lists them.
+pub const NAMES: [&str; 8] = [
+    "confirm",
+    "color",
+    "listsize",
+    "follow-fork-mode",
+    "timeout",
+    "tty",
+    "inferior-tty",
+    "exit-code-passthrough",
+];
+
+/// Which process the debugger stays with when the inferior forks (and forks are not caught).
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FollowForkMode {
+    Parent,
+    Child,
+}
+
+pub struct Settings {
+    /// Whether to ask before doing something that cannot be undone, like killing the inferior.
+    pub confirm: bool,
+    pub color: ColorMode,
+    /// How many lines of source `where` shows around the current one.
+    pub listsize: usize,
+    pub follow_fork_mode: FollowForkMode,
+    /// How long the inferior may run for one command before it is interrupted.
+    pub timeout: Option<Duration>,
+    /// Whether programs are run straight on deet's terminal, rather than with their output
+    /// captured and labeled.
+    pub raw_tty: bool,
+    /// Whether programs are given a pty of their own as their terminal, with deet passing
+    /// keystrokes on to it while they run. This wins over `tty`.
+    pub inferior_tty: bool,
+    /// Whether deet's own exit status mirrors that of the last inferior to exit, as it always
+    /// does in batch mode.
+    pub exit_code_passthrough: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            confirm: true,
+            color: ColorMode::Auto,
+            listsize: 5,
+            follow_fork_mode: FollowForkMode::Parent,
+            timeout: None,
+            raw_tty: false,
+            inferior_tty: false,
+            exit_code_passthrough: false,
+        }
+    }
+}
+
+impl Settings {
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), DeetError> {
+        match name {
+            "confirm" => self.confirm = parse_on_off(value)?,
+            "color" => {
+                self.color = ColorMode::parse(value)
+                    .ok_or_else(|| expected("\"on\", \"off\" or \"auto\"", value))?;
+            }
+            "listsize" => {
+                self.listsize = value
+                    .parse()
+                    .ok()
+                    .filter(|size| *size > 0)
+                    .ok_or_else(|| expected("A number of lines", value))?;
+            }
+            "follow-fork-mode" => {
+                self.follow_fork_mode = match value {
+                    "parent" => FollowForkMode::Parent,
+                    "child" => FollowForkMode::Child,
+                    _ => return Err(expected("\"parent\" or \"child\"", value)),
+                };
+            }
+            "timeout" => {
+                let seconds = match value {
+                    "off" | "unlimited" => 0,
+                    seconds => {
+                        seconds.parse().map_err(|_| expected("Number of seconds", value))?
+                    }
+                };
+                self.timeout = Some(Duration::from_secs(seconds)).filter(|limit| !limit.is_zero());
+            }
+            "tty" => self.raw_tty = parse_on_off(value)?,
+            "inferior-tty" => self.inferior_tty = parse_on_off(value)?,
+            "exit-code-passthrough" => self.exit_code_passthrough = parse_on_off(value)?,
+            _ => return Err(DeetError::Parse(format!("No setting named \"{}\".", name))),
+        }
+        Ok(())
+    }
+
+    /// The value of setting `name`, written the way `set` takes it.
+    pub fn get(&self, name: &str) -> Option<String> {
+        let on_off = |on| String::from(if on { "on" } else { "off" });
+        Some(match name {
+            "confirm" => on_off(self.confirm),
+            "color" => self.color.name().to_string(),
+            "listsize" => self.listsize.to_string(),
+            "follow-fork-mode" => match self.follow_fork_mode {
+                FollowForkMode::Parent => String::from("parent"),
+                FollowForkMode::Child => String::from("child"),
+            },
+            "timeout" => self
+                .timeout
+                .map_or_else(|| String::from("unlimited"), |limit| limit.as_secs().to_string()),
+            "tty" => on_off(self.raw_tty),
+            "inferior-tty" => on_off(self.inferior_tty),
+            "exit-code-passthrough" => on_off(self.exit_code_passthrough),
+            _ => return None,
+        })
+    }
+
+    /// Applies the settings in `text`, as written by `to_file`. Returns the line number and
+    /// error of each line that could not be applied.
+    pub fn load(&mut self, text: &str) -> Vec<(usize, DeetError)> {
+        let mut errors = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            let mut words = line.split_whitespace();
+            let result = match (words.next(), words.next()) {
+                (None, _) => Ok(()),
+                (Some(name), Some(value)) => self.set(name, value),
+                (Some(name), None) => Err(DeetError::Parse(format!("No value for \"{}\".", name))),
+            };
+            if let Err(err) = result {
+                errors.push((i + 1, err));
+            }
+        }
+        errors
+    }
+
+    pub fn to_file(&self) -> String {
+        let mut text = String::new();
+        for name in &NAMES {
+            text.push_str(&format!("{} {}\n", name, self.get(name).unwrap()));
+        }
+        text
+    }
+}
+
+pub fn parse_on_off(value: &str) -> Result<bool, DeetError> {
+    match value {
+        "on" => Ok(true),
+        "off" => Ok(false),
+        _ => Err(expected("\"on\" or \"off\"", value)),
+    }
+}
+
+fn expected(what: &str, value: &str) -> DeetError {
+    DeetError::Parse(format!("{} expected, not \"{}\".", what, value))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_settings_round_trip() {
+        let mut settings = Settings::default();
+        settings.set("timeout", "30").unwrap();
+        settings.set("follow-fork-mode", "child").unwrap();
+        settings.set("confirm", "off").unwrap();
+        assert!(settings.set("listsize", "0").is_err());
+        assert!(settings.set("colour", "on").is_err());
+
+        let mut loaded = Settings::default();
+        assert!(loaded.load(&settings.to_file()).is_empty());
+        assert_eq!(loaded.timeout, Some(Duration::from_secs(30)));
+        assert_eq!(loaded.follow_fork_mode, FollowForkMode::Child);
+        assert!(!loaded.confirm);
+        assert_eq!(loaded.get("timeout").unwrap(), "30");
+
+        let errors = loaded.load("listsize 8\ntty maybe\n");
+        assert_eq!(loaded.listsize, 8);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0, 2);
+    }
+}
@@ -0,0 +1,168 @@
+//! The ELF symbol table, for naming addresses that have no debugging information (such as code in
+//! shared libraries, or in a target built without -g).
+
+use object::{Object, ObjectSection, SectionKind, SymbolKind};
+use std::fs;
+use std::io;
+
+pub struct Symbol {
+    pub name: String,
+    pub address: usize,
+    pub size: usize,
+}
+
+pub struct Section {
+    pub name: String,
+    pub address: usize,
+    pub size: usize,
+    /// Where the section's contents start in the file, if they are there at all (`.bss` only
+    /// takes up memory).
+    pub file_offset: Option<usize>,
+    /// Whether the program cannot write to it (code and constants), so that what is in memory
+    /// should be what is in the file.
+    pub read_only: bool,
+}
+
+pub struct SymbolTable {
+    /// Function and data symbols, sorted by address.
+    symbols: Vec<Symbol>,
+    /// Sections that are loaded into memory.
+    sections: Vec<Section>,
+}
+
+impl SymbolTable {
+    #[cfg(test)]
+    pub fn empty() -> SymbolTable {
+        SymbolTable {
+            symbols: Vec::new(),
+            sections: Vec::new(),
+        }
+    }
+
+    pub fn from_object(object: &object::File) -> SymbolTable {
+        // Shared libraries are usually stripped down to their dynamic symbols
+        let mut symbols: Vec<Symbol> = object
+            .symbols()
+            .chain(object.dynamic_symbols())
+            .map(|(_, symbol)| symbol)
+            .filter(|symbol| symbol.kind() == SymbolKind::Text || symbol.kind() == SymbolKind::Data)
+            .filter(|symbol| symbol.address() != 0)
+            .filter_map(|symbol| {
+                Some(Symbol {
+                    name: symbol.name().filter(|name| !name.is_empty())?.to_string(),
+                    address: symbol.address() as usize,
+                    size: symbol.size() as usize,
+                })
+            })
+            .collect();
+        symbols.sort_by(|a, b| (a.address, &a.name).cmp(&(b.address, &b.name)));
+        symbols.dedup_by(|a, b| a.address == b.address && a.name == b.name);
+        let sections = object
+            .sections()
+            .filter(|section| section.address() != 0)
+            .map(|section| Section {
+                name: section.name().unwrap_or("").to_string(),
+                address: section.address() as usize,
+                size: section.size() as usize,
+                file_offset: section.file_range().map(|(offset, _)| offset as usize),
+                read_only: matches!(
+                    section.kind(),
+                    SectionKind::Text | SectionKind::ReadOnlyData | SectionKind::ReadOnlyString
+                ),
+            })
+            .collect();
+        SymbolTable { symbols, sections }
+    }
+
+    pub fn from_file(path: &str) -> Result<SymbolTable, io::Error> {
+        let data = fs::read(path)?;
+        let object = object::File::parse(&data)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?;
+        Ok(SymbolTable::from_object(&object))
+    }
+
+    /// Finds the symbol `addr` belongs to, and how far into it `addr` is. Symbols of unknown size
+    /// are assumed to extend up to the next one, within the same section.
+    pub fn lookup(&self, addr: usize) -> Option<(&Symbol, usize)> {
+        let index = match self.symbols.binary_search_by_key(&addr, |symbol| symbol.address) {
+            Ok(index) => index,
+            Err(0) => return None,
+            Err(index) => index - 1,
+        };
+        // Several symbols can share an address; any one of them will do
+        let symbol = &self.symbols[index];
+        let offset = addr - symbol.address;
+        if symbol.size != 0 && offset >= symbol.size {
+            return None;
+        }
+        if symbol.size == 0 {
+            let section = self.section_containing(addr)?;
+            if symbol.address < section.address {
+                return None;
+            }
+        }
+        Some((symbol, offset))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    /// Returns the address of the symbol called `name`.
+    pub fn address_of(&self, name: &str) -> Option<usize> {
+        self.symbols
+            .iter()
+            .find(|symbol| symbol.name == name)
+            .map(|symbol| symbol.address)
+    }
+
+    /// The sections that are loaded into memory, in the order the file has them.
+    pub fn sections(&self) -> &[Section] {
+        &self.sections
+    }
+
+    pub fn section_containing(&self, addr: usize) -> Option<&Section> {
+        self.sections
+            .iter()
+            .find(|section| section.address <= addr && addr < section.address + section.size)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn symbol(name: &str, address: usize, size: usize) -> Symbol {
+        Symbol {
+            name: name.to_string(),
+            address,
+            size,
+        }
+    }
+
+    #[test]
+    fn test_lookup() {
+        let table = SymbolTable {
+            symbols: vec![
+                symbol("a", 0x1000, 0x10),
+                symbol("b", 0x1020, 0),
+                symbol("c", 0x1100, 8),
+            ],
+            sections: vec![Section {
+                name: String::from(".text"),
+                address: 0x1000,
+                size: 0x1000,
+                file_offset: Some(0x1000),
+                read_only: true,
+            }],
+        };
+        assert!(table.lookup(0xfff).is_none());
+        assert_eq!(table.lookup(0x1000).map(|(s, off)| (s.name.as_str(), off)), Some(("a", 0)));
+        assert_eq!(table.lookup(0x100f).map(|(s, off)| (s.name.as_str(), off)), Some(("a", 0xf)));
+        assert!(table.lookup(0x1010).is_none());
+        assert_eq!(table.lookup(0x1050).map(|(s, off)| (s.name.as_str(), off)), Some(("b", 0x30)));
+        assert!(table.lookup(0x1108).is_none());
+        // Past the end of the section, a symbol without a size stops counting
+        assert!(table.lookup(0x2000).is_none());
+    }
+}
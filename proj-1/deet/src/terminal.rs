@@ -0,0 +1,192 @@
+//! A terminal of the inferior's own, for programs that behave differently on one (curses, line
+//! editors, anything checking `isatty` on stdin). The inferior gets the slave side of a pty as
+//! its controlling terminal and standard streams, and a relay thread copies what it writes to
+//! deet's stdout unchanged. While it runs in the foreground, deet's terminal is put in raw mode
+//! and keystrokes are passed on to it too, so that ^C and the like reach it the way they would
+//! outside the debugger.
+
+use crate::error::DeetError;
+use nix::errno::Errno;
+use nix::fcntl::{fcntl, FcntlArg, FdFlag, OFlag};
+use nix::poll::{poll, PollFd, PollFlags};
+use nix::pty::{openpty, Winsize};
+use nix::sys::termios::{self, SetArg, Termios};
+use nix::unistd;
+use std::io::Write;
+use std::os::unix::io::RawFd;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+/// How long the relay thread sleeps between checks of whether it should stop, in milliseconds.
+const POLL_INTERVAL: i32 = 100;
+
+/// The state shared between the relay thread and the debugger.
+struct Shared {
+    master: RawFd,
+    /// Cleared once nothing holds the slave side open any more.
+    open: bool,
+    /// Whether deet's stdin currently belongs to the inferior.
+    forwarding: bool,
+}
+
+impl Shared {
+    /// Copies whatever the inferior has written to deet's stdout.
+    fn drain(&mut self) {
+        let mut buf = [0u8; 4096];
+        while self.open {
+            match unistd::read(self.master, &mut buf) {
+                Ok(0) => self.open = false,
+                Ok(len) => {
+                    let mut stdout = std::io::stdout();
+                    let _ = stdout.write_all(&buf[..len]);
+                    let _ = stdout.flush();
+                }
+                Err(nix::Error::Sys(Errno::EAGAIN)) => break,
+                Err(nix::Error::Sys(Errno::EINTR)) => {}
+                // EIO: every process holding the slave side has gone
+                Err(_) => self.open = false,
+            }
+        }
+    }
+
+    /// Passes what was typed on to the inferior, if it is still the one reading it.
+    fn forward_input(&mut self) {
+        if !self.forwarding || !self.open {
+            return;
+        }
+        let mut buf = [0u8; 1024];
+        if let Ok(len) = unistd::read(libc::STDIN_FILENO, &mut buf) {
+            let _ = unistd::write(self.master, &buf[..len]);
+        }
+    }
+}
+
+/// The pty the inferior runs on, and the thread relaying to and from it.
+pub struct InferiorTerminal {
+    shared: Arc<Mutex<Shared>>,
+    /// The slave end, for the inferior to open as its terminal. Closed once it has been started.
+    slave: Option<RawFd>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl InferiorTerminal {
+    /// Opens a pty the size of deet's own terminal.
+    pub fn open() -> Result<InferiorTerminal, DeetError> {
+        let mut size = Winsize { ws_row: 24, ws_col: 80, ws_xpixel: 0, ws_ypixel: 0 };
+        unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) };
+        let pty = openpty(&size, None)?;
+        for fd in &[pty.master, pty.slave] {
+            fcntl(*fd, FcntlArg::F_SETFD(FdFlag::FD_CLOEXEC))?;
+        }
+        fcntl(pty.master, FcntlArg::F_SETFL(OFlag::O_NONBLOCK))?;
+        let shared = Arc::new(Mutex::new(Shared {
+            master: pty.master,
+            open: true,
+            forwarding: false,
+        }));
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let shared = shared.clone();
+            let stop = stop.clone();
+            thread::spawn(move || relay(&shared, &stop))
+        };
+        Ok(InferiorTerminal { shared, slave: Some(pty.slave), stop, thread: Some(thread) })
+    }
+
+    /// The file descriptor the inferior should take as its terminal.
+    pub fn slave(&self) -> Option<RawFd> {
+        self.slave
+    }
+
+    /// Closes our copy of the slave end, once the inferior has its own.
+    pub fn close_slave(&mut self) {
+        if let Some(slave) = self.slave.take() {
+            let _ = unistd::close(slave);
+        }
+    }
+
+    /// Prints everything the inferior has written so far.
+    pub fn flush(&self) {
+        self.shared.lock().unwrap().drain();
+    }
+
+    /// Hands deet's terminal over to the inferior until the returned guard is dropped. Nothing
+    /// is forwarded when deet's stdin is not a terminal, since it then holds deet's commands.
+    pub fn foreground(&self) -> Foreground {
+        let saved = match unistd::isatty(libc::STDIN_FILENO) {
+            Ok(true) => termios::tcgetattr(libc::STDIN_FILENO).ok(),
+            _ => None,
+        };
+        if let Some(saved) = &saved {
+            // Output processing is off too, since the inferior's pty has already done its own
+            let mut raw = saved.clone();
+            termios::cfmakeraw(&mut raw);
+            let _ = termios::tcsetattr(libc::STDIN_FILENO, SetArg::TCSADRAIN, &raw);
+            self.shared.lock().unwrap().forwarding = true;
+        }
+        Foreground { shared: self.shared.clone(), saved }
+    }
+}
+
+impl Drop for InferiorTerminal {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        self.flush();
+        self.close_slave();
+        let _ = unistd::close(self.shared.lock().unwrap().master);
+    }
+}
+
+/// Gives deet's terminal back, with the settings it had, when dropped.
+pub struct Foreground {
+    shared: Arc<Mutex<Shared>>,
+    saved: Option<Termios>,
+}
+
+impl Drop for Foreground {
+    fn drop(&mut self) {
+        self.shared.lock().unwrap().forwarding = false;
+        if let Some(saved) = &self.saved {
+            let _ = termios::tcsetattr(libc::STDIN_FILENO, SetArg::TCSADRAIN, saved);
+        }
+    }
+}
+
+/// The relay thread: prints the inferior's output as it arrives and, while it is in the
+/// foreground, passes keystrokes on to it, until told to stop.
+fn relay(shared: &Mutex<Shared>, stop: &AtomicBool) {
+    while !stop.load(Ordering::Relaxed) {
+        let (master, open, forwarding) = {
+            let shared = shared.lock().unwrap();
+            (shared.master, shared.open, shared.forwarding)
+        };
+        let mut fds = Vec::new();
+        if open {
+            fds.push(PollFd::new(master, PollFlags::POLLIN));
+        }
+        if forwarding {
+            fds.push(PollFd::new(libc::STDIN_FILENO, PollFlags::POLLIN));
+        }
+        if fds.is_empty() {
+            thread::sleep(std::time::Duration::from_millis(POLL_INTERVAL as u64));
+            continue;
+        }
+        if let Ok(ready) = poll(&mut fds, POLL_INTERVAL) {
+            if ready == 0 {
+                continue;
+            }
+            let mut shared = shared.lock().unwrap();
+            shared.drain();
+            let typed =
+                fds.last().unwrap().revents().is_some_and(|r| r.contains(PollFlags::POLLIN));
+            if forwarding && typed {
+                shared.forward_input();
+            }
+        }
+    }
+}
@@ -0,0 +1,106 @@
+//! Software watchpoints. Instead of using the (few, small) hardware debug registers, the pages
+//! holding the watched memory are write-protected in the inferior. Every write to those pages then
+//! raises a SIGSEGV, which the debugger intercepts: it unprotects the pages, single-steps the
+//! faulting instruction, protects them again, and checks whether the watched bytes changed.
+//!
+//! Writes made by the kernel on the inferior's behalf (e.g. `read(2)` into a watched buffer) fail
+//! with EFAULT instead of faulting, so they are not caught.
+//!
+//! Registers cannot be protected that way. While one is watched, the inferior is single-stepped
+//! and the register looked at after every instruction.
+
+use std::collections::HashMap;
+
+#[derive(Clone)]
+pub struct Watchpoint {
+    pub id: usize,
+    pub expr: String,
+    pub addr: usize,
+    pub len: usize,
+    /// Contents of the watched range when it was last checked.
+    pub value: Vec<u8>,
+}
+
+/// A register being watched, such as `$rbx`.
+pub struct RegisterWatch {
+    pub id: usize,
+    /// The register's name, as `info registers` shows it.
+    pub register: String,
+    /// The register's value when it was last checked.
+    pub value: u64,
+}
+
+pub fn page_size() -> usize {
+    unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize }
+}
+
+/// Returns the start address of every page overlapping `[addr, addr + len)`.
+pub fn pages_covering(addr: usize, len: usize) -> Vec<usize> {
+    let page_size = page_size();
+    let first = addr & !(page_size - 1);
+    (first..addr + len.max(1)).step_by(page_size).collect()
+}
+
+/// The pages that must stay write-protected for `watchpoints`, mapped to the protection they had
+/// originally (as looked up with `original_protection`).
+pub fn watched_pages(
+    watchpoints: &[Watchpoint],
+    original_protection: &HashMap<usize, i32>,
+) -> HashMap<usize, i32> {
+    let mut pages = HashMap::new();
+    for watchpoint in watchpoints {
+        for page in pages_covering(watchpoint.addr, watchpoint.len) {
+            if let Some(prot) = original_protection.get(&page) {
+                pages.insert(page, *prot);
+            }
+        }
+    }
+    pages
+}
+
+/// Returns the range of bytes (first changed byte to last changed byte, exclusive) that differ
+/// between `old` and `new`, which have the same length.
+pub fn changed_range(old: &[u8], new: &[u8]) -> Option<(usize, usize)> {
+    let differs = |i: &usize| old[*i] != new[*i];
+    let first = (0..old.len()).find(differs)?;
+    let last = (0..old.len()).rev().find(differs)?;
+    Some((first, last + 1))
+}
+
+/// Parses the permissions column of /proc/<pid>/maps (e.g. `rw-p`) into `PROT_*` flags.
+pub fn parse_protection(perms: &str) -> i32 {
+    let mut prot = libc::PROT_NONE;
+    for (flag, bit) in perms.chars().zip(&[libc::PROT_READ, libc::PROT_WRITE, libc::PROT_EXEC]) {
+        if flag != '-' {
+            prot |= bit;
+        }
+    }
+    prot
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_pages_covering() {
+        let page_size = page_size();
+        assert_eq!(pages_covering(page_size + 10, 4), vec![page_size]);
+        assert_eq!(pages_covering(page_size - 2, 4), vec![0, page_size]);
+        assert_eq!(pages_covering(0, 3 * page_size).len(), 3);
+    }
+
+    #[test]
+    fn test_changed_range() {
+        assert_eq!(changed_range(&[1, 2, 3, 4], &[1, 2, 3, 4]), None);
+        assert_eq!(changed_range(&[1, 2, 3, 4], &[1, 9, 3, 9]), Some((1, 4)));
+        assert_eq!(changed_range(&[1], &[2]), Some((0, 1)));
+    }
+
+    #[test]
+    fn test_parse_protection() {
+        assert_eq!(parse_protection("rw-p"), libc::PROT_READ | libc::PROT_WRITE);
+        assert_eq!(parse_protection("r-xp"), libc::PROT_READ | libc::PROT_EXEC);
+        assert_eq!(parse_protection("---p"), libc::PROT_NONE);
+    }
+}
@@ -0,0 +1,125 @@
+//! Scripts the debugger against the programs in `samples/`, the way a batch-mode session would.
+
+use deet::debugger::Debugger;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Compiles `samples/<name>.c` the way the Makefile does, returning the path of the program.
+fn build_sample(name: &str) -> String {
+    compile_sample(name, &["-g"])
+}
+
+fn compile_sample(name: &str, flags: &[&str]) -> String {
+    let samples = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("samples");
+    let source = samples.join(format!("{}.c", name));
+    let program = std::env::temp_dir().join(format!(
+        "deet-{}{}-{}",
+        name,
+        flags.concat(),
+        std::process::id()
+    ));
+    let status = Command::new("cc")
+        .args(flags)
+        .args(["-O0", "-no-pie", "-fno-omit-frame-pointer", "-o"])
+        .arg(&program)
+        .arg(&source)
+        .status()
+        .expect("Could not run cc");
+    assert!(status.success(), "Could not compile {}", source.display());
+    program.to_string_lossy().into_owned()
+}
+
+fn batch_debugger(program: &str) -> Debugger {
+    let mut debugger = Debugger::new(program).unwrap();
+    debugger.set_batch(true);
+    debugger
+}
+
+#[test]
+fn test_breakpoints_and_exit_code() {
+    let program = build_sample("function_calls");
+    let mut debugger = batch_debugger(&program);
+    debugger.execute_line("break func3").unwrap();
+    debugger.execute_line("run").unwrap();
+    debugger.execute_line("backtrace").unwrap();
+    debugger.execute_line("continue").unwrap();
+    assert_eq!(debugger.exit_code(), None);
+    debugger.execute_line("continue").unwrap();
+    assert_eq!(debugger.exit_code(), Some(0));
+    assert!(debugger.execute_line("continue").is_err());
+    let _ = std::fs::remove_file(program);
+}
+
+#[test]
+fn test_crash_exit_code() {
+    let program = build_sample("segfault");
+    let mut debugger = batch_debugger(&program);
+    debugger.execute_line("run").unwrap();
+    debugger.execute_line("print a").unwrap();
+    debugger.shut_down();
+    // As if the program had died of the SIGSEGV
+    assert_eq!(debugger.exit_code(), Some(139));
+    let _ = std::fs::remove_file(program);
+}
+
+#[test]
+fn test_without_debug_info() {
+    let program = compile_sample("function_calls", &[]);
+    let mut debugger = batch_debugger(&program);
+    debugger.execute_line("break func3").unwrap();
+    debugger.execute_line("run").unwrap();
+    debugger.execute_line("backtrace").unwrap();
+    debugger.execute_line("info registers").unwrap();
+    assert!(debugger.execute_line("print a").is_err());
+    debugger.execute_line("continue").unwrap();
+    debugger.execute_line("continue").unwrap();
+    assert_eq!(debugger.exit_code(), Some(0));
+    let _ = std::fs::remove_file(program);
+}
+
+#[test]
+fn test_rhai_script() {
+    let program = build_sample("function_calls");
+    let mut debugger = batch_debugger(&program);
+    let script = std::env::temp_dir().join(format!("deet-hook-{}.rhai", std::process::id()));
+    let hook = r#"
+        let id = break_at("func3");
+        on_stop(|event| {
+            if event.breakpoint != id || pc() != event.pc || evaluate("global") != "5" {
+                throw "stopped in the wrong place";
+            }
+            execute("continue");
+        });
+    "#;
+    std::fs::write(&script, hook).unwrap();
+    debugger.execute_line(&format!("source {}", script.display())).unwrap();
+    // The hook continues past the first stop at func3. The second stop, which its own continue
+    // runs into, does not run the hooks again.
+    debugger.execute_line("run").unwrap();
+    debugger.execute_line("rhai if breakpoints()[0].hits != 2 { throw \"hits\" }").unwrap();
+    assert!(debugger.execute_line("rhai write_memory(0, [1])").is_err());
+    debugger.execute_line("continue").unwrap();
+    assert_eq!(debugger.exit_code(), Some(0));
+    let _ = std::fs::remove_file(script);
+    let _ = std::fs::remove_file(program);
+}
+
+#[test]
+fn test_threads() {
+    let program = build_sample("threads");
+    let mut debugger = batch_debugger(&program);
+    debugger.execute_line("break all_named").unwrap();
+    debugger.execute_line("break work").unwrap();
+    debugger.execute_line("run").unwrap();
+    debugger.execute_line("info inferiors").unwrap();
+    // Each worker stops at work in turn, which switches to its thread
+    debugger.execute_line("continue").unwrap();
+    debugger.execute_line("next").unwrap();
+    debugger.execute_line("print id").unwrap();
+    debugger.execute_line("thread 1").unwrap();
+    assert!(debugger.execute_line("thread 9").is_err());
+    debugger.execute_line("continue").unwrap();
+    debugger.execute_line("continue").unwrap();
+    assert_eq!(debugger.exit_code(), Some(0));
+    let _ = std::fs::remove_file(program);
+}
@@ -0,0 +1,146 @@
+//! Pushes, pops, iterates over and sorts `LinkedList`, `Vec` and `VecDeque` of several sizes, so
+//! claims about how linked lists compare can be checked. Run with `cargo bench`, or e.g.
+//! `cargo bench -- sort` for a single group; criterion writes its reports under target/criterion.
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use linked_list::linked_list::LinkedList;
+use std::collections::VecDeque;
+
+const SIZES: [usize; 4] = [100, 1_000, 10_000, 100_000];
+
+// Scrambled but reproducible input, so that sorting has work to do and every run sorts the same
+fn values(n: usize) -> Vec<u64> {
+    let mut state = 0x2545_f491_4f6c_dd1d_u64;
+    (0..n)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        })
+        .collect()
+}
+
+fn push(c: &mut Criterion) {
+    let mut group = c.benchmark_group("push");
+    for &n in SIZES.iter() {
+        group.bench_with_input(BenchmarkId::new("LinkedList", n), &n, |b, &n| {
+            b.iter(|| {
+                let mut list = LinkedList::new();
+                for i in 0..n {
+                    list.push_back(i);
+                }
+                list
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("Vec", n), &n, |b, &n| {
+            b.iter(|| {
+                let mut vec = Vec::new();
+                for i in 0..n {
+                    vec.push(i);
+                }
+                vec
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("VecDeque", n), &n, |b, &n| {
+            b.iter(|| {
+                let mut deque = VecDeque::new();
+                for i in 0..n {
+                    deque.push_back(i);
+                }
+                deque
+            })
+        });
+    }
+    group.finish();
+}
+
+// Empties each collection from the end it is cheapest to take from: the front of the list, the
+// back of the Vec and the front of the VecDeque
+fn pop(c: &mut Criterion) {
+    let mut group = c.benchmark_group("pop");
+    for &n in SIZES.iter() {
+        group.bench_with_input(BenchmarkId::new("LinkedList", n), &n, |b, &n| {
+            b.iter_batched(
+                || (0..n).collect::<LinkedList<usize>>(),
+                |mut list| while let Some(value) = list.pop_front() { black_box(value); },
+                BatchSize::LargeInput,
+            )
+        });
+        group.bench_with_input(BenchmarkId::new("Vec", n), &n, |b, &n| {
+            b.iter_batched(
+                || (0..n).collect::<Vec<usize>>(),
+                |mut vec| while let Some(value) = vec.pop() { black_box(value); },
+                BatchSize::LargeInput,
+            )
+        });
+        group.bench_with_input(BenchmarkId::new("VecDeque", n), &n, |b, &n| {
+            b.iter_batched(
+                || (0..n).collect::<VecDeque<usize>>(),
+                |mut deque| while let Some(value) = deque.pop_front() { black_box(value); },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn iterate(c: &mut Criterion) {
+    let mut group = c.benchmark_group("iterate");
+    for &n in SIZES.iter() {
+        let list: LinkedList<u64> = values(n).into_iter().collect();
+        let vec = values(n);
+        let deque: VecDeque<u64> = values(n).into_iter().collect();
+        group.bench_with_input(BenchmarkId::new("LinkedList", n), &list, |b, list| {
+            b.iter(|| list.iter().fold(0u64, |sum, &value| sum.wrapping_add(value)))
+        });
+        group.bench_with_input(BenchmarkId::new("Vec", n), &vec, |b, vec| {
+            b.iter(|| vec.iter().fold(0u64, |sum, &value| sum.wrapping_add(value)))
+        });
+        group.bench_with_input(BenchmarkId::new("VecDeque", n), &deque, |b, deque| {
+            b.iter(|| deque.iter().fold(0u64, |sum, &value| sum.wrapping_add(value)))
+        });
+    }
+    group.finish();
+}
+
+// VecDeque has no sort of its own; sorting it means making it contiguous and sorting the slice
+fn sort(c: &mut Criterion) {
+    let mut group = c.benchmark_group("sort");
+    for &n in SIZES.iter() {
+        group.bench_with_input(BenchmarkId::new("LinkedList", n), &n, |b, &n| {
+            b.iter_batched(
+                || values(n).into_iter().collect::<LinkedList<u64>>(),
+                |mut list| {
+                    list.sort();
+                    list
+                },
+                BatchSize::LargeInput,
+            )
+        });
+        group.bench_with_input(BenchmarkId::new("Vec", n), &n, |b, &n| {
+            b.iter_batched(
+                || values(n),
+                |mut vec| {
+                    vec.sort();
+                    vec
+                },
+                BatchSize::LargeInput,
+            )
+        });
+        group.bench_with_input(BenchmarkId::new("VecDeque", n), &n, |b, &n| {
+            b.iter_batched(
+                || values(n).into_iter().collect::<VecDeque<u64>>(),
+                |mut deque| {
+                    deque.make_contiguous().sort();
+                    deque
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, push, pop, iterate, sort);
+criterion_main!(benches);
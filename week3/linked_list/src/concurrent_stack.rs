@@ -0,0 +1,105 @@
+use crate::linked_list::LinkedList;
+use std::sync::Mutex;
+
+/// A stack that any number of threads can push onto and pop from through a shared reference.
+/// It is a `LinkedList` behind a lock: a lock-free (Treiber) stack would also need hazard
+/// pointers or epochs to know when a popped node can be freed, which is more than this is for.
+pub struct ConcurrentStack<T> {
+    list: Mutex<LinkedList<T>>,
+}
+
+impl<T> ConcurrentStack<T> {
+    pub fn new() -> ConcurrentStack<T> {
+        ConcurrentStack {list: Mutex::new(LinkedList::new())}
+    }
+
+    pub fn push(&self, value: T) {
+        self.list.lock().unwrap().push_front(value);
+    }
+
+    pub fn pop(&self) -> Option<T> {
+        self.list.lock().unwrap().pop_front()
+    }
+
+    /// How many elements there are, which other threads may already have changed by the time
+    /// it is looked at.
+    pub fn get_size(&self) -> usize {
+        self.list.lock().unwrap().get_size()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.get_size() == 0
+    }
+
+    /// Takes the list out of the stack, with the most recently pushed element at the front.
+    pub fn into_inner(self) -> LinkedList<T> {
+        self.list.into_inner().unwrap()
+    }
+}
+
+impl<T> Default for ConcurrentStack<T> {
+    fn default() -> Self {
+        ConcurrentStack::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashSet;
+    use std::sync::Arc;
+    use std::thread;
+
+    const THREADS: usize = 8;
+    const PER_THREAD: usize = 10_000;
+
+    #[test]
+    fn test_push_and_pop_from_many_threads() {
+        let stack = Arc::new(ConcurrentStack::new());
+        let pushers: Vec<_> = (0..THREADS)
+            .map(|t| {
+                let stack = stack.clone();
+                thread::spawn(move || {
+                    for i in 0..PER_THREAD {
+                        stack.push(t * PER_THREAD + i);
+                    }
+                })
+            })
+            .collect();
+        for pusher in pushers {
+            pusher.join().unwrap();
+        }
+        assert_eq!(stack.get_size(), THREADS * PER_THREAD);
+
+        let poppers: Vec<_> = (0..THREADS)
+            .map(|_| {
+                let stack = stack.clone();
+                thread::spawn(move || {
+                    let mut popped = Vec::new();
+                    while let Some(value) = stack.pop() {
+                        popped.push(value);
+                    }
+                    popped
+                })
+            })
+            .collect();
+        let mut seen = HashSet::new();
+        for popper in poppers {
+            for value in popper.join().unwrap() {
+                assert!(seen.insert(value), "{} was popped twice", value);
+            }
+        }
+        assert_eq!(seen.len(), THREADS * PER_THREAD);
+        assert!(stack.is_empty());
+    }
+
+    #[test]
+    fn test_last_in_first_out() {
+        let stack = ConcurrentStack::new();
+        stack.push(1);
+        stack.push(2);
+        assert_eq!(stack.pop(), Some(2));
+        stack.push(3);
+        assert_eq!(Vec::from(stack.into_inner()), vec![3, 1]);
+    }
+}
@@ -0,0 +1,420 @@
+use alloc::boxed::Box;
+use core::fmt;
+use core::iter::{FromIterator, FusedIterator};
+use core::marker::PhantomData;
+use core::ptr::NonNull;
+
+type Link<T> = Option<NonNull<Node<T>>>;
+
+// The list owns every node; `prev` and `next` are raw pointers so that a node can be reached from
+// both of its neighbours. Nodes are allocated with Box::into_raw and given back with Box::from_raw
+// when they are removed.
+pub struct DoublyLinkedList<T> {
+    head: Link<T>,
+    tail: Link<T>,
+    size: usize,
+    marker: PhantomData<Box<Node<T>>>,
+}
+
+// Raw pointers make the list neither Send nor Sync on its own, so it says when it is, going by
+// these invariants:
+// - every node is reachable from `head` through `next` (and from `tail` through `prev`), and
+//   belongs to exactly one list, which frees it exactly once;
+// - nothing outside the list and the iterators borrowing it holds a pointer to a node;
+// - the list only hands out references to values with lifetimes tied to a borrow of the list
+//   itself, so `&self` gives shared access to the values and `&mut self` exclusive access.
+// The list is therefore like a `Box<[T]>`: sending it sends its values, and sharing it shares
+// them.
+unsafe impl<T: Send> Send for DoublyLinkedList<T> {}
+unsafe impl<T: Sync> Sync for DoublyLinkedList<T> {}
+
+struct Node<T> {
+    value: T,
+    prev: Link<T>,
+    next: Link<T>,
+}
+
+impl<T> Node<T> {
+    fn new(value: T, prev: Link<T>, next: Link<T>) -> NonNull<Node<T>> {
+        let node = Box::new(Node {value, prev, next});
+        unsafe { NonNull::new_unchecked(Box::into_raw(node)) }
+    }
+}
+
+impl<T> DoublyLinkedList<T> {
+    pub fn new() -> DoublyLinkedList<T> {
+        DoublyLinkedList {head: None, tail: None, size: 0, marker: PhantomData}
+    }
+
+    pub fn get_size(&self) -> usize {
+        self.size
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.get_size() == 0
+    }
+
+    pub fn push_front(&mut self, value: T) {
+        let node = Node::new(value, None, self.head);
+        match self.head {
+            Some(head) => unsafe { (*head.as_ptr()).prev = Some(node) },
+            None => self.tail = Some(node),
+        }
+        self.head = Some(node);
+        self.size += 1;
+    }
+
+    pub fn push_back(&mut self, value: T) {
+        let node = Node::new(value, self.tail, None);
+        match self.tail {
+            Some(tail) => unsafe { (*tail.as_ptr()).next = Some(node) },
+            None => self.head = Some(node),
+        }
+        self.tail = Some(node);
+        self.size += 1;
+    }
+
+    pub fn pop_front(&mut self) -> Option<T> {
+        let node = unsafe { Box::from_raw(self.head?.as_ptr()) };
+        self.head = node.next;
+        match self.head {
+            Some(head) => unsafe { (*head.as_ptr()).prev = None },
+            None => self.tail = None,
+        }
+        self.size -= 1;
+        Some(node.value)
+    }
+
+    pub fn pop_back(&mut self) -> Option<T> {
+        let node = unsafe { Box::from_raw(self.tail?.as_ptr()) };
+        self.tail = node.prev;
+        match self.tail {
+            Some(tail) => unsafe { (*tail.as_ptr()).next = None },
+            None => self.head = None,
+        }
+        self.size -= 1;
+        Some(node.value)
+    }
+
+    pub fn front(&self) -> Option<&T> {
+        self.head.map(|head| unsafe { &(*head.as_ptr()).value })
+    }
+
+    pub fn back(&self) -> Option<&T> {
+        self.tail.map(|tail| unsafe { &(*tail.as_ptr()).value })
+    }
+
+    /// Iterates from front to back; call `rev()` on it to go from back to front.
+    pub fn iter(&self) -> DoublyLinkedListIter<'_, T> {
+        DoublyLinkedListIter {
+            head: self.head,
+            tail: self.tail,
+            remaining: self.size,
+            marker: PhantomData,
+        }
+    }
+
+    pub fn iter_mut(&mut self) -> DoublyLinkedListIterMut<'_, T> {
+        DoublyLinkedListIterMut {
+            head: self.head,
+            tail: self.tail,
+            remaining: self.size,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for DoublyLinkedList<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, value) in self.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{}", value)?;
+        }
+        Ok(())
+    }
+}
+
+impl<T> Default for DoublyLinkedList<T> {
+    fn default() -> Self {
+        DoublyLinkedList::new()
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for DoublyLinkedList<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self).finish()
+    }
+}
+
+impl<T> FromIterator<T> for DoublyLinkedList<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut list = DoublyLinkedList::new();
+        for value in iter {
+            list.push_back(value);
+        }
+        list
+    }
+}
+
+impl<T> Drop for DoublyLinkedList<T> {
+    fn drop(&mut self) {
+        while self.pop_front().is_some() {}
+    }
+}
+
+// An iterator gives out `&T`s, like a `&[T]` does
+unsafe impl<'a, T: Sync> Send for DoublyLinkedListIter<'a, T> {}
+unsafe impl<'a, T: Sync> Sync for DoublyLinkedListIter<'a, T> {}
+
+pub struct DoublyLinkedListIter<'a, T> {
+    head: Link<T>,
+    tail: Link<T>,
+    // The two ends meet in the middle; counting what is left says when they have
+    remaining: usize,
+    marker: PhantomData<&'a Node<T>>,
+}
+
+impl<'a, T> Iterator for DoublyLinkedListIter<'a, T> {
+    type Item = &'a T;
+    fn next(&mut self) -> Option<&'a T> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let node = unsafe { &*self.head?.as_ptr() };
+        self.head = node.next;
+        self.remaining -= 1;
+        Some(&node.value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for DoublyLinkedListIter<'a, T> {
+    fn next_back(&mut self) -> Option<&'a T> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let node = unsafe { &*self.tail?.as_ptr() };
+        self.tail = node.prev;
+        self.remaining -= 1;
+        Some(&node.value)
+    }
+}
+
+impl<'a, T> ExactSizeIterator for DoublyLinkedListIter<'a, T> {}
+
+impl<'a, T> FusedIterator for DoublyLinkedListIter<'a, T> {}
+
+impl<'a, T> IntoIterator for &'a DoublyLinkedList<T> {
+    type Item = &'a T;
+    type IntoIter = DoublyLinkedListIter<'a, T>;
+    fn into_iter(self) -> DoublyLinkedListIter<'a, T> {
+        self.iter()
+    }
+}
+
+// A mutable iterator gives out `&mut T`s, like a `&mut [T]` does
+unsafe impl<'a, T: Send> Send for DoublyLinkedListIterMut<'a, T> {}
+unsafe impl<'a, T: Sync> Sync for DoublyLinkedListIterMut<'a, T> {}
+
+pub struct DoublyLinkedListIterMut<'a, T> {
+    head: Link<T>,
+    tail: Link<T>,
+    remaining: usize,
+    marker: PhantomData<&'a mut Node<T>>,
+}
+
+impl<'a, T> Iterator for DoublyLinkedListIterMut<'a, T> {
+    type Item = &'a mut T;
+    fn next(&mut self) -> Option<&'a mut T> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let node = unsafe { &mut *self.head?.as_ptr() };
+        self.head = node.next;
+        self.remaining -= 1;
+        Some(&mut node.value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for DoublyLinkedListIterMut<'a, T> {
+    fn next_back(&mut self) -> Option<&'a mut T> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let node = unsafe { &mut *self.tail?.as_ptr() };
+        self.tail = node.prev;
+        self.remaining -= 1;
+        Some(&mut node.value)
+    }
+}
+
+impl<'a, T> ExactSizeIterator for DoublyLinkedListIterMut<'a, T> {}
+
+impl<'a, T> FusedIterator for DoublyLinkedListIterMut<'a, T> {}
+
+impl<'a, T> IntoIterator for &'a mut DoublyLinkedList<T> {
+    type Item = &'a mut T;
+    type IntoIter = DoublyLinkedListIterMut<'a, T>;
+    fn into_iter(self) -> DoublyLinkedListIterMut<'a, T> {
+        self.iter_mut()
+    }
+}
+
+pub struct DoublyLinkedListIntoIter<T> {
+    list: DoublyLinkedList<T>,
+}
+
+impl<T> Iterator for DoublyLinkedListIntoIter<T> {
+    type Item = T;
+    fn next(&mut self) -> Option<T> {
+        self.list.pop_front()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.list.get_size(), Some(self.list.get_size()))
+    }
+}
+
+impl<T> DoubleEndedIterator for DoublyLinkedListIntoIter<T> {
+    fn next_back(&mut self) -> Option<T> {
+        self.list.pop_back()
+    }
+}
+
+impl<T> ExactSizeIterator for DoublyLinkedListIntoIter<T> {}
+
+impl<T> FusedIterator for DoublyLinkedListIntoIter<T> {}
+
+impl<T> IntoIterator for DoublyLinkedList<T> {
+    type Item = T;
+    type IntoIter = DoublyLinkedListIntoIter<T>;
+    fn into_iter(self) -> DoublyLinkedListIntoIter<T> {
+        DoublyLinkedListIntoIter {list: self}
+    }
+}
+
+// Run under Miri (`cargo +nightly miri test --lib doubly_linked_list`), these also check that the
+// pointer juggling above doesn't alias or free anything it shouldn't. The lists are shortened
+// there, as Miri is slow.
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::thread;
+
+    const LEN: usize = if cfg!(miri) { 20 } else { 10_000 };
+
+    // Records its id in a shared log when dropped
+    struct Noisy {
+        id: usize,
+        log: Rc<RefCell<Vec<usize>>>,
+    }
+
+    impl Drop for Noisy {
+        fn drop(&mut self) {
+            self.log.borrow_mut().push(self.id);
+        }
+    }
+
+    fn assert_send<T: Send>() {}
+    fn assert_sync<T: Sync>() {}
+
+    #[test]
+    fn test_send_and_sync() {
+        assert_send::<DoublyLinkedList<i32>>();
+        assert_sync::<DoublyLinkedList<i32>>();
+        assert_send::<DoublyLinkedListIter<'static, i32>>();
+        assert_send::<DoublyLinkedListIterMut<'static, i32>>();
+        assert_send::<DoublyLinkedListIntoIter<i32>>();
+    }
+
+    #[test]
+    fn test_move_to_another_thread() {
+        let list: DoublyLinkedList<usize> = (0..LEN).collect();
+        let list = thread::spawn(move || {
+            let mut list = list;
+            list.push_front(LEN);
+            list
+        })
+        .join()
+        .unwrap();
+        assert_eq!(list.get_size(), LEN + 1);
+        assert_eq!(list.front(), Some(&LEN));
+    }
+
+    #[test]
+    fn test_share_between_threads() {
+        let list: DoublyLinkedList<usize> = (0..LEN).collect();
+        let (forwards, backwards) = thread::scope(|scope| {
+            let forwards = scope.spawn(|| list.iter().sum::<usize>());
+            let backwards = scope.spawn(|| list.iter().rev().sum::<usize>());
+            (forwards.join().unwrap(), backwards.join().unwrap())
+        });
+        assert_eq!(forwards, (0..LEN).sum::<usize>());
+        assert_eq!(forwards, backwards);
+    }
+
+    #[test]
+    fn test_drop_order() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut list = DoublyLinkedList::new();
+        for id in 0..5 {
+            list.push_back(Noisy {id, log: log.clone()});
+        }
+        drop(list.pop_back());
+        drop(list.pop_front());
+        assert_eq!(*log.borrow(), vec![4, 0]);
+        drop(list);
+        // Each value is dropped exactly once, the rest front to back with the list
+        assert_eq!(*log.borrow(), vec![4, 0, 1, 2, 3]);
+
+        let mut iter = DoublyLinkedList::from_iter((0..3).map(|id| Noisy {id, log: log.clone()}))
+            .into_iter();
+        drop(iter.next_back());
+        drop(iter);
+        assert_eq!(log.borrow()[5..], [2, 0, 1]);
+    }
+
+    #[test]
+    fn test_iterating_from_both_ends() {
+        let mut list: DoublyLinkedList<usize> = (0..LEN).collect();
+        // Alternate ends, so the two cursors meet in the middle without handing out the same
+        // element twice
+        let mut iter = list.iter_mut();
+        let mut seen = 0;
+        while let Some(front) = iter.next() {
+            *front += 1;
+            seen += 1;
+            if let Some(back) = iter.next_back() {
+                *back += 1;
+                seen += 1;
+            }
+        }
+        assert_eq!(seen, LEN);
+        assert!(list.iter().cloned().eq(1..LEN + 1));
+    }
+
+    #[test]
+    fn test_emptying_and_refilling() {
+        let mut list = DoublyLinkedList::new();
+        for round in 0..3 {
+            list.push_back(round);
+            list.push_front(round);
+            assert_eq!(list.front(), list.back());
+            assert_eq!(list.pop_back(), Some(round));
+            assert_eq!(list.pop_back(), Some(round));
+            assert!(list.is_empty());
+            assert_eq!((list.front(), list.back()), (None, None));
+        }
+    }
+}
@@ -0,0 +1,14 @@
+//! Singly- and doubly-linked lists, and a persistent one. Apart from `ConcurrentStack`, which
+//! needs std's `Mutex`, the crate only needs `alloc`: turn off the default `std` feature to build
+//! it without the standard library for embedded and OS-course projects.
+
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "std")]
+pub mod concurrent_stack;
+pub mod doubly_linked_list;
+pub mod linked_list;
+pub mod persistent_list;
+pub mod stack_queue;
@@ -1,25 +1,172 @@
-use std::fmt;
-use std::option::Option;
+use alloc::alloc::{dealloc, Layout};
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::iter::{FromIterator, FusedIterator};
+use core::marker::PhantomData;
+use core::mem::{self, MaybeUninit};
+use core::option::Option;
+use core::ptr::{self, NonNull};
 
+/// Builds a list out of its elements, in order: `linked_list![1, 2, 3]`.
+#[macro_export]
+macro_rules! linked_list {
+    () => {
+        $crate::linked_list::LinkedList::new()
+    };
+    ($($value:expr),+ $(,)?) => {
+        $crate::linked_list::LinkedList::from([$($value),+])
+    };
+}
+
+type Link<T> = Option<NonNull<Node<T>>>;
+
+// The list owns every node, as DoublyLinkedList does; `next` is a raw pointer so that `tail` can
+// point at the last node without the chain from `head` owning it twice. Nodes are allocated with
+// Box::into_raw and given back with Box::from_raw when they are removed, unless the list has an
+// arena, which they are then taken from and given back to instead.
 pub struct LinkedList<T> {
-    head: Option<Box<Node<T>>>,
+    head: Link<T>,
+    // The last node, so that appending doesn't have to walk the whole list. None exactly when
+    // the list is empty.
+    tail: Link<T>,
     size: usize,
+    // Where the nodes live, for a list made with `with_arena`
+    arena: Option<Box<Arena<T>>>,
+    marker: PhantomData<Box<Node<T>>>,
 }
 
+// Raw pointers make the list neither Send nor Sync on its own. It is both whenever its elements
+// are, since every node belongs to exactly one list and is only reached through it, and
+// references to the values only ever come from borrowing the list.
+unsafe impl<T: Send> Send for LinkedList<T> {}
+unsafe impl<T: Sync> Sync for LinkedList<T> {}
+
 struct Node<T> {
     value: T,
-    next: Option<Box<Node<T>>>,
+    next: Link<T>,
 }
 
 impl<T> Node<T> {
-    pub fn new(value: T, next: Option<Box<Node<T>>>) -> Node<T> {
-        Node {value: value, next: next}
+    fn new(value: T, next: Link<T>) -> NonNull<Node<T>> {
+        let node = Box::new(Node {value, next});
+        unsafe { NonNull::new_unchecked(Box::into_raw(node)) }
+    }
+}
+
+// How many nodes the first slab of an arena holds. Each slab after it holds twice as many as the
+// one before.
+const FIRST_SLAB_LEN: usize = 16;
+
+// The node storage of a list made with `with_arena`: slabs of node-sized slots, handed out in
+// order. A slab never moves once allocated, so the nodes in it are linked by pointer just like
+// boxed ones. Slots given back are chained through their `next` and handed out again before any
+// new one; the slabs themselves are freed with the arena.
+struct Arena<T> {
+    slabs: Vec<NonNull<[MaybeUninit<Node<T>>]>>,
+    // How many slots of the last slab have been handed out
+    used: usize,
+    // The slots given back. Only their `next` is initialized.
+    free: Link<T>,
+}
+
+impl<T> Arena<T> {
+    fn new() -> Arena<T> {
+        Arena {slabs: Vec::new(), used: 0, free: None}
+    }
+
+    fn alloc(&mut self, value: T, next: Link<T>) -> NonNull<Node<T>> {
+        let slot = match self.free {
+            Some(slot) => {
+                self.free = unsafe { ptr::addr_of!((*slot.as_ptr()).next).read() };
+                slot
+            }
+            None => self.new_slot(),
+        };
+        unsafe { slot.as_ptr().write(Node {value, next}) };
+        slot
+    }
+
+    // Takes the value out of `node`, which has to be one of this arena's and unlinked, and puts
+    // its slot on the free list
+    fn free(&mut self, node: NonNull<Node<T>>) -> T {
+        unsafe {
+            let value = ptr::addr_of!((*node.as_ptr()).value).read();
+            ptr::addr_of_mut!((*node.as_ptr()).next).write(self.free);
+            self.free = Some(node);
+            value
+        }
+    }
+
+    // Hands out the next slot of the last slab, allocating a bigger one if it is full
+    fn new_slot(&mut self) -> NonNull<Node<T>> {
+        let len = match self.slabs.last() {
+            Some(slab) if self.used < slab.len() => None,
+            Some(slab) => Some(slab.len() * 2),
+            None => Some(FIRST_SLAB_LEN),
+        };
+        if let Some(len) = len {
+            let slab: Box<[MaybeUninit<Node<T>>]> =
+                (0..len).map(|_| MaybeUninit::uninit()).collect();
+            self.slabs.push(NonNull::from(Box::leak(slab)));
+            self.used = 0;
+        }
+        let slab = self.slabs[self.slabs.len() - 1].cast::<Node<T>>();
+        let slot = unsafe { NonNull::new_unchecked(slab.as_ptr().add(self.used)) };
+        self.used += 1;
+        slot
+    }
+}
+
+// The list drops its elements before its arena, so there are only the slabs left to free
+impl<T> Drop for Arena<T> {
+    fn drop(&mut self) {
+        for slab in &self.slabs {
+            unsafe { drop(Box::from_raw(slab.as_ptr())) }
+        }
     }
 }
 
 impl<T> LinkedList<T> {
     pub fn new() -> LinkedList<T> {
-        LinkedList {head: None, size: 0}
+        LinkedList {head: None, tail: None, size: 0, arena: None, marker: PhantomData}
+    }
+
+    /// Makes an empty list whose nodes are allocated from an arena of its own rather than one
+    /// by one: they are carved out of a few large slabs, which keeps them close together for
+    /// faster walks, and dropping the list frees the slabs instead of every node. Removed nodes'
+    /// slots are reused. Nodes can't move to another list's storage, so `append` and
+    /// `split_off` move the elements of an arena-backed list one at a time rather than splicing
+    /// chains.
+    pub fn with_arena() -> LinkedList<T> {
+        let arena = Some(Box::new(Arena::new()));
+        LinkedList {head: None, tail: None, size: 0, arena, marker: PhantomData}
+    }
+
+    // Makes an empty list that allocates its nodes the way this one does
+    fn new_like<U>(&self) -> LinkedList<U> {
+        match self.arena {
+            Some(_) => LinkedList::with_arena(),
+            None => LinkedList::new(),
+        }
+    }
+
+    fn new_node(&mut self, value: T, next: Link<T>) -> NonNull<Node<T>> {
+        match &mut self.arena {
+            Some(arena) => arena.alloc(value, next),
+            None => Node::new(value, next),
+        }
+    }
+
+    // Frees `node`, which has to be one of this list's and unlinked, returning its value
+    fn free_node(&mut self, node: NonNull<Node<T>>) -> T {
+        match &mut self.arena {
+            Some(arena) => arena.free(node),
+            None => unsafe { Box::from_raw(node.as_ptr()) }.value,
+        }
     }
     
     pub fn get_size(&self) -> usize {
@@ -31,103 +178,1384 @@ impl<T> LinkedList<T> {
     }
     
     pub fn push_front(&mut self, value: T) {
-        let new_node: Box<Node<T>> = Box::new(Node::new(value, self.head.take()));
-        self.head = Some(new_node);
+        let node = self.new_node(value, self.head);
+        if self.tail.is_none() {
+            self.tail = Some(node);
+        }
+        self.head = Some(node);
+        self.size += 1;
+    }
+
+    pub fn push_back(&mut self, value: T) {
+        let node = self.new_node(value, None);
+        self.push_back_node(node);
+    }
+
+    // Links in a node whose `next` is None as the new tail
+    fn push_back_node(&mut self, node: NonNull<Node<T>>) {
+        match self.tail {
+            Some(tail) => unsafe { (*tail.as_ptr()).next = Some(node) },
+            None => self.head = Some(node),
+        }
+        self.tail = Some(node);
         self.size += 1;
     }
     
     pub fn pop_front(&mut self) -> Option<T> {
-        let node: Box<Node<T>> = self.head.take()?;
-        self.head = node.next;
+        let head = self.head?;
+        self.head = unsafe { (*head.as_ptr()).next };
+        if self.head.is_none() {
+            self.tail = None;
+        }
+        self.size -= 1;
+        Some(self.free_node(head))
+    }
+
+    /// Moves all of `other`'s elements onto the end of this list, leaving `other` empty. The
+    /// chains are spliced together, so this takes constant time, unless either list has an
+    /// arena: `other`'s elements are then moved over one at a time.
+    pub fn append(&mut self, other: &mut LinkedList<T>) {
+        if self.arena.is_some() || other.arena.is_some() {
+            while let Some(value) = other.pop_front() {
+                self.push_back(value);
+            }
+            return;
+        }
+        let other_head = match other.head.take() {
+            Some(head) => head,
+            None => return,
+        };
+        match self.tail {
+            Some(tail) => unsafe { (*tail.as_ptr()).next = Some(other_head) },
+            None => self.head = Some(other_head),
+        }
+        self.tail = other.tail.take();
+        self.size += other.size;
+        other.size = 0;
+    }
+
+    pub fn front(&self) -> Option<&T> {
+        self.head.map(|head| unsafe { &(*head.as_ptr()).value })
+    }
+
+    pub fn front_mut(&mut self) -> Option<&mut T> {
+        self.head.map(|head| unsafe { &mut (*head.as_ptr()).value })
+    }
+
+    /// Returns the last element, in constant time since the list keeps track of its tail.
+    pub fn back(&self) -> Option<&T> {
+        self.tail.map(|tail| unsafe { &(*tail.as_ptr()).value })
+    }
+
+    pub fn back_mut(&mut self) -> Option<&mut T> {
+        self.tail.map(|tail| unsafe { &mut (*tail.as_ptr()).value })
+    }
+
+    /// Returns the element at position `index`, or None if the list is shorter than that.
+    pub fn get(&self, index: usize) -> Option<&T> {
+        self.iter().nth(index)
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        self.iter_mut().nth(index)
+    }
+
+    /// Returns the first element `pred` accepts.
+    pub fn find(&self, pred: impl Fn(&T) -> bool) -> Option<&T> {
+        self.iter().find(|value| pred(value))
+    }
+
+    /// Returns the index of the first element `pred` accepts.
+    pub fn position(&self, pred: impl Fn(&T) -> bool) -> Option<usize> {
+        self.iter().position(pred)
+    }
+
+    /// Inserts `value` so that it ends up at position `index`, shifting everything after it
+    /// back by one. Panics if `index > len`, like `Vec::insert`.
+    pub fn insert(&mut self, index: usize, value: T) {
+        let size = self.size;
+        assert!(index <= size, "insertion index (is {}) should be <= len (is {})", index, size);
+        let node = self.new_node(value, None);
+        let link = self.link_at(index);
+        unsafe { (*node.as_ptr()).next = *link };
+        *link = Some(node);
+        if index == size {
+            self.tail = Some(node);
+        }
+        self.size += 1;
+    }
+
+    /// Unlinks and returns the element at position `index`, or None if there is none.
+    pub fn remove(&mut self, index: usize) -> Option<T> {
+        if index.checked_add(1) == Some(self.size) {
+            self.tail = match index {
+                0 => None,
+                _ => *self.link_at(index - 1),
+            };
+        }
+        let link = self.link_at(index);
+        let node = (*link)?;
+        *link = unsafe { (*node.as_ptr()).next };
         self.size -= 1;
-        Some(node.value)
+        Some(self.free_node(node))
+    }
+
+    /// Splits the list in two at `at`: this list keeps the first `at` elements and the rest are
+    /// returned as a new list, which has an arena of its own if this one has one. Panics if
+    /// `at > len`, like `Vec::split_off`.
+    pub fn split_off(&mut self, at: usize) -> LinkedList<T> {
+        let size = self.size;
+        assert!(at <= size, "`at` split index (is {}) should be <= len (is {})", at, size);
+        if at == size {
+            return LinkedList::new();
+        }
+        let new_tail = match at {
+            0 => None,
+            _ => *self.link_at(at - 1),
+        };
+        let head = self.link_at(at).take();
+        let tail = self.tail;
+        self.tail = new_tail;
+        self.size = at;
+        if self.arena.is_none() {
+            return LinkedList {head, tail, size: size - at, arena: None, marker: PhantomData};
+        }
+        // The nodes stay in this list's arena, so their elements move to new ones
+        let mut rest = LinkedList::with_arena();
+        let mut current = head;
+        while let Some(node) = current {
+            current = unsafe { (*node.as_ptr()).next };
+            rest.push_back(self.free_node(node));
+        }
+        rest
+    }
+
+    /// Moves the first `k` elements to the back, in order, so that the element at position `k`
+    /// becomes the front. The nodes are re-linked rather than popped and pushed, so this takes
+    /// O(k) time and never moves an element. Panics if `k > len`, like `VecDeque::rotate_left`.
+    pub fn rotate_left(&mut self, k: usize) {
+        let size = self.size;
+        assert!(k <= size, "rotation amount (is {}) should be <= len (is {})", k, size);
+        if k == 0 || k == size {
+            return;
+        }
+        // Splitting and appending would move the elements of an arena-backed list, so the
+        // chain is closed into a ring and cut after the new tail instead. With 0 < k < len,
+        // the list has at least two nodes, and the new tail is one of them.
+        let tail = self.tail.unwrap();
+        let new_tail = self.link_at(k - 1).unwrap();
+        unsafe {
+            (*tail.as_ptr()).next = self.head;
+            self.head = (*new_tail.as_ptr()).next.take();
+        }
+        self.tail = Some(new_tail);
+    }
+
+    /// Moves the last `k` elements to the front, in order. This is `rotate_left(len - k)`: the
+    /// list only links forwards, so finding where the last `k` elements start means walking past
+    /// the `len - k` before them, which makes this O(len - k) rather than O(k). Panics if
+    /// `k > len`, like `VecDeque::rotate_right`.
+    pub fn rotate_right(&mut self, k: usize) {
+        let size = self.size;
+        assert!(k <= size, "rotation amount (is {}) should be <= len (is {})", k, size);
+        self.rotate_left(size - k);
+    }
+
+    // Returns the link that points at the node at position `index` (the head for 0), walking
+    // no further than the end of the list.
+    fn link_at(&mut self, index: usize) -> &mut Link<T> {
+        let mut link = &mut self.head;
+        for _ in 0..index {
+            match *link {
+                Some(node) => link = unsafe { &mut (*node.as_ptr()).next },
+                None => break,
+            }
+        }
+        link
+    }
+
+    /// Reverses the list in place by re-linking its nodes, without moving any values.
+    pub fn reverse(&mut self) {
+        self.tail = self.head;
+        let mut reversed: Link<T> = None;
+        let mut current = self.head;
+        while let Some(node) = current {
+            unsafe {
+                current = (*node.as_ptr()).next;
+                (*node.as_ptr()).next = reversed;
+            }
+            reversed = Some(node);
+        }
+        self.head = reversed;
+    }
+
+    pub fn sort(&mut self)
+    where
+        T: Ord,
+    {
+        self.sort_by(|a, b| a.cmp(b));
+    }
+
+    /// Sorts the list with a stable, bottom-up merge sort that re-links the nodes: sorted runs
+    /// of 1, 2, 4, ... nodes are merged pairwise until a single run is left. Takes O(n log n)
+    /// time and constant extra space, and never moves or clones an element. If `cmp` panics,
+    /// the list keeps all of its elements, in some order.
+    pub fn sort_by<F>(&mut self, mut cmp: F)
+    where
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        let mut runs = SortRuns {list: self, pending: None, left: None, right: None};
+        let mut width = 1;
+        loop {
+            runs.pending = runs.list.head.take();
+            let mut tail = &mut runs.list.head;
+            let mut merges = 0;
+            while runs.pending.is_some() {
+                runs.left = runs.pending.take();
+                runs.right = split_after(&mut runs.left, width);
+                runs.pending = split_after(&mut runs.right, width);
+                tail = merge(tail, &mut runs.left, &mut runs.right, &mut cmp);
+                merges += 1;
+            }
+            if merges <= 1 {
+                break;
+            }
+            width *= 2;
+        }
+    }
+
+    /// Removes consecutive elements that map to the same key, keeping the first of each run.
+    pub fn dedup_by_key<K, F>(&mut self, mut key: F)
+    where
+        K: PartialEq,
+        F: FnMut(&mut T) -> K,
+    {
+        self.dedup_by(|a, b| key(a) == key(b));
+    }
+
+    /// Removes consecutive elements that `same_bucket` considers the same, keeping the first of
+    /// each run. As with `Vec::dedup_by`, `same_bucket` is passed the later element first.
+    pub fn dedup_by<F>(&mut self, mut same_bucket: F)
+    where
+        F: FnMut(&mut T, &mut T) -> bool,
+    {
+        let mut current = self.head;
+        while let Some(node) = current {
+            let node_ref = unsafe { &mut *node.as_ptr() };
+            while let Some(next) = node_ref.next {
+                if !same_bucket(unsafe { &mut (*next.as_ptr()).value }, &mut node_ref.value) {
+                    break;
+                }
+                node_ref.next = unsafe { (*next.as_ptr()).next };
+                if node_ref.next.is_none() {
+                    self.tail = Some(node);
+                }
+                self.size -= 1;
+                self.free_node(next);
+            }
+            current = node_ref.next;
+        }
+    }
+
+    /// Consumes the list and returns one of `f` applied to each element, in order. When a node
+    /// of the new list is the same size and alignment as a node of this one, each node's
+    /// allocation is reused for its mapped element, so no allocation happens at all. A list with
+    /// an arena is mapped into a new list with an arena of its own.
+    pub fn map<U, F>(self, mut f: F) -> LinkedList<U>
+    where
+        F: FnMut(T) -> U,
+    {
+        self.filter_map(|value| Some(f(value)))
+    }
+
+    /// Consumes the list and returns the elements `f` maps to Some, in order. Like `map`, a node
+    /// is reused for the element it is mapped to whenever the layouts allow it; the nodes of
+    /// elements mapped to None are freed.
+    pub fn filter_map<U, F>(mut self, mut f: F) -> LinkedList<U>
+    where
+        F: FnMut(T) -> Option<U>,
+    {
+        let mut mapped = self.new_like();
+        self.tail = None;
+        // The nodes still to map stay on this list, so that if `f` panics they are freed by its
+        // drop
+        while let Some(head) = self.head {
+            self.head = unsafe { (*head.as_ptr()).next.take() };
+            self.size -= 1;
+            let node = match self.arena {
+                // The arena's slots go with it, so the mapped elements get nodes of their own
+                Some(_) => f(self.free_node(head)).map(|value| mapped.new_node(value, None)),
+                None => map_node(unsafe { Box::from_raw(head.as_ptr()) }, &mut f),
+            };
+            if let Some(node) = node {
+                mapped.push_back_node(node);
+            }
+        }
+        mapped
+    }
+
+    /// Returns an iterator that removes the elements `pred` accepts and yields them, in order.
+    /// Elements are only looked at as the iterator is advanced: dropping it early leaves the rest
+    /// of the list as it was.
+    pub fn extract_if<F>(&mut self, pred: F) -> LinkedListExtractIf<'_, T, F>
+    where
+        F: FnMut(&mut T) -> bool,
+    {
+        LinkedListExtractIf {list: self, prev: None, pred}
+    }
+
+    /// Returns a cursor on the first element (or on the "ghost" position past the end, if the
+    /// list is empty), for editing the list in place as it is walked.
+    pub fn cursor_front_mut(&mut self) -> CursorMut<'_, T> {
+        CursorMut {list: self, prev: None, index: 0}
+    }
+
+    pub fn iter(&self) -> LinkedListIter<'_, T> {
+        LinkedListIter {current: self.head, remaining: self.size, marker: PhantomData}
+    }
+
+    pub fn iter_mut(&mut self) -> LinkedListIterMut<'_, T> {
+        LinkedListIterMut {current: self.head, remaining: self.size, marker: PhantomData}
+    }
+}
+
+impl<T: PartialEq> LinkedList<T> {
+    /// Whether some element equals `value`, stopping at the first that does.
+    pub fn contains(&self, value: &T) -> bool {
+        self.iter().any(|element| element == value)
+    }
+
+    /// Removes consecutive equal elements, like `Vec::dedup`.
+    pub fn dedup(&mut self) {
+        self.dedup_by(|a, b| a == b);
+    }
+}
+
+// Maps the value of an unlinked node, putting the result back in the same allocation when a
+// Node<U> has exactly the layout of a Node<T>. The allocation is freed if `f` returns None or
+// panics.
+fn map_node<T, U, F>(node: Box<Node<T>>, f: F) -> Option<NonNull<Node<U>>>
+where
+    F: FnOnce(T) -> Option<U>,
+{
+    if Layout::new::<Node<T>>() != Layout::new::<Node<U>>() {
+        return f(node.value).map(|value| Node::new(value, None));
+    }
+
+    struct Allocation<T>(*mut Node<T>);
+    impl<T> Drop for Allocation<T> {
+        fn drop(&mut self) {
+            unsafe { dealloc(self.0 as *mut u8, Layout::new::<Node<T>>()) }
+        }
+    }
+
+    let raw = Box::into_raw(node);
+    let allocation = Allocation(raw);
+    // The node is unlinked, so `next` is None and moving the value out is all there is to it.
+    // From here on the allocation only holds uninitialized memory, which `allocation` frees.
+    let value = unsafe { ptr::read(&(*raw).value) };
+    let value = f(value)?;
+    mem::forget(allocation);
+    let raw = raw as *mut Node<U>;
+    // A Node<U> fits the allocation exactly, so it can be written there and later handed back
+    // to a Box, which will free it with the layout it was allocated with
+    unsafe {
+        ptr::write(raw, Node {value, next: None});
+        Some(NonNull::new_unchecked(raw))
+    }
+}
+
+// Cuts the chain starting at `link` after `n` nodes, returning the rest of it
+fn split_after<T>(link: &mut Link<T>, n: usize) -> Link<T> {
+    let mut link = link;
+    for _ in 0..n {
+        match *link {
+            Some(node) => link = unsafe { &mut (*node.as_ptr()).next },
+            None => return None,
+        }
+    }
+    link.take()
+}
+
+// Moves the nodes of the sorted chains `left` and `right` onto `tail`, the empty link that ends
+// a chain, in order, taking from `left` on ties so that sorting stays stable. Returns the link
+// that ends the chain afterwards. A node is only moved once `cmp` has returned, so if it panics
+// every node is still on one of the three chains.
+fn merge<'a, T, F>(
+    mut tail: &'a mut Link<T>,
+    left: &mut Link<T>,
+    right: &mut Link<T>,
+    cmp: &mut F,
+) -> &'a mut Link<T>
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    while let (Some(l), Some(r)) = (*left, *right) {
+        let (l_value, r_value) = unsafe { (&(*l.as_ptr()).value, &(*r.as_ptr()).value) };
+        let (source, node) = match cmp(r_value, l_value) {
+            Ordering::Less => (&mut *right, r),
+            _ => (&mut *left, l),
+        };
+        unsafe {
+            *source = (*node.as_ptr()).next.take();
+            *tail = Some(node);
+            tail = &mut (*node.as_ptr()).next;
+        }
+    }
+    *tail = left.take().or_else(|| right.take());
+    while let Some(node) = *tail {
+        tail = unsafe { &mut (*node.as_ptr()).next };
+    }
+    tail
+}
+
+// The chains `sort_by` has cut the list into. Every node is either on the list or on exactly one
+// of them, and dropping this puts them back on the end of the list and finds its new tail: once
+// sorting is done they are all empty, and if a comparison panics, no element is lost.
+struct SortRuns<'a, T> {
+    list: &'a mut LinkedList<T>,
+    pending: Link<T>,
+    left: Link<T>,
+    right: Link<T>,
+}
+
+impl<'a, T> Drop for SortRuns<'a, T> {
+    fn drop(&mut self) {
+        let chains = [self.left.take(), self.right.take(), self.pending.take()];
+        let mut chains = IntoIterator::into_iter(chains);
+        let list = &mut *self.list;
+        list.tail = None;
+        let mut link = &mut list.head;
+        loop {
+            while let Some(node) = *link {
+                list.tail = Some(node);
+                link = unsafe { &mut (*node.as_ptr()).next };
+            }
+            match chains.next() {
+                Some(chain) => *link = chain,
+                None => break,
+            }
+        }
     }
 }
 
 //只有T实现了fmt::Display trait，才为LinkedList<T>实现fmt::Display
 impl <T: fmt::Display> fmt::Display for LinkedList<T> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let mut current: &Option<Box<Node<T>>> = &self.head;
+        self.write_joined(f, " ")
+    }
+}
+
+impl<T: fmt::Display> LinkedList<T> {
+    /// Formats the elements with `sep` between each of them: `list.join(", ")`.
+    pub fn join(&self, sep: &str) -> String {
         let mut result = String::new();
-        loop {
-            match current {
-                Some(node) => {
-                    result = format!("{} {}", result, node.value);
-                    current = &node.next;
-                },
-                None => break,
+        // Writing to a String can't fail
+        let _ = self.write_joined(&mut result, sep);
+        result
+    }
+
+    fn write_joined(&self, out: &mut impl fmt::Write, sep: &str) -> fmt::Result {
+        for (i, value) in self.iter().enumerate() {
+            if i > 0 {
+                out.write_str(sep)?;
             }
+            write!(out, "{}", value)?;
         }
-        write!(f, "{}", result)
+        Ok(())
+    }
+}
+
+impl<T> Default for LinkedList<T> {
+    fn default() -> Self {
+        LinkedList::new()
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for LinkedList<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self).finish()
     }
 }
 
 impl <T> Drop for LinkedList<T> {
     fn drop(&mut self) {
-        let mut current = self.head.take();
-        while let Some(mut node) = current {
-            current = node.next.take();
-        }
+        while self.pop_front().is_some() {}
     }
 }
 
 
-impl <T:Clone> Clone for Node<T> {
+// Copies the chain front to back, one node at a time: cloning node by node recursively would
+// overflow the stack on long lists. The copy has an arena if the original does.
+impl <T:Clone> Clone for LinkedList<T> {
     fn clone(&self) -> Self {
-        Self {
-            value: self.value.clone(),
-            next: self.next.clone(),
+        let mut list = self.new_like();
+        for value in self {
+            list.push_back(value.clone());
         }
+        list
     }
 }
 
-impl <T:Clone> Clone for LinkedList<T> {
-    fn clone(&self) -> Self {
-        Self {
-            head: self.head.clone(),
-            size: self.size.clone(),
+// Walks both chains side by side rather than comparing node by node recursively, which would
+// overflow the stack on long lists
+impl <T: PartialEq> PartialEq for LinkedList<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.size == other.size && self.iter().eq(other.iter())
+    }
+}
+
+// Appends at the end of the list as it goes, so the list comes out in the iterator's order
+impl<T> FromIterator<T> for LinkedList<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut list = LinkedList::new();
+        for value in iter {
+            list.push_back(value);
         }
+        list
     }
 }
 
-impl <T: PartialEq> PartialEq for Node<T> {
-    fn eq(&self, other: &Self) -> bool {
-        self.value == other.value && self.next == other.next
+impl<T> From<Vec<T>> for LinkedList<T> {
+    fn from(vec: Vec<T>) -> Self {
+        vec.into_iter().collect()
     }
 }
 
-impl <T: PartialEq> PartialEq for LinkedList<T> {
-    fn eq(&self, other: &Self) -> bool {
-        self.size == other.size && self.head == other.head
+impl<T, const N: usize> From<[T; N]> for LinkedList<T> {
+    fn from(array: [T; N]) -> Self {
+        IntoIterator::into_iter(array).collect()
+    }
+}
+
+impl<T> From<LinkedList<T>> for Vec<T> {
+    fn from(list: LinkedList<T>) -> Self {
+        list.into_iter().collect()
+    }
+}
+
+impl<T: Eq> Eq for LinkedList<T> {}
+
+// Compares element by element, as Vec does: the first difference decides, and a list that runs
+// out first is the smaller
+impl<T: PartialOrd> PartialOrd for LinkedList<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.iter().partial_cmp(other.iter())
+    }
+}
+
+impl<T: Ord> Ord for LinkedList<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.iter().cmp(other.iter())
+    }
+}
+
+// Hashes the length first, as Vec does, so that lists nested in a tuple can't run together
+impl<T: Hash> Hash for LinkedList<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.size.hash(state);
+        for value in self {
+            value.hash(state);
+        }
     }
 }
 
+// An iterator gives out `&T`s, like a `&[T]` does
+unsafe impl<'a, T: Sync> Send for LinkedListIter<'a, T> {}
+unsafe impl<'a, T: Sync> Sync for LinkedListIter<'a, T> {}
+
 pub struct LinkedListIter<'a, T> {
-    current: &'a Option<Box<Node<T>>>,
+    current: Link<T>,
+    // How many elements are left, taken from the list's size so that it needn't be counted
+    remaining: usize,
+    marker: PhantomData<&'a Node<T>>,
+}
+
+impl<'a, T> Iterator for LinkedListIter<'a, T> {
+    type Item = &'a T;
+    fn next(&mut self) -> Option<&'a T> {
+        let node = unsafe { &*self.current?.as_ptr() };
+        self.current = node.next;
+        self.remaining -= 1;
+        Some(&node.value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
 }
 
-impl <'a, T: Clone> Iterator for LinkedListIter<'_, T> {
+impl<'a, T> ExactSizeIterator for LinkedListIter<'a, T> {}
+
+impl<'a, T> FusedIterator for LinkedListIter<'a, T> {}
+
+impl<'a, T> IntoIterator for &'a LinkedList<T> {
+    type Item = &'a T;
+    type IntoIter = LinkedListIter<'a, T>;
+    fn into_iter(self) -> LinkedListIter<'a, T> {
+        self.iter()
+    }
+}
+
+// A mutable iterator gives out `&mut T`s, like a `&mut [T]` does
+unsafe impl<'a, T: Send> Send for LinkedListIterMut<'a, T> {}
+unsafe impl<'a, T: Sync> Sync for LinkedListIterMut<'a, T> {}
+
+pub struct LinkedListIterMut<'a, T> {
+    current: Link<T>,
+    remaining: usize,
+    marker: PhantomData<&'a mut Node<T>>,
+}
+
+impl<'a, T> Iterator for LinkedListIterMut<'a, T> {
+    type Item = &'a mut T;
+    fn next(&mut self) -> Option<&'a mut T> {
+        let node = unsafe { &mut *self.current?.as_ptr() };
+        self.current = node.next;
+        self.remaining -= 1;
+        Some(&mut node.value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, T> ExactSizeIterator for LinkedListIterMut<'a, T> {}
+
+impl<'a, T> FusedIterator for LinkedListIterMut<'a, T> {}
+
+impl<'a, T> IntoIterator for &'a mut LinkedList<T> {
+    type Item = &'a mut T;
+    type IntoIter = LinkedListIterMut<'a, T>;
+    fn into_iter(self) -> LinkedListIterMut<'a, T> {
+        self.iter_mut()
+    }
+}
+
+pub struct LinkedListExtractIf<'a, T, F> {
+    list: &'a mut LinkedList<T>,
+    // The last node kept, which becomes the list's tail if everything after it is removed. Its
+    // `next` (the list's head while it is None) links to the next node to look at.
+    prev: Link<T>,
+    pred: F,
+}
+
+impl<'a, T, F: FnMut(&mut T) -> bool> Iterator for LinkedListExtractIf<'a, T, F> {
     type Item = T;
     fn next(&mut self) -> Option<T> {
-        match self.current {
+        loop {
+            let link = match self.prev {
+                Some(prev) => unsafe { &mut (*prev.as_ptr()).next },
+                None => &mut self.list.head,
+            };
+            let node = (*link)?;
+            if (self.pred)(unsafe { &mut (*node.as_ptr()).value }) {
+                *link = unsafe { (*node.as_ptr()).next };
+                if link.is_none() {
+                    self.list.tail = self.prev;
+                }
+                self.list.size -= 1;
+                return Some(self.list.free_node(node));
+            }
+            self.prev = Some(node);
+        }
+    }
+}
+
+/// A position in a list that elements can be inserted at and removed from in constant time,
+/// after the design of std's (unstable) `CursorMut`. Besides sitting on an element, the cursor can
+/// sit on a "ghost" position past the last one, from which `move_next` wraps around to the front.
+/// The list is singly linked, so the cursor only moves forwards.
+pub struct CursorMut<'a, T> {
+    list: &'a mut LinkedList<T>,
+    // The node before the current one (the last node on the ghost), None at the head. Its `next`
+    // is the link to the current node; that link is looked up afresh rather than kept, as a
+    // pointer into the list's head would not outlive the next use of `list`
+    prev: Link<T>,
+    index: usize,
+}
+
+impl<'a, T> CursorMut<'a, T> {
+    /// The position of the current element, or None on the ghost.
+    pub fn index(&self) -> Option<usize> {
+        let current = match self.prev {
+            Some(node) => unsafe { (*node.as_ptr()).next },
+            None => self.list.head,
+        };
+        current.map(|_| self.index)
+    }
+
+    pub fn current(&mut self) -> Option<&mut T> {
+        self.current_node().map(|node| &mut node.value)
+    }
+
+    /// Moves on to the next element, onto the ghost from the last one, and from the ghost to
+    /// the front of the list.
+    pub fn move_next(&mut self) {
+        match *self.link() {
             Some(node) => {
-                // YOU FILL THIS IN!
-                self.current = &node.next;
-                Some(node.value.clone())
-            },
-            None => None,// YOU FILL THIS IN!
+                self.prev = Some(node);
+                self.index += 1;
+            }
+            None => {
+                self.prev = None;
+                self.index = 0;
+            }
+        }
+    }
+
+    /// Inserts `value` just before the current element, which stays current. On the ghost, that
+    /// makes it the new last element.
+    pub fn insert_before(&mut self, value: T) {
+        let node = self.list.new_node(value, None);
+        let link = self.link();
+        let at_end = link.is_none();
+        unsafe { (*node.as_ptr()).next = *link };
+        *link = Some(node);
+        if at_end {
+            self.list.tail = Some(node);
+        }
+        self.prev = Some(node);
+        self.index += 1;
+        self.list.size += 1;
+    }
+
+    /// Inserts `value` just after the current element. On the ghost, that makes it the new
+    /// first element.
+    pub fn insert_after(&mut self, value: T) {
+        if self.list.is_empty() {
+            // The ghost sits at the head, which is where the element goes
+            return self.insert_before(value);
+        }
+        let node = match *self.link() {
+            Some(node) => node,
+            None => {
+                self.list.push_front(value);
+                self.index = self.list.size;
+                return;
+            }
+        };
+        let next = unsafe { (*node.as_ptr()).next };
+        let new_node = self.list.new_node(value, next);
+        unsafe { (*node.as_ptr()).next = Some(new_node) };
+        if next.is_none() {
+            self.list.tail = Some(new_node);
+        }
+        self.list.size += 1;
+    }
+
+    /// Unlinks the current element and returns it, leaving the cursor on the element that
+    /// followed it. Does nothing on the ghost.
+    pub fn remove_current(&mut self) -> Option<T> {
+        let link = self.link();
+        let node = (*link)?;
+        *link = unsafe { (*node.as_ptr()).next };
+        if link.is_none() {
+            self.list.tail = self.prev;
         }
+        self.list.size -= 1;
+        Some(self.list.free_node(node))
     }
+
+    fn current_node(&mut self) -> Option<&mut Node<T>> {
+        self.link().map(|node| unsafe { &mut *node.as_ptr() })
+    }
+
+    // The link pointing at the current node, or the one at the end of the list on the ghost
+    fn link(&mut self) -> &mut Link<T> {
+        match self.prev {
+            Some(node) => unsafe { &mut (*node.as_ptr()).next },
+            None => &mut self.list.head,
+        }
+    }
+}
+
+pub struct LinkedListIntoIter<T> {
+    list: LinkedList<T>,
 }
 
-impl<'a, T: Clone> IntoIterator for &'a LinkedList<T> {
+impl<T> Iterator for LinkedListIntoIter<T> {
     type Item = T;
-    type IntoIter = LinkedListIter<'a, T>;
-    fn into_iter(self) -> LinkedListIter<'a, T> {
-        Self::IntoIter {
-            current: &self.head,
+    fn next(&mut self) -> Option<T> {
+        self.list.pop_front()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.list.get_size(), Some(self.list.get_size()))
+    }
+}
+
+impl<T> ExactSizeIterator for LinkedListIntoIter<T> {}
+
+impl<T> FusedIterator for LinkedListIntoIter<T> {}
+
+impl<T> IntoIterator for LinkedList<T> {
+    type Item = T;
+    type IntoIter = LinkedListIntoIter<T>;
+    fn into_iter(self) -> LinkedListIntoIter<T> {
+        LinkedListIntoIter {list: self}
+    }
+}
+
+// Lists are (de)serialized as plain sequences, the way Vec is
+#[cfg(feature = "serde")]
+mod serde_impls {
+    use super::LinkedList;
+    use serde::de::{Deserialize, Deserializer, SeqAccess, Visitor};
+    use serde::ser::{Serialize, Serializer};
+    use core::fmt;
+    use core::marker::PhantomData;
+
+    impl<T: Serialize> Serialize for LinkedList<T> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_seq(self)
+        }
+    }
+
+    struct LinkedListVisitor<T> {
+        marker: PhantomData<T>,
+    }
+
+    impl<'de, T: Deserialize<'de>> Visitor<'de> for LinkedListVisitor<T> {
+        type Value = LinkedList<T>;
+
+        fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("a sequence")
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<LinkedList<T>, A::Error> {
+            let mut list = LinkedList::new();
+            while let Some(value) = seq.next_element()? {
+                list.push_back(value);
+            }
+            Ok(list)
+        }
+    }
+
+    impl<'de, T: Deserialize<'de>> Deserialize<'de> for LinkedList<T> {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            deserializer.deserialize_seq(LinkedListVisitor {marker: PhantomData})
         }
     }
 }
 
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::hash_map::DefaultHasher;
+    use std::collections::HashSet;
 
+    fn hash_of<T: Hash>(value: &T) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn test_equal_lists_hash_identically() {
+        let a: LinkedList<i32> = (1..5).collect();
+        let b: LinkedList<i32> = (1..5).collect();
+        let c: LinkedList<i32> = (1..6).collect();
+        assert_eq!(a, b);
+        assert_eq!(hash_of(&a), hash_of(&b));
+        assert_ne!(a, c);
+
+        let mut set = HashSet::new();
+        set.insert(a);
+        assert!(set.contains(&b));
+        assert!(!set.contains(&c));
+    }
+
+    #[test]
+    fn test_lexicographic_order() {
+        let list = |values: &[i32]| values.iter().cloned().collect::<LinkedList<i32>>();
+        assert!(list(&[1, 2, 3]) < list(&[1, 3]));
+        assert!(list(&[1, 2]) < list(&[1, 2, 0]));
+        assert!(list(&[]) < list(&[0]));
+        assert_eq!(list(&[4, 5]).cmp(&list(&[4, 5])), Ordering::Equal);
+
+        let mut lists = vec![list(&[2]), list(&[1, 9]), list(&[1])];
+        lists.sort();
+        assert_eq!(lists, vec![list(&[1]), list(&[1, 9]), list(&[2])]);
+    }
+
+    #[test]
+    fn test_insert() {
+        let mut list: LinkedList<i32> = vec![1, 3].into_iter().collect();
+        list.insert(1, 2);
+        list.insert(3, 4);
+        list.insert(0, 0);
+        assert_eq!(list.iter().cloned().collect::<Vec<_>>(), vec![0, 1, 2, 3, 4]);
+        assert_eq!(list.get_size(), 5);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_insert_out_of_range() {
+        let mut list: LinkedList<i32> = LinkedList::new();
+        list.insert(1, 0);
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut list: LinkedList<i32> = (0..5).collect();
+        assert_eq!(list.remove(2), Some(2));
+        assert_eq!(list.remove(3), Some(4));
+        assert_eq!(list.remove(3), None);
+        assert_eq!(list.remove(0), Some(0));
+        assert_eq!(list.iter().cloned().collect::<Vec<_>>(), vec![1, 3]);
+        assert_eq!(list.get_size(), 2);
+        assert_eq!(list.remove(usize::MAX), None);
+        assert_eq!(list.get_size(), 2);
+    }
+
+    #[test]
+    fn test_get() {
+        let mut list: LinkedList<i32> = (0..3).collect();
+        assert_eq!(list.get(1), Some(&1));
+        assert_eq!(list.get(3), None);
+        *list.get_mut(2).unwrap() = 20;
+        assert_eq!(list.get(2), Some(&20));
+        assert_eq!(list.get_mut(3), None);
+    }
+
+    #[test]
+    fn test_contains() {
+        let list: LinkedList<String> = vec!["a".to_string(), "b".to_string()].into_iter().collect();
+        assert!(list.contains(&"b".to_string()));
+        assert!(!list.contains(&"c".to_string()));
+        assert!(!LinkedList::new().contains(&0));
+    }
+
+    #[test]
+    fn test_find_and_position() {
+        let list: LinkedList<i32> = vec![3, 8, 5, 10].into_iter().collect();
+        assert_eq!(list.find(|value| value % 2 == 0), Some(&8));
+        assert_eq!(list.position(|value| value % 2 == 0), Some(1));
+        assert_eq!(list.find(|value| *value > 10), None);
+        assert_eq!(list.position(|value| *value > 10), None);
+    }
+
+    #[test]
+    fn test_reverse() {
+        let mut empty: LinkedList<i32> = LinkedList::new();
+        empty.reverse();
+        assert!(empty.is_empty());
+
+        let mut single: LinkedList<i32> = (0..1).collect();
+        single.reverse();
+        assert_eq!(single, (0..1).collect());
+
+        let mut long: LinkedList<i32> = (0..100_000).collect();
+        long.reverse();
+        assert_eq!(long.get_size(), 100_000);
+        assert!(long.iter().cloned().eq((0..100_000).rev()));
+    }
+
+    #[test]
+    fn test_append() {
+        let mut list: LinkedList<i32> = (0..3).collect();
+        let mut other: LinkedList<i32> = (3..6).collect();
+        list.append(&mut other);
+        assert!(other.is_empty());
+        assert_eq!(list.get_size(), 6);
+        // The tail has to have moved along for pushing onto the back to keep working
+        list.push_back(6);
+        assert!(list.iter().cloned().eq(0..7));
+
+        let mut empty: LinkedList<i32> = LinkedList::new();
+        empty.append(&mut list);
+        empty.append(&mut other);
+        assert!(empty.iter().cloned().eq(0..7));
+    }
+
+    #[test]
+    fn test_tail_follows_changes() {
+        let mut list: LinkedList<i32> = LinkedList::new();
+        list.push_front(1);
+        list.insert(1, 2);
+        list.reverse();
+        list.push_back(3);
+        assert_eq!(list.remove(2), Some(3));
+        list.push_back(4);
+        assert_eq!(list.pop_front(), Some(2));
+        assert_eq!(list.pop_front(), Some(1));
+        assert_eq!(list.pop_front(), Some(4));
+        list.push_back(5);
+        assert!(list.iter().cloned().eq(vec![5]));
+    }
+
+    #[test]
+    fn test_split_off() {
+        let mut list: LinkedList<i32> = (0..5).collect();
+        let mut rest = list.split_off(2);
+        assert_eq!((list.get_size(), rest.get_size()), (2, 3));
+        list.push_back(10);
+        rest.push_back(11);
+        assert!(list.iter().cloned().eq(vec![0, 1, 10]));
+        assert!(rest.iter().cloned().eq(vec![2, 3, 4, 11]));
+
+        let all = list.split_off(0);
+        assert!(list.is_empty());
+        assert_eq!(all.get_size(), 3);
+        assert!(rest.split_off(4).is_empty());
+    }
+
+    #[test]
+    fn test_rotate() {
+        let mut list: LinkedList<i32> = (0..5).collect();
+        let first = list.head.unwrap();
+        list.rotate_left(2);
+        assert!(list.iter().cloned().eq(vec![2, 3, 4, 0, 1]));
+        list.push_back(5);
+        assert!(list.iter().cloned().eq(vec![2, 3, 4, 0, 1, 5]));
+        assert_eq!(*list.link_at(3), Some(first));
+
+        let new_head = *list.link_at(4);
+        list.rotate_right(2);
+        assert!(list.iter().cloned().eq(vec![1, 5, 2, 3, 4, 0]));
+        assert_eq!(list.head, new_head);
+        assert_eq!(list.back(), Some(&0));
+        list.push_back(6);
+        list.rotate_right(1);
+        assert!(list.iter().cloned().eq(vec![6, 1, 5, 2, 3, 4, 0]));
+        assert_eq!(list.back(), Some(&0));
+        assert_eq!(list.pop_front(), Some(6));
+        list.rotate_left(0);
+        list.rotate_right(6);
+        assert!(list.iter().cloned().eq(vec![1, 5, 2, 3, 4, 0]));
+        assert_eq!(list.get_size(), 6);
+
+        let mut empty: LinkedList<i32> = LinkedList::new();
+        empty.rotate_left(0);
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_rotate_past_the_end() {
+        let mut list: LinkedList<i32> = (0..3).collect();
+        list.rotate_left(4);
+    }
+
+    #[test]
+    fn test_map_reuses_nodes() {
+        let list: LinkedList<i32> = (0..3).collect();
+        let first = list.head.unwrap().cast::<Node<u32>>();
+        let mut mapped = list.map(|value| value as u32 * 10);
+        assert!(mapped.iter().cloned().eq(vec![0, 10, 20]));
+        assert_eq!(mapped.head, Some(first));
+        mapped.push_back(30);
+        assert_eq!((mapped.get_size(), mapped.back()), (4, Some(&30)));
+
+        let strings = mapped.map(|value| value.to_string());
+        assert!(strings.iter().eq(vec!["0", "10", "20", "30"]));
+        assert!(LinkedList::<i32>::new().map(|value| value + 1).is_empty());
+    }
+
+    #[test]
+    fn test_filter_map() {
+        let list: LinkedList<i32> = (0..10).collect();
+        let mut odds = list.filter_map(|value| Some(value as u32).filter(|value| value % 2 == 1));
+        assert!(odds.iter().cloned().eq(vec![1, 3, 5, 7, 9]));
+        assert_eq!((odds.get_size(), odds.back()), (5, Some(&9)));
+        odds.push_back(11);
+        assert_eq!(odds.back(), Some(&11));
+
+        let none = odds.filter_map(|_| None::<u32>);
+        assert!(none.is_empty());
+        assert_eq!(none.back(), None);
+    }
+
+    #[test]
+    fn test_map_panicking_on_a_long_list() {
+        let list: LinkedList<i32> = (0..1_000_000).collect();
+        let result = std::panic::catch_unwind(|| {
+            list.map(|value| if value == 500_000 { panic!("mapping failed") } else { value })
+        });
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_arena() {
+        let mut list = LinkedList::with_arena();
+        list.push_back(2);
+        list.push_front(1);
+        list.insert(2, 3);
+        assert!(list.iter().cloned().eq(vec![1, 2, 3]));
+        assert_eq!(list.pop_front(), Some(1));
+        assert_eq!(list.remove(1), Some(3));
+        // Both freed slots are taken again before the arena hands out a new one
+        list.push_back(4);
+        list.push_front(0);
+        let arena = list.arena.as_ref().unwrap();
+        assert_eq!((arena.slabs.len(), arena.used), (1, 3));
+        assert_eq!(list.to_string(), "0 2 4");
+        assert!(list.clone().arena.is_some());
+
+        // Elements still in the list when it is dropped are dropped with it
+        let counted = std::rc::Rc::new(());
+        let mut list = LinkedList::with_arena();
+        for _ in 0..FIRST_SLAB_LEN * 3 {
+            list.push_back(counted.clone());
+        }
+        list.pop_front();
+        assert_eq!(list.arena.as_ref().unwrap().slabs.len(), 2);
+        drop(list);
+        assert_eq!(std::rc::Rc::strong_count(&counted), 1);
+    }
+
+    #[test]
+    fn test_arena_relinking() {
+        let mut list: LinkedList<usize> = LinkedList::with_arena();
+        for value in (0..100).rev() {
+            list.push_back(value);
+        }
+        list.sort();
+        list.rotate_left(30);
+        list.rotate_right(30);
+        assert!(list.iter().cloned().eq(0..100));
+        assert_eq!(list.extract_if(|value| *value >= 10).count(), 90);
+        let mut cursor = list.cursor_front_mut();
+        cursor.insert_after(100);
+        assert_eq!(cursor.remove_current(), Some(0));
+        list.dedup_by_key(|value| *value / 100);
+        assert!(list.iter().cloned().eq(vec![100, 1]));
+    }
+
+    #[test]
+    fn test_arena_moves_elements_between_lists() {
+        let mut list: LinkedList<i32> = LinkedList::with_arena();
+        for value in 0..6 {
+            list.push_back(value);
+        }
+        let mut rest = list.split_off(3);
+        assert!(rest.arena.is_some());
+        rest.push_back(6);
+        let mut boxed: LinkedList<i32> = (7..9).collect();
+        rest.append(&mut boxed);
+        list.append(&mut rest);
+        assert!(boxed.is_empty() && rest.is_empty());
+        assert!(list.iter().cloned().eq(0..9));
+        assert_eq!(list.back(), Some(&8));
+
+        let mut mapped = list.filter_map(|value| Some(value as u32 * 10).filter(|v| *v < 50));
+        assert!(mapped.arena.is_some());
+        mapped.push_back(50);
+        assert!(mapped.iter().cloned().eq(vec![0, 10, 20, 30, 40, 50]));
+        assert_eq!(mapped.map(|value| value.to_string()).join(","), "0,10,20,30,40,50");
+    }
+
+    #[test]
+    fn test_extract_if() {
+        let mut list: LinkedList<i32> = (0..10).collect();
+        let evens: Vec<i32> = list.extract_if(|value| *value % 2 == 0).collect();
+        assert_eq!(evens, vec![0, 2, 4, 6, 8]);
+        assert!(list.iter().cloned().eq(vec![1, 3, 5, 7, 9]));
+        assert_eq!(list.get_size(), 5);
+
+        // Removing the last element moves the tail back
+        assert_eq!(list.extract_if(|value| *value > 5).count(), 2);
+        list.push_back(11);
+        assert!(list.iter().cloned().eq(vec![1, 3, 5, 11]));
+
+        // Stopping early leaves what hasn't been looked at yet alone
+        assert_eq!(list.extract_if(|_| true).next(), Some(1));
+        assert!(list.iter().cloned().eq(vec![3, 5, 11]));
+    }
+
+    #[test]
+    fn test_sort() {
+        let mut empty: LinkedList<i32> = LinkedList::new();
+        empty.sort();
+        assert!(empty.is_empty());
+
+        let values: Vec<i32> = (0..1000).map(|i| (i * 7919) % 1009).collect();
+        let mut list: LinkedList<i32> = values.iter().cloned().collect();
+        list.sort();
+        let mut expected = values.clone();
+        expected.sort();
+        assert!(list.iter().cloned().eq(expected));
+        list.push_back(-1);
+        assert_eq!(list.get(1000), Some(&-1));
+    }
+
+    #[test]
+    fn test_sort_by_is_stable() {
+        let mut list: LinkedList<(i32, char)> =
+            vec![(2, 'a'), (1, 'b'), (2, 'c'), (1, 'd'), (0, 'e')].into_iter().collect();
+        list.sort_by(|a, b| a.0.cmp(&b.0));
+        let order: String = list.iter().map(|(_, c)| *c).collect();
+        assert_eq!(order, "ebdac");
+    }
+
+    #[test]
+    fn test_sort_panicking_keeps_every_element() {
+        let mut list: LinkedList<i32> = (0..100).rev().collect();
+        let mut comparisons = 0;
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            list.sort_by(|a, b| {
+                comparisons += 1;
+                assert!(comparisons < 200, "comparison failed");
+                a.cmp(b)
+            })
+        }));
+        assert!(result.is_err());
+        assert_eq!(list.iter().count(), 100);
+        let mut values: Vec<i32> = list.iter().cloned().collect();
+        values.sort();
+        assert!(values.into_iter().eq(0..100));
+        list.push_back(100);
+        assert_eq!(list.get(100), Some(&100));
+    }
+
+    #[test]
+    fn test_dedup() {
+        let mut list: LinkedList<i32> = vec![1, 1, 2, 3, 3, 3, 1, 4, 4].into_iter().collect();
+        list.dedup();
+        assert!(list.iter().cloned().eq(vec![1, 2, 3, 1, 4]));
+        assert_eq!(list.get_size(), 5);
+        list.push_back(5);
+        assert_eq!(list.get(5), Some(&5));
+
+        let mut list: LinkedList<i32> = vec![10, 11, 20, 30, 31].into_iter().collect();
+        list.dedup_by_key(|value| *value / 10);
+        assert!(list.iter().cloned().eq(vec![10, 20, 30]));
+    }
+
+    #[test]
+    fn test_front() {
+        let mut list: LinkedList<i32> = LinkedList::new();
+        assert_eq!(list.front(), None);
+        list.push_front(1);
+        *list.front_mut().unwrap() += 1;
+        assert_eq!(list.front(), Some(&2));
+        assert_eq!(list.get_size(), 1);
+    }
+
+    #[test]
+    fn test_back() {
+        let mut list: LinkedList<i32> = LinkedList::new();
+        assert_eq!(list.back(), None);
+        list.push_front(2);
+        list.push_front(1);
+        *list.back_mut().unwrap() *= 10;
+        assert_eq!(list.back(), Some(&20));
+        assert!(list.iter().cloned().eq(vec![1, 20]));
+    }
+
+    #[test]
+    fn test_cursor() {
+        let mut list: LinkedList<i32> = vec![1, 2, 4].into_iter().collect();
+        let mut cursor = list.cursor_front_mut();
+        assert_eq!(cursor.current(), Some(&mut 1));
+        cursor.insert_before(0);
+        assert_eq!(cursor.index(), Some(1));
+        cursor.move_next();
+        cursor.insert_after(3);
+        cursor.move_next();
+        assert_eq!(cursor.remove_current(), Some(3));
+        assert_eq!(cursor.current(), Some(&mut 4));
+        cursor.move_next();
+        assert_eq!((cursor.index(), cursor.current()), (None, None));
+        cursor.insert_before(5);
+        cursor.move_next();
+        assert_eq!(cursor.current(), Some(&mut 0));
+        assert!(list.iter().cloned().eq(vec![0, 1, 2, 4, 5]));
+        assert_eq!(list.get_size(), 5);
+        list.push_back(6);
+        assert_eq!(list.back(), Some(&6));
+    }
+
+    #[test]
+    fn test_cursor_removing_the_last_element() {
+        let mut list: LinkedList<i32> = (0..2).collect();
+        let mut cursor = list.cursor_front_mut();
+        cursor.move_next();
+        assert_eq!(cursor.remove_current(), Some(1));
+        assert_eq!(cursor.remove_current(), None);
+        cursor.insert_after(-1);
+        assert!(list.iter().cloned().eq(vec![-1, 0]));
+        assert_eq!(list.back(), Some(&0));
+
+        let mut empty: LinkedList<i32> = LinkedList::new();
+        let mut cursor = empty.cursor_front_mut();
+        cursor.insert_after(1);
+        cursor.insert_before(2);
+        assert!(empty.iter().cloned().eq(vec![1, 2]));
+        assert_eq!(empty.back(), Some(&2));
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_serde_round_trip() {
+        let list: LinkedList<i32> = (1..4).collect();
+        let json = serde_json::to_string(&list).unwrap();
+        assert_eq!(json, "[1,2,3]");
+        let mut parsed: LinkedList<i32> = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, list);
+        parsed.push_back(4);
+        assert_eq!(parsed.back(), Some(&4));
+    }
+
+    #[test]
+    fn test_vec_conversions() {
+        let list = LinkedList::from(vec![1, 2, 3]);
+        assert_eq!(list.front(), Some(&1));
+        assert_eq!(Vec::from(list), vec![1, 2, 3]);
+        assert_eq!(Vec::from(LinkedList::<i32>::new()), Vec::<i32>::new());
+    }
+
+    #[test]
+    fn test_from_array_and_macro() {
+        let list = LinkedList::from([1, 2, 3]);
+        assert_eq!(Vec::from(list.clone()), vec![1, 2, 3]);
+        assert_eq!(linked_list![1, 2, 3], list);
+        assert_eq!(linked_list![1, 2, 3,], list);
+        let empty: LinkedList<i32> = linked_list![];
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn test_display_and_join() {
+        let list = linked_list![1, 2, 3];
+        assert_eq!(list.to_string(), "1 2 3");
+        assert_eq!(list.join(", "), "1, 2, 3");
+        assert_eq!(LinkedList::<i32>::new().to_string(), "");
+        assert_eq!(linked_list!["only"].join("-"), "only");
+    }
+
+    #[test]
+    fn test_iterators_know_their_length() {
+        let mut list = linked_list![1, 2, 3];
+        let mut iter = list.iter();
+        assert_eq!(iter.len(), 3);
+        iter.next();
+        assert_eq!(iter.size_hint(), (2, Some(2)));
+        assert_eq!(list.iter_mut().skip(1).len(), 2);
+        let mut into_iter = list.into_iter();
+        into_iter.next();
+        assert_eq!(into_iter.len(), 2);
+        assert_eq!(into_iter.by_ref().count(), 2);
+        assert_eq!(into_iter.next(), None);
+    }
+
+    #[test]
+    fn test_clone_a_long_list() {
+        let list: LinkedList<u32> = (0..1_000_000).collect();
+        let copy = list.clone();
+        assert_eq!(copy.get_size(), 1_000_000);
+        assert!(copy.iter().eq(list.iter()));
+        assert_eq!(copy.back(), Some(&999_999));
+    }
+
+    #[test]
+    fn test_compare_long_lists() {
+        let list: LinkedList<u32> = (0..1_000_000).collect();
+        let mut other = list.clone();
+        assert_eq!(list, other);
+        *other.back_mut().unwrap() += 1;
+        assert_ne!(list, other);
+    }
+}
@@ -1,5 +1,5 @@
-use linked_list::LinkedList;
-pub mod linked_list;
+use linked_list::doubly_linked_list::DoublyLinkedList;
+use linked_list::linked_list::LinkedList;
 
 fn main() {
     let mut list: LinkedList<String> = LinkedList::new();
@@ -16,8 +16,33 @@ fn main() {
     println!("size: {}", list.get_size());
     println!("{}", list.to_string()); // ToString impl for anything impl Display
 
-    // If you implement iterator trait:
-    //for val in &list {
-    //    println!("{}", val);
-    //}
+    for val in &list {
+        println!("{}", val);
+    }
+
+    for val in list.iter_mut() {
+        val.push('!');
+    }
+    println!("{}", list);
+
+    let lengths: Vec<usize> = list.into_iter().map(|val| val.len()).collect();
+    println!("lengths: {:?}", lengths);
+
+    let numbers: LinkedList<i32> = (1..10).collect();
+    println!("{:?}", numbers);
+    println!("{:?}", linked_list::linked_list!["built", "with", "the", "macro"]);
+
+    let mut deque: DoublyLinkedList<i32> = DoublyLinkedList::new();
+    deque.push_back(2);
+    deque.push_back(3);
+    deque.push_front(1);
+    println!("{}", deque);
+    println!("backwards: {:?}", deque.iter().rev().collect::<Vec<_>>());
+    println!("last odd element at: {:?}", deque.iter().rposition(|val| val % 2 == 1));
+    for val in deque.iter_mut().rev().take(1) {
+        *val *= 10;
+    }
+    println!("back element: {}", deque.pop_back().unwrap());
+    println!("front element: {}", deque.pop_front().unwrap());
+    println!("{}", deque);
 }
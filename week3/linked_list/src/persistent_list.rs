@@ -0,0 +1,146 @@
+use alloc::rc::Rc;
+use core::fmt;
+
+type Link<T> = Option<Rc<Node<T>>>;
+
+/// An immutable list whose versions share structure: `cons` makes a new list out of an element
+/// and an existing list without copying the latter, and cloning a list only bumps a reference
+/// count. A node lives as long as any list that reaches it.
+pub struct PersistentList<T> {
+    head: Link<T>,
+}
+
+struct Node<T> {
+    value: T,
+    next: Link<T>,
+}
+
+impl<T> PersistentList<T> {
+    pub fn new() -> PersistentList<T> {
+        PersistentList {head: None}
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.head.is_none()
+    }
+
+    /// Returns a new list with `value` in front of this one, which is left as it was.
+    pub fn cons(&self, value: T) -> PersistentList<T> {
+        PersistentList {head: Some(Rc::new(Node {value, next: self.head.clone()}))}
+    }
+
+    pub fn head(&self) -> Option<&T> {
+        self.head.as_ref().map(|node| &node.value)
+    }
+
+    /// Returns the list without its first element. The tail of the empty list is empty.
+    pub fn tail(&self) -> PersistentList<T> {
+        PersistentList {head: self.head.as_ref().and_then(|node| node.next.clone())}
+    }
+
+    pub fn iter(&self) -> PersistentListIter<'_, T> {
+        PersistentListIter {next: self.head.as_deref()}
+    }
+}
+
+impl<T> Clone for PersistentList<T> {
+    fn clone(&self) -> Self {
+        PersistentList {head: self.head.clone()}
+    }
+}
+
+impl<T> Default for PersistentList<T> {
+    fn default() -> Self {
+        PersistentList::new()
+    }
+}
+
+impl<T: PartialEq> PartialEq for PersistentList<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.iter().eq(other.iter())
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for PersistentList<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self).finish()
+    }
+}
+
+impl<T> Drop for PersistentList<T> {
+    // Frees the nodes only this list holds one at a time, rather than recursively (which could
+    // overflow the stack), stopping at the first one some other list still shares
+    fn drop(&mut self) {
+        let mut current = self.head.take();
+        while let Some(node) = current {
+            match Rc::try_unwrap(node) {
+                Ok(mut node) => current = node.next.take(),
+                Err(_) => break,
+            }
+        }
+    }
+}
+
+pub struct PersistentListIter<'a, T> {
+    next: Option<&'a Node<T>>,
+}
+
+impl<'a, T> Iterator for PersistentListIter<'a, T> {
+    type Item = &'a T;
+    fn next(&mut self) -> Option<&'a T> {
+        let node = self.next?;
+        self.next = node.next.as_deref();
+        Some(&node.value)
+    }
+}
+
+impl<'a, T> IntoIterator for &'a PersistentList<T> {
+    type Item = &'a T;
+    type IntoIter = PersistentListIter<'a, T>;
+    fn into_iter(self) -> PersistentListIter<'a, T> {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_cons_head_tail() {
+        let empty = PersistentList::new();
+        let one = empty.cons(1);
+        let two = one.cons(2);
+        let other = one.cons(3);
+        assert_eq!(two.head(), Some(&2));
+        assert_eq!(two.tail(), one);
+        assert!(two.iter().cloned().eq(vec![2, 1]));
+        assert!(other.iter().cloned().eq(vec![3, 1]));
+        assert!(one.tail().is_empty());
+        assert!(empty.tail().is_empty());
+        assert_eq!(empty.head(), None);
+    }
+
+    #[test]
+    fn test_structure_is_shared() {
+        let base = PersistentList::new().cons(1);
+        let a = base.cons(2);
+        let b = base.cons(3);
+        let shared = |list: &PersistentList<i32>| list.head.as_ref().unwrap().next.clone().unwrap();
+        assert!(Rc::ptr_eq(&shared(&a), &shared(&b)));
+        assert!(Rc::ptr_eq(&shared(&a), base.head.as_ref().unwrap()));
+        drop(base);
+        assert!(b.iter().cloned().eq(vec![3, 1]));
+    }
+
+    #[test]
+    fn test_dropping_a_long_list() {
+        let mut list = PersistentList::new();
+        for i in 0..1_000_000 {
+            list = list.cons(i);
+        }
+        let copy = list.clone();
+        drop(list);
+        assert_eq!(copy.head(), Some(&999_999));
+    }
+}
@@ -0,0 +1,191 @@
+//! `Stack` and `Queue` abstractions, so that exercises can be written once and run (or
+//! benchmarked) against each list in the crate and against std's `VecDeque`.
+
+use crate::doubly_linked_list::DoublyLinkedList;
+use crate::linked_list::LinkedList;
+use alloc::collections::VecDeque;
+
+/// Last in, first out.
+pub trait Stack<T> {
+    fn push(&mut self, value: T);
+    fn pop(&mut self) -> Option<T>;
+    /// The element `pop` would return next.
+    fn peek(&self) -> Option<&T>;
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// First in, first out.
+pub trait Queue<T> {
+    fn enqueue(&mut self, value: T);
+    fn dequeue(&mut self) -> Option<T>;
+    /// The element `dequeue` would return next.
+    fn peek(&self) -> Option<&T>;
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T> Stack<T> for LinkedList<T> {
+    fn push(&mut self, value: T) {
+        self.push_front(value);
+    }
+
+    fn pop(&mut self) -> Option<T> {
+        self.pop_front()
+    }
+
+    fn peek(&self) -> Option<&T> {
+        self.front()
+    }
+
+    fn len(&self) -> usize {
+        self.get_size()
+    }
+}
+
+// In at the tail, out at the head, both of which the list reaches in constant time
+impl<T> Queue<T> for LinkedList<T> {
+    fn enqueue(&mut self, value: T) {
+        self.push_back(value);
+    }
+
+    fn dequeue(&mut self) -> Option<T> {
+        self.pop_front()
+    }
+
+    fn peek(&self) -> Option<&T> {
+        self.front()
+    }
+
+    fn len(&self) -> usize {
+        self.get_size()
+    }
+}
+
+impl<T> Stack<T> for DoublyLinkedList<T> {
+    fn push(&mut self, value: T) {
+        self.push_front(value);
+    }
+
+    fn pop(&mut self) -> Option<T> {
+        self.pop_front()
+    }
+
+    fn peek(&self) -> Option<&T> {
+        self.front()
+    }
+
+    fn len(&self) -> usize {
+        self.get_size()
+    }
+}
+
+impl<T> Queue<T> for DoublyLinkedList<T> {
+    fn enqueue(&mut self, value: T) {
+        self.push_back(value);
+    }
+
+    fn dequeue(&mut self) -> Option<T> {
+        self.pop_front()
+    }
+
+    fn peek(&self) -> Option<&T> {
+        self.front()
+    }
+
+    fn len(&self) -> usize {
+        self.get_size()
+    }
+}
+
+// A VecDeque grows at the back like a Vec, so it stacks there
+impl<T> Stack<T> for VecDeque<T> {
+    fn push(&mut self, value: T) {
+        self.push_back(value);
+    }
+
+    fn pop(&mut self) -> Option<T> {
+        self.pop_back()
+    }
+
+    fn peek(&self) -> Option<&T> {
+        self.back()
+    }
+
+    fn len(&self) -> usize {
+        VecDeque::len(self)
+    }
+}
+
+impl<T> Queue<T> for VecDeque<T> {
+    fn enqueue(&mut self, value: T) {
+        self.push_back(value);
+    }
+
+    fn dequeue(&mut self) -> Option<T> {
+        self.pop_front()
+    }
+
+    fn peek(&self) -> Option<&T> {
+        self.front()
+    }
+
+    fn len(&self) -> usize {
+        VecDeque::len(self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn check_stack<S: Stack<i32> + Default>() {
+        let mut stack = S::default();
+        assert!(stack.is_empty());
+        for value in 0..3 {
+            stack.push(value);
+        }
+        assert_eq!((Stack::peek(&stack), Stack::len(&stack)), (Some(&2), 3));
+        assert_eq!(stack.pop(), Some(2));
+        stack.push(3);
+        assert_eq!(stack.pop(), Some(3));
+        assert_eq!(stack.pop(), Some(1));
+        assert_eq!(stack.pop(), Some(0));
+        assert_eq!(stack.pop(), None);
+    }
+
+    fn check_queue<Q: Queue<i32> + Default>() {
+        let mut queue = Q::default();
+        assert!(queue.is_empty());
+        for value in 0..3 {
+            queue.enqueue(value);
+        }
+        assert_eq!((Queue::peek(&queue), Queue::len(&queue)), (Some(&0), 3));
+        assert_eq!(queue.dequeue(), Some(0));
+        queue.enqueue(3);
+        assert_eq!(queue.dequeue(), Some(1));
+        assert_eq!(queue.dequeue(), Some(2));
+        assert_eq!(queue.dequeue(), Some(3));
+        assert_eq!(queue.dequeue(), None);
+    }
+
+    #[test]
+    fn test_stacks() {
+        check_stack::<LinkedList<i32>>();
+        check_stack::<DoublyLinkedList<i32>>();
+        check_stack::<VecDeque<i32>>();
+    }
+
+    #[test]
+    fn test_queues() {
+        check_queue::<LinkedList<i32>>();
+        check_queue::<DoublyLinkedList<i32>>();
+        check_queue::<VecDeque<i32>>();
+    }
+}
@@ -0,0 +1,101 @@
+//! Runs random sequences of operations against `LinkedList` and against `VecDeque` as an
+//! oracle, checking after every step that they agree. This is what catches a size that drifted
+//! or a tail left pointing at a node that was relinked elsewhere.
+
+use linked_list::linked_list::LinkedList;
+use proptest::prelude::*;
+use std::collections::VecDeque;
+
+#[derive(Clone, Debug)]
+enum Op {
+    PushFront(i32),
+    PushBack(i32),
+    PopFront,
+    // Indices are taken modulo the length (plus one, for insert), so most land in the list
+    Insert(usize, i32),
+    Remove(usize),
+    Reverse,
+    Sort,
+    SplitOffAndAppend(usize),
+    RotateLeft(usize),
+    RotateRight(usize),
+}
+
+fn op() -> impl Strategy<Value = Op> {
+    prop_oneof![
+        any::<i32>().prop_map(Op::PushFront),
+        any::<i32>().prop_map(Op::PushBack),
+        Just(Op::PopFront),
+        (any::<usize>(), any::<i32>()).prop_map(|(index, value)| Op::Insert(index, value)),
+        any::<usize>().prop_map(Op::Remove),
+        Just(Op::Reverse),
+        Just(Op::Sort),
+        any::<usize>().prop_map(Op::SplitOffAndAppend),
+        any::<usize>().prop_map(Op::RotateLeft),
+        any::<usize>().prop_map(Op::RotateRight),
+    ]
+}
+
+fn apply(op: &Op, list: &mut LinkedList<i32>, oracle: &mut VecDeque<i32>) {
+    match *op {
+        Op::PushFront(value) => {
+            list.push_front(value);
+            oracle.push_front(value);
+        }
+        Op::PushBack(value) => {
+            list.push_back(value);
+            oracle.push_back(value);
+        }
+        Op::PopFront => assert_eq!(list.pop_front(), oracle.pop_front()),
+        Op::Insert(index, value) => {
+            let index = index % (oracle.len() + 1);
+            list.insert(index, value);
+            oracle.insert(index, value);
+        }
+        Op::Remove(index) => {
+            // Past the end now and then, where both should come back empty-handed
+            let index = index % (oracle.len() + 2);
+            assert_eq!(list.remove(index), oracle.remove(index));
+        }
+        Op::Reverse => {
+            list.reverse();
+            oracle.make_contiguous().reverse();
+        }
+        Op::Sort => {
+            list.sort();
+            oracle.make_contiguous().sort();
+        }
+        Op::SplitOffAndAppend(at) => {
+            let at = at % (oracle.len() + 1);
+            let mut rest = list.split_off(at);
+            assert_eq!(rest.get_size(), oracle.len() - at);
+            list.append(&mut rest);
+            assert!(rest.is_empty());
+        }
+        Op::RotateLeft(k) => {
+            let k = k % (oracle.len() + 1);
+            list.rotate_left(k);
+            oracle.rotate_left(k);
+        }
+        Op::RotateRight(k) => {
+            let k = k % (oracle.len() + 1);
+            list.rotate_right(k);
+            oracle.rotate_right(k);
+        }
+    }
+}
+
+proptest! {
+    #[test]
+    fn test_matches_vecdeque(ops in prop::collection::vec(op(), 0..200)) {
+        let mut list = LinkedList::new();
+        let mut oracle = VecDeque::new();
+        for op in &ops {
+            apply(op, &mut list, &mut oracle);
+            prop_assert_eq!(list.get_size(), oracle.len());
+            prop_assert_eq!(list.front(), oracle.front());
+            prop_assert_eq!(list.back(), oracle.back());
+            prop_assert!(list.iter().eq(oracle.iter()));
+        }
+    }
+}